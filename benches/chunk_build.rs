@@ -0,0 +1,60 @@
+//! Builds chunks from a synthetic 64 KiB buffer of 80-byte lines, against splitting the same
+//! buffer into one `String` per line as chunks used to. Run with `cargo bench`.
+
+use std::{hint::black_box, mem::size_of, ops::Range, time::Instant};
+
+use loss_viewer::chunk::Chunk;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+const LINE_LEN: usize = 80;
+const ROUNDS: usize = 2000;
+
+fn synthetic_buffer() -> String {
+    let line = format!("{}\n", "x".repeat(LINE_LEN - 1));
+    line.repeat(BUFFER_SIZE / LINE_LEN)
+}
+
+// rows the way chunks used to build and hold them
+fn split_into_strings(content: &str) -> Vec<String> {
+    let mut rows = vec![];
+    let mut cur_index = 0;
+    while let Some(pos) = content[cur_index..].find("\n") {
+        rows.push(content[cur_index..cur_index + pos].to_string());
+        cur_index += pos + 1;
+    }
+    rows
+}
+
+// seconds per round
+fn time<T>(mut build: impl FnMut() -> T) -> f64 {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(build());
+    }
+    start.elapsed().as_secs_f64() / ROUNDS as f64
+}
+
+fn main() {
+    let buffer = synthetic_buffer();
+    let chunk_time = time(|| Chunk::build_chunk(&buffer, 0, false, false, "\n"));
+    let strings_time = time(|| split_into_strings(&buffer));
+
+    let chunk = Chunk::build_chunk(&buffer, 0, false, false, "\n");
+    let chunk_heap = chunk.size() + chunk.row_count() * size_of::<Range<usize>>();
+    let strings = split_into_strings(&buffer);
+    let strings_heap =
+        strings.len() * size_of::<String>() + strings.iter().map(String::capacity).sum::<usize>();
+
+    println!("{} lines of {LINE_LEN} bytes", chunk.row_count());
+    println!(
+        "ranges:  {:>8.1} us, {:>7} bytes, 2 allocations",
+        chunk_time * 1e6,
+        chunk_heap
+    );
+    println!(
+        "strings: {:>8.1} us, {:>7} bytes, {} allocations",
+        strings_time * 1e6,
+        strings_heap,
+        strings.len() + 1
+    );
+}
@@ -0,0 +1,67 @@
+//! Scans a synthetic 100 MB log for lines matching any of 5 raw patterns, one substring search
+//! per pattern against a single pass with a combined matcher. Run with `cargo bench`, or set
+//! `LOSS_BENCH_MB` for a different size.
+
+use std::{env, hint::black_box, time::Instant};
+
+use loss_viewer::pattern::{find_range_of_match, PatternType, RawPatternSet};
+
+const DEFAULT_SIZE_MB: usize = 100;
+const PATTERNS: [&str; 5] = ["FATAL", "timeout", "refused", "panic", "OutOfMemory"];
+
+// lines of about 100 bytes, a few of them matching
+fn synthetic_log(size: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut total = 0;
+    let mut index = 0;
+    while total < size {
+        let detail = match index % 97 {
+            0 => "connection refused by upstream",
+            41 => "request timeout after 30s",
+            _ => "request served from cache in 3ms",
+        };
+        let line = format!(
+            "2024-05-01 12:{:02}:{:02}.{:03} INFO [worker-{}] id={index} {detail}",
+            index / 60 % 60,
+            index % 60,
+            index % 1000,
+            index % 16
+        );
+        total += line.len() + 1;
+        lines.push(line);
+        index += 1;
+    }
+    lines
+}
+
+// seconds taken and lines matched
+fn time(lines: &[String], matches: impl Fn(&str) -> bool) -> (f64, usize) {
+    let start = Instant::now();
+    let count = lines.iter().filter(|line| matches(black_box(line))).count();
+    (start.elapsed().as_secs_f64(), count)
+}
+
+fn main() {
+    let size_mb = env::var("LOSS_BENCH_MB")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_SIZE_MB);
+    let lines = synthetic_log(size_mb * 1024 * 1024);
+
+    let (per_pattern_time, per_pattern_count) = time(&lines, |line| {
+        PATTERNS
+            .iter()
+            .any(|pattern| find_range_of_match(PatternType::Raw, pattern, line).is_some())
+    });
+    let set = RawPatternSet::new(&PATTERNS).unwrap();
+    let (combined_time, combined_count) = time(&lines, |line| set.is_match(line));
+    assert_eq!(per_pattern_count, combined_count);
+
+    println!(
+        "{} lines, {size_mb} MB, {} patterns, {combined_count} matching",
+        lines.len(),
+        PATTERNS.len()
+    );
+    println!("per pattern: {:>8.1} ms", per_pattern_time * 1e3);
+    println!("combined:    {:>8.1} ms", combined_time * 1e3);
+}
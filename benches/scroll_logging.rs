@@ -0,0 +1,80 @@
+//! Scrolls a synthetic document line by line with a logger formatting every record it's given,
+//! as the log file does, once at info level and once at trace level, where the per-query
+//! records that used to be logged at info are still emitted. Run with `cargo bench`.
+
+use std::{
+    hint::black_box,
+    io::{sink, Cursor, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use loss_viewer::document::Document;
+
+const LINE_COUNT: usize = 50_000;
+const WINDOW_HEIGHT: usize = 50;
+
+// formats records like the log file, then throws them away rather than touching the disk
+struct FormattingLogger {
+    record_count: AtomicUsize,
+}
+
+impl Log for FormattingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.record_count.fetch_add(1, Ordering::Relaxed);
+            let line = format!(
+                "{} [{}] [{}] {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            writeln!(sink(), "{line}").unwrap();
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: FormattingLogger = FormattingLogger {
+    record_count: AtomicUsize::new(0),
+};
+
+// seconds per scrolled line and records logged in all
+fn scroll(content: &str, level: LevelFilter) -> (f64, usize) {
+    log::set_max_level(level);
+    LOGGER.record_count.store(0, Ordering::Relaxed);
+    let mut document = Document::new(Cursor::new(content)).unwrap();
+    let start = Instant::now();
+    let mut offset = 0;
+    let mut step_count = 0;
+    while offset < document.last_line_start_offset() {
+        black_box(document.query_lines(offset, WINDOW_HEIGHT).unwrap());
+        offset += document.query_distance_to_below_n_lines(offset, 1).unwrap();
+        step_count += 1;
+    }
+    let per_step = start.elapsed().as_secs_f64() / step_count as f64;
+    (per_step, LOGGER.record_count.load(Ordering::Relaxed))
+}
+
+fn main() {
+    log::set_logger(&LOGGER).unwrap();
+    let content: String = (0..LINE_COUNT)
+        .map(|index| format!("2024-05-01 10:00:00.000 INFO request {index} served\n"))
+        .collect();
+
+    println!("{LINE_COUNT} lines scrolled one at a time, {WINDOW_HEIGHT} lines per frame");
+    for (name, level) in [("info", LevelFilter::Info), ("trace", LevelFilter::Trace)] {
+        let (per_step, record_count) = scroll(&content, level);
+        println!(
+            "{name:<5}: {:>8.2} us per line, {record_count:>7} records",
+            per_step * 1e6
+        );
+    }
+}
@@ -2,35 +2,84 @@ use std::collections::BTreeMap;
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-use crate::{canvas::Canvas, event_source::Direction, render::LineWithRenderScheme};
+use crate::{
+    canvas::Canvas,
+    event_source::Direction,
+    popup_menu::PopupMenu,
+    render::{LineWithRenderScheme, RenderScheme},
+};
 
 pub const BOOKMARK_NAME_MAX_LEN: usize = 50;
+// rows of the menu below its title, also what a page key moves by
+const MENU_HEIGHT: usize = 9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub offset: usize,
+    pub line: String,
+    // the active finder slots matching the line when it was bookmarked, e.g. `1:ERROR 3:auth`
+    pub context: Option<String>,
+}
 
 #[derive(Debug, Default)]
 pub struct BookmarkStore {
-    bookmarks: BTreeMap<String, (usize, String)>,
+    bookmarks: BTreeMap<String, Bookmark>,
     menu_index: Option<usize>,
-    filtered_bookmarks: Vec<(String, usize, String)>,
+    filtered_bookmarks: Vec<Bookmark>,
     filter_content: String,
+    // where the cursor was when the menu was last closed, selected again when it reopens
+    last_selected: Option<String>,
+    // reader offset of the document start, moved by `:trim`. bookmarks are kept at reader
+    // offsets, so those above the start are hidden rather than lost, and the menu hands out
+    // offsets in the document
+    document_start: usize,
 }
 
 impl BookmarkStore {
-    pub fn new_bookmark(&mut self, name: &str, offset: usize, line: &str) {
-        self.bookmarks
-            .insert(name.to_string(), (offset, line.to_string()));
+    // `slot_matches` are the active slots matching the line with their patterns
+    pub fn new_bookmark(
+        &mut self,
+        name: &str,
+        offset: usize,
+        line: &str,
+        slot_matches: &[(usize, &str)],
+    ) {
+        let context = (!slot_matches.is_empty()).then(|| {
+            slot_matches
+                .iter()
+                .map(|(slot_index, pattern)| format!("{slot_index}:{pattern}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        self.bookmarks.insert(
+            name.to_string(),
+            Bookmark {
+                name: name.to_string(),
+                offset: self.document_start + offset,
+                line: line.to_string(),
+                context,
+            },
+        );
+    }
+
+    // bookmarks above the new start are hidden until it moves back up
+    pub fn set_document_start(&mut self, document_start: usize) {
+        self.document_start = document_start;
     }
 
     pub fn is_active(&self) -> bool {
         self.menu_index.is_some()
     }
 
-    pub fn handle_enter_event(&mut self) -> Option<&(String, usize, String)> {
+    pub fn handle_enter_event(&mut self) -> Option<&Bookmark> {
         if self.filtered_bookmarks.is_empty() {
             None
         } else {
             assert!(self.menu_index.unwrap() < self.filtered_bookmarks.len());
             let bookmark = &self.filtered_bookmarks[self.menu_index.unwrap()];
             self.menu_index = None;
+            self.last_selected = Some(bookmark.name.clone());
             Some(bookmark)
         }
     }
@@ -39,8 +88,13 @@ impl BookmarkStore {
         match action {
             BookmarkMenuAction::Start => {
                 assert!(self.menu_index.is_none());
-                self.menu_index = Some(0);
+                self.filter_content.clear();
                 self.load_filtered_bookmarks("");
+                let last_selected = self
+                    .filtered_bookmarks
+                    .iter()
+                    .position(|bookmark| Some(&bookmark.name) == self.last_selected.as_ref());
+                self.menu_index = Some(last_selected.unwrap_or(0));
             }
             BookmarkMenuAction::Arrow(direction) => {
                 if self.filtered_bookmarks.is_empty() {
@@ -55,6 +109,22 @@ impl BookmarkStore {
                     } % self.filtered_bookmarks.len(),
                 );
             }
+            // unlike arrows, these stop at either end
+            BookmarkMenuAction::Page(direction) => {
+                if self.filtered_bookmarks.is_empty() {
+                    return;
+                }
+                let index = self.menu_index.unwrap();
+                self.menu_index = Some(if direction == Direction::Up {
+                    index.saturating_sub(MENU_HEIGHT)
+                } else {
+                    std::cmp::min(index + MENU_HEIGHT, self.filtered_bookmarks.len() - 1)
+                });
+            }
+            BookmarkMenuAction::First => self.menu_index = Some(0),
+            BookmarkMenuAction::Last => {
+                self.menu_index = Some(self.filtered_bookmarks.len().saturating_sub(1));
+            }
             BookmarkMenuAction::Content(filter_content) => {
                 let prev_bookmark = if self.filtered_bookmarks.is_empty() {
                     String::default()
@@ -65,7 +135,7 @@ impl BookmarkStore {
                 if let Some(index) = self
                     .filtered_bookmarks
                     .iter()
-                    .position(|(name, _, _)| *name == prev_bookmark)
+                    .position(|bookmark| bookmark.name == prev_bookmark)
                 {
                     self.menu_index = Some(index);
                 } else {
@@ -76,6 +146,9 @@ impl BookmarkStore {
             }
             BookmarkMenuAction::Enter => unreachable!(),
             BookmarkMenuAction::Cancel => {
+                if !self.filtered_bookmarks.is_empty() {
+                    self.last_selected = Some(self.current_bookmark().to_string());
+                }
                 self.menu_index = None;
             }
         }
@@ -83,72 +156,59 @@ impl BookmarkStore {
 
     fn current_bookmark(&self) -> &str {
         assert!(self.menu_index.unwrap() < self.filtered_bookmarks.len());
-        &self.filtered_bookmarks[self.menu_index.unwrap()].0
+        &self.filtered_bookmarks[self.menu_index.unwrap()].name
     }
 
     fn load_filtered_bookmarks(&mut self, filter_content: &str) {
         self.filtered_bookmarks = self
             .bookmarks
-            .iter()
-            .filter(|(name, _)| name.contains(filter_content))
-            .map(|(name, (offset, line))| (name.clone(), *offset, line.clone()))
+            .values()
+            .filter(|bookmark| bookmark.name.contains(filter_content))
+            .filter(|bookmark| bookmark.offset >= self.document_start)
+            .map(|bookmark| Bookmark {
+                offset: bookmark.offset - self.document_start,
+                ..bookmark.clone()
+            })
             .collect();
     }
 
     pub fn render(&self, canvas: &mut Canvas, window_width: usize, window_height: usize) {
-        const MENU_HEIGHT: usize = 10;
-        const BOOK_MENU_STR: &str = " Bookmark Menu ";
-        let width = std::cmp::max(window_width, 20);
-        let mut title = "=".repeat(width);
-        let begin = (width - BOOK_MENU_STR.len()) / 2;
-        title.replace_range(begin..begin + BOOK_MENU_STR.len(), BOOK_MENU_STR);
-        title.truncate(window_width);
-        if window_height < MENU_HEIGHT + 5 {
-            canvas.status_bar = LineWithRenderScheme::new(&title);
-            canvas.cursor_pos_x = None;
-            return;
-        }
-        canvas.popup_menu.clear();
-        canvas.popup_menu.push(LineWithRenderScheme::new(&title));
-        let menu_index = self.menu_index.unwrap();
-        let displayed_bookmarkes: Vec<_> =
-            if menu_index + MENU_HEIGHT > self.filtered_bookmarks.len() {
-                self.filtered_bookmarks
-                    .iter()
-                    .enumerate()
-                    .rev()
-                    .take(MENU_HEIGHT - 1)
-                    .rev()
-                    .collect()
-            } else {
-                self.filtered_bookmarks
-                    .iter()
-                    .enumerate()
-                    .skip(menu_index)
-                    .take(MENU_HEIGHT - 1)
-                    .collect()
-            };
-        for (index, (name, _, line)) in displayed_bookmarkes.iter() {
-            let maybe_cursor = if *index == menu_index { '>' } else { ' ' };
-            let raw_line = &format!(" {maybe_cursor} {name:<BOOKMARK_NAME_MAX_LEN$}    {line}");
-            let menu_line = LineWithRenderScheme::new(raw_line).truncate(window_width);
-            canvas.popup_menu.push(menu_line);
+        let rows = self
+            .filtered_bookmarks
+            .iter()
+            .map(|bookmark| {
+                let mut row = LineWithRenderScheme::new(&format!(
+                    "{:<BOOKMARK_NAME_MAX_LEN$}    ",
+                    bookmark.name
+                ));
+                // dimmed ahead of the line, which may be too long for anything after it to show
+                if let Some(context) = bookmark.context.as_ref() {
+                    row.push_str_with_scheme(&format!("[{context}]"), RenderScheme::Dim);
+                    row.push_str("  ");
+                }
+                row.push_str(&bookmark.line);
+                row
+            })
+            .collect();
+        PopupMenu {
+            title: "Bookmark Menu",
+            height: MENU_HEIGHT,
+            min_width: 20,
+            rows,
+            selected: self.menu_index,
+            prompt: Some(format!("Filter bookmark: {}", self.filter_content)),
         }
-        assert!(canvas.popup_menu.len() <= MENU_HEIGHT);
-        canvas
-            .popup_menu
-            .resize(MENU_HEIGHT, LineWithRenderScheme::default());
-
-        let status_bar_text = &format!("Filter bookmark: {}", self.filter_content);
-        canvas.status_bar = LineWithRenderScheme::new(status_bar_text).truncate(window_width);
-        canvas.cursor_pos_x = Some(status_bar_text.len());
+        .render(canvas, window_width, window_height);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BookmarkMenuAction {
     Start,
     Arrow(Direction),
+    Page(Direction),
+    First,
+    Last,
     Content(String),
     Enter,
     Cancel,
@@ -165,9 +225,11 @@ impl BookMarkMenu {
         self.active
     }
 
+    // the filter starts empty each time, as it does in the store
     pub fn activate(&mut self) {
         assert!(!self.active);
         self.active = true;
+        self.filter_content.clear();
     }
 
     pub fn handle_raw_event(&mut self, key: &KeyEvent) -> Option<BookmarkMenuAction> {
@@ -194,8 +256,168 @@ impl BookMarkMenu {
                 }
                 KeyCode::Up => Some(BookmarkMenuAction::Arrow(Direction::Up)),
                 KeyCode::Down => Some(BookmarkMenuAction::Arrow(Direction::Down)),
+                KeyCode::PageUp => Some(BookmarkMenuAction::Page(Direction::Up)),
+                KeyCode::PageDown => Some(BookmarkMenuAction::Page(Direction::Down)),
+                KeyCode::Home => Some(BookmarkMenuAction::First),
+                KeyCode::End => Some(BookmarkMenuAction::Last),
                 _ => None,
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_menu_keys() {
+        let mut menu = BookMarkMenu::default();
+        menu.activate();
+        let mut press = |code| menu.handle_raw_event(&KeyEvent::new(code, KeyModifiers::NONE));
+        assert_eq!(
+            press(KeyCode::PageDown),
+            Some(BookmarkMenuAction::Page(Direction::Down))
+        );
+        assert_eq!(
+            press(KeyCode::PageUp),
+            Some(BookmarkMenuAction::Page(Direction::Up))
+        );
+        assert_eq!(press(KeyCode::Home), Some(BookmarkMenuAction::First));
+        assert_eq!(press(KeyCode::End), Some(BookmarkMenuAction::Last));
+        // typed letters still filter
+        assert_eq!(
+            press(KeyCode::Char('G')),
+            Some(BookmarkMenuAction::Content("G".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_menu_navigation() {
+        let mut store = BookmarkStore::default();
+        for index in 0..20 {
+            store.new_bookmark(&format!("mark {index:02}"), index * 10, "line", &[]);
+        }
+        store.handle_other_event(BookmarkMenuAction::Start);
+        let mut move_by = |action| {
+            store.handle_other_event(action);
+            store.menu_index.unwrap()
+        };
+        assert_eq!(move_by(BookmarkMenuAction::Page(Direction::Down)), 9);
+        assert_eq!(move_by(BookmarkMenuAction::Page(Direction::Down)), 18);
+        assert_eq!(move_by(BookmarkMenuAction::Page(Direction::Down)), 19);
+        assert_eq!(move_by(BookmarkMenuAction::Page(Direction::Up)), 10);
+        assert_eq!(move_by(BookmarkMenuAction::First), 0);
+        assert_eq!(move_by(BookmarkMenuAction::Page(Direction::Up)), 0);
+        assert_eq!(move_by(BookmarkMenuAction::Last), 19);
+
+        // the selection is kept in view
+        let mut canvas = Canvas::default();
+        store.render(&mut canvas, 80, 30);
+        assert!(canvas.popup_menu[9].raw_content().starts_with(" > mark 19"));
+        assert_eq!(store.handle_enter_event().unwrap().offset, 190);
+    }
+
+    #[test]
+    fn test_document_start() {
+        let mut store = BookmarkStore::default();
+        store.new_bookmark("top", 0, "line", &[]);
+        store.new_bookmark("middle", 100, "line", &[]);
+        let listed = |store: &mut BookmarkStore| {
+            store.load_filtered_bookmarks("");
+            store
+                .filtered_bookmarks
+                .iter()
+                .map(|bookmark| (bookmark.name.clone(), bookmark.offset))
+                .collect::<Vec<_>>()
+        };
+        store.set_document_start(50);
+        assert_eq!(listed(&mut store), [("middle".to_string(), 50)]);
+        // bookmarked while trimmed, then both show up again once the start moves back
+        store.new_bookmark("below", 10, "line", &[]);
+        store.set_document_start(0);
+        assert_eq!(
+            listed(&mut store),
+            [
+                ("below".to_string(), 60),
+                ("middle".to_string(), 100),
+                ("top".to_string(), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slot_context() {
+        let mut store = BookmarkStore::default();
+        store.new_bookmark(
+            "login",
+            40,
+            "ERROR auth failed",
+            &[(1, "ERROR"), (3, "auth")],
+        );
+        store.new_bookmark("plain", 0, "started", &[]);
+        store.handle_other_event(BookmarkMenuAction::Start);
+        let context = |name: &str| store.bookmarks[name].context.clone();
+        assert_eq!(context("login").as_deref(), Some("1:ERROR 3:auth"));
+        assert_eq!(context("plain"), None);
+
+        // the context is dimmed between the name and the line
+        let mut canvas = Canvas::default();
+        store.render(&mut canvas, 120, 30);
+        let name_column = |name: &str| format!("{name:<BOOKMARK_NAME_MAX_LEN$}    ");
+        let mut expected = LineWithRenderScheme::new(&format!(" > {}", name_column("login")));
+        expected.push_str_with_scheme("[1:ERROR 3:auth]", RenderScheme::Dim);
+        expected.push_str("  ERROR auth failed");
+        assert_eq!(canvas.popup_menu[1].render(), expected.render());
+        assert_eq!(
+            canvas.popup_menu[2].raw_content(),
+            format!("   {}started", name_column("plain"))
+        );
+    }
+
+    fn press(menu: &mut BookMarkMenu, store: &mut BookmarkStore, code: KeyCode) {
+        match menu.handle_raw_event(&KeyEvent::new(code, KeyModifiers::NONE)) {
+            Some(BookmarkMenuAction::Enter) => {
+                store.handle_enter_event();
+            }
+            Some(action) => store.handle_other_event(action),
+            None => {}
+        }
+    }
+
+    fn open(menu: &mut BookMarkMenu, store: &mut BookmarkStore) {
+        menu.activate();
+        store.handle_other_event(BookmarkMenuAction::Start);
+    }
+
+    #[test]
+    fn test_reopen_menu() {
+        let mut menu = BookMarkMenu::default();
+        let mut store = BookmarkStore::default();
+        for name in ["alpha", "beta", "gamma", "delta"] {
+            store.new_bookmark(name, 0, "line", &[]);
+        }
+
+        // a filter typed before cancelling is gone when the menu reopens, in the parser too
+        open(&mut menu, &mut store);
+        press(&mut menu, &mut store, KeyCode::Char('a'));
+        press(&mut menu, &mut store, KeyCode::Char('m'));
+        assert_eq!(store.current_bookmark(), "gamma");
+        press(&mut menu, &mut store, KeyCode::Esc);
+        open(&mut menu, &mut store);
+        assert_eq!(store.filter_content, "");
+        assert_eq!(store.filtered_bookmarks.len(), 4);
+        // the cursor is back on the bookmark selected last
+        assert_eq!(store.current_bookmark(), "gamma");
+        press(&mut menu, &mut store, KeyCode::Char('e'));
+        assert_eq!(store.filter_content, "e");
+        assert_eq!(store.current_bookmark(), "beta");
+
+        // the same after going to a bookmark
+        press(&mut menu, &mut store, KeyCode::Down);
+        press(&mut menu, &mut store, KeyCode::Enter);
+        open(&mut menu, &mut store);
+        assert_eq!(store.filter_content, "");
+        assert_eq!(store.current_bookmark(), "delta");
+    }
+}
@@ -1,6 +1,6 @@
 use crate::render::LineWithRenderScheme;
 
-use std::io::{stdout, Write};
+use std::io::{Read, Write};
 
 use anyhow::{Ok, Result};
 use crossterm::{
@@ -8,25 +8,28 @@ use crossterm::{
     terminal::{Clear, ClearType},
     ExecutableCommand,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Canvas {
     pub body_area: Vec<LineWithRenderScheme>,
     pub popup_menu: Vec<LineWithRenderScheme>,
     pub status_bar: LineWithRenderScheme,
     pub cursor_pos_x: Option<usize>,
+    // ring the terminal bell along with the next render
+    pub bell: bool,
+    pub single_line: bool,
 }
 
 impl Canvas {
-    pub fn clear(&mut self) {
-        self.body_area.clear();
-        self.popup_menu.clear();
-        self.status_bar.clear();
-    }
-
-    pub fn render(&self) -> Result<()> {
+    // a terminal of a single line only has room for the status bar, which keeps prompts and
+    // messages visible
+    fn screen_lines(&self) -> Vec<String> {
+        if self.single_line {
+            return vec![self.status_bar.render()];
+        }
         let mut screen_buffer: Vec<String> = vec![];
-        let body_area_height = self.body_area.len() - self.popup_menu.len();
+        let body_area_height = self.body_area.len().saturating_sub(self.popup_menu.len());
         for line in self.body_area.iter().take(body_area_height) {
             screen_buffer.push(format!("{}\r\n", line.render()));
         }
@@ -34,28 +37,280 @@ impl Canvas {
             screen_buffer.push(format!("{}\r\n", line.render()));
         }
         screen_buffer.push(self.status_bar.render());
+        screen_buffer
+    }
+
+    /// A screen of a body `height` rows high telling that `filename` is being loaded, drawn
+    /// before the document is opened as that may take a while.
+    pub fn loading(filename: &str, width: usize, height: usize, single_line: bool) -> Self {
+        let text = format!("Loading {filename}…");
+        let mut canvas = Canvas::default();
+        if single_line {
+            canvas.single_line = true;
+            canvas.status_bar = LineWithRenderScheme::new(&text);
+            return canvas;
+        }
+        let padding = width.saturating_sub(text.chars().count()) / 2;
+        canvas.body_area = vec![LineWithRenderScheme::default(); height];
+        canvas.body_area[(height - 1) / 2] =
+            LineWithRenderScheme::new(&format!("{}{text}", " ".repeat(padding)));
+        canvas
+    }
+
+    pub fn clear(&mut self) {
+        self.body_area.clear();
+        self.popup_menu.clear();
+        self.status_bar.clear();
+    }
 
-        clear_screen_and_reset_cursor()?;
+    /// Draws the whole screen to `out`, which is stdout unless the pager is embedded.
+    pub fn render(&self, out: &mut dyn Write) -> Result<()> {
+        let screen_buffer = self.screen_lines();
+        out.execute(Clear(ClearType::All))?.execute(MoveTo(0, 0))?;
         for line in screen_buffer {
-            print!("{line}");
+            write!(out, "{line}")?;
         }
-        stdout().flush().unwrap();
+        self.finish_render(out)
+    }
+
+    /// Draws only the status bar, over a screen showing the rest of the canvas already. Far
+    /// less is written than for the whole screen, e.g. when a message expires.
+    pub fn render_status_bar_only(&self, out: &mut dyn Write) -> Result<()> {
+        out.execute(MoveTo(0, self.status_bar_row() as u16))?
+            .execute(Clear(ClearType::CurrentLine))?;
+        write!(out, "{}", self.status_bar.render())?;
+        self.finish_render(out)
+    }
+
+    fn status_bar_row(&self) -> usize {
+        if self.single_line {
+            0
+        } else {
+            self.body_area.len()
+        }
+    }
+
+    // rings the bell and leaves the cursor in the status bar, or hides it
+    fn finish_render(&self, out: &mut dyn Write) -> Result<()> {
+        if self.bell {
+            write!(out, "\x07")?;
+        }
+        out.flush()?;
 
         if let Some(x) = self.cursor_pos_x {
-            stdout()
-                .execute(Show)?
-                .execute(MoveTo(x as u16, self.body_area.len() as u16))?;
+            out.execute(Show)?
+                .execute(MoveTo(x as u16, self.status_bar_row() as u16))?;
         } else {
-            stdout().execute(Hide)?;
+            out.execute(Hide)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A drawn frame along with the size of the window it was laid out in, dumped as json by
+/// `:dump-frame` so that a rendering issue can be looked into without the file shown.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrameDump {
+    pub width: usize,
+    // rows of the body, without the status bar
+    pub height: usize,
+    pub canvas: Canvas,
+}
+
+impl FrameDump {
+    pub fn write_json<W: Write>(&self, mut out: W) -> Result<()> {
+        serde_json::to_writer_pretty(&mut out, self)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn read_json<R: Read>(reader: R) -> Result<Self> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Writes the rows of the frame as text, with the schemes as ANSI attributes like they're
+    /// drawn, for `loss --render-frame`.
+    pub fn write_rows(&self, out: &mut dyn Write) -> Result<()> {
+        for line in self.canvas.screen_lines() {
+            writeln!(out, "{}", line.trim_end_matches("\r\n"))?;
         }
+        Ok(())
+    }
+}
+
+/// Where frames are drawn, the terminal when running for real.
+pub trait FrameSink {
+    fn draw(&mut self, canvas: &Canvas) -> Result<()>;
+
+    // the rest of `canvas` is on screen already, as drawn last
+    fn draw_status_bar(&mut self, canvas: &Canvas) -> Result<()> {
+        self.draw(canvas)
+    }
+
+    // leaves the screen empty on exit
+    fn clear(&mut self) -> Result<()>;
+}
+
+impl<W: Write> FrameSink for W {
+    fn draw(&mut self, canvas: &Canvas) -> Result<()> {
+        canvas.render(self)
+    }
+
+    fn draw_status_bar(&mut self, canvas: &Canvas) -> Result<()> {
+        canvas.render_status_bar_only(self)
+    }
 
+    fn clear(&mut self) -> Result<()> {
+        self.execute(Clear(ClearType::All))?.execute(MoveTo(0, 0))?;
         Ok(())
     }
 }
 
-pub fn clear_screen_and_reset_cursor() -> Result<()> {
-    stdout()
-        .execute(Clear(ClearType::All))?
-        .execute(MoveTo(0, 0))?;
-    Ok(())
+/// Keeps every drawn frame as plain rows, body then popup menu then status bar, with the
+/// schemes marked as by [`LineWithRenderScheme::marked`]. Clones share the frames, so one can
+/// be handed to the manager and the other kept to look at them.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct CapturingCanvas {
+    frames: std::rc::Rc<std::cell::RefCell<Vec<Vec<String>>>>,
+}
+
+#[cfg(test)]
+impl CapturingCanvas {
+    pub fn frames(&self) -> Vec<Vec<String>> {
+        self.frames.borrow().clone()
+    }
+
+    pub fn last_frame(&self) -> Vec<String> {
+        self.frames.borrow().last().cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+impl FrameSink for CapturingCanvas {
+    fn draw(&mut self, canvas: &Canvas) -> Result<()> {
+        let body_area_height = canvas
+            .body_area
+            .len()
+            .saturating_sub(canvas.popup_menu.len());
+        let frame = canvas
+            .body_area
+            .iter()
+            .take(body_area_height)
+            .chain(canvas.popup_menu.iter())
+            .chain([&canvas.status_bar])
+            .map(|line| line.marked().trim_end().to_string())
+            .collect();
+        self.frames.borrow_mut().push(frame);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+    use crate::render::RenderScheme;
+
+    #[test]
+    fn test_screen_lines() {
+        let mut canvas = Canvas {
+            body_area: vec![LineWithRenderScheme::new("body")],
+            status_bar: LineWithRenderScheme::new("Search: "),
+            ..Canvas::default()
+        };
+        assert_eq!(canvas.screen_lines(), ["body\r\n", "Search: "]);
+        canvas.single_line = true;
+        assert_eq!(canvas.screen_lines(), ["Search: "]);
+
+        // popup menu taller than the body
+        canvas.single_line = false;
+        canvas.popup_menu = vec![LineWithRenderScheme::new("menu"); 2];
+        assert_eq!(canvas.screen_lines().len(), 3);
+    }
+
+    #[test]
+    fn test_frame_dump_round_trip() {
+        let mut finder = crate::finder::Finder::new();
+        finder.update_search_pattern("error");
+        let mut highlighted = finder
+            .attach_render_scheme("an error\there")
+            .with_invisibles();
+        highlighted.underline(20);
+        let mut status_bar = LineWithRenderScheme::new("Search: ");
+        status_bar.push_str_with_scheme("error", RenderScheme::Reverse);
+        let dump = FrameDump {
+            width: 20,
+            height: 3,
+            canvas: Canvas {
+                body_area: vec![
+                    highlighted,
+                    LineWithRenderScheme::new("héllo").with_cut_len(100),
+                    LineWithRenderScheme::new("~"),
+                ],
+                popup_menu: vec![LineWithRenderScheme::new("menu")],
+                status_bar,
+                cursor_pos_x: Some(8),
+                ..Canvas::default()
+            },
+        };
+        let mut json = vec![];
+        dump.write_json(&mut json).unwrap();
+        let read = FrameDump::read_json(json.as_slice()).unwrap();
+        assert_eq!((read.width, read.height), (20, 3));
+        assert_eq!(read.canvas.cursor_pos_x, Some(8));
+        assert_eq!(read.canvas.screen_lines(), dump.canvas.screen_lines());
+        let marked = |canvas: &Canvas| -> Vec<String> {
+            canvas.body_area.iter().map(|line| line.marked()).collect()
+        };
+        assert_eq!(marked(&read.canvas), marked(&dump.canvas));
+        assert_eq!(marked(&read.canvas)[0], "an [error]→       here");
+
+        let mut rows = vec![];
+        read.write_rows(&mut rows).unwrap();
+        let rows = String::from_utf8(rows).unwrap();
+        assert_eq!(rows.lines().count(), 4);
+        assert!(rows.ends_with(&format!("Search: {}\n", "error".reverse())));
+        assert!(FrameDump::read_json("{}".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_render_status_bar_only() {
+        let canvas = Canvas {
+            body_area: vec![LineWithRenderScheme::new("body"); 3],
+            status_bar: LineWithRenderScheme::new("42%"),
+            ..Canvas::default()
+        };
+        let mut out = vec![];
+        canvas.render_status_bar_only(&mut out).unwrap();
+        // moved to the last row and cleared, the body is left as it is
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[4;1H\x1b[2K42%\x1b[?25l"
+        );
+    }
+
+    #[test]
+    fn test_loading() {
+        let canvas = Canvas::loading("app.log", 30, 5, false);
+        assert_eq!(
+            canvas.screen_lines(),
+            [
+                "\r\n",
+                "\r\n",
+                "       Loading app.log…\r\n",
+                "\r\n",
+                "\r\n",
+                ""
+            ]
+        );
+        let canvas = Canvas::loading("app.log", 10, 1, true);
+        assert_eq!(canvas.screen_lines(), ["Loading app.log…"]);
+    }
 }
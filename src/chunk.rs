@@ -1,74 +1,176 @@
-use core::panic;
+use std::ops::Range;
 
+use anyhow::Result;
+
+use crate::document::DocumentError;
+
+pub const DEFAULT_LINE_DELIMITER: &str = "\n";
+/// The UTF-8 byte order mark, `EF BB BF`, which some Windows tools write ahead of the text.
+pub const UTF8_BOM: &str = "\u{feff}";
+
+/// A contiguous run of whole lines of a document, starting at `offset_begin`.
 #[derive(Debug, PartialEq)]
 pub struct Chunk {
     pub offset_begin: usize,
     pub offset_end: usize,
-    pub rows: Vec<String>,
+    // the bytes from `offset_begin` to `offset_end`, line delimiters included
+    content: String,
+    // where each row is in `content`, without the delimiter
+    row_ranges: Vec<Range<usize>>,
+    // rows don't contain the delimiter, but offsets count it in
+    pub delimiter_len: usize,
+    // a byte order mark at the document start is left out of the first row, which still
+    // starts at offset 0 and counts it in
+    lead_len: usize,
 }
 
 impl Chunk {
+    /// Splits `content` read at `content_offset` into rows. A partial first or last line can be
+    /// dropped when `content` doesn't start or end at a line boundary. A byte order mark at
+    /// offset 0 is skipped.
     pub fn build_chunk(
         content: &str,
         content_offset: usize,
         drop_first: bool,
         drop_last: bool,
+        delimiter: &str,
     ) -> Chunk {
+        // a char is searched for much faster than a string
+        let find_delimiter = |text: &str| match delimiter.as_bytes() {
+            [byte] if byte.is_ascii() => text.find(*byte as char),
+            _ => text.find(delimiter),
+        };
         let mut cur_index = 0;
         if drop_first {
-            let first_line_break = content.find('\n');
-            cur_index = first_line_break.unwrap() + 1;
+            let first_line_break = find_delimiter(content);
+            cur_index = first_line_break.unwrap() + delimiter.len();
         }
-        let offset_begin = content_offset + cur_index;
-        let mut rows = vec![];
-        while let Some(pos) = content[cur_index..].find('\n') {
+        let begin_index = cur_index;
+        let mut lead_len = 0;
+        if content_offset + begin_index == 0 && content.starts_with(UTF8_BOM) {
+            lead_len = UTF8_BOM.len();
+            cur_index = lead_len;
+        }
+        let mut row_ranges = vec![];
+        while let Some(pos) = find_delimiter(&content[cur_index..]) {
             let next_line_break = cur_index + pos;
-            rows.push(content[cur_index..next_line_break].to_string());
-            cur_index = next_line_break + 1;
+            row_ranges.push(cur_index - begin_index..next_line_break - begin_index);
+            cur_index = next_line_break + delimiter.len();
         }
         if !drop_last && cur_index < content.len() {
-            rows.push(content[cur_index..].to_string());
-            cur_index += content[cur_index..].len();
+            row_ranges.push(cur_index - begin_index..content.len() - begin_index);
+            cur_index = content.len();
         }
-        let offset_end = content_offset + cur_index;
         Chunk {
-            offset_begin,
-            offset_end,
-            rows,
+            offset_begin: content_offset + begin_index,
+            offset_end: content_offset + cur_index,
+            content: content[begin_index..cur_index].to_string(),
+            row_ranges,
+            delimiter_len: delimiter.len(),
+            lead_len,
         }
     }
 
+    pub fn size(&self) -> usize {
+        self.offset_end - self.offset_begin
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.row_ranges.len()
+    }
+
+    pub fn row(&self, index: usize) -> &str {
+        &self.content[self.row_ranges[index].clone()]
+    }
+
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.row_ranges
+            .iter()
+            .map(|range| &self.content[range.clone()])
+    }
+
+    /// Each row along with the bytes it takes in the document, its delimiter and a skipped
+    /// byte order mark included.
+    pub fn rows_with_len(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (&str, usize)> + ExactSizeIterator {
+        self.row_ranges.iter().enumerate().map(|(index, range)| {
+            let lead_len = if index == 0 { self.lead_len } else { 0 };
+            (
+                &self.content[range.clone()],
+                lead_len + range.len() + self.delimiter_len,
+            )
+        })
+    }
+
+    /// Removes the last row, returned along with the delimiter after it if there's one.
+    pub fn pop_row(&mut self) -> Option<String> {
+        let range = self.row_ranges.pop()?;
+        let start = if self.row_ranges.is_empty() {
+            self.lead_len = 0;
+            0
+        } else {
+            range.start
+        };
+        let row = self.content.split_off(start);
+        self.offset_end -= row.len();
+        Some(row)
+    }
+
+    pub fn can_merge(&self, next: &Chunk, max_size: usize) -> bool {
+        self.offset_end == next.offset_begin && self.size() + next.size() <= max_size
+    }
+
+    pub fn merge(&mut self, next: Chunk) {
+        assert_eq!(self.offset_end, next.offset_begin);
+        let shift = self.content.len();
+        self.content.push_str(&next.content);
+        self.row_ranges.extend(
+            next.row_ranges
+                .into_iter()
+                .map(|range| range.start + shift..range.end + shift),
+        );
+        self.offset_end = next.offset_end;
+    }
+
+    /// Index of the row which `offset` falls in, its trailing delimiter included.
     pub fn query_line_index(&self, offset: usize) -> usize {
         assert!(offset >= self.offset_begin && offset < self.offset_end);
-        let mut current_line_offset_begin = self.offset_begin;
-        for (index, row) in self.rows.iter().enumerate() {
-            if offset <= current_line_offset_begin + row.len() {
-                return index;
-            }
-            current_line_offset_begin += row.len() + 1;
-        }
-        unreachable!();
+        let offset = offset - self.offset_begin;
+        // the first row starts at 0, a byte order mark included, so it's the one for any
+        // offset before the second row
+        self.row_ranges
+            .partition_point(|range| range.start <= offset)
+            .saturating_sub(1)
     }
 
-    pub fn query_line_index_exactly(&self, offset: usize) -> usize {
-        assert!(offset >= self.offset_begin && offset < self.offset_end);
-        let mut current_line_offset_begin = self.offset_begin;
-        for (index, row) in self.rows.iter().enumerate() {
-            if offset == current_line_offset_begin {
-                return index;
+    /// Index of the row starting exactly at `offset`.
+    pub fn query_line_index_exactly(&self, offset: usize) -> Result<usize> {
+        if offset < self.offset_begin || offset >= self.offset_end {
+            return Err(DocumentError::OffsetOutOfChunk {
+                offset,
+                offset_begin: self.offset_begin,
+                offset_end: self.offset_end,
             }
-            current_line_offset_begin += row.len() + 1;
+            .into());
         }
-        panic!("cannot find line index exactly");
+        let index = self.query_line_index(offset);
+        if self.query_line_start_offset(index) != offset {
+            return Err(DocumentError::NotAtLineStart { offset }.into());
+        }
+        Ok(index)
     }
 
+    /// Start offset of the row at `index`, or `offset_end` if `index` equals the row count.
+    /// Panics if `index` is larger than that.
     pub fn query_line_start_offset(&self, index: usize) -> usize {
-        assert!(index <= self.rows.len());
-        self.rows
-            .iter()
-            .take(index)
-            .map(|line| line.len() + 1)
-            .sum::<usize>()
+        assert!(index <= self.row_ranges.len());
+        match self.row_ranges.get(index) {
+            // a byte order mark belongs to the first row
+            Some(_) if index == 0 => self.offset_begin,
+            Some(range) => self.offset_begin + range.start,
+            None => self.offset_end,
+        }
     }
 }
 
@@ -80,7 +182,7 @@ mod tests {
     #[test]
     fn test_query_line_start_offset() {
         let content = "123456\n12345\n12\n\n123456\n";
-        let chunk = Chunk::build_chunk(content, 0, false, false);
+        let chunk = Chunk::build_chunk(content, 0, false, false, "\n");
         assert_eq!(chunk.query_line_start_offset(0), 0);
         assert_eq!(chunk.query_line_start_offset(1), 7);
         assert_eq!(chunk.query_line_start_offset(2), 13);
@@ -89,28 +191,60 @@ mod tests {
         assert_eq!(chunk.query_line_start_offset(5), 24);
     }
 
+    #[test]
+    fn test_query_line_start_offset_at_offset_end() {
+        let content = "12\n\n\n345\nabc";
+        let chunk = Chunk::build_chunk(content, 10, true, true, "\n");
+        assert_eq!(chunk.rows().collect::<Vec<_>>(), vec!["", "", "345"]);
+        assert_eq!(chunk.query_line_start_offset(0), 13);
+        assert_eq!(chunk.query_line_start_offset(2), 15);
+        assert_eq!(chunk.query_line_start_offset(3), chunk.offset_end);
+        assert_eq!(chunk.offset_end, 19);
+    }
+
     #[test]
     fn test_query_line_index_exactly_ok() {
         let content = "123456\n12345\n12\n\n123456\n";
-        let chunk = Chunk::build_chunk(content, 0, false, false);
-        assert_eq!(chunk.query_line_index_exactly(7), 1);
+        let chunk = Chunk::build_chunk(content, 0, false, false, "\n");
+        assert_eq!(chunk.query_line_index_exactly(0).unwrap(), 0);
+        assert_eq!(chunk.query_line_index_exactly(7).unwrap(), 1);
+        assert_eq!(chunk.query_line_index_exactly(16).unwrap(), 3);
+        assert_eq!(chunk.query_line_index_exactly(17).unwrap(), 4);
     }
 
     #[test]
-    #[should_panic(expected = "cannot find line index exactly")]
-    fn test_query_line_index_exactly_panic() {
+    fn test_query_line_index_exactly_err() {
         let content = "123456\n12345\n12\n\n123456\n";
-        let chunk = Chunk::build_chunk(content, 0, false, false);
-        chunk.query_line_index_exactly(3);
+        let chunk = Chunk::build_chunk(content, 0, false, false, "\n");
+        let error = |offset| {
+            chunk
+                .query_line_index_exactly(offset)
+                .unwrap_err()
+                .downcast::<DocumentError>()
+                .unwrap()
+        };
+        assert_eq!(error(3), DocumentError::NotAtLineStart { offset: 3 });
+        assert_eq!(error(23), DocumentError::NotAtLineStart { offset: 23 });
+        assert_eq!(
+            error(24),
+            DocumentError::OffsetOutOfChunk {
+                offset: 24,
+                offset_begin: 0,
+                offset_end: 24
+            }
+        );
+        let empty = Chunk::build_chunk("", 5, false, false, "\n");
+        assert_eq!(empty.row_count(), 0);
+        assert!(empty.query_line_index_exactly(5).is_err());
     }
 
     #[test]
     fn test_query_line_index() {
         let content = "123456\n12345\n12\n\n123456\n";
-        let chunk = Chunk::build_chunk(content, 0, false, false);
+        let chunk = Chunk::build_chunk(content, 0, false, false, "\n");
         assert_eq!(chunk.offset_begin, 0);
         assert_eq!(chunk.offset_end, 24);
-        assert_eq!(chunk.rows.len(), 5);
+        assert_eq!(chunk.row_count(), 5);
         assert_eq!(chunk.query_line_index(0), 0);
         assert_eq!(chunk.query_line_index(6), 0);
         assert_eq!(chunk.query_line_index(9), 1);
@@ -126,46 +260,126 @@ mod tests {
     fn test_build_chunk() {
         let content = "123456\n12345\n12\n\n123456";
 
-        let chunk = Chunk::build_chunk(content, 2, false, false);
+        let chunk = Chunk::build_chunk(content, 2, false, false, "\n");
         assert_eq!(chunk.offset_begin, 2);
         assert_eq!(chunk.offset_end, 25);
-        assert_eq!(chunk.rows, vec!["123456", "12345", "12", "", "123456"]);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["123456", "12345", "12", "", "123456"]
+        );
 
-        let chunk = Chunk::build_chunk(content, 2, true, false);
+        let chunk = Chunk::build_chunk(content, 2, true, false, "\n");
         assert_eq!(chunk.offset_begin, 9);
         assert_eq!(chunk.offset_end, 25);
-        assert_eq!(chunk.rows, vec!["12345", "12", "", "123456"]);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["12345", "12", "", "123456"]
+        );
 
-        let chunk = Chunk::build_chunk(content, 2, false, true);
+        let chunk = Chunk::build_chunk(content, 2, false, true, "\n");
         assert_eq!(chunk.offset_begin, 2);
         assert_eq!(chunk.offset_end, 19);
-        assert_eq!(chunk.rows, vec!["123456", "12345", "12", ""]);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["123456", "12345", "12", ""]
+        );
 
-        let chunk = Chunk::build_chunk(content, 2, true, true);
+        let chunk = Chunk::build_chunk(content, 2, true, true, "\n");
         assert_eq!(chunk.offset_begin, 9);
         assert_eq!(chunk.offset_end, 19);
-        assert_eq!(chunk.rows, vec!["12345", "12", ""]);
+        assert_eq!(chunk.rows().collect::<Vec<_>>(), vec!["12345", "12", ""]);
 
         let content = "\nabc\n12\n\n\n12345\n";
 
-        let chunk = Chunk::build_chunk(content, 1, false, false);
+        let chunk = Chunk::build_chunk(content, 1, false, false, "\n");
         assert_eq!(chunk.offset_begin, 1);
         assert_eq!(chunk.offset_end, 17);
-        assert_eq!(chunk.rows, vec!["", "abc", "12", "", "", "12345"]);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["", "abc", "12", "", "", "12345"]
+        );
 
-        let chunk = Chunk::build_chunk(content, 1, true, false);
+        let chunk = Chunk::build_chunk(content, 1, true, false, "\n");
         assert_eq!(chunk.offset_begin, 2);
         assert_eq!(chunk.offset_end, 17);
-        assert_eq!(chunk.rows, vec!["abc", "12", "", "", "12345"]);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["abc", "12", "", "", "12345"]
+        );
 
-        let chunk = Chunk::build_chunk(content, 1, false, true);
+        let chunk = Chunk::build_chunk(content, 1, false, true, "\n");
         assert_eq!(chunk.offset_begin, 1);
         assert_eq!(chunk.offset_end, 17);
-        assert_eq!(chunk.rows, vec!["", "abc", "12", "", "", "12345"]);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["", "abc", "12", "", "", "12345"]
+        );
 
-        let chunk = Chunk::build_chunk(content, 1, true, true);
+        let chunk = Chunk::build_chunk(content, 1, true, true, "\n");
         assert_eq!(chunk.offset_begin, 2);
         assert_eq!(chunk.offset_end, 17);
-        assert_eq!(chunk.rows, vec!["abc", "12", "", "", "12345"]);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["abc", "12", "", "", "12345"]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut chunk = Chunk::build_chunk("123\n45\n", 0, false, false, "\n");
+        let next = Chunk::build_chunk("6\n\n", 7, false, false, "\n");
+        let apart = Chunk::build_chunk("7\n", 12, false, false, "\n");
+        assert!(chunk.can_merge(&next, 10));
+        assert!(!chunk.can_merge(&next, 9));
+        assert!(!chunk.can_merge(&apart, 100));
+        chunk.merge(next);
+        assert_eq!(chunk.offset_begin, 0);
+        assert_eq!(chunk.offset_end, 10);
+        assert_eq!(chunk.rows().collect::<Vec<_>>(), vec!["123", "45", "6", ""]);
+        assert_eq!(chunk.query_line_index_exactly(9).unwrap(), 3);
+        assert_eq!(chunk.row(1), "45");
+    }
+
+    #[test]
+    fn test_pop_row() {
+        let mut chunk = Chunk::build_chunk("123\n45\n", 0, false, false, "\n");
+        assert_eq!(chunk.pop_row().as_deref(), Some("45\n"));
+        assert_eq!(chunk.offset_end, 4);
+        assert_eq!(chunk.rows().collect::<Vec<_>>(), vec!["123"]);
+        // without a delimiter at the end
+        let mut chunk = Chunk::build_chunk("123\n45", 0, false, false, "\n");
+        assert_eq!(chunk.pop_row().as_deref(), Some("45"));
+        assert_eq!(chunk.pop_row().as_deref(), Some("123\n"));
+        assert_eq!(chunk.pop_row(), None);
+        assert_eq!(chunk.offset_end, 0);
+    }
+
+    #[test]
+    fn test_build_chunk_with_delimiter() {
+        let content = "1234\r\nab\r\n\r\nc\rd\r\n";
+        let chunk = Chunk::build_chunk(content, 0, false, false, "\r\n");
+        assert_eq!(chunk.offset_end, 17);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["1234", "ab", "", "c\rd"]
+        );
+        assert_eq!(chunk.query_line_start_offset(3), 12);
+        assert_eq!(chunk.query_line_index(5), 0);
+        assert_eq!(chunk.query_line_index(9), 1);
+        assert_eq!(chunk.query_line_index(11), 2);
+        assert_eq!(chunk.query_line_index_exactly(12).unwrap(), 3);
+
+        let chunk = Chunk::build_chunk(content, 3, true, true, "\r\n");
+        assert_eq!(chunk.offset_begin, 9);
+        assert_eq!(chunk.offset_end, 20);
+        assert_eq!(chunk.rows().collect::<Vec<_>>(), vec!["ab", "", "c\rd"]);
+
+        let content = "{\"a\":1}\x1e{\"b\":\n2}\x1e{";
+        let chunk = Chunk::build_chunk(content, 0, false, true, "\x1e");
+        assert_eq!(chunk.offset_end, 17);
+        assert_eq!(
+            chunk.rows().collect::<Vec<_>>(),
+            vec!["{\"a\":1}", "{\"b\":\n2}"]
+        );
     }
 }
@@ -0,0 +1,506 @@
+use std::{
+    fs::{self, File},
+    io::ErrorKind,
+    path::Path,
+};
+
+use crate::{
+    config::{parse_line_delimiter, Config, LogFile},
+    manager::StartPosition,
+};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ViewOptions {
+    pub filename: String,
+    pub no_session: bool,
+    pub line_delimiter: Option<String>,
+    // print the file like `cat` instead of showing the pager, implied if stdout isn't a tty
+    pub raw_output: bool,
+    // highlights in the raw output as ANSI colors
+    pub color: bool,
+    pub filter_profile: Option<String>,
+    // show the last lines instead of the position of last visit
+    pub last_lines: Option<usize>,
+    // 1-based line to start at, from `+N` or a `:N` suffix of the filename
+    pub line_number: Option<usize>,
+    pub follow: bool,
+    pub log_file: Option<LogFile>,
+    // unix socket accepting `:` commands
+    pub control_path: Option<String>,
+    // only the last bytes of the file are read, as if it started there
+    pub tail_bytes: Option<usize>,
+}
+
+impl ViewOptions {
+    /// Whether more than one start position is asked for, e.g. `+F` with `-n`, where all but
+    /// one would be left unused. Checked again once a `:N` suffix is resolved.
+    pub fn has_conflicting_start(&self) -> bool {
+        let given = [
+            self.follow,
+            self.line_number.is_some(),
+            self.last_lines.is_some(),
+        ];
+        given.into_iter().filter(|given| *given).count() > 1
+    }
+
+    pub fn start_position(&self) -> StartPosition {
+        if self.follow {
+            StartPosition::Follow
+        } else if let Some(line_number) = self.line_number {
+            StartPosition::Line(line_number)
+        } else if let Some(line_count) = self.last_lines {
+            StartPosition::LastLines(line_count)
+        } else {
+            StartPosition::LastVisit
+        }
+    }
+
+    /// Moves a `:N` suffix of the filename, as printed by `grep -n` or editors, to the line to
+    /// start at. Only done when there's no file by the literal name but there is one without
+    /// the suffix, so that files with a colon in their names still open.
+    pub fn resolve_line_suffix(&mut self) {
+        if Path::new(&self.filename).exists() {
+            return;
+        }
+        let Some((path, suffix)) = self.filename.rsplit_once(':') else {
+            return;
+        };
+        let Some(line_number) = parse_line_number(suffix) else {
+            return;
+        };
+        if Path::new(path).exists() {
+            self.filename = path.to_string();
+            self.line_number = Some(line_number);
+        }
+    }
+
+    // command line flags take precedence over the config file
+    pub fn apply_to(&self, config: &mut Config) {
+        if self.no_session {
+            config.restore_session = false;
+        }
+        if let Some(line_delimiter) = self.line_delimiter.as_ref() {
+            config.line_delimiter = line_delimiter.clone();
+        }
+        if let Some(log_file) = self.log_file.as_ref() {
+            config.log_file = log_file.clone();
+        }
+        if self.tail_bytes.is_some() {
+            config.tail_bytes = self.tail_bytes;
+            // positions in a tail don't match those saved for the whole file
+            config.restore_session = false;
+        }
+    }
+}
+
+// prints diagnostics of the file instead of showing the pager
+#[derive(Debug, Default, PartialEq)]
+pub struct InspectOptions {
+    pub filename: String,
+    pub line_delimiter: Option<String>,
+    pub json: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    PrintVersion,
+    PrintHelp,
+    // the arguments don't make sense, the usage is printed to stderr
+    UsageError,
+    View(ViewOptions),
+    Inspect(InspectOptions),
+    // prints a frame dumped by `:dump-frame` instead of showing the pager
+    RenderFrame(String),
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Command {
+    let mut options = ViewOptions::default();
+    let mut filename = None;
+    let (mut inspect, mut json) = (false, false);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // whatever follows is ignored, even a filename
+            "-v" | "--version" => return Command::PrintVersion,
+            "-h" | "--help" => return Command::PrintHelp,
+            "--no-session" => options.no_session = true,
+            "--raw-output" => options.raw_output = true,
+            "--color" => options.color = true,
+            "+F" => options.follow = true,
+            _ if arg.starts_with('+') => {
+                let Some(line_number) = parse_line_number(&arg[1..]) else {
+                    return Command::UsageError;
+                };
+                options.line_number = Some(line_number);
+            }
+            "--inspect" => inspect = true,
+            "--json" => json = true,
+            "--log" => options.log_file = Some(LogFile::Default),
+            "--render-frame" => {
+                let Some(path) = args.next() else {
+                    return Command::UsageError;
+                };
+                return match args.next() {
+                    Some(_) => Command::UsageError,
+                    None => Command::RenderFrame(path),
+                };
+            }
+            "--control" => {
+                let Some(path) = args.next() else {
+                    return Command::UsageError;
+                };
+                options.control_path = Some(path);
+            }
+            _ if arg.starts_with("--log=") => {
+                options.log_file = Some(LogFile::parse(&arg["--log=".len()..]));
+            }
+            "-n" | "--lines" => {
+                let Some(Ok(line_count)) = args.next().map(|value| value.parse::<usize>()) else {
+                    return Command::UsageError;
+                };
+                if line_count == 0 {
+                    return Command::UsageError;
+                }
+                options.last_lines = Some(line_count);
+            }
+            "--tail-bytes" => {
+                let Some(tail_bytes) = args.next().as_deref().and_then(parse_byte_count) else {
+                    return Command::UsageError;
+                };
+                options.tail_bytes = Some(tail_bytes);
+            }
+            "--filter-profile" => {
+                let Some(name) = args.next() else {
+                    return Command::UsageError;
+                };
+                options.filter_profile = Some(name);
+            }
+            "-d" | "--delimiter" => {
+                let Some(Ok(delimiter)) = args.next().map(|value| parse_line_delimiter(&value))
+                else {
+                    return Command::UsageError;
+                };
+                options.line_delimiter = Some(delimiter);
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => return Command::UsageError,
+            _ => {
+                if filename.is_some() {
+                    return Command::UsageError;
+                }
+                filename = Some(arg);
+            }
+        }
+    }
+    match filename {
+        Some(filename) if inspect => Command::Inspect(InspectOptions {
+            filename,
+            line_delimiter: options.line_delimiter,
+            json,
+        }),
+        // json is for inspecting only
+        Some(_) if json => Command::UsageError,
+        // following keeps the last page in view, which would leave the line count unused, as
+        // would a line to start at
+        Some(_) if options.has_conflicting_start() => Command::UsageError,
+        Some(filename) => {
+            options.filename = filename;
+            Command::View(options)
+        }
+        None => Command::UsageError,
+    }
+}
+
+// a 1-based line number, digits only
+fn parse_line_number(value: &str) -> Option<usize> {
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok().filter(|line_number| *line_number > 0)
+}
+
+// a positive count of bytes, with an optional `K`, `M` or `G` suffix for binary multiples
+fn parse_byte_count(value: &str) -> Option<usize> {
+    let (digits, multiple) = match value.char_indices().last()? {
+        (index, 'K' | 'k') => (&value[..index], 1 << 10),
+        (index, 'M' | 'm') => (&value[..index], 1 << 20),
+        (index, 'G' | 'g') => (&value[..index], 1 << 30),
+        _ => (value, 1),
+    };
+    let count = digits.parse::<usize>().ok()?.checked_mul(multiple)?;
+    (count > 0).then_some(count)
+}
+
+// a readable regular file or fifo, with the reason in plain words otherwise
+pub fn check_input_file(path: &Path) -> Result<(), String> {
+    let describe = |kind: ErrorKind| match kind {
+        ErrorKind::NotFound => "no such file or directory".to_string(),
+        ErrorKind::PermissionDenied => "permission denied".to_string(),
+        kind => kind.to_string(),
+    };
+    let metadata = fs::metadata(path).map_err(|e| describe(e.kind()))?;
+    if metadata.is_dir() {
+        return Err("is a directory".to_string());
+    }
+    #[cfg(unix)]
+    let is_fifo = std::os::unix::fs::FileTypeExt::is_fifo(&metadata.file_type());
+    #[cfg(not(unix))]
+    let is_fifo = false;
+    if !metadata.is_file() && !is_fifo {
+        return Err("not a regular file".to_string());
+    }
+    // opening a fifo for reading would block until there's a writer
+    if !is_fifo {
+        File::open(path).map_err(|e| describe(e.kind()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Command {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&[]), Command::UsageError);
+        assert_eq!(parse(&["a.log", "b.log"]), Command::UsageError);
+        assert_eq!(parse(&["--unknown", "a.log"]), Command::UsageError);
+        assert_eq!(
+            parse(&["a.log"]),
+            Command::View(ViewOptions {
+                filename: "a.log".to_string(),
+                ..ViewOptions::default()
+            })
+        );
+        assert_eq!(
+            parse(&["--no-session", "a.log"]),
+            Command::View(ViewOptions {
+                filename: "a.log".to_string(),
+                no_session: true,
+                ..ViewOptions::default()
+            })
+        );
+        assert_eq!(
+            parse(&["a.log", "-d", "crlf"]),
+            Command::View(ViewOptions {
+                filename: "a.log".to_string(),
+                line_delimiter: Some("\r\n".to_string()),
+                ..ViewOptions::default()
+            })
+        );
+        assert_eq!(
+            parse(&[
+                "--raw-output",
+                "--color",
+                "--filter-profile",
+                "nginx",
+                "a.log"
+            ]),
+            Command::View(ViewOptions {
+                filename: "a.log".to_string(),
+                raw_output: true,
+                color: true,
+                filter_profile: Some("nginx".to_string()),
+                ..ViewOptions::default()
+            })
+        );
+        assert_eq!(parse(&["a.log", "--filter-profile"]), Command::UsageError);
+        assert_eq!(
+            parse(&["--control", "/tmp/loss.sock", "a.log"]),
+            Command::View(ViewOptions {
+                filename: "a.log".to_string(),
+                control_path: Some("/tmp/loss.sock".to_string()),
+                ..ViewOptions::default()
+            })
+        );
+        let Command::View(options) = parse(&["-n", "500", "a.log"]) else {
+            panic!("expect view command");
+        };
+        assert_eq!(options.start_position(), StartPosition::LastLines(500));
+        assert_eq!(
+            parse(&["--lines", "500", "+F", "a.log"]),
+            Command::UsageError
+        );
+        let Command::View(options) = parse(&["+F", "a.log"]) else {
+            panic!("expect view command");
+        };
+        assert_eq!(options.start_position(), StartPosition::Follow);
+        assert_eq!(parse(&["-n", "0", "a.log"]), Command::UsageError);
+        let Command::View(options) = parse(&["+4821", "a.log"]) else {
+            panic!("expect view command");
+        };
+        assert_eq!(options.start_position(), StartPosition::Line(4821));
+        for conflicting in [
+            &["+4821", "+F", "a.log"][..],
+            &["-n", "500", "+4821", "a.log"],
+            &["+F", "+12", "-n", "3", "a.log"],
+        ] {
+            assert_eq!(parse(conflicting), Command::UsageError, "{conflicting:?}");
+        }
+        for invalid in ["+0", "+", "+-3", "+12x"] {
+            assert_eq!(parse(&[invalid, "a.log"]), Command::UsageError, "{invalid}");
+        }
+
+        // a tail is never restored to the position of last visit
+        let Command::View(options) = parse(&["--tail-bytes", "50M", "a.log"]) else {
+            panic!("expect view command");
+        };
+        assert_eq!(options.tail_bytes, Some(50 << 20));
+        let mut config = Config::default();
+        options.apply_to(&mut config);
+        assert_eq!(config.tail_bytes, Some(50 << 20));
+        assert!(!config.restore_session);
+        assert_eq!(parse(&["--tail-bytes", "0", "a.log"]), Command::UsageError);
+        assert_eq!(parse(&["a.log", "--tail-bytes"]), Command::UsageError);
+
+        // `--log` takes precedence over the config file and `$LOSS_LOG`
+        let mut config = Config {
+            log_file: LogFile::Path("/tmp/env.log".into()),
+            ..Config::default()
+        };
+        let Command::View(options) = parse(&["--log=/tmp/flag.log", "a.log"]) else {
+            panic!("expect view command");
+        };
+        options.apply_to(&mut config);
+        assert_eq!(config.log_file, LogFile::Path("/tmp/flag.log".into()));
+        let Command::View(options) = parse(&["--log", "a.log"]) else {
+            panic!("expect view command");
+        };
+        options.apply_to(&mut config);
+        assert_eq!(config.log_file, LogFile::Default);
+        assert_eq!(parse(&["-n", "x", "a.log"]), Command::UsageError);
+        assert_eq!(parse(&["a.log", "--delimiter"]), Command::UsageError);
+        assert_eq!(parse(&["a.log", "--delimiter", "xyz"]), Command::UsageError);
+
+        assert_eq!(
+            parse(&["--inspect", "--json", "-d", "crlf", "a.log"]),
+            Command::Inspect(InspectOptions {
+                filename: "a.log".to_string(),
+                line_delimiter: Some("\r\n".to_string()),
+                json: true,
+            })
+        );
+        assert_eq!(parse(&["--json", "a.log"]), Command::UsageError);
+        assert_eq!(parse(&["--inspect"]), Command::UsageError);
+
+        assert_eq!(
+            parse(&["--render-frame", "frame.json"]),
+            Command::RenderFrame("frame.json".to_string())
+        );
+        assert_eq!(parse(&["--render-frame"]), Command::UsageError);
+        assert_eq!(
+            parse(&["--render-frame", "frame.json", "a.log"]),
+            Command::UsageError
+        );
+    }
+
+    #[test]
+    fn test_parse_help_and_version() {
+        for flag in ["-v", "--version"] {
+            assert_eq!(parse(&[flag]), Command::PrintVersion);
+        }
+        for flag in ["-h", "--help"] {
+            assert_eq!(parse(&[flag]), Command::PrintHelp);
+        }
+        // on either side of the filename, and winning over what follows them
+        assert_eq!(parse(&["a.log", "--help"]), Command::PrintHelp);
+        assert_eq!(parse(&["--version", "a.log"]), Command::PrintVersion);
+        assert_eq!(parse(&["-h", "-v"]), Command::PrintHelp);
+        assert_eq!(parse(&["-v", "-h"]), Command::PrintVersion);
+        assert_eq!(parse(&["-h", "--unknown"]), Command::PrintHelp);
+        assert_eq!(parse(&["-n", "500", "a.log", "-v"]), Command::PrintVersion);
+        // an error before them is still one
+        assert_eq!(parse(&["--unknown", "-h"]), Command::UsageError);
+        assert_eq!(parse(&["a.log", "b.log", "--help"]), Command::UsageError);
+        // taken as the value of the option before them
+        assert_eq!(parse(&["-n", "--help", "a.log"]), Command::UsageError);
+        assert_eq!(parse(&["-vh"]), Command::UsageError);
+    }
+
+    #[test]
+    fn test_parse_byte_count() {
+        assert_eq!(parse_byte_count("1000"), Some(1000));
+        assert_eq!(parse_byte_count("512K"), Some(512 * 1024));
+        assert_eq!(parse_byte_count("50m"), Some(50 * 1024 * 1024));
+        assert_eq!(parse_byte_count("2G"), Some(2 * 1024 * 1024 * 1024));
+        for invalid in ["", "M", "0", "0K", "-5", "1.5M", "10T", "5MB"] {
+            assert_eq!(parse_byte_count(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_line_suffix() {
+        let dir = std::env::temp_dir().join(format!("loss-suffix-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).display().to_string();
+        fs::write(path("app.log"), "").unwrap();
+        fs::write(path("ts.log:12"), "").unwrap();
+        fs::write(path("ts.log"), "").unwrap();
+        let resolve = |filename: String| {
+            let mut options = ViewOptions {
+                filename,
+                ..ViewOptions::default()
+            };
+            options.resolve_line_suffix();
+            (options.filename, options.line_number)
+        };
+
+        assert_eq!(resolve(path("app.log:4821")), (path("app.log"), Some(4821)));
+        assert_eq!(resolve(path("app.log")), (path("app.log"), None));
+        // a file named with the colon wins over the one without the suffix
+        assert_eq!(resolve(path("ts.log:12")), (path("ts.log:12"), None));
+        // neither exists, the error is told about the name as given
+        assert_eq!(
+            resolve(path("missing.log:7")),
+            (path("missing.log:7"), None)
+        );
+        for not_a_line in ["app.log:", "app.log:0", "app.log:12a", "app.log:+3"] {
+            assert_eq!(resolve(path(not_a_line)), (path(not_a_line), None));
+        }
+        // a suffix is a start position too, which `+F` or `-n` would conflict with
+        let mut options = ViewOptions {
+            filename: path("app.log:7"),
+            follow: true,
+            ..ViewOptions::default()
+        };
+        assert!(!options.has_conflicting_start());
+        options.resolve_line_suffix();
+        assert!(options.has_conflicting_start());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_input_file() {
+        let dir = std::env::temp_dir().join(format!("loss-cli-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.log");
+        fs::write(&file, "").unwrap();
+        assert_eq!(check_input_file(&file), Ok(()));
+        assert_eq!(check_input_file(&dir), Err("is a directory".to_string()));
+        assert_eq!(
+            check_input_file(&dir.join("missing.log")),
+            Err("no such file or directory".to_string())
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o000)).unwrap();
+            // root reads anything regardless of permissions
+            if File::open(&file).is_err() {
+                assert_eq!(
+                    check_input_file(&file),
+                    Err("permission denied".to_string())
+                );
+            }
+            assert_eq!(
+                check_input_file(Path::new("/dev/null")),
+                Err("not a regular file".to_string())
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
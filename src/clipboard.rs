@@ -0,0 +1,84 @@
+use std::io::{stdout, Stdout, Write};
+
+use anyhow::{anyhow, Ok, Result};
+
+// terminals cap what they accept in one sequence, commonly around 100 KB of encoded text
+const COPY_MAX_BYTES: usize = 74_000;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Copies text to the system clipboard with OSC 52, through the terminal so that it works over
+/// ssh too. Written to stdout unless another writer is given, terminals not supporting the
+/// sequence ignore it.
+pub struct Clipboard<W: Write = Stdout> {
+    writer: W,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self::with_writer(stdout())
+    }
+}
+
+impl<W: Write> Clipboard<W> {
+    pub fn with_writer(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn copy(&mut self, text: &str) -> Result<()> {
+        if text.len() > COPY_MAX_BYTES {
+            return Err(anyhow!(
+                "too large to copy, at most {} KB",
+                COPY_MAX_BYTES / 1000
+            ));
+        }
+        write!(self.writer, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let padded = [
+            group[0],
+            *group.get(1).unwrap_or(&0),
+            *group.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, padded[0], padded[1], padded[2]]);
+        for index in 0..4 {
+            if index <= group.len() {
+                let sextet = (bits >> (18 - index * 6)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar\n"), "Zm9vYmFyCg==");
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut clipboard = Clipboard::with_writer(vec![]);
+        clipboard.copy("a\nb").unwrap();
+        assert_eq!(clipboard.writer, b"\x1b]52;c;YQpi\x07");
+        assert!(clipboard.copy(&"x".repeat(COPY_MAX_BYTES + 1)).is_err());
+        assert_eq!(clipboard.writer.len(), 12);
+    }
+}
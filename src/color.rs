@@ -0,0 +1,364 @@
+use std::{env, time::Duration};
+
+use anyhow::{anyhow, Result};
+use crossterm::style::Color;
+
+// the 16 ansi colors in palette order, with their usual xterm values
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// component levels of the 6x6x6 cube taking indexes 16 to 231 of the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Colors a terminal can show, highlights are mapped down to what's supported.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorSupport {
+    // highlights fall back to text attributes
+    None,
+    #[default]
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Guesses from the values of `NO_COLOR`, `COLORTERM` and `TERM`.
+    pub fn detect(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Self {
+        // any non-empty value counts, see https://no-color.org
+        if no_color.is_some_and(|value| !value.is_empty()) || term == Some("dumb") {
+            Self::None
+        } else if matches!(colorterm, Some("truecolor" | "24bit")) {
+            Self::TrueColor
+        } else if term.is_some_and(|term| term.contains("256color")) {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let var = |key| env::var(key).ok();
+        Self::detect(
+            var("NO_COLOR").as_deref(),
+            var("COLORTERM").as_deref(),
+            var("TERM").as_deref(),
+        )
+    }
+
+    // `off`, `16`, `256` or `truecolor`, `auto` is handled by the caller
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "off" => Ok(Self::None),
+            "16" => Ok(Self::Ansi16),
+            "256" => Ok(Self::Ansi256),
+            "truecolor" => Ok(Self::TrueColor),
+            _ => Err(anyhow!("invalid color support `{value}`")),
+        }
+    }
+
+    /// The nearest color that can be shown, `Color::Reset` if there's no color at all.
+    pub fn map(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::None, _) => Color::Reset,
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(nearest_ansi256(r, g, b)),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => nearest_ansi16(r, g, b),
+            (Self::Ansi16, Color::AnsiValue(index)) => {
+                let (r, g, b) = ansi256_to_rgb(index);
+                nearest_ansi16(r, g, b)
+            }
+            _ => color,
+        }
+    }
+}
+
+/// Whether the terminal background is dark or light, which picks the highlight palette.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Background {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Background {
+    // `dark` or `light`, `auto` is handled by the caller
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "dark" => Ok(Self::Dark),
+            "light" => Ok(Self::Light),
+            _ => Err(anyhow!("invalid background `{value}`")),
+        }
+    }
+
+    /// Classifies the color in an OSC 11 answer like `\x1b]11;rgb:ffff/ffff/dddd\x07`, ended
+    /// by BEL or ST. Other bytes around it, e.g. the answer to another query, are skipped.
+    pub fn from_osc11_response(response: &[u8]) -> Option<Self> {
+        let response = String::from_utf8_lossy(response);
+        let (_, body) = response.split_once("\x1b]11;")?;
+        let end = body.find(['\x07', '\x1b'])?;
+        let (_, components) = body[..end].split_once(':')?;
+        let channels: Vec<f64> = components
+            .split('/')
+            .take(3)
+            .map(parse_hex_channel)
+            .collect::<Option<_>>()?;
+        let [r, g, b] = channels[..] else {
+            return None;
+        };
+        let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        Some(if luminance > 0.5 {
+            Self::Light
+        } else {
+            Self::Dark
+        })
+    }
+}
+
+// 1 to 4 hex digits scaled to 0.0..=1.0
+fn parse_hex_channel(hex: &str) -> Option<f64> {
+    if hex.is_empty() || hex.len() > 4 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let max = (1u32 << (4 * hex.len())) - 1;
+    Some(value as f64 / max as f64)
+}
+
+/// Asks the terminal for its background color, `None` if it doesn't answer within `timeout`.
+/// Input is read directly from the terminal, so this must run before events are read. A
+/// terminal answering no query at all leaves the reader waiting, taking the first key pressed.
+#[cfg(unix)]
+pub fn query_background(timeout: Duration) -> Option<Background> {
+    use std::{
+        fs::OpenOptions,
+        io::{Read, Write},
+        sync::mpsc,
+        thread,
+    };
+
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let mut reader = tty.try_clone().ok()?;
+    // without raw mode the answer would be echoed and held until a newline
+    let was_raw = is_raw_mode_enabled().ok()?;
+    if !was_raw {
+        enable_raw_mode().ok()?;
+    }
+    // nearly every terminal answers the device attributes query, which ends the read even
+    // if the background query is ignored
+    let response = tty
+        .write_all(b"\x1b]11;?\x07\x1b[c")
+        .and_then(|_| tty.flush())
+        .ok()
+        .and_then(|_| {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let mut response = vec![];
+                let mut byte = [0];
+                while !device_attributes_answered(&response)
+                    && reader.read(&mut byte).is_ok_and(|n| n == 1)
+                {
+                    response.push(byte[0]);
+                }
+                let _ = sender.send(response);
+            });
+            receiver.recv_timeout(timeout).ok()
+        });
+    if !was_raw {
+        let _ = disable_raw_mode();
+    }
+    Background::from_osc11_response(&response?)
+}
+
+#[cfg(not(unix))]
+pub fn query_background(_timeout: Duration) -> Option<Background> {
+    None
+}
+
+// the answer to `\x1b[c` is `\x1b[?` followed by attributes and ended by `c`
+#[cfg_attr(not(unix), allow(dead_code))]
+fn device_attributes_answered(response: &[u8]) -> bool {
+    response
+        .windows(3)
+        .position(|window| window == b"\x1b[?")
+        .is_some_and(|start| response[start..].ends_with(b"c"))
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let square = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    square(r1, r2) + square(g1, g2) + square(b1, b2)
+}
+
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .unwrap()
+        .0
+}
+
+fn nearest_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |component: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|index| (CUBE_LEVELS[*index] as i32 - component as i32).abs())
+            .unwrap() as u8
+    };
+    let cube = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+    // the grayscale ramp is finer than the cube diagonal
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    [cube, gray]
+        .into_iter()
+        .min_by_key(|index| distance(ansi256_to_rgb(*index), (r, g, b)))
+        .unwrap()
+}
+
+fn ansi256_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI16[index as usize].1,
+        16..=231 => {
+            let index = index - 16;
+            (
+                CUBE_LEVELS[(index / 36) as usize],
+                CUBE_LEVELS[(index / 6 % 6) as usize],
+                CUBE_LEVELS[(index % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let detect = ColorSupport::detect;
+        assert_eq!(detect(None, None, None), ColorSupport::Ansi16);
+        assert_eq!(detect(None, None, Some("xterm")), ColorSupport::Ansi16);
+        assert_eq!(
+            detect(None, None, Some("xterm-256color")),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            detect(None, Some("truecolor"), Some("xterm-256color")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(detect(None, None, Some("dumb")), ColorSupport::None);
+        assert_eq!(
+            detect(Some("1"), Some("truecolor"), Some("xterm")),
+            ColorSupport::None
+        );
+        // an empty `NO_COLOR` is ignored
+        assert_eq!(detect(Some(""), None, Some("xterm")), ColorSupport::Ansi16);
+    }
+
+    #[test]
+    fn test_map() {
+        let orange = Color::Rgb {
+            r: 255,
+            g: 135,
+            b: 0,
+        };
+        assert_eq!(ColorSupport::TrueColor.map(orange), orange);
+        assert_eq!(ColorSupport::Ansi256.map(orange), Color::AnsiValue(208));
+        assert_eq!(ColorSupport::Ansi16.map(orange), Color::DarkYellow);
+        assert_eq!(ColorSupport::None.map(orange), Color::Reset);
+
+        let gray = Color::Rgb {
+            r: 100,
+            g: 100,
+            b: 100,
+        };
+        assert_eq!(ColorSupport::Ansi256.map(gray), Color::AnsiValue(241));
+        assert_eq!(ColorSupport::Ansi16.map(gray), Color::DarkGrey);
+
+        // named colors are understood everywhere
+        assert_eq!(ColorSupport::Ansi16.map(Color::Blue), Color::Blue);
+        assert_eq!(ColorSupport::Ansi16.map(Color::AnsiValue(9)), Color::Red);
+        assert_eq!(
+            ColorSupport::Ansi16.map(Color::AnsiValue(21)),
+            Color::DarkBlue
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.map(Color::AnsiValue(21)),
+            Color::AnsiValue(21)
+        );
+    }
+
+    #[test]
+    fn test_from_osc11_response() {
+        let parse = Background::from_osc11_response;
+        assert_eq!(
+            parse(b"\x1b]11;rgb:ffff/ffff/ffff\x07"),
+            Some(Background::Light)
+        );
+        assert_eq!(
+            parse(b"\x1b]11;rgb:1e1e/1e1e/2e2e\x1b\\"),
+            Some(Background::Dark)
+        );
+        // short components and the answer to the device attributes query after it
+        assert_eq!(
+            parse(b"\x1b]11;rgb:fd/f6/e3\x07\x1b[?62;22c"),
+            Some(Background::Light)
+        );
+        assert_eq!(
+            parse(b"\x1b]11;rgba:0000/0000/0000/ffff\x07"),
+            Some(Background::Dark)
+        );
+        // only the device attributes were answered, or the answer is cut short
+        assert_eq!(parse(b"\x1b[?62;22c"), None);
+        assert_eq!(parse(b"\x1b]11;rgb:ffff/ff"), None);
+        assert_eq!(parse(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+        assert_eq!(parse(b"\x1b]11;rgb:fffff/0/0\x07"), None);
+        assert_eq!(parse(b""), None);
+    }
+
+    #[test]
+    fn test_device_attributes_answered() {
+        assert!(!device_attributes_answered(b""));
+        assert!(!device_attributes_answered(
+            b"\x1b]11;rgb:0/0/0\x07\x1b[?62"
+        ));
+        assert!(device_attributes_answered(
+            b"\x1b]11;rgb:0/0/0\x07\x1b[?62;22c"
+        ));
+        assert!(device_attributes_answered(b"\x1b[?1;2c"));
+    }
+
+    #[test]
+    fn test_ansi256_round_trip() {
+        for index in 16..=255 {
+            let (r, g, b) = ansi256_to_rgb(index);
+            assert_eq!(
+                ansi256_to_rgb(nearest_ansi256(r, g, b)),
+                (r, g, b),
+                "{index}"
+            );
+        }
+    }
+}
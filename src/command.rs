@@ -0,0 +1,355 @@
+use anyhow::{anyhow, Ok, Result};
+
+use crate::config::{parse_context_lines, parse_scroll_off, ScrollOff};
+
+/// A command entered in the `:` prompt.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Edit(String),
+    // write lines passing the fold and exclusive slots to a file, with the highlights shown as
+    // ANSI colors if the flag is set
+    Write(String, bool),
+    // write the offset and text of each line collected by the match list
+    WriteMatches(String),
+    Set(Setting),
+    LoadProfile(String),
+    // 1-based line number
+    GotoLine(usize),
+    JumpToTimestamp(String),
+    // follow mode stopping at the first appended line matching the pattern, or the active
+    // search pattern if `None`
+    FollowUntil(Option<String>),
+    // installs a filter expression hiding the lines it doesn't match, `None` removes it
+    Filter(Option<String>),
+    // the top line becomes the start of the document, until `Untrim`
+    Trim,
+    Untrim,
+    // re-read the config file and the active profile
+    ReloadConfig,
+    // write the frame on screen as json, to look into rendering issues
+    DumpFrame(String),
+    Help,
+    Quit,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Setting {
+    Wrap(bool),
+    Plain(bool),
+    Invisibles(bool),
+    ScrollOff(Option<ScrollOff>),
+    ContextLines(usize),
+    StepLineMatches(bool),
+}
+
+// names completed with tab, in the order listed by `:help`
+const COMMAND_NAMES: [&str; 14] = [
+    "edit",
+    "write",
+    "matches",
+    "set",
+    "profile",
+    "timestamp",
+    "follow-until",
+    "filter",
+    "trim",
+    "untrim",
+    "reload-config",
+    "dump-frame",
+    "help",
+    "quit",
+];
+
+pub fn help_text() -> String {
+    "Commands: e <path>, w [--color] <path>, matches <path>, \
+     set wrap|nowrap|plain|noplain|invisibles|noinvisibles|linematches|nolinematches|scrolloff=<n|center>|context=<n>, profile load <name>, t <timestamp>, follow-until [pattern], filter [expr], trim, untrim, reload-config, dump-frame <path>, <line>, help, q"
+        .to_string()
+}
+
+pub fn parse_command(input: &str) -> Result<Command> {
+    // an expression has quotes and whitespace of its own, it's taken as is
+    if let Some(expr) = input.trim_start().strip_prefix("filter") {
+        if expr.is_empty() || expr.starts_with(char::is_whitespace) {
+            let expr = expr.trim();
+            return Ok(Command::Filter(
+                (!expr.is_empty()).then(|| expr.to_string()),
+            ));
+        }
+    }
+    let words = split_words(input)?;
+    let Some((name, args)) = words.split_first() else {
+        return Err(anyhow!("Empty command"));
+    };
+    if let std::result::Result::Ok(line_number) = name.parse::<usize>() {
+        expect_arg_count(name, args, 0)?;
+        return Ok(Command::GotoLine(line_number));
+    }
+    match name.as_str() {
+        "e" | "edit" => {
+            expect_arg_count(name, args, 1)?;
+            Ok(Command::Edit(args[0].clone()))
+        }
+        "w" | "write" => match args {
+            [flag, filename] | [filename, flag] if flag == "--color" => {
+                Ok(Command::Write(filename.clone(), true))
+            }
+            [filename] => Ok(Command::Write(filename.clone(), false)),
+            _ => Err(anyhow!("Usage: w [--color] <path>")),
+        },
+        "matches" => {
+            expect_arg_count(name, args, 1)?;
+            Ok(Command::WriteMatches(args[0].clone()))
+        }
+        "set" => {
+            expect_arg_count(name, args, 1)?;
+            parse_setting(&args[0]).map(Command::Set)
+        }
+        "profile" => match args {
+            [action, profile] if action == "load" => Ok(Command::LoadProfile(profile.clone())),
+            _ => Err(anyhow!("Usage: profile load <name>")),
+        },
+        // a timestamp usually has a space between date and time
+        "t" | "timestamp" if !args.is_empty() => Ok(Command::JumpToTimestamp(args.join(" "))),
+        "t" | "timestamp" => Err(anyhow!("Usage: t <timestamp>")),
+        "follow-until" => match args {
+            [] => Ok(Command::FollowUntil(None)),
+            [pattern] => Ok(Command::FollowUntil(Some(pattern.clone()))),
+            _ => Err(anyhow!("Usage: follow-until [pattern]")),
+        },
+        "trim" => {
+            expect_arg_count(name, args, 0)?;
+            Ok(Command::Trim)
+        }
+        "untrim" => {
+            expect_arg_count(name, args, 0)?;
+            Ok(Command::Untrim)
+        }
+        "reload-config" => {
+            expect_arg_count(name, args, 0)?;
+            Ok(Command::ReloadConfig)
+        }
+        "dump-frame" => {
+            expect_arg_count(name, args, 1)?;
+            Ok(Command::DumpFrame(args[0].clone()))
+        }
+        "h" | "help" => Ok(Command::Help),
+        "q" | "quit" => Ok(Command::Quit),
+        _ => Err(anyhow!("Unknown command: {name}")),
+    }
+}
+
+fn expect_arg_count(name: &str, args: &[String], count: usize) -> Result<()> {
+    if args.len() != count {
+        return Err(anyhow!("{name} expects {count} argument(s)"));
+    }
+    Ok(())
+}
+
+fn parse_setting(arg: &str) -> Result<Setting> {
+    match arg.split_once('=') {
+        None => match arg {
+            "wrap" => Ok(Setting::Wrap(true)),
+            "nowrap" => Ok(Setting::Wrap(false)),
+            "plain" => Ok(Setting::Plain(true)),
+            "noplain" => Ok(Setting::Plain(false)),
+            "invisibles" => Ok(Setting::Invisibles(true)),
+            "noinvisibles" => Ok(Setting::Invisibles(false)),
+            "linematches" => Ok(Setting::StepLineMatches(true)),
+            "nolinematches" => Ok(Setting::StepLineMatches(false)),
+            _ => Err(anyhow!("Unknown setting: {arg}")),
+        },
+        Some(("scrolloff" | "scroll_off", value)) => {
+            let scroll_off = parse_scroll_off(value).map_err(|_| anyhow!("Invalid {arg}"))?;
+            Ok(Setting::ScrollOff(scroll_off))
+        }
+        Some(("context", value)) => {
+            let context_lines = parse_context_lines(value).map_err(|_| anyhow!("Invalid {arg}"))?;
+            Ok(Setting::ContextLines(context_lines))
+        }
+        Some((key, _)) => Err(anyhow!("Unknown setting: {key}")),
+    }
+}
+
+// splits on whitespace, single or double quotes keep whitespace in a word and a backslash
+// escapes the next char outside of single quotes
+fn split_words(input: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some('\''), _) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                let escaped = chars.next().ok_or_else(|| anyhow!("Trailing backslash"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, _) if c.is_whitespace() => words.extend(word.take()),
+            _ => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("Unterminated quote"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Completes the command name being typed, `None` if there's no single candidate.
+pub fn complete_command(input: &str) -> Option<String> {
+    let prefix = input.trim_start();
+    if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+        return None;
+    }
+    let mut candidates = COMMAND_NAMES.iter().filter(|name| name.starts_with(prefix));
+    match (candidates.next(), candidates.next()) {
+        (Some(name), None) => Some(format!("{name} ")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words("  e  a.log ").unwrap(), ["e", "a.log"]);
+        assert_eq!(
+            split_words(r#"w "my file.txt""#).unwrap(),
+            ["w", "my file.txt"]
+        );
+        assert_eq!(
+            split_words(r"w 'a \ b' c\ d").unwrap(),
+            ["w", r"a \ b", "c d"]
+        );
+        assert_eq!(split_words(r#"w """#).unwrap(), ["w", ""]);
+        assert!(split_words(r#"w "a.log"#).is_err());
+        assert!(split_words(r"w a\").is_err());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command("e /var/log/a.log").unwrap(),
+            Command::Edit("/var/log/a.log".to_string())
+        );
+        assert_eq!(
+            parse_command("w 'filtered out.txt'").unwrap(),
+            Command::Write("filtered out.txt".to_string(), false)
+        );
+        assert_eq!(
+            parse_command("w --color out.txt").unwrap(),
+            Command::Write("out.txt".to_string(), true)
+        );
+        assert_eq!(
+            parse_command("w out.txt --color").unwrap(),
+            Command::Write("out.txt".to_string(), true)
+        );
+        // a path named like the flag comes with the flag
+        assert_eq!(
+            parse_command("w --color --color").unwrap(),
+            Command::Write("--color".to_string(), true)
+        );
+        assert!(parse_command("w a.txt b.txt").is_err());
+        assert_eq!(
+            parse_command("matches errors.txt").unwrap(),
+            Command::WriteMatches("errors.txt".to_string())
+        );
+        assert_eq!(
+            parse_command(r#"filter  svc=auth AND NOT msg~"time out" "#).unwrap(),
+            Command::Filter(Some(r#"svc=auth AND NOT msg~"time out""#.to_string()))
+        );
+        assert_eq!(parse_command("filter").unwrap(), Command::Filter(None));
+        assert!(parse_command("filterx").is_err());
+        assert_eq!(parse_command("trim").unwrap(), Command::Trim);
+        assert_eq!(parse_command(" untrim ").unwrap(), Command::Untrim);
+        assert!(parse_command("trim 10").is_err());
+        assert_eq!(
+            parse_command("set nowrap").unwrap(),
+            Command::Set(Setting::Wrap(false))
+        );
+        assert_eq!(
+            parse_command("set scrolloff=5").unwrap(),
+            Command::Set(Setting::ScrollOff(Some(ScrollOff::Lines(5))))
+        );
+        assert_eq!(
+            parse_command("set scrolloff=center").unwrap(),
+            Command::Set(Setting::ScrollOff(Some(ScrollOff::Center)))
+        );
+        assert!(parse_command("set scrolloff=top").is_err());
+        assert_eq!(
+            parse_command("set noinvisibles").unwrap(),
+            Command::Set(Setting::Invisibles(false))
+        );
+        assert_eq!(
+            parse_command("set linematches").unwrap(),
+            Command::Set(Setting::StepLineMatches(true))
+        );
+        assert_eq!(
+            parse_command("set context=3").unwrap(),
+            Command::Set(Setting::ContextLines(3))
+        );
+        assert!(parse_command("set context=some").is_err());
+        assert_eq!(
+            parse_command("profile load nginx").unwrap(),
+            Command::LoadProfile("nginx".to_string())
+        );
+        assert!(parse_command("profile save nginx").is_err());
+        assert_eq!(
+            parse_command("t 2024-05-01 10:00:00").unwrap(),
+            Command::JumpToTimestamp("2024-05-01 10:00:00".to_string())
+        );
+        assert!(parse_command("timestamp").is_err());
+        assert_eq!(
+            parse_command("follow-until 'out of memory'").unwrap(),
+            Command::FollowUntil(Some("out of memory".to_string()))
+        );
+        assert_eq!(
+            parse_command("follow-until").unwrap(),
+            Command::FollowUntil(None)
+        );
+        assert!(parse_command("follow-until out of memory").is_err());
+        assert_eq!(parse_command("42").unwrap(), Command::GotoLine(42));
+        assert!(parse_command("42 43").is_err());
+        assert!(parse_command("-1").is_err());
+        assert_eq!(
+            parse_command("reload-config").unwrap(),
+            Command::ReloadConfig
+        );
+        assert_eq!(
+            parse_command("dump-frame /tmp/frame.json").unwrap(),
+            Command::DumpFrame("/tmp/frame.json".to_string())
+        );
+        assert!(parse_command("dump-frame").is_err());
+        assert_eq!(parse_command("q").unwrap(), Command::Quit);
+        assert!(parse_command("e").is_err());
+        assert!(parse_command("").is_err());
+        assert_eq!(
+            parse_command("frobnicate").unwrap_err().to_string(),
+            "Unknown command: frobnicate"
+        );
+        assert_eq!(
+            parse_command("set colors").unwrap_err().to_string(),
+            "Unknown setting: colors"
+        );
+    }
+
+    #[test]
+    fn test_complete_command() {
+        assert_eq!(complete_command("pro"), Some("profile ".to_string()));
+        assert_eq!(complete_command("e"), Some("edit ".to_string()));
+        assert_eq!(complete_command("rel"), Some("reload-config ".to_string()));
+        assert_eq!(complete_command("fo"), Some("follow-until ".to_string()));
+        assert_eq!(complete_command("fi"), Some("filter ".to_string()));
+        assert_eq!(complete_command("f"), None);
+        assert_eq!(complete_command("x"), None);
+        assert_eq!(complete_command(""), None);
+        assert_eq!(complete_command("set w"), None);
+    }
+}
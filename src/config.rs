@@ -0,0 +1,716 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Ok, Result};
+
+use chrono::FixedOffset;
+use log::LevelFilter;
+use loss_viewer::{chunk::DEFAULT_LINE_DELIMITER, log_timestamp::parse_timezone};
+
+use crate::{
+    color::{Background, ColorSupport},
+    event_source::MoveSteps,
+    notifier::{Notification, NotifyPolicy},
+    status_bar::StatusLayout,
+};
+
+/// Lines kept above the target line when a search or jump moves the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollOff {
+    Lines(usize),
+    Center,
+}
+
+/// What to do when a scroll hits the start or end of the document.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BoundaryFeedback {
+    #[default]
+    Flash,
+    Bell,
+    Off,
+}
+
+/// Where the debug log is written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogFile {
+    Off,
+    // `loss.log` in the cache directory
+    Default,
+    Path(PathBuf),
+}
+
+impl LogFile {
+    // `off`, `default` or a path
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "off" | "" => Self::Off,
+            "default" => Self::Default,
+            _ => Self::Path(PathBuf::from(value)),
+        }
+    }
+
+    pub fn resolve(&self) -> Option<PathBuf> {
+        match self {
+            Self::Off => None,
+            Self::Default => cache_dir().map(|dir| dir.join("loss.log")),
+            Self::Path(path) => Some(path.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub restore_session: bool,
+    pub line_delimiter: String,
+    // `None` for the local timezone
+    pub log_timezone: Option<FixedOffset>,
+    // rows each line takes at most when wrapped lines are folded
+    pub wrap_row_limit: usize,
+    // leads the rows after the first of a wrapped line, empty for none
+    pub wrap_indicator: String,
+    // whether fold and exclusive slots still hide lines in plain mode
+    pub plain_mode_keeps_filters: bool,
+    // lines shown around each exclusive match, like `grep -C`
+    pub context_lines: usize,
+    // `None` keeps the target at the window top
+    pub scroll_off: Option<ScrollOff>,
+    pub boundary_feedback: BoundaryFeedback,
+    // `None` detects it from the environment
+    pub color: Option<ColorSupport>,
+    // `None` asks the terminal
+    pub background: Option<Background>,
+    pub notify: NotifyPolicy,
+    pub status_layout: StatusLayout,
+    pub move_steps: MoveSteps,
+    // whether a held key repeats, on terminals reporting repeats apart from presses
+    pub key_repeat: bool,
+    // whether `n` and `N` step through the matches of a line before leaving it
+    pub step_line_matches: bool,
+    // whether bare digits switch the active slot outside the finder menu too
+    pub direct_slot_digits: bool,
+    // whether a slow frame is told in the status bar, besides the warning in the log
+    pub show_slow_frames: bool,
+    // `--log` takes precedence over `$LOSS_LOG`, which takes precedence over the config file
+    pub log_file: LogFile,
+    pub log_level: LevelFilter,
+    // only the last bytes of the file are read, set by `--tail-bytes` rather than the file
+    pub tail_bytes: Option<usize>,
+    // a file not modified for longer is warned about on startup, `None` for never
+    pub stale_after: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            restore_session: true,
+            line_delimiter: DEFAULT_LINE_DELIMITER.to_string(),
+            log_timezone: None,
+            wrap_row_limit: 3,
+            wrap_indicator: String::default(),
+            plain_mode_keeps_filters: true,
+            context_lines: 0,
+            scroll_off: None,
+            boundary_feedback: BoundaryFeedback::Flash,
+            color: None,
+            background: None,
+            notify: NotifyPolicy::default(),
+            status_layout: StatusLayout::default(),
+            move_steps: MoveSteps::default(),
+            key_repeat: false,
+            step_line_matches: false,
+            direct_slot_digits: false,
+            show_slow_frames: false,
+            log_file: LogFile::Off,
+            log_level: LevelFilter::Info,
+            tail_bytes: None,
+            stale_after: Some(Duration::from_secs(24 * 3600)),
+        }
+    }
+}
+
+/// Keys changed by a reloaded config file.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigChanges {
+    pub applied: Vec<&'static str>,
+    // kept at their current values until loss is restarted
+    pub restart_required: Vec<&'static str>,
+}
+
+// keys that only take effect at startup
+const RESTART_REQUIRED_KEYS: [&str; 5] = [
+    "restore_session",
+    "line_delimiter",
+    "log_file",
+    "log_level",
+    "stale_after",
+];
+
+impl Config {
+    // missing config file is not an error, defaults are used instead
+    pub fn load() -> Result<Self> {
+        let mut config = match config_file_path() {
+            Some(path) => match fs::read_to_string(&path) {
+                std::result::Result::Ok(content) => Self::parse(&content)
+                    .map_err(|e| anyhow!("invalid config file {}: {e}", path.display()))?,
+                Err(_) => Self::default(),
+            },
+            None => Self::default(),
+        };
+        config.apply_log_env(env::var("LOSS_LOG").ok().as_deref());
+        Ok(config)
+    }
+
+    // applies what changed between two loads of the config file. values overridden on the
+    // command line are all restart-required, so they are never touched here
+    pub fn apply_reloaded(&mut self, previous: &Self, reloaded: &Self) -> ConfigChanges {
+        let mut changes = ConfigChanges::default();
+        for key in previous.changed_keys(reloaded) {
+            if RESTART_REQUIRED_KEYS.contains(&key) {
+                changes.restart_required.push(key);
+                continue;
+            }
+            match key {
+                "log_timezone" => self.log_timezone = reloaded.log_timezone,
+                "wrap_row_limit" => self.wrap_row_limit = reloaded.wrap_row_limit,
+                "wrap_indicator" => self.wrap_indicator = reloaded.wrap_indicator.clone(),
+                "plain_mode_keeps_filters" => {
+                    self.plain_mode_keeps_filters = reloaded.plain_mode_keeps_filters
+                }
+                "context_lines" => self.context_lines = reloaded.context_lines,
+                "scroll_off" => self.scroll_off = reloaded.scroll_off,
+                "boundary_feedback" => self.boundary_feedback = reloaded.boundary_feedback,
+                "color" => self.color = reloaded.color,
+                "background" => self.background = reloaded.background,
+                "status_layout" => self.status_layout = reloaded.status_layout,
+                "step_arrow" => self.move_steps.arrow = reloaded.move_steps.arrow,
+                "step_page" => self.move_steps.page = reloaded.move_steps.page,
+                "step_ctrl_arrow" => self.move_steps.ctrl_arrow = reloaded.move_steps.ctrl_arrow,
+                "step_ctrl_page" => self.move_steps.ctrl_page = reloaded.move_steps.ctrl_page,
+                "key_repeat" => self.key_repeat = reloaded.key_repeat,
+                "step_line_matches" => self.step_line_matches = reloaded.step_line_matches,
+                "direct_slot_digits" => self.direct_slot_digits = reloaded.direct_slot_digits,
+                "show_slow_frames" => self.show_slow_frames = reloaded.show_slow_frames,
+                "notify_not_found" => self.notify.not_found = reloaded.notify.not_found,
+                "notify_follow_match" => self.notify.follow_match = reloaded.notify.follow_match,
+                "notify_error" => self.notify.error = reloaded.notify.error,
+                _ => unreachable!("unknown config key {key}"),
+            }
+            changes.applied.push(key);
+        }
+        changes
+    }
+
+    fn changed_keys(&self, other: &Self) -> Vec<&'static str> {
+        let mut keys = vec![];
+        let mut check = |key, changed| {
+            if changed {
+                keys.push(key);
+            }
+        };
+        check(
+            "restore_session",
+            self.restore_session != other.restore_session,
+        );
+        check(
+            "line_delimiter",
+            self.line_delimiter != other.line_delimiter,
+        );
+        check("log_timezone", self.log_timezone != other.log_timezone);
+        check(
+            "wrap_row_limit",
+            self.wrap_row_limit != other.wrap_row_limit,
+        );
+        check(
+            "wrap_indicator",
+            self.wrap_indicator != other.wrap_indicator,
+        );
+        check(
+            "plain_mode_keeps_filters",
+            self.plain_mode_keeps_filters != other.plain_mode_keeps_filters,
+        );
+        check("context_lines", self.context_lines != other.context_lines);
+        check("scroll_off", self.scroll_off != other.scroll_off);
+        check(
+            "boundary_feedback",
+            self.boundary_feedback != other.boundary_feedback,
+        );
+        check("color", self.color != other.color);
+        check("background", self.background != other.background);
+        check("status_layout", self.status_layout != other.status_layout);
+        let (steps, other_steps) = (&self.move_steps, &other.move_steps);
+        check("step_arrow", steps.arrow != other_steps.arrow);
+        check("step_page", steps.page != other_steps.page);
+        check(
+            "step_ctrl_arrow",
+            steps.ctrl_arrow != other_steps.ctrl_arrow,
+        );
+        check("step_ctrl_page", steps.ctrl_page != other_steps.ctrl_page);
+        check("key_repeat", self.key_repeat != other.key_repeat);
+        check(
+            "step_line_matches",
+            self.step_line_matches != other.step_line_matches,
+        );
+        check(
+            "direct_slot_digits",
+            self.direct_slot_digits != other.direct_slot_digits,
+        );
+        check(
+            "show_slow_frames",
+            self.show_slow_frames != other.show_slow_frames,
+        );
+        check(
+            "notify_not_found",
+            self.notify.not_found != other.notify.not_found,
+        );
+        check(
+            "notify_follow_match",
+            self.notify.follow_match != other.notify.follow_match,
+        );
+        check("notify_error", self.notify.error != other.notify.error);
+        check("log_file", self.log_file != other.log_file);
+        check("log_level", self.log_level != other.log_level);
+        check("stale_after", self.stale_after != other.stale_after);
+        keys
+    }
+
+    fn apply_log_env(&mut self, value: Option<&str>) {
+        if let Some(value) = value {
+            self.log_file = LogFile::parse(value);
+        }
+    }
+
+    // one `key = value` per line, `#` starts a comment
+    fn parse(content: &str) -> Result<Self> {
+        let mut config = Self::default();
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(anyhow!("line {}: expect `key = value`", line_number + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "restore_session" => config.restore_session = parse_bool(value)?,
+                "line_delimiter" => config.line_delimiter = parse_line_delimiter(value)?,
+                "log_timezone" => config.log_timezone = parse_log_timezone(value)?,
+                "plain_mode_keeps_filters" => config.plain_mode_keeps_filters = parse_bool(value)?,
+                "boundary_feedback" => {
+                    config.boundary_feedback = match value {
+                        "flash" => BoundaryFeedback::Flash,
+                        "bell" => BoundaryFeedback::Bell,
+                        "off" => BoundaryFeedback::Off,
+                        _ => return Err(anyhow!("invalid boundary feedback `{value}`")),
+                    }
+                }
+                "context_lines" => config.context_lines = parse_context_lines(value)?,
+                "scroll_off" => config.scroll_off = parse_scroll_off(value)?,
+                "color" => {
+                    config.color = match value {
+                        "auto" => None,
+                        _ => Some(ColorSupport::parse(value)?),
+                    }
+                }
+                "background" => {
+                    config.background = match value {
+                        "auto" => None,
+                        _ => Some(Background::parse(value)?),
+                    }
+                }
+                "status_layout" => config.status_layout = StatusLayout::parse(value)?,
+                "step_arrow" => config.move_steps.arrow = parse_step(value)?,
+                "step_page" => config.move_steps.page = parse_step(value)?,
+                "step_ctrl_arrow" => config.move_steps.ctrl_arrow = parse_step(value)?,
+                "step_ctrl_page" => config.move_steps.ctrl_page = parse_step(value)?,
+                "key_repeat" => config.key_repeat = parse_bool(value)?,
+                "step_line_matches" => config.step_line_matches = parse_bool(value)?,
+                "direct_slot_digits" => config.direct_slot_digits = parse_bool(value)?,
+                "show_slow_frames" => config.show_slow_frames = parse_bool(value)?,
+                "notify_not_found" => config.notify.not_found = Notification::parse(value)?,
+                "notify_follow_match" => config.notify.follow_match = Notification::parse(value)?,
+                "notify_error" => config.notify.error = Notification::parse(value)?,
+                "log_file" => config.log_file = LogFile::parse(value),
+                "log_level" => {
+                    config.log_level = match value {
+                        "info" => LevelFilter::Info,
+                        "debug" => LevelFilter::Debug,
+                        "trace" => LevelFilter::Trace,
+                        _ => return Err(anyhow!("invalid log level `{value}`")),
+                    }
+                }
+                "wrap_row_limit" => match value.parse() {
+                    std::result::Result::Ok(limit) if limit > 0 => config.wrap_row_limit = limit,
+                    _ => return Err(anyhow!("invalid row limit `{value}`")),
+                },
+                "wrap_indicator" => config.wrap_indicator = parse_wrap_indicator(value)?,
+                "stale_after" => config.stale_after = parse_stale_after(value)?,
+                _ => return Err(anyhow!("line {}: unknown key `{key}`", line_number + 1)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value {
+        "true" | "on" | "1" => Ok(true),
+        "false" | "off" | "0" => Ok(false),
+        _ => Err(anyhow!("invalid boolean `{value}`")),
+    }
+}
+
+fn parse_step(value: &str) -> Result<usize> {
+    match value.parse() {
+        std::result::Result::Ok(step) if step > 0 => Ok(step),
+        _ => Err(anyhow!("invalid step `{value}`")),
+    }
+}
+
+// accepts `lf`, `crlf`, `rs`, a `\xHH` escape or a single ascii char
+pub fn parse_line_delimiter(value: &str) -> Result<String> {
+    match value {
+        "lf" => return Ok("\n".to_string()),
+        "crlf" => return Ok("\r\n".to_string()),
+        "rs" => return Ok("\x1e".to_string()),
+        _ => {}
+    }
+    let byte = if let Some(hex) = value.strip_prefix("\\x") {
+        u8::from_str_radix(hex, 16).ok()
+    } else if value.len() == 1 {
+        Some(value.as_bytes()[0])
+    } else {
+        None
+    };
+    match byte {
+        // a non-ascii byte would split utf-8 sequences apart
+        Some(byte) if byte.is_ascii() => Ok((byte as char).to_string()),
+        _ => Err(anyhow!("invalid line delimiter `{value}`")),
+    }
+}
+
+// `off` or up to 4 printable chars
+fn parse_wrap_indicator(value: &str) -> Result<String> {
+    match value {
+        "off" => Ok(String::default()),
+        _ if value.is_empty()
+            || value.chars().count() > 4
+            || value.chars().any(|c| c.is_control()) =>
+        {
+            Err(anyhow!("invalid wrap indicator `{value}`"))
+        }
+        _ => Ok(value.to_string()),
+    }
+}
+
+// `off` or a positive count of minutes, hours or days, e.g. `12h`
+fn parse_stale_after(value: &str) -> Result<Option<Duration>> {
+    if value == "off" {
+        return Ok(None);
+    }
+    let invalid = || anyhow!("invalid staleness threshold `{value}`");
+    let unit = match value.chars().last() {
+        Some('m') => 60,
+        Some('h') => 3600,
+        Some('d') => 24 * 3600,
+        _ => return Err(invalid()),
+    };
+    match value[..value.len() - 1].parse::<u64>() {
+        std::result::Result::Ok(count) if count > 0 => Ok(Some(Duration::from_secs(count * unit))),
+        _ => Err(invalid()),
+    }
+}
+
+pub fn parse_scroll_off(value: &str) -> Result<Option<ScrollOff>> {
+    match value {
+        "center" => Ok(Some(ScrollOff::Center)),
+        "0" => Ok(None),
+        _ => value
+            .parse()
+            .map(|line_count| Some(ScrollOff::Lines(line_count)))
+            .map_err(|_| anyhow!("invalid scroll-off `{value}`")),
+    }
+}
+
+pub fn parse_context_lines(value: &str) -> Result<usize> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("invalid context lines `{value}`"))
+}
+
+fn parse_log_timezone(value: &str) -> Result<Option<FixedOffset>> {
+    if value == "local" {
+        return Ok(None);
+    }
+    parse_timezone(value)
+        .map(Some)
+        .ok_or_else(|| anyhow!("invalid timezone `{value}`"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+// directory for files written by the user, e.g. config and filter profiles
+pub fn config_dir() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")))?;
+    Some(config_dir.join("loss"))
+}
+
+pub fn config_file_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("LOSS_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    Some(config_dir()?.join("config"))
+}
+
+/// The last seen modification time of a file, to notice it being rewritten.
+#[derive(Debug)]
+pub struct FileStamp {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified_time(&path);
+        Self { path, modified }
+    }
+
+    // whether the file was modified, created or removed since the last refresh
+    pub fn refresh(&mut self) -> bool {
+        let modified = modified_time(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// directory for disposable data, e.g. the debug log
+fn cache_dir() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".cache")))?;
+    Some(cache_dir.join("loss"))
+}
+
+// directory for data written by loss itself, e.g. session records
+pub fn state_dir() -> Option<PathBuf> {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".local").join("state")))?;
+    Some(state_dir.join("loss"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(
+            Config::parse("# comment\n\nrestore_session = false # trailing\n").unwrap(),
+            Config {
+                restore_session: false,
+                ..Config::default()
+            }
+        );
+        assert_eq!(
+            Config::parse("line_delimiter = crlf")
+                .unwrap()
+                .line_delimiter,
+            "\r\n"
+        );
+        assert_eq!(
+            Config::parse("log_timezone = +08:00").unwrap().log_timezone,
+            FixedOffset::east_opt(8 * 3600)
+        );
+        assert_eq!(
+            Config::parse("log_timezone = local").unwrap().log_timezone,
+            None
+        );
+        assert!(Config::parse("log_timezone = mars").is_err());
+        assert_eq!(
+            Config::parse("wrap_row_limit = 5").unwrap().wrap_row_limit,
+            5
+        );
+        assert!(Config::parse("wrap_row_limit = 0").is_err());
+        assert_eq!(
+            Config::parse("wrap_indicator = ↪").unwrap().wrap_indicator,
+            "↪"
+        );
+        assert_eq!(
+            Config::parse("wrap_indicator = off")
+                .unwrap()
+                .wrap_indicator,
+            ""
+        );
+        assert!(Config::parse("wrap_indicator = -----").is_err());
+        assert!(Config::parse("wrap_indicator =").is_err());
+        assert_eq!(
+            Config::parse("scroll_off = 5").unwrap().scroll_off,
+            Some(ScrollOff::Lines(5))
+        );
+        assert_eq!(
+            Config::parse("scroll_off = center").unwrap().scroll_off,
+            Some(ScrollOff::Center)
+        );
+        assert_eq!(Config::parse("scroll_off = 0").unwrap().scroll_off, None);
+        assert!(Config::parse("scroll_off = top").is_err());
+        assert_eq!(Config::parse("context_lines = 2").unwrap().context_lines, 2);
+        assert!(Config::parse("context_lines = -1").is_err());
+        assert_eq!(
+            Config::parse("boundary_feedback = bell")
+                .unwrap()
+                .boundary_feedback,
+            BoundaryFeedback::Bell
+        );
+        assert!(Config::parse("boundary_feedback = loud").is_err());
+        assert_eq!(
+            Config::parse("color = 256").unwrap().color,
+            Some(ColorSupport::Ansi256)
+        );
+        assert_eq!(Config::parse("color = auto").unwrap().color, None);
+        assert!(Config::parse("color = 88").is_err());
+        assert_eq!(
+            Config::parse("background = light").unwrap().background,
+            Some(Background::Light)
+        );
+        assert_eq!(Config::parse("background = auto").unwrap().background, None);
+        assert!(Config::parse("background = grey").is_err());
+        assert_eq!(
+            Config::parse("notify_follow_match = osc9\nnotify_error = flash")
+                .unwrap()
+                .notify,
+            NotifyPolicy {
+                follow_match: Notification::Desktop,
+                error: Notification::Flash,
+                ..NotifyPolicy::default()
+            }
+        );
+        assert!(Config::parse("notify_not_found = loud").is_err());
+        assert_eq!(
+            Config::parse("status_layout = verbose")
+                .unwrap()
+                .status_layout,
+            StatusLayout::Verbose
+        );
+        assert!(Config::parse("status_layout = full").is_err());
+        assert_eq!(
+            Config::parse("step_page = 10\nstep_ctrl_arrow = 3")
+                .unwrap()
+                .move_steps,
+            MoveSteps {
+                page: 10,
+                ctrl_arrow: 3,
+                ..MoveSteps::default()
+            }
+        );
+        assert!(Config::parse("step_arrow = 0").is_err());
+        assert!(Config::parse("key_repeat = on").unwrap().key_repeat);
+        assert!(
+            Config::parse("direct_slot_digits = true")
+                .unwrap()
+                .direct_slot_digits
+        );
+        assert!(
+            Config::parse("show_slow_frames = true")
+                .unwrap()
+                .show_slow_frames
+        );
+        assert!(
+            Config::parse("step_line_matches = true")
+                .unwrap()
+                .step_line_matches
+        );
+        assert_eq!(
+            Config::parse("stale_after = 12h").unwrap().stale_after,
+            Some(Duration::from_secs(12 * 3600))
+        );
+        assert_eq!(
+            Config::parse("stale_after = off").unwrap().stale_after,
+            None
+        );
+        for invalid in ["0d", "3", "2w", "-1h", "h"] {
+            assert!(Config::parse(&format!("stale_after = {invalid}")).is_err());
+        }
+        assert!(Config::parse("restore_session").is_err());
+        assert!(Config::parse("restore_session = maybe").is_err());
+        assert!(Config::parse("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_log_file_resolution_order() {
+        let mut config = Config::parse("log_file = /tmp/config.log").unwrap();
+        assert_eq!(config.log_file, LogFile::Path("/tmp/config.log".into()));
+        config.apply_log_env(None);
+        assert_eq!(config.log_file, LogFile::Path("/tmp/config.log".into()));
+        config.apply_log_env(Some("off"));
+        assert_eq!(config.log_file, LogFile::Off);
+        config.apply_log_env(Some("/tmp/env.log"));
+        assert_eq!(config.log_file, LogFile::Path("/tmp/env.log".into()));
+
+        assert_eq!(Config::default().log_file.resolve(), None);
+        assert_eq!(
+            LogFile::parse("/tmp/env.log").resolve(),
+            Some(PathBuf::from("/tmp/env.log"))
+        );
+        assert_eq!(
+            Config::parse("log_level = debug").unwrap().log_level,
+            LevelFilter::Debug
+        );
+        assert!(Config::parse("log_level = verbose").is_err());
+    }
+
+    #[test]
+    fn test_apply_reloaded() {
+        let previous = Config::default();
+        let mut config = Config {
+            // overridden on the command line
+            line_delimiter: "\r\n".to_string(),
+            ..Config::default()
+        };
+        let reloaded = Config::parse("scroll_off = center\nlog_level = debug\n").unwrap();
+        let changes = config.apply_reloaded(&previous, &reloaded);
+        assert_eq!(
+            changes,
+            ConfigChanges {
+                applied: vec!["scroll_off"],
+                restart_required: vec!["log_level"],
+            }
+        );
+        assert_eq!(config.scroll_off, Some(ScrollOff::Center));
+        assert_eq!(config.log_level, LevelFilter::Info);
+        assert_eq!(config.line_delimiter, "\r\n");
+
+        let changes = config.apply_reloaded(&reloaded, &reloaded);
+        assert_eq!(changes, ConfigChanges::default());
+    }
+
+    #[test]
+    fn test_parse_line_delimiter() {
+        assert_eq!(parse_line_delimiter("lf").unwrap(), "\n");
+        assert_eq!(parse_line_delimiter("crlf").unwrap(), "\r\n");
+        assert_eq!(parse_line_delimiter("rs").unwrap(), "\x1e");
+        assert_eq!(parse_line_delimiter("\\x1e").unwrap(), "\x1e");
+        assert_eq!(parse_line_delimiter(";").unwrap(), ";");
+        assert!(parse_line_delimiter("\\xff").is_err());
+        assert!(parse_line_delimiter("\\xzz").is_err());
+        assert!(parse_line_delimiter("ab").is_err());
+        assert!(parse_line_delimiter("").is_err());
+    }
+}
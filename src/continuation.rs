@@ -0,0 +1,294 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Seek},
+};
+
+use anyhow::{Ok, Result};
+use chrono::NaiveDateTime;
+use loss_viewer::document::{Document, Line};
+
+/// Collapses lines not starting with a timestamp, e.g. stack traces, under the line above them,
+/// which is called their parent. Offsets and searches still work on every line.
+#[derive(Debug, Default)]
+pub struct ContinuationFolder {
+    // the format telling parent lines apart, `None` while folding is off
+    timestamp_format: Option<String>,
+    // offsets of parents whose continuations are shown anyway
+    expanded: HashSet<usize>,
+}
+
+impl ContinuationFolder {
+    pub fn is_enabled(&self) -> bool {
+        self.timestamp_format.is_some()
+    }
+
+    pub fn enable(&mut self, timestamp_format: &str) {
+        self.timestamp_format = Some(timestamp_format.to_string());
+    }
+
+    pub fn disable(&mut self) {
+        self.timestamp_format = None;
+        self.expanded.clear();
+    }
+
+    // every line is a parent while folding is off
+    pub fn is_parent(&self, line: &str) -> bool {
+        match self.timestamp_format.as_ref() {
+            Some(fmt) => NaiveDateTime::parse_and_remainder(line, fmt).is_ok(),
+            None => true,
+        }
+    }
+
+    pub fn is_collapsed(&self, parent_offset: usize) -> bool {
+        self.is_enabled() && !self.expanded.contains(&parent_offset)
+    }
+
+    pub fn is_expanded(&self, parent_offset: usize) -> bool {
+        self.expanded.contains(&parent_offset)
+    }
+
+    // offsets are counted from another document start, e.g. after `:trim`. parents above it
+    // are dropped
+    pub fn rebase(&mut self, old_start: usize, new_start: usize) {
+        self.expanded = self
+            .expanded
+            .iter()
+            .filter_map(|offset| (old_start + offset).checked_sub(new_start))
+            .collect();
+    }
+
+    pub fn toggle_expanded(&mut self, parent_offset: usize) {
+        if !self.expanded.remove(&parent_offset) {
+            self.expanded.insert(parent_offset);
+        }
+    }
+
+    /// Appends `lines` to `shown`, dropping collapsed continuations and counting them per
+    /// parent in `hidden`. Continuations at the start of `shown` have no parent to fold under,
+    /// so they are kept.
+    pub fn collapse_into(
+        &self,
+        lines: Vec<Line>,
+        shown: &mut Vec<Line>,
+        hidden: &mut HashMap<usize, usize>,
+    ) {
+        let mut collapsing_under = shown
+            .last()
+            .filter(|line| self.is_parent(&line.text) && self.is_collapsed(line.offset))
+            .map(|line| line.offset);
+        for line in lines {
+            if self.is_parent(&line.text) {
+                collapsing_under = self.is_collapsed(line.offset).then_some(line.offset);
+            } else if let Some(parent_offset) = collapsing_under {
+                *hidden.entry(parent_offset).or_default() += 1;
+                continue;
+            }
+            shown.push(line);
+        }
+    }
+
+    /// Offset of the parent the line at `offset` is a continuation of, `None` if it's a parent
+    /// itself or there's no parent above.
+    pub fn query_parent_offset<R: Read + Seek>(
+        &self,
+        document: &mut Document<R>,
+        offset: usize,
+    ) -> Result<Option<usize>> {
+        let line = &document.query_lines(offset, 1)?[0];
+        if self.is_parent(&line.text) {
+            return Ok(None);
+        }
+        let distance = document.query_distance_to_prev_match(offset, |l| self.is_parent(l))?;
+        Ok(distance.map(|distance| offset - distance))
+    }
+
+    /// Like [`Document::query_distance_to_below_n_lines`], skipping collapsed continuations.
+    pub fn query_distance_to_below_n_lines<R: Read + Seek>(
+        &self,
+        document: &mut Document<R>,
+        offset: usize,
+        line_count: usize,
+    ) -> Result<usize> {
+        if !self.is_enabled() {
+            return document.query_distance_to_below_n_lines(offset, line_count);
+        }
+        let mut current = offset;
+        for _ in 0..line_count {
+            let next = current + document.query_distance_to_below_n_lines(current, 1)?;
+            if next == current {
+                break;
+            }
+            let line = &document.query_lines(current, 1)?[0];
+            if self.is_parent(&line.text) && self.is_collapsed(current) {
+                match document.query_distance_to_next_match(next, |l| self.is_parent(l))? {
+                    Some(distance) => current = next + distance,
+                    // everything below is collapsed under this line
+                    None => break,
+                }
+            } else {
+                current = next;
+            }
+        }
+        Ok(current - offset)
+    }
+
+    /// Like [`Document::query_distance_to_above_n_lines`], skipping collapsed continuations.
+    pub fn query_distance_to_above_n_lines<R: Read + Seek>(
+        &self,
+        document: &mut Document<R>,
+        offset: usize,
+        line_count: usize,
+    ) -> Result<usize> {
+        if !self.is_enabled() {
+            return document.query_distance_to_above_n_lines(offset, line_count);
+        }
+        let mut current = offset;
+        // the parent of the continuations passed through, carried over instead of being
+        // searched for again from every line of an expanded trace
+        let mut known_parent: Option<Option<usize>> = None;
+        for _ in 0..line_count {
+            let above = current - document.query_distance_to_above_n_lines(current, 1)?;
+            if above == current {
+                break;
+            }
+            let line = &document.query_lines(above, 1)?[0];
+            if self.is_parent(&line.text) {
+                known_parent = None;
+                current = above;
+                continue;
+            }
+            let parent_offset = match known_parent {
+                Some(parent_offset) => parent_offset,
+                None => document
+                    .query_distance_to_prev_match(above, |l| self.is_parent(l))?
+                    .map(|distance| above - distance),
+            };
+            known_parent = Some(parent_offset);
+            current = match parent_offset {
+                Some(parent_offset) if self.is_collapsed(parent_offset) => {
+                    known_parent = None;
+                    parent_offset
+                }
+                _ => above,
+            };
+        }
+        Ok(offset - current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    // two exceptions, of 3 and 6 continuation lines
+    fn open_fixture() -> Document<File> {
+        let path = format!(
+            "{}/tests/fixtures/exceptions.log",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        Document::<File>::open_file(&path, "\n").unwrap()
+    }
+
+    fn folder_for(document: &mut Document<File>) -> ContinuationFolder {
+        let mut folder = ContinuationFolder::default();
+        let fmt = document.detect_log_timestamp_format(0).unwrap().unwrap();
+        folder.enable(&fmt);
+        folder
+    }
+
+    fn collapse(
+        folder: &ContinuationFolder,
+        lines: Vec<Line>,
+    ) -> (Vec<usize>, HashMap<usize, usize>) {
+        let (mut shown, mut hidden) = (vec![], HashMap::new());
+        folder.collapse_into(lines, &mut shown, &mut hidden);
+        (shown.iter().map(|line| line.offset).collect(), hidden)
+    }
+
+    #[test]
+    fn test_collapse_traces() {
+        let mut document = open_fixture();
+        let mut folder = folder_for(&mut document);
+        let lines = document.query_lines(0, 100).unwrap();
+        assert_eq!(lines.len(), 14);
+
+        let (shown, hidden) = collapse(&folder, lines.clone());
+        assert_eq!(shown, [0, 46, 234, 273, 497]);
+        assert_eq!(hidden, HashMap::from([(46, 3), (273, 6)]));
+
+        folder.toggle_expanded(273);
+        let (shown, hidden) = collapse(&folder, lines.clone());
+        assert_eq!(shown.len(), 11);
+        assert_eq!(hidden, HashMap::from([(46, 3)]));
+
+        // lines arriving in batches, the first trace spanning both
+        let (mut shown, mut hidden) = (vec![], HashMap::new());
+        folder.collapse_into(lines[..3].to_vec(), &mut shown, &mut hidden);
+        folder.collapse_into(lines[3..].to_vec(), &mut shown, &mut hidden);
+        assert_eq!(shown.len(), 11);
+        assert_eq!(hidden, HashMap::from([(46, 3)]));
+
+        // a window starting inside a trace keeps the lines above its next parent
+        let (shown, _) = collapse(&folder, document.query_lines(142, 3).unwrap());
+        assert_eq!(shown, [142, 186, 234]);
+
+        folder.disable();
+        let (shown, hidden) = collapse(&folder, lines);
+        assert_eq!(shown.len(), 14);
+        assert!(hidden.is_empty());
+    }
+
+    #[test]
+    fn test_navigate_over_collapsed_traces() {
+        let mut document = open_fixture();
+        let folder = folder_for(&mut document);
+        let below = |document: &mut Document<File>, offset, count| {
+            offset
+                + folder
+                    .query_distance_to_below_n_lines(document, offset, count)
+                    .unwrap()
+        };
+        assert_eq!(below(&mut document, 0, 1), 46);
+        assert_eq!(below(&mut document, 46, 1), 234);
+        assert_eq!(below(&mut document, 0, 4), 497);
+        assert_eq!(below(&mut document, 0, 10), 497);
+        // moving down inside a trace goes line by line
+        assert_eq!(below(&mut document, 142, 1), 186);
+
+        let above = |document: &mut Document<File>, offset, count| {
+            offset
+                - folder
+                    .query_distance_to_above_n_lines(document, offset, count)
+                    .unwrap()
+        };
+        assert_eq!(above(&mut document, 234, 1), 46);
+        assert_eq!(above(&mut document, 497, 1), 273);
+        assert_eq!(above(&mut document, 497, 2), 234);
+        assert_eq!(above(&mut document, 497, 10), 0);
+
+        // moving up through an expanded trace goes line by line
+        let mut expanded = folder_for(&mut document);
+        expanded.toggle_expanded(273);
+        let mut above_expanded = |offset, count| {
+            offset
+                - expanded
+                    .query_distance_to_above_n_lines(&mut document, offset, count)
+                    .unwrap()
+        };
+        assert_eq!(above_expanded(497, 1), 474);
+        assert_eq!(above_expanded(497, 7), 273);
+        assert_eq!(above_expanded(497, 8), 234);
+        assert_eq!(above_expanded(497, 9), 46);
+
+        assert_eq!(
+            folder.query_parent_offset(&mut document, 439).unwrap(),
+            Some(273)
+        );
+        assert_eq!(
+            folder.query_parent_offset(&mut document, 273).unwrap(),
+            None
+        );
+    }
+}
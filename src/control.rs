@@ -0,0 +1,239 @@
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Ok, Result};
+use log::{info, warn};
+
+// a client sending a longer line without line break is dropped
+const CONTROL_LINE_MAX_LEN: usize = 4096;
+
+/// A unix socket accepting newline-delimited commands in the syntax of the `:` prompt, each
+/// answered by a single line.
+pub struct ControlChannel {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<ControlClient<UnixStream>>,
+}
+
+impl ControlChannel {
+    pub fn bind(path: &Path) -> Result<Self> {
+        // a socket left behind by a crashed instance, anything else is kept untouched
+        if let std::result::Result::Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() || UnixStream::connect(path).is_ok() {
+                return Err(anyhow!("{} already exists", path.display()));
+            }
+            fs::remove_file(path)?;
+        }
+        // other users shouldn't be able to drive this instance, so the socket is created owner
+        // only instead of being narrowed after bind, when a client may already have connected
+        // SAFETY: umask only swaps the file mode creation mask of the process
+        let umask = unsafe { libc::umask(0o177) };
+        let bound = UnixListener::bind(path);
+        unsafe { libc::umask(umask) };
+        let listener = bound?;
+        listener.set_nonblocking(true)?;
+        info!("[bind] control channel: {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            listener,
+            clients: vec![],
+        })
+    }
+
+    // accepts pending connections and executes complete commands received so far. returns
+    // whether any command was executed
+    pub fn serve<F>(&mut self, mut execute: F) -> Result<bool>
+    where
+        F: FnMut(&str) -> String,
+    {
+        loop {
+            match self.listener.accept() {
+                std::result::Result::Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    self.clients.push(ControlClient::new(stream));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let mut executed = false;
+        self.clients.retain_mut(|client| {
+            let alive = client.serve(&mut execute, &mut executed);
+            if let Err(e) = alive.as_ref() {
+                warn!("[serve] drop control client: {e}");
+            }
+            matches!(alive, std::result::Result::Ok(true))
+        });
+        Ok(executed)
+    }
+}
+
+impl Drop for ControlChannel {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+struct ControlClient<S: Read + Write> {
+    stream: S,
+    // received but not terminated by a line break yet
+    buffer: Vec<u8>,
+}
+
+impl<S: Read + Write> ControlClient<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: vec![],
+        }
+    }
+
+    // reads what's available without blocking and answers each complete line. returns whether
+    // the client is still connected
+    fn serve<F>(&mut self, execute: &mut F, executed: &mut bool) -> io::Result<bool>
+    where
+        F: FnMut(&str) -> String,
+    {
+        let mut alive = true;
+        let mut read_buffer = [0; 1024];
+        loop {
+            match self.stream.read(&mut read_buffer) {
+                std::result::Result::Ok(0) => {
+                    alive = false;
+                    break;
+                }
+                std::result::Result::Ok(len) => self.buffer.extend(&read_buffer[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        while let Some(pos) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line[..pos]);
+            let command = line.trim();
+            if command.is_empty() {
+                continue;
+            }
+            let response = execute(command);
+            *executed = true;
+            self.stream.write_all(format!("{response}\n").as_bytes())?;
+        }
+        if self.buffer.len() > CONTROL_LINE_MAX_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "line too long"));
+        }
+        io::Result::Ok(alive)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, io::BufRead, io::BufReader, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    // hands out queued input a piece at a time, then would block until more is queued
+    #[derive(Default)]
+    struct FakeStream {
+        input: VecDeque<Vec<u8>>,
+        closed: bool,
+        output: Vec<u8>,
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.input.pop_front() {
+                Some(piece) => {
+                    buf[..piece.len()].copy_from_slice(&piece);
+                    io::Result::Ok(piece.len())
+                }
+                None if self.closed => io::Result::Ok(0),
+                None => Err(ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            io::Result::Ok(())
+        }
+    }
+
+    fn execute(command: &str) -> String {
+        match crate::command::parse_command(command) {
+            std::result::Result::Ok(command) => format!("ok {command:?}"),
+            Err(e) => format!("error {e}"),
+        }
+    }
+
+    #[test]
+    fn test_client_serves_complete_lines() {
+        let mut client = ControlClient::new(FakeStream::default());
+        client.stream.input.push_back(b"42\nset wr".to_vec());
+        let mut executed = false;
+        assert!(client.serve(&mut execute, &mut executed).unwrap());
+        assert!(executed);
+        assert_eq!(client.stream.output, b"ok GotoLine(42)\n");
+
+        client.stream.output.clear();
+        client
+            .stream
+            .input
+            .push_back(b"ap\n\nfrobnicate\n".to_vec());
+        client.stream.closed = true;
+        assert!(!client.serve(&mut execute, &mut executed).unwrap());
+        assert_eq!(
+            String::from_utf8(client.stream.output.clone()).unwrap(),
+            "ok Set(Wrap(true))\nerror Unknown command: frobnicate\n"
+        );
+
+        let mut client = ControlClient::new(FakeStream::default());
+        let long_line = vec![b'x'; 1024];
+        for _ in 0..5 {
+            client.stream.input.push_back(long_line.clone());
+        }
+        assert!(client.serve(&mut execute, &mut executed).is_err());
+    }
+
+    #[test]
+    fn test_channel_over_socket() {
+        let dir = std::env::temp_dir().join(format!("loss-control-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("control.sock");
+        let mut channel = ControlChannel::bind(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(ControlChannel::bind(&path).is_err());
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"profile load\n").unwrap();
+        // the connection may not be accepted on the first round
+        let mut executed = false;
+        for _ in 0..100 {
+            executed |= channel.serve(execute).unwrap();
+            if executed {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(executed);
+        let mut response = String::new();
+        BufReader::new(&stream).read_line(&mut response).unwrap();
+        assert_eq!(response, "error Usage: profile load <name>\n");
+
+        drop(channel);
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,37 @@
+/// Direction of window movement or searching.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn is_horizontal(&self) -> bool {
+        *self == Direction::Left || *self == Direction::Right
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        *self == Direction::Up || *self == Direction::Down
+    }
+
+    pub fn reverse(&self) -> Direction {
+        match *self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    /// Panics if the direction is horizontal.
+    pub fn above_or_below(&self) -> &str {
+        assert!(self.is_vertical());
+        if *self == Direction::Up {
+            "above"
+        } else {
+            "below"
+        }
+    }
+}
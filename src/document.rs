@@ -1,165 +1,745 @@
 use anyhow::{Ok, Result};
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use core::panic;
-use log::info;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use log::{debug, info, trace, warn};
 use std::{
+    cell::Cell,
+    fmt,
     fs::File,
-    io::{Read, Seek, SeekFrom},
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    ops::Range,
+    time::{Duration, Instant},
 };
 
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, UTF8_BOM};
+use crate::direction::Direction;
 use crate::log_timestamp::detect_log_timstamp_format;
 
+/// Errors on violated preconditions of [`Document`] queries, wrapped in [`anyhow::Error`].
+#[derive(Debug, PartialEq)]
+pub enum DocumentError {
+    EmptyLineDelimiter,
+    /// The underlying reader got shorter than it was.
+    Shrunk {
+        old_size: usize,
+        new_size: usize,
+    },
+    /// Offset is beyond the start of the last line.
+    OffsetOutOfRange {
+        offset: usize,
+        last_line_start_offset: usize,
+    },
+    NotAtLineStart {
+        offset: usize,
+    },
+    OffsetOutOfChunk {
+        offset: usize,
+        offset_begin: usize,
+        offset_end: usize,
+    },
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyLineDelimiter => write!(f, "line delimiter shouldn't be empty"),
+            Self::Shrunk { old_size, new_size } => {
+                write!(f, "document shrinks from {old_size} to {new_size} bytes")
+            }
+            Self::OffsetOutOfRange {
+                offset,
+                last_line_start_offset,
+            } => write!(
+                f,
+                "offset {offset} is beyond last line start {last_line_start_offset}"
+            ),
+            Self::NotAtLineStart { offset } => write!(f, "offset {offset} is not at line start"),
+            Self::OffsetOutOfChunk {
+                offset,
+                offset_begin,
+                offset_end,
+            } => write!(
+                f,
+                "offset {offset} is out of chunk [{offset_begin}, {offset_end})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+/// Where [`Document::query_offset_by_timestamp`] lands, always at a line start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimestampMatch {
+    /// the first line whose timestamp is not earlier than the target
+    Found(usize),
+    /// the target is earlier than any timestamp, so the first timestamped line is taken
+    ClampedToFirst(usize, NaiveDateTime),
+    /// the target is later than any timestamp, so the last timestamped line is taken
+    ClampedToLast(usize, NaiveDateTime),
+}
+
+impl TimestampMatch {
+    pub fn offset(&self) -> usize {
+        match *self {
+            Self::Found(offset)
+            | Self::ClampedToFirst(offset, _)
+            | Self::ClampedToLast(offset, _) => offset,
+        }
+    }
+}
+
+/// Result of [`Document::position_ratio`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PositionRatio {
+    #[default]
+    Empty,
+    /// always in `0..=100`
+    Percent(usize),
+    /// at the last line
+    End,
+}
+
+/// Result of [`Document::count_matches`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MatchCount {
+    pub count: usize,
+    pub first_offset: Option<usize>,
+}
+
+/// A line returned by [`Document::query_lines`], without its line delimiter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// where the line starts in the document
+    pub offset: usize,
+    pub text: String,
+}
+
+/// Counters of the work done by a [`Document`] since it was created, returned by
+/// [`Document::stats`]. Chunks are merged with their neighbors but never evicted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DocumentStats {
+    pub chunk_loads: usize,
+    /// bytes read from the reader into chunks
+    pub bytes_loaded: usize,
+    pub chunk_merges: usize,
+    /// lines tested by searches and match scans
+    pub lines_scanned: usize,
+    /// queries of lines which loaded more than [`QUERY_LINES_MAX_CHUNK_LOADS`] chunks, a hint
+    /// that the chunk size is too small for the lines or the window
+    pub chunk_heavy_queries: usize,
+    /// time spent reading chunks from the reader
+    pub load_time: Duration,
+}
+
+// chunk loads are summarized once in a while at info level, each load is only logged at debug
+#[derive(Debug)]
+struct ChunkLoadStats {
+    chunk_count: usize,
+    byte_count: usize,
+    since: Instant,
+}
+
+const CHUNK_LOAD_SUMMARY_INTERVAL: Duration = Duration::from_secs(1);
+
+impl ChunkLoadStats {
+    fn new() -> Self {
+        Self {
+            chunk_count: 0,
+            byte_count: 0,
+            since: Instant::now(),
+        }
+    }
+
+    fn record(&mut self, byte_count: usize) {
+        self.chunk_count += 1;
+        self.byte_count += byte_count;
+        let elapsed = self.since.elapsed();
+        if elapsed >= CHUNK_LOAD_SUMMARY_INTERVAL {
+            info!(
+                "[load_chunk] {} chunks ({} bytes) loaded in {:.1}s",
+                self.chunk_count,
+                self.byte_count,
+                elapsed.as_secs_f64()
+            );
+            *self = Self::new();
+        }
+    }
+}
+
+/// A document read lazily in chunks from `reader`, addressed by byte offsets.
+///
+/// Offsets passed to the query functions must be at a line start and no larger than
+/// [`Document::last_line_start_offset`], otherwise a [`DocumentError`] is returned.
 #[derive(Debug)]
 pub struct Document<R: Read + Seek> {
     reader: R,
     chunks: Vec<Chunk>,
     log_timestamp_format: Option<String>,
     log_default_date: Option<NaiveDate>,
-    last_line: String,
+    // configured or detected from the log, local timezone is assumed if unknown
+    log_timezone: Option<FixedOffset>,
+    // the last line is never held in memory, it may be as large as the document. chunks end
+    // where it starts
+    last_line_start: usize,
+    // whether a line delimiter ends the document, as part of the last line
+    final_line_delimiter: bool,
     document_size: usize,
     default_chunk_size: usize,
+    // adjacent chunks are merged as long as the result doesn't exceed this
+    max_merged_chunk_size: usize,
+    line_delimiter: String,
+    load_stats: ChunkLoadStats,
+    stats: DocumentStats,
+    // the most bytes read from the reader at once
+    max_read_len: usize,
+    // whether the document starts with a byte order mark, left out of the first line. `None`
+    // until there are enough bytes to tell
+    bom: Option<bool>,
+    // bytes of the reader left out before the document, which starts as if the reader did
+    bytes_skipped: usize,
 }
 
 const DEFAULT_CHUNK_SIZE: usize = 65536;
+/// Chunk sizes below this are raised to it by [`Document::set_chunk_size`], as scrolling loads
+/// more chunks per frame the smaller they are.
+pub const MIN_CHUNK_SIZE: usize = 4096;
+/// More chunk loads than this for a single query of lines count as chunk heavy in the stats.
+pub const QUERY_LINES_MAX_CHUNK_LOADS: usize = 8;
+const MAX_MERGED_CHUNK_SIZE: usize = DEFAULT_CHUNK_SIZE * 4;
+// enough to bisect any file size, just a safety net against not converging
+const TIMESTAMP_BISECT_MAX_ITERATIONS: usize = 64;
+// beyond windows of this many chunks on either side, a nearest timestamp is bisected for instead
+const NEAREST_TIMESTAMP_MAX_CHUNKS: usize = 16;
+
+/// The chunk size a document needs so that any line of `line_len` bytes, its delimiter
+/// included, is read whole into a chunk loaded around an offset within it. Never smaller than
+/// the default one.
+pub fn required_chunk_size(line_len: usize) -> usize {
+    // a chunk is loaded centered on the offset, which may be at either end of the line
+    std::cmp::max(DEFAULT_CHUNK_SIZE, (line_len * 2).next_power_of_two())
+}
 
 impl<R: Read + Seek> Document<R> {
-    fn new(reader: R) -> Result<Self> {
-        let mut document = Self {
+    /// Creates a document whose lines are delimited by `\n`.
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_line_delimiter(reader, crate::chunk::DEFAULT_LINE_DELIMITER)
+    }
+
+    pub fn with_line_delimiter(reader: R, line_delimiter: &str) -> Result<Self> {
+        let mut document = Self::unloaded(reader, line_delimiter)?;
+        document.update_docsize_and_lastline()?;
+        Ok(document)
+    }
+
+    /// Like [`Document::with_line_delimiter`], but over the last `tail_bytes` of the reader only,
+    /// starting at the first line start in them as if the reader did. Nothing before that is
+    /// ever read but the line delimiter right above, and offsets are counted from there.
+    pub fn with_tail(reader: R, line_delimiter: &str, tail_bytes: usize) -> Result<Self> {
+        let mut document = Self::unloaded(reader, line_delimiter)?;
+        document.bytes_skipped = document.locate_tail_start(tail_bytes)?;
+        info!(
+            "[with_tail] tail_bytes: {tail_bytes}, bytes skipped: {}",
+            document.bytes_skipped
+        );
+        document.update_docsize_and_lastline()?;
+        Ok(document)
+    }
+
+    fn unloaded(reader: R, line_delimiter: &str) -> Result<Self> {
+        if line_delimiter.is_empty() {
+            return Err(DocumentError::EmptyLineDelimiter.into());
+        }
+        Ok(Self {
             reader,
             chunks: vec![],
             log_timestamp_format: None,
             log_default_date: None,
-            last_line: String::default(),
+            log_timezone: None,
+            last_line_start: 0,
+            final_line_delimiter: false,
             document_size: 0,
             default_chunk_size: DEFAULT_CHUNK_SIZE,
-        };
-        document.update_docsize_and_lastline()?;
-        Ok(document)
+            max_merged_chunk_size: MAX_MERGED_CHUNK_SIZE,
+            line_delimiter: line_delimiter.to_string(),
+            load_stats: ChunkLoadStats::new(),
+            stats: DocumentStats::default(),
+            max_read_len: 0,
+            bom: None,
+            bytes_skipped: 0,
+        })
+    }
+
+    // the first line start at or after the beginning of the last `tail_bytes`, or the first char
+    // boundary if no line starts there. the reader is read from that beginning on only
+    fn locate_tail_start(&mut self, tail_bytes: usize) -> Result<usize> {
+        let size = self.reader.seek(SeekFrom::End(0))? as usize;
+        if size <= tail_bytes {
+            return Ok(0);
+        }
+        let tail_start = size - tail_bytes;
+        let delimiter = self.line_delimiter.clone().into_bytes();
+        // a delimiter right before the beginning makes it a line start already
+        let mut window_begin = tail_start.saturating_sub(delimiter.len());
+        while window_begin < size {
+            // a delimiter may straddle the window end
+            let window_end = std::cmp::min(
+                window_begin + self.default_chunk_size + delimiter.len() - 1,
+                size,
+            );
+            let buffer = self.read_bytes(window_begin, window_end)?;
+            if let Some(pos) = buffer
+                .windows(delimiter.len())
+                .position(|window| window == delimiter)
+            {
+                return Ok(window_begin + pos + delimiter.len());
+            }
+            window_begin += self.default_chunk_size;
+        }
+        // no more than 3 continuation bytes lead a char
+        let lead = self.read_bytes(tail_start, std::cmp::min(tail_start + 3, size))?;
+        let continuation_len = lead.iter().take_while(|byte| **byte & 0xc0 == 0x80).count();
+        Ok(tail_start + continuation_len)
+    }
+
+    /// Bytes of the reader before the document start, see [`Document::with_tail`].
+    pub fn bytes_skipped(&self) -> usize {
+        self.bytes_skipped
+    }
+
+    /// Moves the document start to `bytes_skipped` bytes into the reader, which should be at a
+    /// line start, as if the document was created with a tail starting there. Offsets are
+    /// counted from the new start, so loaded chunks are dropped and the timestamp format is
+    /// detected again. Moving it back brings back what was skipped.
+    pub fn set_bytes_skipped(&mut self, bytes_skipped: usize) -> Result<()> {
+        info!(
+            "[set_bytes_skipped] bytes skipped: {} -> {bytes_skipped}",
+            self.bytes_skipped
+        );
+        self.bytes_skipped = bytes_skipped;
+        self.chunks.clear();
+        self.document_size = 0;
+        self.last_line_start = 0;
+        self.final_line_delimiter = false;
+        self.bom = None;
+        self.log_timestamp_format = None;
+        self.log_default_date = None;
+        self.update_docsize_and_lastline()?;
+        Ok(())
     }
 
-    pub fn open_file(filename: &str) -> Result<Document<File>> {
+    pub fn open_file(filename: &str, line_delimiter: &str) -> Result<Document<File>> {
         let file = File::open(filename)?;
-        Document::<File>::new(file)
+        Document::<File>::with_line_delimiter(file, line_delimiter)
+    }
+
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    /// Sets the timezone which timestamps in the log are in, `None` for the local timezone.
+    /// An offset written in the log timestamps themselves takes precedence once detected.
+    pub fn set_log_timezone(&mut self, timezone: Option<FixedOffset>) {
+        self.log_timezone = timezone;
     }
 
-    // return whether document has been updated
-    pub fn update_docsize_and_lastline(&mut self) -> Result<bool> {
-        let new_size = self.reader.seek(SeekFrom::End(0))? as usize;
+    /// Picks up data appended to the reader, returns the byte range appended if any.
+    pub fn update_docsize_and_lastline(&mut self) -> Result<Option<Range<usize>>> {
+        let new_size =
+            (self.reader.seek(SeekFrom::End(0))? as usize).saturating_sub(self.bytes_skipped);
         if new_size < self.document_size {
-            // todo: exit gracefully
-            panic!("document shouldn't shrink");
+            return Err(DocumentError::Shrunk {
+                old_size: self.document_size,
+                new_size,
+            }
+            .into());
         }
         if new_size == self.document_size {
-            Ok(false)
+            Ok(None)
         } else {
-            self.document_size = new_size;
-            self.load_chunk(
-                self.document_size.saturating_sub(DEFAULT_CHUNK_SIZE),
-                self.document_size,
-            )?;
-            Ok(true)
+            let old_size = std::mem::replace(&mut self.document_size, new_size);
+            if self.bom.is_none() && new_size >= UTF8_BOM.len() {
+                // only the start of the reader may have one
+                let bom = self.bytes_skipped == 0
+                    && self.read_bytes(0, UTF8_BOM.len())? == UTF8_BOM.as_bytes();
+                if bom {
+                    info!("[update_docsize_and_lastline] byte order mark detected");
+                }
+                self.bom = Some(bom);
+            }
+            // the last line can only start further on
+            self.locate_last_line(self.last_line_start)?;
+            // the lines before the last one, the end is where a window is often shown first
+            let offset_begin = self.last_line_start.saturating_sub(self.default_chunk_size);
+            self.load_chunk(offset_begin, self.last_line_start)?;
+            Ok(Some(old_size..new_size))
+        }
+    }
+
+    // finds the start of the last line at or after `floor`, reading backwards from the end a
+    // chunk at a time so that a long last line is never read whole
+    fn locate_last_line(&mut self, floor: usize) -> Result<()> {
+        let delimiter = self.line_delimiter.clone().into_bytes();
+        let delimiter_len = delimiter.len();
+        self.final_line_delimiter = self.document_size >= delimiter_len
+            && self.read_bytes(self.document_size - delimiter_len, self.document_size)?
+                == delimiter;
+        // a delimiter ending the document belongs to the last line
+        let content_end = if self.final_line_delimiter {
+            self.document_size - delimiter_len
+        } else {
+            self.document_size
+        };
+        let mut window_end = content_end;
+        while window_end > floor {
+            let window_begin = std::cmp::max(
+                window_end.saturating_sub(self.default_chunk_size),
+                floor.saturating_sub(delimiter_len - 1),
+            );
+            // a delimiter may straddle the window end
+            let read_end = std::cmp::min(window_end + delimiter_len - 1, content_end);
+            let buffer = self.read_bytes(window_begin, read_end)?;
+            if let Some(pos) = buffer
+                .windows(delimiter_len)
+                .rposition(|window| window == delimiter)
+            {
+                self.last_line_start = std::cmp::max(window_begin + pos + delimiter_len, floor);
+                return Ok(());
+            }
+            window_end = window_begin;
+        }
+        self.last_line_start = std::cmp::min(floor, content_end);
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, offset_begin: usize, offset_end: usize) -> Result<Vec<u8>> {
+        let mut buffer = vec![0; offset_end - offset_begin];
+        self.max_read_len = std::cmp::max(self.max_read_len, buffer.len());
+        self.seek_reader(offset_begin)?;
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    // to the reader position of a document offset
+    fn seek_reader(&mut self, offset: usize) -> Result<()> {
+        let position = self.bytes_skipped + offset;
+        self.reader.seek(SeekFrom::Start(position as u64))?;
+        Ok(())
+    }
+
+    /// Counters of the work done since the document was created, see [`DocumentStats`].
+    pub fn stats(&self) -> DocumentStats {
+        self.stats
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Bytes held in loaded chunks, which make up most of the memory used by a document.
+    pub fn chunk_bytes(&self) -> usize {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.offset_end - chunk.offset_begin)
+            .sum()
+    }
+
+    /// The most bytes read from the reader at once so far, the whole last line counts when its
+    /// text is queried.
+    pub fn max_read_len(&self) -> usize {
+        self.max_read_len
+    }
+
+    /// Sets the size of chunks loaded from now on, raised to [`MIN_CHUNK_SIZE`] with a warning
+    /// when smaller. Returns the size in effect.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) -> usize {
+        if chunk_size < MIN_CHUNK_SIZE {
+            warn!("[set_chunk_size] {chunk_size} is too small, using {MIN_CHUNK_SIZE} instead");
+        }
+        self.default_chunk_size = std::cmp::max(chunk_size, MIN_CHUNK_SIZE);
+        self.max_merged_chunk_size = self.default_chunk_size * 4;
+        self.default_chunk_size
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.default_chunk_size
+    }
+
+    /// Whether the document starts with a UTF-8 byte order mark. It's left out of the text of
+    /// the first line, whose offset is still 0.
+    pub fn has_bom(&self) -> bool {
+        self.bom == Some(true)
+    }
+
+    // read on demand, without the line delimiter ending the document
+    fn last_line_text(&mut self) -> Result<String> {
+        let mut offset_end = self.document_size;
+        if self.final_line_delimiter {
+            offset_end -= self.line_delimiter.len();
+        }
+        let mut offset_begin = self.last_line_start;
+        if offset_begin == 0 && self.has_bom() {
+            offset_begin = std::cmp::min(UTF8_BOM.len(), offset_end);
         }
+        let buffer = self.read_bytes(offset_begin, offset_end)?;
+        Ok(String::from_utf8(buffer)?)
     }
 
+    pub fn document_size(&self) -> usize {
+        self.document_size
+    }
+
+    /// Whether the document ends in the middle of its last line, without a line delimiter
+    /// after it. An empty document lacks nothing.
+    pub fn lacks_final_line_delimiter(&self) -> bool {
+        self.document_size > 0 && !self.final_line_delimiter
+    }
+
+    /// The last line is the one after the last line delimiter, or before it if the document
+    /// ends with a line delimiter.
     pub fn last_line_start_offset(&self) -> usize {
-        self.document_size - self.last_line.len()
+        self.last_line_start
     }
 
+    /// Whether the document is a single line, with or without a trailing line delimiter. Every
+    /// offset query lands on offset 0 then, so positions within the line are up to the caller.
+    pub fn is_single_line(&self) -> bool {
+        self.document_size > 0 && self.last_line_start_offset() == 0
+    }
+
+    /// Position of `offset` relative to the last line start, in percentage clamped to 100.
     pub fn percent_ratio_of_offset(&self, offset: usize) -> usize {
         if self.last_line_start_offset() == 0 {
             100
         } else {
-            offset * 100 / self.last_line_start_offset()
+            std::cmp::min(offset * 100 / self.last_line_start_offset(), 100)
         }
     }
 
-    fn load_chunk(
-        &mut self,
-        mut offset_begin: usize,
-        mut offset_end: usize,
-    ) -> Result<Option<usize>> {
-        info!("[load_chunk] offset_begin: {offset_begin} offset_end: {offset_end}");
-        offset_end = std::cmp::min(offset_end, self.document_size);
-        assert!(offset_begin < offset_end);
-
-        // avoid chunk overlap
-        if let Some(chunk_index_begin) = self.get_chunk_index_by_offset(offset_begin) {
-            for index in chunk_index_begin..self.chunks.len() {
-                if offset_begin < self.chunks[index].offset_begin {
-                    break;
-                }
-                offset_begin = self.chunks[index].offset_end;
+    /// Position of `offset` for display, telling apart an empty document and the last line.
+    pub fn position_ratio(&self, offset: usize) -> PositionRatio {
+        if self.document_size == 0 {
+            PositionRatio::Empty
+        } else if offset >= self.last_line_start_offset() {
+            PositionRatio::End
+        } else {
+            PositionRatio::Percent(self.percent_ratio_of_offset(offset))
+        }
+    }
+
+    fn load_chunk(&mut self, offset_begin: usize, offset_end: usize) -> Result<Option<usize>> {
+        debug!("[load_chunk] offset_begin: {offset_begin} offset_end: {offset_end}");
+        // the last line is left out of chunks
+        let offset_end = std::cmp::min(offset_end, self.last_line_start);
+        if offset_begin >= offset_end {
+            return Ok(None);
+        }
+
+        // only the gaps between existing chunks are read, each as a chunk of its own
+        let mut loaded_index = None;
+        for (gap_begin, gap_end) in self.uncovered_ranges(offset_begin, offset_end) {
+            if let Some(index) = self.load_gap(gap_begin, gap_end)? {
+                loaded_index = Some(index);
             }
         }
-        if let Some(chunk_index_end) = self.get_chunk_index_by_offset(offset_end) {
-            for index in (0..=chunk_index_end).rev() {
-                if offset_end > self.chunks[index].offset_end {
-                    break;
-                }
-                offset_end = self.chunks[index].offset_begin;
+        Ok(loaded_index)
+    }
+
+    // sub-ranges of [offset_begin, offset_end) not covered by any chunk, in order
+    fn uncovered_ranges(&self, offset_begin: usize, offset_end: usize) -> Vec<(usize, usize)> {
+        let mut ranges = vec![];
+        let mut cursor = offset_begin;
+        for chunk in &self.chunks {
+            if chunk.offset_end <= cursor {
+                continue;
+            }
+            if chunk.offset_begin >= offset_end {
+                break;
+            }
+            if chunk.offset_begin > cursor {
+                ranges.push((cursor, chunk.offset_begin));
             }
+            cursor = chunk.offset_end;
         }
-        if offset_begin >= offset_end {
-            return Ok(None);
+        if cursor < offset_end {
+            ranges.push((cursor, offset_end));
         }
-        // actually a temporary hack to make sure first line is not dropped
-        offset_begin = offset_begin.saturating_sub(1);
+        ranges
+    }
+
+    // loads [offset_begin, offset_end) which overlaps no chunk, returns the index of the chunk it
+    // ends up in
+    fn load_gap(&mut self, offset_begin: usize, offset_end: usize) -> Result<Option<usize>> {
+        // the first row is a partial line unless offset_begin is at a line start, which is known
+        // for sure at the document start or the end of an existing chunk
+        let at_line_start = offset_begin == 0
+            || self.chunks.iter().any(|c| c.offset_end == offset_begin)
+            || self.is_preceded_by_line_delimiter(offset_begin)?;
+        // likewise the last row is a whole line when the gap ends where a chunk or the last line
+        // begins
+        let at_line_end = offset_end == self.last_line_start
+            || self.chunks.iter().any(|c| c.offset_begin == offset_end);
 
         // build chunk
         let mut buffer = vec![0; offset_end - offset_begin];
-        self.reader.seek(SeekFrom::Start(offset_begin as u64))?;
-        let consumed = self.reader.read(&mut buffer)?;
-        assert!(consumed > 0, "cannot read anything from file");
-        let content = std::str::from_utf8(&buffer[..consumed])?;
-        // drop first unless loading chunk starting from the first byte
-        let drop_first = offset_begin > 0;
-        let cover_end = offset_end >= self.document_size;
-        let mut new_chunk = Chunk::build_chunk(content, offset_begin, drop_first, !cover_end);
-
-        if cover_end {
-            // handle last line
-            assert!(!new_chunk.rows.is_empty());
-            let mut last_line = new_chunk.rows.pop().unwrap();
-            if content.ends_with('\n') {
-                last_line.push('\n');
-            }
-            new_chunk.offset_end -= last_line.len();
-            self.last_line = last_line;
-        }
-        if new_chunk.rows.is_empty() {
+        self.max_read_len = std::cmp::max(self.max_read_len, buffer.len());
+        let started = Instant::now();
+        self.seek_reader(offset_begin)?;
+        let consumed = self.read_fully(&mut buffer)?;
+        self.stats.load_time += started.elapsed();
+        if consumed == 0 {
+            return Err(self.shrunk_error()?.into());
+        }
+        self.load_stats.record(consumed);
+        self.stats.chunk_loads += 1;
+        self.stats.bytes_loaded += consumed;
+        // the reader ended early, e.g. truncated by a rotation since the size was taken. what
+        // was read is all there is, up to its last whole line
+        let short = consumed < buffer.len();
+        if short {
+            warn!(
+                "[load_gap] read {consumed} of {} bytes at {offset_begin}",
+                buffer.len()
+            );
+        }
+        let content = match std::str::from_utf8(&buffer[..consumed]) {
+            std::result::Result::Ok(content) => content,
+            // a char cut by the early end, in the row dropped below
+            Err(e) if short && e.error_len().is_none() => {
+                std::str::from_utf8(&buffer[..e.valid_up_to()])?
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let drop_first = !at_line_start;
+        if drop_first && !content.contains(self.line_delimiter.as_str()) {
+            if short {
+                return Err(self.shrunk_error()?.into());
+            }
+            // a piece of a line whose start isn't loaded
+            return Ok(None);
+        }
+        let new_chunk = Chunk::build_chunk(
+            content,
+            offset_begin,
+            drop_first,
+            short || !at_line_end,
+            &self.line_delimiter,
+        );
+        if new_chunk.row_count() == 0 {
+            if short {
+                return Err(self.shrunk_error()?.into());
+            }
             return Ok(None);
         }
 
         // add into chunk list
-        let mut new_chunk_index = 0;
-        while new_chunk_index < self.chunks.len() {
-            if self.chunks[new_chunk_index].offset_begin >= new_chunk.offset_begin {
-                break;
+        let new_chunk_index = self
+            .chunks
+            .partition_point(|chunk| chunk.offset_begin < new_chunk.offset_begin);
+        self.chunks.insert(new_chunk_index, new_chunk);
+        Ok(Some(self.merge_adjacent_chunks(new_chunk_index)))
+    }
+
+    // reads until `buffer` is full or the reader ends, as a single read may return less anyway
+    fn read_fully(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let mut consumed = 0;
+        while consumed < buffer.len() {
+            match self.reader.read(&mut buffer[consumed..]) {
+                std::result::Result::Ok(0) => break,
+                std::result::Result::Ok(n) => consumed += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
             }
-            new_chunk_index += 1;
         }
-        let mut remove_until_index = new_chunk_index;
-        for index in new_chunk_index..self.chunks.len() {
-            if self.chunks[index].offset_end <= new_chunk.offset_end {
-                remove_until_index = index + 1;
-                continue;
-            }
+        Ok(consumed)
+    }
+
+    // the reader ended before the document size taken last, it shrank since
+    fn shrunk_error(&mut self) -> Result<DocumentError> {
+        let new_size =
+            (self.reader.seek(SeekFrom::End(0))? as usize).saturating_sub(self.bytes_skipped);
+        Ok(DocumentError::Shrunk {
+            old_size: self.document_size,
+            new_size,
+        })
+    }
+
+    // return index of the chunk which the one at `index` is merged into. tests checking exact
+    // chunk layouts turn merging off, merging is covered by test_merge_adjacent_chunks
+    fn merge_adjacent_chunks(&mut self, mut index: usize) -> usize {
+        let max_size = self.max_merged_chunk_size;
+        if index + 1 < self.chunks.len()
+            && self.chunks[index].can_merge(&self.chunks[index + 1], max_size)
+        {
+            let next = self.chunks.remove(index + 1);
+            self.chunks[index].merge(next);
+            self.stats.chunk_merges += 1;
         }
-        self.chunks.drain(new_chunk_index..remove_until_index);
-        self.chunks.insert(new_chunk_index, new_chunk);
-        Ok(Some(new_chunk_index))
+        if index > 0 && self.chunks[index - 1].can_merge(&self.chunks[index], max_size) {
+            let current = self.chunks.remove(index);
+            index -= 1;
+            self.chunks[index].merge(current);
+            self.stats.chunk_merges += 1;
+        }
+        index
+    }
+
+    fn is_preceded_by_line_delimiter(&mut self, offset: usize) -> Result<bool> {
+        let delimiter_len = self.line_delimiter.len();
+        if offset < delimiter_len {
+            return Ok(false);
+        }
+        let mut buffer = vec![0; delimiter_len];
+        self.seek_reader(offset - delimiter_len)?;
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer == self.line_delimiter.as_bytes())
     }
 
     fn load_chunk_around(&mut self, offset: usize) -> Result<Option<usize>> {
-        info!("[load_chunk_around] offset: {offset}");
+        debug!("[load_chunk_around] offset: {offset}");
         let offset_begin = offset.saturating_sub(self.default_chunk_size / 2);
         let offset_end = offset.saturating_add(self.default_chunk_size / 2);
         self.load_chunk(offset_begin, offset_end)
     }
 
+    /// Loads the neighbouring chunk ahead of time when offset gets close to the edge of its chunk
+    /// in the scrolling direction, so that steady scrolling doesn't stall on loading.
+    pub fn prefetch(&mut self, offset: usize, direction: Direction) -> Result<()> {
+        if let Some((offset_begin, offset_end)) = self.prefetch_range(offset, direction) {
+            debug!("[prefetch] offset: {offset} direction: {direction:?}");
+            self.load_chunk(offset_begin, offset_end)?;
+        }
+        Ok(())
+    }
+
+    fn prefetch_range(&self, offset: usize, direction: Direction) -> Option<(usize, usize)> {
+        assert!(direction.is_vertical());
+        let threshold = self.default_chunk_size / 4;
+        let chunk = &self.chunks[self.get_chunk_index_by_offset(offset)?];
+        if direction == Direction::Down {
+            let already_loaded = self.get_chunk_index_by_offset(chunk.offset_end).is_some();
+            if chunk.offset_end - offset < threshold
+                && chunk.offset_end < self.last_line_start_offset()
+                && !already_loaded
+            {
+                return Some((chunk.offset_end, chunk.offset_end + self.default_chunk_size));
+            }
+        } else {
+            let already_loaded = chunk.offset_begin == 0
+                || self
+                    .get_chunk_index_by_offset(chunk.offset_begin - 1)
+                    .is_some();
+            if offset - chunk.offset_begin < threshold && !already_loaded {
+                return Some((
+                    chunk.offset_begin.saturating_sub(self.default_chunk_size),
+                    chunk.offset_begin,
+                ));
+            }
+        }
+        None
+    }
+
     fn get_chunk_index_by_offset(&self, offset: usize) -> Option<usize> {
-        info!("[get_chunk_index_by_offset] offset: {offset}");
+        trace!("[get_chunk_index_by_offset] offset: {offset}");
         for (index, chunk) in self.chunks.iter().enumerate() {
             if offset >= chunk.offset_end {
                 continue;
@@ -175,60 +755,84 @@ impl<R: Read + Seek> Document<R> {
     }
 
     fn get_or_load_chunk_by_offset(&mut self, offset: usize) -> Result<&Chunk> {
-        info!("[get_or_load_chunk_by_offset] offset: {offset}");
+        trace!("[get_or_load_chunk_by_offset] offset: {offset}");
         let chunk_index_opt = self.get_chunk_index_by_offset(offset);
         let chunk_index = if let Some(chunk_index) = chunk_index_opt {
             chunk_index
         } else {
-            self.load_chunk_around(offset)?.unwrap()
+            let loaded_index = self.load_chunk_around(offset)?.unwrap();
+            // other gaps around may be loaded after the one holding offset
+            self.get_chunk_index_by_offset(offset)
+                .unwrap_or(loaded_index)
         };
         let chunk = &self.chunks[chunk_index];
         Ok(chunk)
     }
 
-    pub fn query_lines(&mut self, mut offset: usize, mut line_count: usize) -> Result<Vec<String>> {
-        info!("[query_lines] offset: {offset} line_count: {line_count}");
-        let mut lines: Vec<String> = vec![];
+    /// Returns at most `line_count` lines starting from `offset`, each with its start offset.
+    /// The last line, complete or not, starts at [`Document::last_line_start_offset`].
+    pub fn query_lines(&mut self, mut offset: usize, mut line_count: usize) -> Result<Vec<Line>> {
+        trace!("[query_lines] offset: {offset} line_count: {line_count}");
+        self.check_offset_in_range(offset)?;
+        let chunk_loads = self.stats.chunk_loads;
+        let mut lines = vec![];
         while offset < self.last_line_start_offset() && line_count > 0 {
             let chunk = self.get_or_load_chunk_by_offset(offset)?;
-            let line_index = chunk.query_line_index_exactly(offset);
-            let line_count_taken = std::cmp::min(line_count, chunk.rows.len() - line_index);
-            lines.extend(
-                chunk
-                    .rows
-                    .iter()
-                    .skip(line_index)
-                    .take(line_count_taken)
-                    .cloned()
-                    .collect::<Vec<_>>(),
-            );
+            let line_index = chunk.query_line_index_exactly(offset)?;
+            let line_count_taken = std::cmp::min(line_count, chunk.row_count() - line_index);
+            for (row, len) in chunk
+                .rows_with_len()
+                .skip(line_index)
+                .take(line_count_taken)
+            {
+                lines.push(Line {
+                    offset,
+                    text: row.to_string(),
+                });
+                offset += len;
+            }
             line_count -= line_count_taken;
             offset = chunk.offset_end;
         }
+        let chunk_loads = self.stats.chunk_loads - chunk_loads;
+        if chunk_loads > QUERY_LINES_MAX_CHUNK_LOADS {
+            // only the first is worth a warning, the stats keep counting
+            if self.stats.chunk_heavy_queries == 0 {
+                warn!(
+                    "[query_lines] {chunk_loads} chunks of {} bytes loaded for {} lines, a larger \
+                     chunk size would load fewer",
+                    self.default_chunk_size,
+                    lines.len()
+                );
+            }
+            self.stats.chunk_heavy_queries += 1;
+        }
         if line_count > 0 {
-            lines.push(self.last_line_without_line_break());
+            lines.push(Line {
+                offset: self.last_line_start_offset(),
+                text: self.last_line_text()?,
+            });
         }
         Ok(lines)
     }
 
-    fn last_line_without_line_break(&self) -> String {
-        let mut last_line = self.last_line.clone();
-        if last_line.ends_with('\n') {
-            last_line.pop();
-        }
-        last_line
+    /// Like [`Document::query_lines`], without the offsets.
+    pub fn query_line_texts(&mut self, offset: usize, line_count: usize) -> Result<Vec<String>> {
+        let lines = self.query_lines(offset, line_count)?;
+        Ok(lines.into_iter().map(|line| line.text).collect())
     }
 
+    /// Returns the distance in bytes from `offset` back to the start of the `line_count`th line
+    /// above, or to the document start if there are fewer lines.
     pub fn query_distance_to_above_n_lines(
         &mut self,
         mut offset: usize,
         mut line_count: usize,
     ) -> Result<usize> {
-        info!("[query_distance_to_above_n_lines] offset: {offset} line_count: {line_count}");
-        // offset must be at the line start
+        trace!("[query_distance_to_above_n_lines] offset: {offset} line_count: {line_count}");
+        self.check_offset_in_range(offset)?;
         let mut distance = 0;
         let mut first_loop = true;
-        assert!(offset <= self.last_line_start_offset());
         if offset == self.last_line_start_offset() {
             offset = offset.saturating_sub(1);
             first_loop = false;
@@ -236,22 +840,15 @@ impl<R: Read + Seek> Document<R> {
         while offset > 0 && line_count > 0 {
             let chunk = self.get_or_load_chunk_by_offset(offset)?;
             let above_lines_in_chunk = if first_loop {
-                chunk.query_line_index_exactly(offset)
+                chunk.query_line_index_exactly(offset)?
             } else {
                 chunk.query_line_index(offset) + 1
             };
-            let line_count_skipped = chunk.rows.len() - above_lines_in_chunk;
             let line_count_taken = std::cmp::min(line_count, above_lines_in_chunk);
-
-            distance += chunk
-                .rows
-                .iter()
-                .rev()
-                .skip(line_count_skipped)
-                .take(line_count_taken)
-                // count in the \n
-                .map(|line| line.len() + 1)
-                .sum::<usize>();
+            // from the start of the first line taken to the end of the last one, delimiter
+            // included
+            distance += chunk.query_line_start_offset(above_lines_in_chunk)
+                - chunk.query_line_start_offset(above_lines_in_chunk - line_count_taken);
             line_count -= line_count_taken;
             offset = chunk.offset_begin.saturating_sub(1);
             first_loop = false;
@@ -259,44 +856,84 @@ impl<R: Read + Seek> Document<R> {
         Ok(distance)
     }
 
+    /// Returns the start offset of the `line_count`th line counted back from the last line,
+    /// which is the first one, or 0 if there are fewer lines. Only chunks near the end are
+    /// loaded.
+    pub fn query_offset_of_last_n_lines(&mut self, line_count: usize) -> Result<usize> {
+        let last_line_start_offset = self.last_line_start_offset();
+        let distance = self.query_distance_to_above_n_lines(
+            last_line_start_offset,
+            line_count.saturating_sub(1),
+        )?;
+        Ok(last_line_start_offset - distance)
+    }
+
+    /// Returns the distance in bytes from `offset` to the start of the `line_count`th line
+    /// below, or to the last line start if there are fewer lines.
     pub fn query_distance_to_below_n_lines(
         &mut self,
         mut offset: usize,
         mut line_count: usize,
     ) -> Result<usize> {
-        info!("[query_distance_to_below_n_lines] offset: {offset} line_count: {line_count}");
-        // offset must be at the line start
+        trace!("[query_distance_to_below_n_lines] offset: {offset} line_count: {line_count}");
+        self.check_offset_in_range(offset)?;
         let mut distance = 0;
         while offset < self.last_line_start_offset() && line_count > 0 {
             let chunk = self.get_or_load_chunk_by_offset(offset)?;
-            let line_index = chunk.query_line_index_exactly(offset);
-            let line_count_taken = std::cmp::min(line_count, chunk.rows.len() - line_index);
-            distance += chunk
-                .rows
-                .iter()
-                .skip(line_index)
-                .take(line_count_taken)
-                // count in the \n
-                .map(|line| line.len() + 1)
-                .sum::<usize>();
+            let line_index = chunk.query_line_index_exactly(offset)?;
+            let line_count_taken = std::cmp::min(line_count, chunk.row_count() - line_index);
+            distance += chunk.query_line_start_offset(line_index + line_count_taken) - offset;
             line_count -= line_count_taken;
             offset = chunk.offset_end;
         }
         Ok(distance)
     }
 
+    /// Returns the number of lines from the one at `offset` up to the one at `to`, which is
+    /// excluded. Both are expected at line starts, with `offset <= to`.
+    pub fn count_lines_between(&mut self, mut offset: usize, to: usize) -> Result<usize> {
+        self.check_offset_in_range(to)?;
+        let mut line_count = 0;
+        while offset < to {
+            let chunk = self.get_or_load_chunk_by_offset(offset)?;
+            let line_index = chunk.query_line_index_exactly(offset)?;
+            for (_, len) in chunk.rows_with_len().skip(line_index) {
+                if offset >= to {
+                    break;
+                }
+                offset += len;
+                line_count += 1;
+            }
+        }
+        Ok(line_count)
+    }
+
+    /// Returns the distance in bytes from `offset` back to the start of the nearest line above
+    /// satisfying `search_predict`, the line at `offset` itself excluded.
     pub fn query_distance_to_prev_match<P>(
         &mut self,
-        mut offset: usize,
+        offset: usize,
         search_predict: P,
     ) -> Result<Option<usize>>
     where
         P: Fn(&str) -> bool,
     {
-        // offset must be at the line start
+        let scanned = Cell::new(0);
+        let distance = self.find_prev_match(offset, |line| {
+            scanned.set(scanned.get() + 1);
+            search_predict(line)
+        });
+        self.stats.lines_scanned += scanned.get();
+        distance
+    }
+
+    fn find_prev_match<P>(&mut self, mut offset: usize, search_predict: P) -> Result<Option<usize>>
+    where
+        P: Fn(&str) -> bool,
+    {
+        self.check_offset_in_range(offset)?;
         let mut distance = 0;
         let mut first_loop = true;
-        assert!(offset <= self.last_line_start_offset());
         if offset == self.last_line_start_offset() {
             offset = offset.saturating_sub(1);
             first_loop = false;
@@ -304,13 +941,13 @@ impl<R: Read + Seek> Document<R> {
         while offset > 0 {
             let chunk = self.get_or_load_chunk_by_offset(offset)?;
             let above_lines_in_chunk = if first_loop {
-                chunk.query_line_index_exactly(offset)
+                chunk.query_line_index_exactly(offset)?
             } else {
                 chunk.query_line_index(offset) + 1
             };
-            let line_count_skipped = chunk.rows.len() - above_lines_in_chunk;
-            for line in chunk.rows.iter().rev().skip(line_count_skipped) {
-                distance += line.len() + 1;
+            let line_count_skipped = chunk.row_count() - above_lines_in_chunk;
+            for (line, len) in chunk.rows_with_len().rev().skip(line_count_skipped) {
+                distance += len;
                 if search_predict(line) {
                     return Ok(Some(distance));
                 }
@@ -321,132 +958,545 @@ impl<R: Read + Seek> Document<R> {
         Ok(None)
     }
 
+    /// Returns the distance in bytes from `offset` to the start of the nearest line below
+    /// satisfying `search_predict`, the line at `offset` itself included.
     pub fn query_distance_to_next_match<P>(
         &mut self,
-        mut offset: usize,
+        offset: usize,
         search_predict: P,
     ) -> Result<Option<usize>>
     where
         P: Fn(&str) -> bool,
     {
+        let scanned = Cell::new(0);
+        let distance = self.find_next_match(offset, |line| {
+            scanned.set(scanned.get() + 1);
+            search_predict(line)
+        });
+        self.stats.lines_scanned += scanned.get();
+        distance
+    }
+
+    fn find_next_match<P>(&mut self, mut offset: usize, search_predict: P) -> Result<Option<usize>>
+    where
+        P: Fn(&str) -> bool,
+    {
+        self.check_offset_in_range(offset)?;
         let mut distance = 0;
         while offset < self.last_line_start_offset() {
             let chunk = self.get_or_load_chunk_by_offset(offset)?;
-            let line_index = chunk.query_line_index_exactly(offset);
-            for line in chunk.rows.iter().skip(line_index) {
+            let line_index = chunk.query_line_index_exactly(offset)?;
+            for (line, len) in chunk.rows_with_len().skip(line_index) {
                 if search_predict(line) {
                     return Ok(Some(distance));
                 }
-                distance += line.len() + 1;
+                distance += len;
             }
             offset = chunk.offset_end;
         }
-        if search_predict(&self.last_line) {
+        if search_predict(&self.last_line_text()?) {
             Ok(Some(distance))
         } else {
             Ok(None)
         }
     }
 
+    /// Counts lines satisfying `predict` and locates the first of them. The scan gives up with
+    /// `None` when `interrupted` returns true, which is checked once per chunk.
+    pub fn count_matches<P, I>(&mut self, predict: P, interrupted: I) -> Result<Option<MatchCount>>
+    where
+        P: Fn(&str) -> bool,
+        I: FnMut() -> bool,
+    {
+        let mut match_count = MatchCount::default();
+        let finished = self.scan_matches_from(0, predict, interrupted, |offset| {
+            match_count.count += 1;
+            match_count.first_offset.get_or_insert(offset);
+        })?;
+        Ok(finished.then_some(match_count))
+    }
+
+    /// Counts lines satisfying `predict` in each of `bucket_count` equal spans of the document,
+    /// bucketed the way [`Document::percent_ratio_of_offset`] maps offsets. Interrupted like
+    /// [`Document::count_matches`].
+    pub fn match_density<P, I>(
+        &mut self,
+        predict: P,
+        bucket_count: usize,
+        interrupted: I,
+    ) -> Result<Option<Vec<usize>>>
+    where
+        P: Fn(&str) -> bool,
+        I: FnMut() -> bool,
+    {
+        assert!(bucket_count > 0);
+        let mut buckets = vec![0; bucket_count];
+        let span = std::cmp::max(self.last_line_start_offset(), 1);
+        let finished = self.scan_matches_from(0, predict, interrupted, |offset| {
+            let bucket = std::cmp::min(offset * bucket_count / span, bucket_count - 1);
+            buckets[bucket] += 1;
+        })?;
+        Ok(finished.then_some(buckets))
+    }
+
+    /// Offsets of all lines satisfying `predict`, in document order. Interrupted like
+    /// [`Document::count_matches`].
+    pub fn match_offsets<P, I>(&mut self, predict: P, interrupted: I) -> Result<Option<Vec<usize>>>
+    where
+        P: Fn(&str) -> bool,
+        I: FnMut() -> bool,
+    {
+        let mut offsets = vec![];
+        let finished =
+            self.scan_matches_from(0, predict, interrupted, |offset| offsets.push(offset))?;
+        Ok(finished.then_some(offsets))
+    }
+
+    /// Calls `on_match` with the offset of each line satisfying `predict`, from the line at `from`
+    /// on. Returns false if interrupted like [`Document::count_matches`].
+    pub fn scan_matches_from<P, I, M>(
+        &mut self,
+        from: usize,
+        predict: P,
+        mut interrupted: I,
+        mut on_match: M,
+    ) -> Result<bool>
+    where
+        P: Fn(&str) -> bool,
+        I: FnMut() -> bool,
+        M: FnMut(usize),
+    {
+        if from > 0 {
+            self.check_offset_is_at_line_start(from)?;
+        }
+        let mut offset = from;
+        while offset < self.last_line_start_offset() {
+            if interrupted() {
+                return Ok(false);
+            }
+            let chunk = self.get_or_load_chunk_by_offset(offset)?;
+            let line_index = chunk.query_line_index_exactly(offset)?;
+            let mut scanned = 0;
+            for (line, len) in chunk.rows_with_len().skip(line_index) {
+                if predict(line) {
+                    on_match(offset);
+                }
+                offset += len;
+                scanned += 1;
+            }
+            self.stats.lines_scanned += scanned;
+        }
+        if predict(&self.last_line_text()?) {
+            on_match(offset);
+        }
+        self.stats.lines_scanned += 1;
+        Ok(true)
+    }
+
+    /// Finds the first line whose timestamp is not earlier than the target, using the date of
+    /// the first timestamp in the document if `date` is absent. The target is converted into the
+    /// log timezone if `timezone` is given, and clamped to the first or last timestamped line if
+    /// it falls outside of the logged period. `None` if no timestamp format can be detected.
     pub fn query_offset_by_timestamp(
         &mut self,
         date: Option<NaiveDate>,
         time: NaiveTime,
-    ) -> Result<Option<usize>> {
+        timezone: Option<FixedOffset>,
+    ) -> Result<Option<TimestampMatch>> {
         if self.chunks.is_empty() {
             // empty file or only single line
-            return Ok(Some(0));
-        }
-        if self.log_timestamp_format.is_none() {
-            self.load_log_timestamp_format_and_default_date();
+            return Ok(Some(TimestampMatch::Found(0)));
         }
-        if self.log_timestamp_format.is_none() {
+        if self.log_timestamp_format.is_none() && self.detect_log_timestamp_format(0)?.is_none() {
             // cannot detect log timestamp format or default date
             return Ok(None);
         }
-        let date = date.unwrap_or(self.log_default_date.unwrap());
-        let target_datetime = NaiveDateTime::new(date, time);
+        let target_datetime = self.target_datetime(date, time, timezone);
 
+        let timestamp_format = self.log_timestamp_format.clone().unwrap();
+        // invariant: the target line is in [offset_begin, offset_end), or it's `found` if
+        // there's no line with late enough timestamp in that range
         let mut offset_begin = 0;
         let mut offset_end = self.last_line_start_offset();
-        let timestamp_format = self.log_timestamp_format.clone().unwrap();
-        let mut offset = (offset_begin + offset_end) / 2;
-        loop {
-            let chunk = self.get_or_load_chunk_by_offset(offset)?;
-            let index = chunk.query_line_index(offset);
-            if let Result::Ok((datetime, _)) =
-                NaiveDateTime::parse_and_remainder(&chunk.rows[index], &timestamp_format)
-            {
-                if datetime >= target_datetime {
+        let mut found = None;
+        for _ in 0..TIMESTAMP_BISECT_MAX_ITERATIONS {
+            if offset_begin + self.default_chunk_size >= offset_end {
+                break;
+            }
+            let offset_middle = self.snap_offset_to_line_start((offset_begin + offset_end) / 2)?;
+            // lines without timestamp (e.g. stack traces) are skipped over
+            match self.scan_timestamp_forward(
+                offset_middle,
+                offset_end,
+                &timestamp_format,
+                |_, _| true,
+            )? {
+                Some((offset, datetime)) if datetime >= target_datetime => {
                     offset_end = offset;
-                } else {
-                    offset_begin = offset;
-                }
-                if offset_begin + DEFAULT_CHUNK_SIZE >= offset_end {
-                    return Ok(Some(self.linear_search_timestamp(
-                        offset_begin,
-                        offset_end,
-                        target_datetime,
-                    )?));
+                    found = Some((offset, datetime));
                 }
-                offset = (offset_begin + offset_end) / 2;
+                Some((offset, _)) => offset_begin = offset,
+                None => offset_end = offset_middle,
+            }
+        }
+        let found = self
+            .scan_timestamp_forward(
+                offset_begin,
+                offset_end,
+                &timestamp_format,
+                |_, datetime| datetime >= target_datetime,
+            )?
+            .or(found);
+
+        if let Some((offset, datetime)) = found {
+            let before_first = datetime > target_datetime
+                && self
+                    .scan_timestamp_forward(0, offset, &timestamp_format, |_, _| true)?
+                    .is_none();
+            return Ok(Some(if before_first {
+                TimestampMatch::ClampedToFirst(offset, datetime)
             } else {
-                // a log line without timestamp, try next line
-                offset = chunk.query_line_start_offset(index + 1);
-                if offset >= offset_end {
-                    return Ok(None);
+                TimestampMatch::Found(offset)
+            }));
+        }
+        // the last line is not kept in chunks, so it's checked separately
+        let last_line_start_offset = self.last_line_start_offset();
+        let last =
+            match NaiveDateTime::parse_and_remainder(&self.last_line_text()?, &timestamp_format) {
+                Result::Ok((datetime, _)) => Some((last_line_start_offset, datetime)),
+                Err(_) => self.scan_last_timestamp(last_line_start_offset, &timestamp_format)?,
+            };
+        Ok(last.map(|(offset, datetime)| {
+            if datetime >= target_datetime {
+                TimestampMatch::Found(offset)
+            } else {
+                TimestampMatch::ClampedToLast(offset, datetime)
+            }
+        }))
+    }
+
+    /// Like [`Document::query_offset_by_timestamp`], but finds the occurrence of the target
+    /// nearest to `offset`, for logs whose time repeats, e.g. after a restart. Lines where the
+    /// timestamps reach the target are looked for in windows growing around `offset`, on the
+    /// side the target would be on first. If `direction` is given, only lines strictly above
+    /// or below `offset` are taken, and `None` is returned if there's no such line. Otherwise
+    /// the whole document is bisected once the windows grow past 16 chunks.
+    pub fn query_nearest_offset_by_timestamp(
+        &mut self,
+        offset: usize,
+        direction: Option<Direction>,
+        date: Option<NaiveDate>,
+        time: NaiveTime,
+        timezone: Option<FixedOffset>,
+    ) -> Result<Option<TimestampMatch>> {
+        if self.chunks.is_empty() {
+            // empty file or only single line
+            return Ok(Some(TimestampMatch::Found(0)));
+        }
+        if self.log_timestamp_format.is_none() && self.detect_log_timestamp_format(0)?.is_none() {
+            return Ok(None);
+        }
+        let target_datetime = self.target_datetime(date, time, timezone);
+        let timestamp_format = self.log_timestamp_format.clone().unwrap();
+        self.check_offset_is_at_line_start(offset)?;
+        let last_line_start_offset = self.last_line_start_offset();
+
+        let sides = match direction {
+            Some(direction) => vec![direction],
+            None => {
+                let window_end = (offset + self.default_chunk_size).min(last_line_start_offset);
+                let current = match self.scan_timestamp_forward(
+                    offset,
+                    window_end,
+                    &timestamp_format,
+                    |_, _| true,
+                )? {
+                    Some(current) => Some(current),
+                    None => self.scan_last_timestamp(offset, &timestamp_format)?,
+                };
+                match current {
+                    Some((_, datetime)) if datetime >= target_datetime => {
+                        vec![Direction::Up, Direction::Down]
+                    }
+                    _ => vec![Direction::Down, Direction::Up],
                 }
             }
+        };
+        let mut radius = self.default_chunk_size;
+        loop {
+            let mut exhausted = true;
+            for &side in &sides {
+                let found = if side == Direction::Up {
+                    let window_begin =
+                        self.snap_offset_to_line_start(offset.saturating_sub(radius))?;
+                    exhausted &= window_begin == 0;
+                    // the line at `offset` is taken only without a direction
+                    let window_end = offset + usize::from(direction.is_none());
+                    self.scan_timestamp_crossings(
+                        window_begin,
+                        window_end,
+                        &timestamp_format,
+                        target_datetime,
+                    )?
+                    .pop()
+                } else {
+                    let window_end = offset.saturating_add(radius);
+                    exhausted &= window_end > last_line_start_offset;
+                    self.scan_timestamp_crossings(
+                        offset,
+                        window_end,
+                        &timestamp_format,
+                        target_datetime,
+                    )?
+                    .into_iter()
+                    .find(|found| direction.is_none() || found.offset() > offset)
+                };
+                if found.is_some() {
+                    return Ok(found);
+                }
+            }
+            if direction.is_none()
+                && (exhausted || radius >= self.default_chunk_size * NEAREST_TIMESTAMP_MAX_CHUNKS)
+            {
+                // e.g. the target is later than any timestamp
+                return self.query_offset_by_timestamp(date, time, timezone);
+            }
+            if exhausted {
+                return Ok(None);
+            }
+            radius *= 2;
         }
     }
 
-    fn load_log_timestamp_format_and_default_date(&mut self) {
-        assert!(self.log_timestamp_format.is_none() && self.log_default_date.is_none());
-        assert!(!self.chunks.is_empty());
-        const SAMPLE_LINE_COUNT: usize = 100;
-        for line in self.chunks[0].rows.iter().take(SAMPLE_LINE_COUNT) {
-            if let Some(fmt) = detect_log_timstamp_format(line) {
-                self.log_timestamp_format = Some(fmt.clone());
-                self.log_default_date = Some(
-                    NaiveDateTime::parse_and_remainder(line, &fmt)
-                        .unwrap()
-                        .0
-                        .date(),
-                );
-                return;
+    // lines in [offset_begin, offset_end) where timestamps reach the target, i.e. whose
+    // timestamp is not earlier than the target while the one before them is, or there's none
+    fn scan_timestamp_crossings(
+        &mut self,
+        offset_begin: usize,
+        offset_end: usize,
+        timestamp_format: &str,
+        target_datetime: NaiveDateTime,
+    ) -> Result<Vec<TimestampMatch>> {
+        let mut previous = match offset_begin {
+            0 => None,
+            _ => self
+                .scan_last_timestamp(offset_begin, timestamp_format)?
+                .map(|(_, datetime)| datetime),
+        };
+        let mut crossings = vec![];
+        let mut on_timestamp = |offset, datetime| {
+            if datetime >= target_datetime {
+                match previous {
+                    None if datetime > target_datetime => {
+                        crossings.push(TimestampMatch::ClampedToFirst(offset, datetime))
+                    }
+                    Some(previous) if previous >= target_datetime => {}
+                    _ => crossings.push(TimestampMatch::Found(offset)),
+                }
+            }
+            previous = Some(datetime);
+            false
+        };
+        let last_line_start_offset = self.last_line_start_offset();
+        self.scan_timestamp_forward(
+            offset_begin,
+            offset_end.min(last_line_start_offset),
+            timestamp_format,
+            &mut on_timestamp,
+        )?;
+        // the last line is not kept in chunks, so it's checked separately
+        if offset_end > last_line_start_offset {
+            if let Result::Ok((datetime, _)) =
+                NaiveDateTime::parse_and_remainder(&self.last_line_text()?, timestamp_format)
+            {
+                on_timestamp(last_line_start_offset, datetime);
             }
         }
+        Ok(crossings)
+    }
+
+    // the target in the log timezone, on the date of the first timestamp if `date` is absent
+    fn target_datetime(
+        &self,
+        date: Option<NaiveDate>,
+        time: NaiveTime,
+        timezone: Option<FixedOffset>,
+    ) -> NaiveDateTime {
+        let date = date.unwrap_or(self.log_default_date.unwrap());
+        let target_datetime = NaiveDateTime::new(date, time);
+        match timezone {
+            Some(timezone) => self.convert_to_log_timezone(target_datetime, timezone),
+            None => target_datetime,
+        }
+    }
+
+    fn convert_to_log_timezone(
+        &self,
+        datetime: NaiveDateTime,
+        timezone: FixedOffset,
+    ) -> NaiveDateTime {
+        let utc_datetime = datetime - timezone;
+        match self.log_timezone {
+            Some(log_timezone) => utc_datetime + log_timezone,
+            None => Local.from_utc_datetime(&utc_datetime).naive_local(),
+        }
     }
 
-    fn linear_search_timestamp(
+    // find the first line in [offset_begin, offset_end) whose timestamp satisfies `predict`
+    fn scan_timestamp_forward<P>(
         &mut self,
         offset_begin: usize,
         offset_end: usize,
-        target: NaiveDateTime,
-    ) -> Result<usize> {
-        let timestamp_format = self.log_timestamp_format.clone().unwrap();
+        timestamp_format: &str,
+        mut predict: P,
+    ) -> Result<Option<(usize, NaiveDateTime)>>
+    where
+        P: FnMut(usize, NaiveDateTime) -> bool,
+    {
         let mut offset = offset_begin;
         while offset < offset_end {
             let chunk = self.get_or_load_chunk_by_offset(offset)?;
-            offset = chunk.offset_begin;
-            for line in chunk.rows.iter() {
+            let line_index = chunk.query_line_index_exactly(offset)?;
+            for (line, len) in chunk.rows_with_len().skip(line_index) {
+                if offset >= offset_end {
+                    return Ok(None);
+                }
                 if let Result::Ok((datetime, _)) =
-                    NaiveDateTime::parse_and_remainder(line, &timestamp_format)
+                    NaiveDateTime::parse_and_remainder(line, timestamp_format)
                 {
-                    if datetime >= target {
-                        return Ok(offset);
+                    if predict(offset, datetime) {
+                        return Ok(Some((offset, datetime)));
                     }
                 }
-                offset += line.len() + 1;
+                offset += len;
+            }
+        }
+        Ok(None)
+    }
+
+    // find the last line in [0, offset_end) with a timestamp, one chunk size at a time backward
+    fn scan_last_timestamp(
+        &mut self,
+        mut offset_end: usize,
+        timestamp_format: &str,
+    ) -> Result<Option<(usize, NaiveDateTime)>> {
+        while offset_end > 0 {
+            let offset_begin =
+                self.snap_offset_to_line_start(offset_end.saturating_sub(self.default_chunk_size))?;
+            let mut last = None;
+            self.scan_timestamp_forward(
+                offset_begin,
+                offset_end,
+                timestamp_format,
+                |offset, datetime| {
+                    last = Some((offset, datetime));
+                    false
+                },
+            )?;
+            if last.is_some() {
+                return Ok(last);
             }
-            assert_eq!(offset, chunk.offset_end);
+            offset_end = offset_begin;
         }
-        Ok(offset_end)
+        Ok(None)
     }
 
-    pub fn assert_offset_is_at_line_start(&mut self, offset: usize) -> Result<()> {
+    /// Detects the timestamp format by sampling lines from the document start, then from
+    /// `offset` if nothing is found there. A detected format is kept for later queries, while
+    /// a failed detection is retried next time.
+    pub fn detect_log_timestamp_format(&mut self, offset: usize) -> Result<Option<String>> {
+        if self.log_timestamp_format.is_some() {
+            return Ok(self.log_timestamp_format.clone());
+        }
+        const SAMPLE_LINE_COUNT: usize = 500;
+        let mut samples = self.query_line_texts(0, SAMPLE_LINE_COUNT)?;
+        if offset > 0 {
+            samples.extend(self.query_line_texts(offset, SAMPLE_LINE_COUNT)?);
+        }
+        for line in samples.iter() {
+            if let Some(fmt) = detect_log_timstamp_format(line) {
+                info!("[detect_log_timestamp_format] detected: {fmt}");
+                self.log_default_date = Some(
+                    NaiveDateTime::parse_and_remainder(line, &fmt)
+                        .unwrap()
+                        .0
+                        .date(),
+                );
+                if let Result::Ok((datetime, _)) = DateTime::parse_and_remainder(line, &fmt) {
+                    self.log_timezone = Some(*datetime.offset());
+                }
+                self.log_timestamp_format = Some(fmt);
+                break;
+            }
+        }
+        Ok(self.log_timestamp_format.clone())
+    }
+
+    /// The date of the first timestamp sampled by [`Document::detect_log_timestamp_format`].
+    pub fn log_default_date(&self) -> Option<NaiveDate> {
+        self.log_default_date
+    }
+
+    /// Timestamps of the first and the last timestamped lines, `None` if no timestamp format
+    /// can be detected or no line has a timestamp.
+    pub fn query_first_and_last_timestamps(
+        &mut self,
+    ) -> Result<Option<(NaiveDateTime, NaiveDateTime)>> {
+        let Some(timestamp_format) = self.detect_log_timestamp_format(0)? else {
+            return Ok(None);
+        };
+        let last_line_start_offset = self.last_line_start_offset();
+        // the last line is not kept in chunks, so it's checked separately
+        let last_line =
+            NaiveDateTime::parse_and_remainder(&self.last_line_text()?, &timestamp_format)
+                .ok()
+                .map(|(datetime, _)| (last_line_start_offset, datetime));
+        let first = match self.scan_timestamp_forward(
+            0,
+            last_line_start_offset,
+            &timestamp_format,
+            |_, _| true,
+        )? {
+            Some(first) => Some(first),
+            None => last_line,
+        };
+        let last = match last_line {
+            Some(last) => Some(last),
+            None => self.scan_last_timestamp(last_line_start_offset, &timestamp_format)?,
+        };
+        Ok(first.zip(last).map(|((_, first), (_, last))| (first, last)))
+    }
+
+    /// Moves an arbitrary offset, e.g. a computed or restored one, back to the start of the
+    /// line it falls in, loading its chunk if needed. A line delimiter belongs to the line it
+    /// ends, and offsets from the last line start on, including the document size and beyond,
+    /// are clamped to the last line start.
+    pub fn snap_offset_to_line_start(&mut self, offset: usize) -> Result<usize> {
+        if offset >= self.last_line_start_offset() {
+            return Ok(self.last_line_start_offset());
+        }
         let chunk = self.get_or_load_chunk_by_offset(offset)?;
-        chunk.query_line_index_exactly(offset);
+        let index = chunk.query_line_index(offset);
+        Ok(chunk.query_line_start_offset(index))
+    }
+
+    fn check_offset_in_range(&self, offset: usize) -> Result<()> {
+        let last_line_start_offset = self.last_line_start_offset();
+        if offset > last_line_start_offset {
+            return Err(DocumentError::OffsetOutOfRange {
+                offset,
+                last_line_start_offset,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn check_offset_is_at_line_start(&mut self, offset: usize) -> Result<()> {
+        self.check_offset_in_range(offset)?;
+        if self.snap_offset_to_line_start(offset)? != offset {
+            return Err(DocumentError::NotAtLineStart { offset }.into());
+        }
         Ok(())
     }
 }
@@ -456,6 +1506,66 @@ mod tests {
     use super::*;
     use std::{io::Cursor, vec};
 
+    // counts the bytes read through it
+    #[derive(Debug)]
+    struct CountingReader {
+        inner: Cursor<String>,
+        read_count: usize,
+        // the lowest position read from
+        read_floor: Option<u64>,
+    }
+
+    impl CountingReader {
+        fn new(content: String) -> Self {
+            Self {
+                inner: Cursor::new(content),
+                read_count: 0,
+                read_floor: None,
+            }
+        }
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let position = self.inner.position();
+            let n = self.inner.read(buf)?;
+            self.read_count += n;
+            if n > 0 {
+                self.read_floor = Some(self.read_floor.map_or(position, |f| f.min(position)));
+            }
+            std::io::Result::Ok(n)
+        }
+    }
+
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    // claims the length of `content` when seeked to the end, but reads stop at `readable`, as
+    // if the file was truncated in between. reads return at most `max_read` bytes each
+    #[derive(Debug)]
+    struct ShrinkingReader {
+        inner: Cursor<String>,
+        readable: usize,
+        max_read: usize,
+    }
+
+    impl Read for ShrinkingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let left = self.readable.saturating_sub(self.inner.position() as usize);
+            let len = buf.len().min(left).min(self.max_read);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    impl Seek for ShrinkingReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
     fn doc_query_distance_to_prev_match(
         doc: &mut Document<Cursor<&str>>,
         offset: usize,
@@ -580,6 +1690,46 @@ mod tests {
         assert_eq!(doc.query_distance_to_above_n_lines(47, 4).unwrap(), 12);
     }
 
+    #[test]
+    fn test_count_lines_between() {
+        let cursor = Cursor::new("1234\nabcd\n\n1234\nremain");
+        let mut doc = Document::new(cursor).unwrap();
+        assert_eq!(doc.count_lines_between(0, 0).unwrap(), 0);
+        assert_eq!(doc.count_lines_between(0, 5).unwrap(), 1);
+        assert_eq!(doc.count_lines_between(0, 16).unwrap(), 4);
+        assert_eq!(doc.count_lines_between(10, 16).unwrap(), 2);
+        assert!(doc.count_lines_between(0, 17).is_err());
+
+        // across chunks
+        let content = "line\n".repeat(1000);
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        doc.default_chunk_size = 64;
+        assert_eq!(doc.count_lines_between(0, 4995).unwrap(), 999);
+        assert_eq!(doc.count_lines_between(500, 1000).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_query_offset_of_last_n_lines() {
+        let mut doc = Document::new(Cursor::new("1234\nabcd\n\nremain\n")).unwrap();
+        assert_eq!(doc.query_offset_of_last_n_lines(0).unwrap(), 11);
+        assert_eq!(doc.query_offset_of_last_n_lines(1).unwrap(), 11);
+        assert_eq!(doc.query_offset_of_last_n_lines(2).unwrap(), 10);
+        assert_eq!(doc.query_offset_of_last_n_lines(4).unwrap(), 0);
+        assert_eq!(doc.query_offset_of_last_n_lines(500).unwrap(), 0);
+
+        let mut doc = Document::new(Cursor::new("")).unwrap();
+        assert_eq!(doc.query_offset_of_last_n_lines(500).unwrap(), 0);
+
+        // only the tail of a large document is loaded
+        let content = "line\n".repeat(DEFAULT_CHUNK_SIZE);
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        assert_eq!(
+            doc.query_offset_of_last_n_lines(3).unwrap(),
+            content.len() - 15
+        );
+        assert!(doc.chunks.iter().all(|chunk| chunk.offset_begin > 0));
+    }
+
     #[test]
     fn test_query_distance_to_below_n_lines() {
         let cursor = Cursor::new("1234\nabcd\n1234\nabcd\n1234\nabcd\n1234\nabcd\nremain");
@@ -597,87 +1747,1027 @@ mod tests {
         let mut doc = Document::new(cursor.clone()).unwrap();
         doc.default_chunk_size = 10;
         assert_eq!(doc.chunks.len(), 1);
-        assert_eq!(&doc.last_line, "remain");
+        assert_eq!(doc.last_line_text().unwrap(), "remain");
+        assert!(doc.lacks_final_line_delimiter());
         doc.chunks.pop();
 
-        assert_eq!(doc.query_lines(0, 2).unwrap(), vec!["1234", "abcd"]);
-        assert_eq!(doc.query_lines(15, 1).unwrap(), vec!["abcd"]);
-        assert_eq!(doc.query_lines(0, 1).unwrap(), vec!["1234"]);
+        assert_eq!(doc.query_line_texts(0, 2).unwrap(), vec!["1234", "abcd"]);
+        assert_eq!(doc.query_line_texts(15, 1).unwrap(), vec!["abcd"]);
+        assert_eq!(doc.query_line_texts(0, 1).unwrap(), vec!["1234"]);
         assert_eq!(
-            doc.query_lines(15, 3).unwrap(),
+            doc.query_line_texts(15, 3).unwrap(),
             vec!["abcd", "1234", "abcd"]
         );
-        assert_eq!(doc.query_lines(35, 1).unwrap(), vec!["abcd"]);
-        assert_eq!(doc.query_lines(35, 2).unwrap(), vec!["abcd", "remain"]);
+        assert_eq!(doc.query_line_texts(35, 1).unwrap(), vec!["abcd"]);
+        assert_eq!(doc.query_line_texts(35, 2).unwrap(), vec!["abcd", "remain"]);
 
         let cursor = Cursor::new("123456789\n\n\nabcd\n123456789\n");
         let mut doc = Document::new(cursor.clone()).unwrap();
         doc.default_chunk_size = 24;
         assert_eq!(doc.chunks.len(), 1);
-        assert_eq!(&doc.last_line, "123456789\n");
+        assert_eq!(doc.last_line_text().unwrap(), "123456789");
+        assert!(!doc.lacks_final_line_delimiter());
         doc.chunks.pop();
 
-        assert_eq!(doc.query_lines(0, 2).unwrap(), vec!["123456789", ""]);
-        assert_eq!(doc.query_lines(0, 3).unwrap(), vec!["123456789", "", ""]);
+        assert_eq!(doc.query_line_texts(0, 2).unwrap(), vec!["123456789", ""]);
         assert_eq!(
-            doc.query_lines(0, 4).unwrap(),
+            doc.query_line_texts(0, 3).unwrap(),
+            vec!["123456789", "", ""]
+        );
+        assert_eq!(
+            doc.query_line_texts(0, 4).unwrap(),
             vec!["123456789", "", "", "abcd"]
         );
         assert_eq!(
-            doc.query_lines(0, 5).unwrap(),
+            doc.query_line_texts(0, 5).unwrap(),
             vec!["123456789", "", "", "abcd", "123456789"]
         );
         assert_eq!(
-            doc.query_lines(0, 6).unwrap(),
+            doc.query_line_texts(0, 6).unwrap(),
             vec!["123456789", "", "", "abcd", "123456789"]
         );
         assert_eq!(
-            doc.query_lines(10, 6).unwrap(),
+            doc.query_line_texts(10, 6).unwrap(),
             vec!["", "", "abcd", "123456789"]
         );
         assert_eq!(
-            doc.query_lines(11, 6).unwrap(),
+            doc.query_line_texts(11, 6).unwrap(),
             vec!["", "abcd", "123456789"]
         );
-        assert_eq!(doc.query_lines(12, 6).unwrap(), vec!["abcd", "123456789"]);
-        assert_eq!(doc.query_lines(12, 1).unwrap(), vec!["abcd"]);
+        assert_eq!(
+            doc.query_line_texts(12, 6).unwrap(),
+            vec!["abcd", "123456789"]
+        );
+        assert_eq!(doc.query_line_texts(12, 1).unwrap(), vec!["abcd"]);
 
+        // chunks loaded around 0 and 12 are adjacent, so they're merged into one
         assert_eq!(
             doc.chunks,
-            vec![
-                Chunk {
-                    offset_begin: 0,
-                    offset_end: 12,
-                    rows: vec!["123456789".to_string(), "".to_string(), "".to_string()]
-                },
-                Chunk {
-                    offset_begin: 12,
-                    offset_end: 17,
-                    rows: vec!["abcd".to_string()]
-                },
-            ]
+            vec![Chunk::build_chunk(
+                "123456789\n\n\nabcd\n",
+                0,
+                false,
+                false,
+                "\n"
+            )]
         );
     }
 
+    #[test]
+    fn test_query_lines_with_offsets() {
+        fn offsets(lines: Vec<Line>) -> Vec<usize> {
+            lines.into_iter().map(|line| line.offset).collect()
+        }
+        let cursor = Cursor::new("1234\nabcd\n1234\nabcd\n1234\nabcd\n1234\nabcd\nremain");
+        let mut doc = Document::new(cursor).unwrap();
+        doc.default_chunk_size = 10;
+        doc.chunks.clear();
+        assert_eq!(offsets(doc.query_lines(0, 3).unwrap()), [0, 5, 10]);
+        // across chunks, ending with the incomplete last line
+        assert_eq!(offsets(doc.query_lines(25, 5).unwrap()), [25, 30, 35, 40]);
+        assert_eq!(
+            doc.query_lines(40, 1).unwrap(),
+            [Line {
+                offset: 40,
+                text: "remain".to_string()
+            }]
+        );
+
+        let cursor = Cursor::new("123456789\n\n\nabcd\n123456789\n");
+        let mut doc = Document::new(cursor).unwrap();
+        doc.default_chunk_size = 24;
+        doc.chunks.clear();
+        // the complete last line starts at `last_line_start_offset` too
+        assert_eq!(doc.last_line_start_offset(), 17);
+        assert_eq!(offsets(doc.query_lines(0, 6).unwrap()), [0, 10, 11, 12, 17]);
+
+        let cursor = Cursor::new("1234\r\nab\r\n\r\nc\rd\r\nremain\r\n");
+        let mut doc = Document::with_line_delimiter(cursor, "\r\n").unwrap();
+        assert_eq!(offsets(doc.query_lines(0, 6).unwrap()), [0, 6, 10, 12, 17]);
+
+        let mut doc = Document::new(Cursor::new("")).unwrap();
+        assert_eq!(
+            doc.query_lines(0, 2).unwrap(),
+            [Line {
+                offset: 0,
+                text: String::new()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_crlf_delimiter() {
+        let cursor = Cursor::new("1234\r\nab\r\n\r\nc\rd\r\nremain\r\n");
+        let mut doc = Document::with_line_delimiter(cursor, "\r\n").unwrap();
+        doc.default_chunk_size = 16;
+        assert_eq!(doc.last_line_text().unwrap(), "remain");
+        assert!(!doc.lacks_final_line_delimiter());
+        assert_eq!(doc.last_line_start_offset(), 17);
+        doc.chunks.clear();
+
+        assert_eq!(
+            doc.query_line_texts(0, 6).unwrap(),
+            vec!["1234", "ab", "", "c\rd", "remain"]
+        );
+        assert_eq!(doc.query_line_texts(10, 2).unwrap(), vec!["", "c\rd"]);
+        assert_eq!(doc.query_distance_to_below_n_lines(0, 3).unwrap(), 12);
+        assert_eq!(doc.query_distance_to_above_n_lines(17, 2).unwrap(), 7);
+        assert_eq!(doc.query_distance_to_above_n_lines(17, 5).unwrap(), 17);
+        assert_eq!(
+            doc.query_distance_to_next_match(0, |l| l == "remain")
+                .unwrap(),
+            Some(17)
+        );
+        assert_eq!(
+            doc.query_distance_to_prev_match(17, |l| l == "ab").unwrap(),
+            Some(11)
+        );
+        assert_eq!(doc.snap_offset_to_line_start(9).unwrap(), 6);
+    }
+
+    #[test]
+    fn test_record_separator_delimiter() {
+        let cursor = Cursor::new("{\"a\":1}\x1e{\"b\":\n2}\x1e{\"c\":3}");
+        let mut doc = Document::with_line_delimiter(cursor, "\x1e").unwrap();
+        assert_eq!(doc.last_line_text().unwrap(), "{\"c\":3}");
+        assert!(doc.lacks_final_line_delimiter());
+        assert_eq!(doc.last_line_start_offset(), 17);
+        assert_eq!(
+            doc.query_line_texts(0, 3).unwrap(),
+            vec!["{\"a\":1}", "{\"b\":\n2}", "{\"c\":3}"]
+        );
+        assert_eq!(doc.query_distance_to_below_n_lines(0, 1).unwrap(), 8);
+        assert_eq!(doc.query_distance_to_above_n_lines(17, 1).unwrap(), 9);
+    }
+
+    #[test]
+    fn test_offset_precondition_errors() {
+        let cursor = Cursor::new("1234\nabcd\nremain");
+        let mut doc = Document::new(cursor).unwrap();
+        fn error<T: fmt::Debug>(result: Result<T>) -> DocumentError {
+            result.unwrap_err().downcast::<DocumentError>().unwrap()
+        }
+        assert_eq!(
+            error(doc.query_line_texts(3, 1)),
+            DocumentError::NotAtLineStart { offset: 3 }
+        );
+        assert_eq!(
+            error(doc.query_distance_to_above_n_lines(11, 1)),
+            DocumentError::OffsetOutOfRange {
+                offset: 11,
+                last_line_start_offset: 10
+            }
+        );
+        assert!(doc.query_distance_to_below_n_lines(7, 1).is_err());
+        assert!(doc.query_distance_to_prev_match(20, |_| true).is_err());
+        assert!(doc.query_distance_to_next_match(1, |_| true).is_err());
+        assert!(doc.check_offset_is_at_line_start(10).is_ok());
+        assert_eq!(
+            error(Document::with_line_delimiter(Cursor::new(""), "")),
+            DocumentError::EmptyLineDelimiter
+        );
+    }
+
+    #[test]
+    fn test_query_offset_by_timestamp_with_untimestamped_lines() {
+        let mut content = String::new();
+        let mut line_offsets = vec![];
+        for minute in 0..30 {
+            line_offsets.push(content.len());
+            content += &format!("2024-01-01 12:{minute:02}:00.000 [Error] failure {minute}\n");
+            // stack traces of varying length, including none
+            for depth in 0..(minute % 7) * 3 {
+                content += &format!("    at frame {depth} (src/main.rs:{minute})\n");
+            }
+        }
+        content += "tail";
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        doc.default_chunk_size = 64;
+
+        let mut query = |hour, minute, second| {
+            let time = NaiveTime::from_hms_opt(hour, minute, second).unwrap();
+            doc.query_offset_by_timestamp(None, time, None)
+                .unwrap()
+                .unwrap()
+        };
+        for minute in 0..30 {
+            assert_eq!(query(12, minute, 0).offset(), line_offsets[minute as usize]);
+        }
+        assert_eq!(query(12, 12, 30), TimestampMatch::Found(line_offsets[13]));
+        assert_eq!(query(11, 0, 0).offset(), 0);
+        // later than any line, the trailing line without timestamp is skipped
+        assert!(matches!(
+            query(13, 0, 0),
+            TimestampMatch::ClampedToLast(offset, _) if offset == line_offsets[29]
+        ));
+    }
+
+    #[test]
+    fn test_query_nearest_offset_by_timestamp() {
+        let path = format!("{}/tests/fixtures/restart.log", env!("CARGO_MANIFEST_DIR"));
+        let content = std::fs::read_to_string(&path).unwrap();
+        let offset_of = |text: &str| content.find(text).unwrap();
+        let first_run = offset_of("10:00:00 INFO [run 1]") - "2024-05-01 ".len();
+        let second_run = offset_of("10:00:00 INFO [run 2]") - "2024-05-01 ".len();
+        let restarted = offset_of("---");
+        let mut doc = Document::<File>::open_file(&path, "\n").unwrap();
+        doc.default_chunk_size = 64;
+
+        let mut query = |offset, direction, minute| {
+            let time = NaiveTime::from_hms_opt(10, minute, 0).unwrap();
+            doc.query_nearest_offset_by_timestamp(offset, direction, None, time, None)
+                .unwrap()
+        };
+        // the whole document is bisected into the first run
+        assert_eq!(query(0, None, 0), Some(TimestampMatch::Found(first_run)));
+        // later in the second run, the target is above
+        let later = offset_of("10:01:40 INFO [run 2]") - "2024-05-01 ".len();
+        assert_eq!(
+            query(later, None, 0),
+            Some(TimestampMatch::Found(second_run))
+        );
+        // earlier in the second run, it's below
+        assert_eq!(
+            query(restarted, None, 0),
+            Some(TimestampMatch::Found(second_run))
+        );
+        assert_eq!(
+            query(second_run, None, 0),
+            Some(TimestampMatch::Found(second_run))
+        );
+
+        // forced directions skip the current line
+        assert_eq!(
+            query(second_run, Some(Direction::Up), 0),
+            Some(TimestampMatch::Found(first_run))
+        );
+        assert_eq!(
+            query(first_run, Some(Direction::Down), 0),
+            Some(TimestampMatch::Found(second_run))
+        );
+        assert_eq!(query(second_run, Some(Direction::Down), 0), None);
+        assert_eq!(query(first_run, Some(Direction::Up), 0), None);
+
+        // later than any timestamp, clamped as for the whole document
+        assert!(matches!(
+            query(later, None, 30),
+            Some(TimestampMatch::ClampedToLast(offset, _)) if offset == content.rfind("2024").unwrap()
+        ));
+        assert_eq!(query(later, Some(Direction::Down), 30), None);
+    }
+
+    #[test]
+    fn test_query_offset_by_timestamp_clamping() {
+        let datetime = |minute| {
+            NaiveDate::from_ymd_opt(2024, 1, 1)
+                .unwrap()
+                .and_hms_opt(12, minute, 0)
+                .unwrap()
+        };
+        for tail in ["", "\n", "\n    at frame\n"] {
+            let mut content = "banner\n".to_string();
+            let mut line_offsets = vec![];
+            for minute in [10, 20, 30] {
+                line_offsets.push(content.len());
+                content += &format!("2024-01-01 12:{minute}:00 [Info] line\n");
+            }
+            content.pop();
+            content += tail;
+            let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+            doc.default_chunk_size = 16;
+
+            let mut query = |minute| {
+                let time = NaiveTime::from_hms_opt(12, minute, 0).unwrap();
+                doc.query_offset_by_timestamp(None, time, None)
+                    .unwrap()
+                    .unwrap()
+            };
+            assert_eq!(
+                query(0),
+                TimestampMatch::ClampedToFirst(line_offsets[0], datetime(10))
+            );
+            assert_eq!(query(10), TimestampMatch::Found(line_offsets[0]));
+            assert_eq!(query(15), TimestampMatch::Found(line_offsets[1]));
+            assert_eq!(query(20), TimestampMatch::Found(line_offsets[1]));
+            assert_eq!(query(30), TimestampMatch::Found(line_offsets[2]));
+            assert_eq!(
+                query(40),
+                TimestampMatch::ClampedToLast(line_offsets[2], datetime(30))
+            );
+        }
+    }
+
+    #[test]
+    fn test_query_offset_by_timestamp_across_timezones() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(23, 0, 0)
+            .unwrap();
+        let times: Vec<_> = (0..12)
+            .map(|index| start + chrono::Duration::minutes(index * 10))
+            .collect();
+        let utc = FixedOffset::east_opt(0);
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+
+        // offset written in the log
+        let content: String = times
+            .iter()
+            .map(|datetime| format!("{}+02:00 [Info] a\n", datetime.format("%Y-%m-%dT%H:%M:%S")))
+            .collect();
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        let line_len = content.find('\n').unwrap() + 1;
+        // 22:30 in utc is 00:30 of the next day in the log
+        let found = doc
+            .query_offset_by_timestamp(None, time(22, 30), utc)
+            .unwrap();
+        assert_eq!(found, Some(TimestampMatch::Found(9 * line_len)));
+        let found = doc
+            .query_offset_by_timestamp(None, time(23, 30), None)
+            .unwrap();
+        assert_eq!(found, Some(TimestampMatch::Found(3 * line_len)));
+
+        // configured timezone of a log without offsets, the other way across midnight
+        let content: String = times
+            .iter()
+            .map(|datetime| format!("{} [Info] a\n", datetime.format("%Y-%m-%d %H:%M:%S")))
+            .collect();
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        doc.set_log_timezone(FixedOffset::west_opt(3600));
+        let line_len = content.find('\n').unwrap() + 1;
+        let date = NaiveDate::from_ymd_opt(2024, 1, 2);
+        let found = doc
+            .query_offset_by_timestamp(date, time(1, 20), utc)
+            .unwrap();
+        assert_eq!(found, Some(TimestampMatch::Found(8 * line_len)));
+    }
+
+    #[test]
+    fn test_detect_log_timestamp_format_after_banner() {
+        let banner = |line_count| "=== banner without timestamp ===\n".repeat(line_count);
+        let log = "2024-01-01 12:00:00 [Info] a\n2024-01-01 12:01:00 [Info] b\ntail";
+
+        let content = banner(200) + log;
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        assert_eq!(
+            doc.detect_log_timestamp_format(0).unwrap().as_deref(),
+            Some("%Y-%m-%d %H:%M:%S%.f")
+        );
+        let time = NaiveTime::from_hms_opt(12, 1, 0).unwrap();
+        assert_eq!(
+            doc.query_offset_by_timestamp(None, time, None).unwrap(),
+            Some(TimestampMatch::Found(
+                content.find("2024-01-01 12:01").unwrap()
+            ))
+        );
+
+        // too far from the document start, found around the given offset instead
+        let content = banner(1000) + log;
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        assert_eq!(
+            doc.query_offset_by_timestamp(None, time, None).unwrap(),
+            None
+        );
+        let offset = content.find("2024").unwrap();
+        assert!(doc.detect_log_timestamp_format(offset).unwrap().is_some());
+        assert_eq!(
+            doc.query_offset_by_timestamp(None, time, None).unwrap(),
+            Some(TimestampMatch::Found(
+                content.find("2024-01-01 12:01").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_query_first_and_last_timestamps() {
+        let datetime = |hour, minute| {
+            NaiveDate::from_ymd_opt(2024, 1, 1)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+        let mut doc = Document::new(Cursor::new(
+            "banner\n2024-01-01 12:00:00 a\n2024-01-01 12:01:00 b\n    at frame\ntail",
+        ))
+        .unwrap();
+        doc.default_chunk_size = 48;
+        assert_eq!(
+            doc.query_first_and_last_timestamps().unwrap(),
+            Some((datetime(12, 0), datetime(12, 1)))
+        );
+        assert_eq!(doc.log_default_date(), NaiveDate::from_ymd_opt(2024, 1, 1));
+
+        // the only timestamp is on the last line
+        let mut doc = Document::new(Cursor::new("banner\n2024-01-01 13:00:00 a\n")).unwrap();
+        assert_eq!(
+            doc.query_first_and_last_timestamps().unwrap(),
+            Some((datetime(13, 0), datetime(13, 0)))
+        );
+
+        let mut doc = Document::new(Cursor::new("no\ntimestamp\n")).unwrap();
+        assert_eq!(doc.query_first_and_last_timestamps().unwrap(), None);
+        assert_eq!(doc.log_default_date(), None);
+    }
+
+    #[test]
+    fn test_byte_order_mark() {
+        let path = format!("{}/tests/fixtures/bom.log", env!("CARGO_MANIFEST_DIR"));
+        let content = std::fs::read_to_string(&path).unwrap();
+        let second_line = content.find("2024-05-01 10:00:01").unwrap();
+        let mut doc = Document::<File>::open_file(&path, "\n").unwrap();
+        assert!(doc.has_bom());
+        // left out of the first line, which still starts at offset 0
+        let lines = doc.query_lines(0, 2).unwrap();
+        assert_eq!(lines[0].offset, 0);
+        assert_eq!(lines[0].text, "2024-05-01 10:00:00 INFO service started");
+        assert_eq!(lines[1].offset, second_line);
+        assert_eq!(
+            doc.query_distance_to_below_n_lines(0, 1).unwrap(),
+            second_line
+        );
+        assert_eq!(
+            doc.query_distance_to_above_n_lines(second_line, 5).unwrap(),
+            second_line
+        );
+        assert_eq!(doc.count_lines_between(0, second_line).unwrap(), 1);
+        assert!(doc.check_offset_is_at_line_start(3).is_err());
+
+        // a match at the line start
+        let at_start = |line: &str| line.starts_with("2024-05-01 10:00:00");
+        assert_eq!(
+            doc.query_distance_to_next_match(0, at_start).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            doc.query_distance_to_prev_match(second_line, at_start)
+                .unwrap(),
+            Some(second_line)
+        );
+        assert_eq!(
+            doc.match_offsets(|line| line.starts_with("2024"), || false)
+                .unwrap()
+                .unwrap()
+                .len(),
+            4
+        );
+
+        // timestamps are found on the first line too
+        assert_eq!(
+            doc.detect_log_timestamp_format(0).unwrap().as_deref(),
+            Some("%Y-%m-%d %H:%M:%S%.f")
+        );
+        assert_eq!(
+            doc.query_offset_by_timestamp(None, NaiveTime::from_hms_opt(10, 0, 1).unwrap(), None)
+                .unwrap(),
+            Some(TimestampMatch::Found(second_line))
+        );
+
+        // the same in a document of a single line
+        let mut doc = Document::new(Cursor::new("\u{feff}only line")).unwrap();
+        assert!(doc.has_bom());
+        assert_eq!(doc.query_line_texts(0, 1).unwrap(), ["only line"]);
+        let mut doc = Document::new(Cursor::new("no mark\n")).unwrap();
+        assert!(!doc.has_bom());
+        assert_eq!(doc.query_line_texts(0, 1).unwrap(), ["no mark"]);
+    }
+
+    #[test]
+    fn test_required_chunk_size() {
+        assert_eq!(required_chunk_size(0), DEFAULT_CHUNK_SIZE);
+        assert_eq!(
+            required_chunk_size(DEFAULT_CHUNK_SIZE / 2),
+            DEFAULT_CHUNK_SIZE
+        );
+        assert_eq!(
+            required_chunk_size(DEFAULT_CHUNK_SIZE / 2 + 1),
+            DEFAULT_CHUNK_SIZE * 2
+        );
+    }
+
+    #[test]
+    fn test_single_line_document() {
+        const LINE_LEN: usize = 1 << 20;
+        let content = "0123456789abcdef".repeat(LINE_LEN / 16) + "needle";
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        assert!(doc.is_single_line());
+        assert_eq!(doc.document_size(), LINE_LEN + 6);
+        assert_eq!(doc.last_line_start_offset(), 0);
+        assert_eq!(doc.position_ratio(0), PositionRatio::End);
+        assert_eq!(doc.snap_offset_to_line_start(LINE_LEN / 2).unwrap(), 0);
+
+        let lines = doc.query_lines(0, 10).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text.len(), LINE_LEN + 6);
+        assert_eq!(doc.query_distance_to_below_n_lines(0, 10).unwrap(), 0);
+        assert_eq!(doc.query_distance_to_above_n_lines(0, 10).unwrap(), 0);
+        assert_eq!(doc.query_offset_of_last_n_lines(10).unwrap(), 0);
+
+        // the whole line is a single match
+        let has_needle = |line: &str| line.contains("needle");
+        assert_eq!(
+            doc.query_distance_to_next_match(0, has_needle).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            doc.query_distance_to_prev_match(0, has_needle).unwrap(),
+            None
+        );
+        assert_eq!(
+            doc.count_matches(has_needle, || false).unwrap(),
+            Some(MatchCount {
+                count: 1,
+                first_offset: Some(0),
+            })
+        );
+        let buckets = doc
+            .match_density(has_needle, 10, || false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(buckets[0], 1);
+        assert!(doc.check_offset_is_at_line_start(1).is_err());
+
+        // a trailing line delimiter keeps it a single line
+        let content = content + "\n";
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        assert!(doc.is_single_line());
+        assert_eq!(doc.query_lines(0, 10).unwrap()[0].text.len(), LINE_LEN + 6);
+
+        // a last line longer than a chunk below a short one
+        let content = format!("first\n{}\n", "x".repeat(LINE_LEN));
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        assert!(!doc.is_single_line());
+        assert_eq!(doc.last_line_start_offset(), 6);
+        let lines = doc.query_lines(0, 10).unwrap();
+        assert_eq!(lines[0].text, "first");
+        assert_eq!(lines[1].text.len(), LINE_LEN);
+
+        assert!(!Document::new(Cursor::new("")).unwrap().is_single_line());
+        assert!(!Document::new(Cursor::new("a\nb")).unwrap().is_single_line());
+    }
+
+    #[test]
+    fn test_percent_ratio_of_offset() {
+        let mut doc = Document::new(Cursor::new("0123\n5678\nlast")).unwrap();
+        assert_eq!(doc.percent_ratio_of_offset(0), 0);
+        assert_eq!(doc.percent_ratio_of_offset(5), 50);
+        assert_eq!(doc.percent_ratio_of_offset(10), 100);
+        // transient offset beyond the last line start
+        assert_eq!(doc.percent_ratio_of_offset(14), 100);
+        assert_eq!(doc.position_ratio(0), PositionRatio::Percent(0));
+        assert_eq!(doc.position_ratio(5), PositionRatio::Percent(50));
+        assert_eq!(doc.position_ratio(10), PositionRatio::End);
+        assert_eq!(doc.position_ratio(14), PositionRatio::End);
+
+        doc = Document::new(Cursor::new("")).unwrap();
+        assert_eq!(doc.percent_ratio_of_offset(0), 100);
+        assert_eq!(doc.position_ratio(0), PositionRatio::Empty);
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let content = "a1\nb\na2\nc\nb\na3";
+        let mut doc = Document::new(Cursor::new(content)).unwrap();
+        doc.default_chunk_size = 4;
+        doc.max_merged_chunk_size = 0;
+        let count = |doc: &mut Document<_>, pattern: &str| {
+            doc.count_matches(|line| line.contains(pattern), || false)
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(
+            count(&mut doc, "a"),
+            MatchCount {
+                count: 3,
+                first_offset: Some(0)
+            }
+        );
+        assert_eq!(
+            count(&mut doc, "b"),
+            MatchCount {
+                count: 2,
+                first_offset: Some(3)
+            }
+        );
+        assert_eq!(count(&mut doc, "a3").first_offset, Some(12));
+        assert_eq!(count(&mut doc, "z"), MatchCount::default());
+
+        // interrupted after the first chunk
+        let content = "line\n".repeat(DEFAULT_CHUNK_SIZE);
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        doc.default_chunk_size = 16;
+        doc.max_merged_chunk_size = 0;
+        let mut checks = 0;
+        let interrupted = || {
+            checks += 1;
+            checks > 1
+        };
+        assert_eq!(doc.count_matches(|_| true, interrupted).unwrap(), None);
+    }
+
+    #[test]
+    fn test_match_offsets() {
+        let content = "a1\nb\na2\nc\nb\na3";
+        let mut doc = Document::new(Cursor::new(content)).unwrap();
+        doc.default_chunk_size = 4;
+        doc.max_merged_chunk_size = 0;
+        let offsets = |doc: &mut Document<_>, pattern: &str| {
+            doc.match_offsets(|line| line.contains(pattern), || false)
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(offsets(&mut doc, "a"), [0, 5, 12]);
+        assert_eq!(offsets(&mut doc, "b"), [3, 10]);
+        assert!(offsets(&mut doc, "z").is_empty());
+        assert_eq!(doc.match_offsets(|_| true, || true).unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_chunk_size() {
+        let mut doc = Document::new(Cursor::new("1234\nabc")).unwrap();
+        assert_eq!(doc.set_chunk_size(16), MIN_CHUNK_SIZE);
+        assert_eq!(doc.chunk_size(), MIN_CHUNK_SIZE);
+        assert_eq!(doc.max_merged_chunk_size, MIN_CHUNK_SIZE * 4);
+        assert_eq!(doc.set_chunk_size(1 << 20), 1 << 20);
+        assert_eq!(doc.query_line_texts(0, 2).unwrap(), ["1234", "abc"]);
+    }
+
+    #[test]
+    fn test_shrink_between_size_and_read() {
+        let content: String = (0..10).map(|index| format!("line {index}\n")).collect();
+        let content = content + "last";
+        let reader = ShrinkingReader {
+            inner: Cursor::new(content.clone()),
+            readable: content.len(),
+            max_read: 3,
+        };
+        let mut doc = Document::new(reader).unwrap();
+        // short reads of a reader that didn't shrink are read on until the gap is full
+        assert_eq!(doc.query_line_texts(0, 2).unwrap(), ["line 0", "line 1"]);
+        assert_eq!(doc.chunk_bytes(), 70);
+
+        // truncated within line 5, the lines before are still read
+        doc.chunks.clear();
+        doc.reader.readable = content.find("line 5").unwrap() + 2;
+        let lines = doc.query_line_texts(0, 5).unwrap();
+        assert_eq!(lines, ["line 0", "line 1", "line 2", "line 3", "line 4"]);
+        assert_eq!(doc.chunk_bytes(), 35);
+        let shrunk = DocumentError::Shrunk {
+            old_size: content.len(),
+            new_size: content.len(),
+        };
+        let error = doc.query_line_texts(0, 6).unwrap_err();
+        assert_eq!(error.downcast_ref::<DocumentError>(), Some(&shrunk));
+
+        // nothing readable at all
+        doc.chunks.clear();
+        doc.reader.readable = 0;
+        let error = doc.query_line_texts(35, 1).unwrap_err();
+        assert_eq!(error.downcast_ref::<DocumentError>(), Some(&shrunk));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut doc = Document::new(Cursor::new("1234\n1234\n1234\n1234\nabc")).unwrap();
+        // the lines above the last one are loaded on creation
+        assert_eq!(doc.stats().chunk_loads, 1);
+        assert_eq!(doc.stats().bytes_loaded, 20);
+        doc.chunks.clear();
+
+        doc.load_chunk(0, 10).unwrap();
+        doc.load_chunk(10, 20).unwrap();
+        let stats = doc.stats();
+        assert_eq!((stats.chunk_loads, stats.bytes_loaded), (3, 40));
+        assert_eq!(stats.chunk_merges, 1);
+        assert_eq!((doc.chunk_count(), doc.chunk_bytes()), (1, 20));
+
+        // searching down from the second line tests it and every line below
+        doc.query_distance_to_next_match(5, |line| line == "abc")
+            .unwrap();
+        assert_eq!(doc.stats().lines_scanned, 4);
+
+        doc.query_distance_to_prev_match(10, |_| false).unwrap();
+        assert_eq!(doc.stats().lines_scanned, 6);
+        doc.count_matches(|_| true, || false).unwrap();
+        assert_eq!(doc.stats().lines_scanned, 11);
+
+        // small chunks hold a line or two each, more than a single query should load
+        let content = "1234\n".repeat(QUERY_LINES_MAX_CHUNK_LOADS * 2) + "abc";
+        let mut doc = Document::new(Cursor::new(content)).unwrap();
+        doc.chunks.clear();
+        doc.default_chunk_size = 10;
+        doc.max_merged_chunk_size = 0;
+        doc.query_lines(0, 2).unwrap();
+        assert_eq!(doc.stats().chunk_heavy_queries, 0);
+        doc.query_lines(0, QUERY_LINES_MAX_CHUNK_LOADS * 2).unwrap();
+        assert_eq!(doc.stats().chunk_heavy_queries, 1);
+    }
+
+    #[test]
+    fn test_match_density() {
+        let content = "a1\nb\na2\nc\nb\na3";
+        let mut doc = Document::new(Cursor::new(content)).unwrap();
+        let density = |doc: &mut Document<_>, pattern: &str, bucket_count| {
+            doc.match_density(|line| line.contains(pattern), bucket_count, || false)
+                .unwrap()
+                .unwrap()
+        };
+        // the last line goes into the last bucket
+        assert_eq!(density(&mut doc, "a", 4), [1, 1, 0, 1]);
+        assert_eq!(density(&mut doc, "b", 4), [0, 1, 0, 1]);
+        assert_eq!(density(&mut doc, "a", 1), [3]);
+        assert_eq!(density(&mut doc, "z", 2), [0, 0]);
+        assert_eq!(doc.match_density(|_| true, 4, || true).unwrap(), None);
+
+        let mut doc = Document::new(Cursor::new("")).unwrap();
+        assert_eq!(density(&mut doc, "", 3), [1, 0, 0]);
+    }
+
+    #[test]
+    fn test_scan_matches_from() {
+        let mut doc = Document::new(Cursor::new("a1\nb\na2\nc\nb\na3")).unwrap();
+        let scan = |doc: &mut Document<_>, from| {
+            let mut offsets = vec![];
+            let finished = doc
+                .scan_matches_from(
+                    from,
+                    |line| line.contains('a'),
+                    || false,
+                    |offset| offsets.push(offset),
+                )
+                .unwrap();
+            assert!(finished);
+            offsets
+        };
+        assert_eq!(scan(&mut doc, 0), [0, 5, 12]);
+        assert_eq!(scan(&mut doc, 5), [5, 12]);
+        assert_eq!(scan(&mut doc, 12), [12]);
+        assert!(doc
+            .scan_matches_from(4, |_| true, || false, |_| {})
+            .is_err());
+    }
+
+    #[test]
+    fn test_final_line_delimiter() {
+        // the last line reads the same with or without a final line delimiter
+        for (content, lacks) in [("a\nb", true), ("a\nb\n", false)] {
+            let doc = Document::new(Cursor::new(content)).unwrap();
+            assert_eq!(doc.lacks_final_line_delimiter(), lacks);
+            assert_eq!(doc.last_line_start_offset(), 2);
+        }
+        let mut doc = Document::new(Cursor::new("a\nb\n")).unwrap();
+        assert_eq!(doc.query_line_texts(0, 3).unwrap(), vec!["a", "b"]);
+        let doc = Document::new(Cursor::new("")).unwrap();
+        assert!(!doc.lacks_final_line_delimiter());
+
+        // appended data completing the last line, then starting another
+        let mut doc = Document::new(Cursor::new("a\nb")).unwrap();
+        assert_eq!(doc.query_line_texts(0, 3).unwrap(), vec!["a", "b"]);
+        doc.reader = Cursor::new("a\nbc\n");
+        assert_eq!(doc.update_docsize_and_lastline().unwrap(), Some(3..5));
+        assert!(!doc.lacks_final_line_delimiter());
+        assert_eq!(doc.last_line_start_offset(), 2);
+        assert_eq!(doc.query_line_texts(0, 3).unwrap(), vec!["a", "bc"]);
+        doc.reader = Cursor::new("a\nbc\nd");
+        assert_eq!(doc.update_docsize_and_lastline().unwrap(), Some(5..6));
+        assert!(doc.lacks_final_line_delimiter());
+        assert_eq!(doc.last_line_start_offset(), 5);
+        assert_eq!(doc.query_line_texts(0, 4).unwrap(), vec!["a", "bc", "d"]);
+    }
+
+    #[test]
+    fn test_long_last_line() {
+        let long_line = "x".repeat(DEFAULT_CHUNK_SIZE * 8);
+        for end in ["", "\n"] {
+            let content = format!("a\nb\n{long_line}{end}");
+            let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+            // the end is scanned for the last line start a chunk at a time
+            assert!(doc.max_read_len() <= DEFAULT_CHUNK_SIZE * 2);
+            assert_eq!(doc.last_line_start_offset(), 4);
+            assert_eq!(doc.lacks_final_line_delimiter(), end.is_empty());
+            assert_eq!(doc.query_line_texts(0, 2).unwrap(), vec!["a", "b"]);
+            assert_eq!(doc.query_distance_to_below_n_lines(0, 5).unwrap(), 4);
+            assert_eq!(doc.snap_offset_to_line_start(content.len() - 1).unwrap(), 4);
+            assert!(doc.max_read_len() <= DEFAULT_CHUNK_SIZE * 2);
+
+            // read whole only when its text is asked for
+            let lines = doc.query_lines(2, 2).unwrap();
+            assert_eq!(lines[1].offset, 4);
+            assert_eq!(lines[1].text.len(), long_line.len());
+            assert_eq!(doc.max_read_len(), long_line.len());
+        }
+
+        // appended data completing the long line
+        let content = format!("a\n{long_line}");
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        let content = format!("{content}\nc");
+        doc.reader = Cursor::new(content.as_str());
+        doc.update_docsize_and_lastline().unwrap();
+        assert_eq!(doc.last_line_start_offset(), content.len() - 1);
+        assert!(doc.max_read_len() <= DEFAULT_CHUNK_SIZE * 2);
+    }
+
+    #[test]
+    fn test_with_tail() {
+        // a first run at 09:xx that the tail leaves out, then the same minutes at 10:xx
+        let mut content = String::new();
+        for hour in [9, 10] {
+            for minute in 0..60 {
+                content += &format!("2024-05-01 {hour:02}:{minute:02}:00 INFO step {minute}\n");
+            }
+        }
+        let tail_bytes = content.len() / 2 + 10;
+        let tail_start = content.len() - tail_bytes;
+        let bytes_skipped = content[tail_start..].find('\n').unwrap() + 1 + tail_start;
+        let tail = &content[bytes_skipped..];
+
+        let mut doc =
+            Document::with_tail(CountingReader::new(content.clone()), "\n", tail_bytes).unwrap();
+        doc.default_chunk_size = 64;
+        assert_eq!(doc.bytes_skipped(), bytes_skipped);
+        assert_eq!(doc.document_size(), tail.len());
+        // offsets are counted from the tail start, where the first line is
+        let lines = doc.query_lines(0, 2).unwrap();
+        assert_eq!(lines[0].offset, 0);
+        assert_eq!(lines[0].text, tail.lines().next().unwrap());
+        assert_eq!(doc.position_ratio(0), PositionRatio::Percent(0));
+        assert_eq!(
+            doc.position_ratio(doc.last_line_start_offset()),
+            PositionRatio::End
+        );
+
+        // searches and timestamp jumps stay within the tail
+        let first_09 = |line: &str| line.contains(" 09:");
+        assert_eq!(
+            doc.query_distance_to_prev_match(doc.last_line_start_offset(), first_09)
+                .unwrap(),
+            None
+        );
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        assert_eq!(
+            doc.detect_log_timestamp_format(0).unwrap().as_deref(),
+            Some("%Y-%m-%d %H:%M:%S%.f")
+        );
+        assert!(matches!(
+            doc.query_offset_by_timestamp(None, time(9, 0), None)
+                .unwrap(),
+            Some(TimestampMatch::ClampedToFirst(0, _))
+        ));
+        let found = doc
+            .query_offset_by_timestamp(None, time(10, 30), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            found,
+            TimestampMatch::Found(tail.find("10:30").unwrap() - 11)
+        );
+        doc.snap_offset_to_line_start(0).unwrap();
+        // the delimiter right before the tail start is the only byte read above it
+        assert!(doc.reader.read_floor.unwrap() >= tail_start as u64 - 1);
+
+        // appended data is picked up at the end, relative to the tail start
+        doc.reader.inner.get_mut().push_str("appended\n");
+        let appended = doc.update_docsize_and_lastline().unwrap().unwrap();
+        assert_eq!(appended, tail.len()..tail.len() + 9);
+        assert_eq!(doc.last_line_text().unwrap(), "appended");
+        assert!(doc.reader.read_floor.unwrap() >= tail_start as u64 - 1);
+
+        // the whole reader if it's not longer than the tail
+        let doc = Document::with_tail(Cursor::new("a\nb\n"), "\n", 4).unwrap();
+        assert_eq!(doc.bytes_skipped(), 0);
+        // a tail starting right at a line start keeps that line
+        let mut doc = Document::with_tail(Cursor::new("a\nb\nc\n"), "\n", 4).unwrap();
+        assert_eq!(doc.bytes_skipped(), 2);
+        assert_eq!(doc.query_line_texts(0, 2).unwrap(), ["b", "c"]);
+        // no line start in the tail, a char boundary is taken
+        let mut doc = Document::with_tail(Cursor::new("a\nxéééé"), "\n", 5).unwrap();
+        assert_eq!(doc.bytes_skipped(), 7);
+        assert_eq!(doc.query_line_texts(0, 1).unwrap(), ["éé"]);
+    }
+
+    #[test]
+    fn test_set_bytes_skipped() {
+        let banner: String = (0..50)
+            .map(|index| format!("env VAR_{index}=x\n"))
+            .collect();
+        let mut log = String::new();
+        for minute in 0..50 {
+            log += &format!("2024-05-01 10:{minute:02}:00 INFO step {minute}\n");
+        }
+        let content = banner.clone() + &log;
+        let mut doc = Document::new(Cursor::new(content.clone())).unwrap();
+        doc.default_chunk_size = 64;
+        let last_line_start = doc.last_line_start_offset();
+        let middle = doc
+            .snap_offset_to_line_start(banner.len() + log.len() / 2)
+            .unwrap();
+
+        doc.set_bytes_skipped(banner.len()).unwrap();
+        assert_eq!(doc.bytes_skipped(), banner.len());
+        assert_eq!(doc.document_size(), log.len());
+        assert_eq!(
+            doc.query_line_texts(0, 1).unwrap(),
+            [log.lines().next().unwrap()]
+        );
+        // percentages are relative to the new start
+        let middle = middle - banner.len();
+        assert_eq!(
+            doc.percent_ratio_of_offset(middle),
+            middle * 100 / doc.last_line_start_offset()
+        );
+        assert_eq!(doc.position_ratio(0), PositionRatio::Percent(0));
+        // and searches don't go above it
+        let in_banner = |line: &str| line.starts_with("env");
+        assert_eq!(
+            doc.query_distance_to_prev_match(doc.last_line_start_offset(), in_banner)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            doc.query_distance_to_next_match(0, in_banner).unwrap(),
+            None
+        );
+
+        doc.set_bytes_skipped(0).unwrap();
+        assert_eq!(doc.document_size(), content.len());
+        assert_eq!(doc.last_line_start_offset(), last_line_start);
+        let last_banner_line = banner.len() - "env VAR_49=x\n".len();
+        assert_eq!(
+            doc.query_distance_to_prev_match(last_line_start, in_banner)
+                .unwrap(),
+            Some(last_line_start - last_banner_line)
+        );
+    }
+
+    #[test]
+    fn test_snap_offset_to_line_start() {
+        let cursor = Cursor::new("1234\nabcd\n\n1234\nremain");
+        let mut doc = Document::new(cursor).unwrap();
+        assert_eq!(doc.snap_offset_to_line_start(0).unwrap(), 0);
+        assert_eq!(doc.snap_offset_to_line_start(3).unwrap(), 0);
+        assert_eq!(doc.snap_offset_to_line_start(4).unwrap(), 0);
+        assert_eq!(doc.snap_offset_to_line_start(5).unwrap(), 5);
+        assert_eq!(doc.snap_offset_to_line_start(10).unwrap(), 10);
+        assert_eq!(doc.snap_offset_to_line_start(13).unwrap(), 11);
+        assert_eq!(doc.snap_offset_to_line_start(16).unwrap(), 16);
+        // offset beyond a shrunk document is clamped to the last line
+        assert_eq!(doc.snap_offset_to_line_start(18).unwrap(), 16);
+        assert_eq!(doc.snap_offset_to_line_start(1000).unwrap(), 16);
+    }
+
+    #[test]
+    fn test_snap_offset_to_line_start_at_chunk_seams() {
+        for delimiter in ["\n", "\r\n"] {
+            for end in ["", delimiter] {
+                let lines = ["first", "", "a", "a bit longer line", "", "", "last"];
+                let content = lines.join(delimiter) + end;
+                let mut line_starts = vec![0];
+                for line in &lines[..lines.len() - 1] {
+                    line_starts.push(line_starts.last().unwrap() + line.len() + delimiter.len());
+                }
+                let last_line_start = *line_starts.last().unwrap();
+                for chunk_size in [1, 3, 7, 64] {
+                    let mut doc =
+                        Document::with_line_delimiter(Cursor::new(content.as_str()), delimiter)
+                            .unwrap();
+                    doc.default_chunk_size = chunk_size;
+                    assert_eq!(doc.last_line_start_offset(), last_line_start);
+                    // backwards too, so that offsets land in chunks loaded in either order
+                    let offsets = (0..content.len() + 3).chain((0..content.len()).rev());
+                    for offset in offsets {
+                        let expected = line_starts
+                            .iter()
+                            .rev()
+                            .find(|start| **start <= offset)
+                            .copied()
+                            .unwrap();
+                        assert_eq!(
+                            doc.snap_offset_to_line_start(offset).unwrap(),
+                            expected,
+                            "offset {offset} in {content:?} with chunks of {chunk_size}"
+                        );
+                    }
+                }
+            }
+        }
+
+        // an empty document, and one of a single line, have only the line at 0
+        for content in ["", "only", "only\n"] {
+            let mut doc = Document::new(Cursor::new(content)).unwrap();
+            for offset in [0, 1, 4, 5, 100] {
+                assert_eq!(doc.snap_offset_to_line_start(offset).unwrap(), 0);
+            }
+        }
+    }
+
     #[test]
     fn test_get_chunk_index_by_offset() {
         let cursor = Cursor::new("");
         let mut doc = Document::new(cursor).unwrap();
-        doc.chunks.push(Chunk {
-            offset_begin: 0,
-            offset_end: 5,
-            rows: vec![],
-        });
-        doc.chunks.push(Chunk {
-            offset_begin: 5,
-            offset_end: 10,
-            rows: vec![],
-        });
-        doc.chunks.push(Chunk {
-            offset_begin: 15,
-            offset_end: 20,
-            rows: vec![],
-        });
+        doc.chunks
+            .push(Chunk::build_chunk("1234\n", 0, false, false, "\n"));
+        doc.chunks
+            .push(Chunk::build_chunk("1234\n", 5, false, false, "\n"));
+        doc.chunks
+            .push(Chunk::build_chunk("1234\n", 15, false, false, "\n"));
         assert_eq!(doc.get_chunk_index_by_offset(0), Some(0));
         assert_eq!(doc.get_chunk_index_by_offset(2), Some(0));
         assert_eq!(doc.get_chunk_index_by_offset(5), Some(1));
@@ -691,24 +2781,29 @@ mod tests {
     fn test_load_chunk() {
         let cursor = Cursor::new("1234\n1234\n1234\n1234\n1234\n1234\n1234\n1234\nabc");
         let mut doc = Document::new(cursor.clone()).unwrap();
+        doc.max_merged_chunk_size = 0;
         assert_eq!(doc.chunks.len(), 1);
-        assert_eq!(&doc.last_line, "abc");
+        assert_eq!(doc.last_line_text().unwrap(), "abc");
+        assert!(doc.lacks_final_line_delimiter());
         doc.chunks.pop();
 
         doc.load_chunk(0, 11).unwrap();
         assert_eq!(doc.chunks[0].offset_begin, 0);
         assert_eq!(doc.chunks[0].offset_end, 10);
-        assert_eq!(doc.chunks[0].rows, vec!["1234", "1234"]);
+        assert_eq!(
+            doc.chunks[0].rows().collect::<Vec<_>>(),
+            vec!["1234", "1234"]
+        );
 
         doc.load_chunk(5, 16).unwrap();
         assert_eq!(doc.chunks[1].offset_begin, 10);
         assert_eq!(doc.chunks[1].offset_end, 15);
-        assert_eq!(doc.chunks[1].rows.len(), 1);
+        assert_eq!(doc.chunks[1].row_count(), 1);
 
         doc.load_chunk(28, 39).unwrap();
         assert_eq!(doc.chunks[2].offset_begin, 30);
         assert_eq!(doc.chunks[2].offset_end, 35);
-        assert_eq!(doc.chunks[2].rows.len(), 1);
+        assert_eq!(doc.chunks[2].row_count(), 1);
 
         doc.load_chunk(15, 28).unwrap();
         assert_eq!(doc.chunks[2].offset_begin, 15);
@@ -736,14 +2831,154 @@ mod tests {
 
         doc.load_chunk(6, 31).unwrap();
         assert_eq!(doc.chunks.len(), 6);
+
+        // covered bytes in the middle aren't read again
+        let reader = CountingReader::new("1234\n".repeat(8) + "abc");
+        let mut doc = Document::new(reader).unwrap();
+        doc.max_merged_chunk_size = 0;
+        doc.chunks.clear();
+        doc.load_chunk(10, 20).unwrap();
+        doc.reader.read_count = 0;
+        assert_eq!(doc.load_chunk(3, 33).unwrap(), Some(2));
+        // 1 byte to tell if 3 is at a line start, then [3, 10) and [20, 33)
+        assert_eq!(doc.reader.read_count, 1 + 7 + 13);
+        let offsets: Vec<_> = doc
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.offset_begin, chunk.offset_end))
+            .collect();
+        // the gap before the existing chunk keeps its last line
+        assert_eq!(offsets, vec![(5, 10), (10, 20), (20, 30)]);
+        assert_eq!(
+            doc.query_line_texts(5, 5).unwrap(),
+            vec!["1234", "1234", "1234", "1234", "1234"]
+        );
+    }
+
+    #[test]
+    fn test_merge_adjacent_chunks() {
+        let cursor = Cursor::new("1234\n1234\n1234\n1234\n1234\n1234\n1234\n1234\nabc");
+        let mut doc = Document::new(cursor.clone()).unwrap();
+        doc.chunks.pop();
+
+        doc.load_chunk(0, 11).unwrap();
+        assert_eq!(doc.load_chunk(5, 16).unwrap(), Some(0));
+        assert_eq!(doc.chunks.len(), 1);
+        assert_eq!(doc.chunks[0].offset_end, 15);
+        assert_eq!(doc.chunks[0].row_count(), 3);
+
+        assert_eq!(doc.load_chunk(28, 39).unwrap(), Some(1));
+        assert_eq!(doc.load_chunk(15, 28).unwrap(), Some(0));
+        assert_eq!(doc.chunks.len(), 2);
+        assert_eq!(doc.chunks[0].offset_end, 25);
+        assert_eq!(doc.chunks[1].offset_begin, 30);
+
+        // fill the gap, both neighbours are merged
+        assert_eq!(doc.load_chunk(18, 32).unwrap(), Some(0));
+        assert_eq!(doc.chunks.len(), 1);
+        assert_eq!(doc.chunks[0].offset_begin, 0);
+        assert_eq!(doc.chunks[0].offset_end, 35);
+        assert_eq!(doc.chunks[0].rows().collect::<Vec<_>>(), vec!["1234"; 7]);
+        assert_eq!(
+            doc.query_line_texts(30, 3).unwrap(),
+            vec!["1234", "1234", "abc"]
+        );
+
+        // merged size is bounded
+        let mut doc = Document::new(cursor).unwrap();
+        doc.chunks.pop();
+        doc.max_merged_chunk_size = 10;
+        doc.load_chunk(0, 6).unwrap();
+        doc.load_chunk(5, 11).unwrap();
+        doc.load_chunk(10, 16).unwrap();
+        assert_eq!(doc.chunks.len(), 2);
+        assert_eq!(doc.chunks[0].offset_end, 10);
+        assert_eq!(doc.chunks[1].offset_begin, 10);
+        assert_eq!(doc.chunks[1].offset_end, 15);
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let content = "1234\n".repeat(20) + "remain";
+        let mut doc = Document::new(Cursor::new(content.as_str())).unwrap();
+        doc.default_chunk_size = 40;
+        doc.chunks.clear();
+        doc.load_chunk(30, 60).unwrap();
+        assert_eq!(doc.chunks[0].offset_begin, 30);
+        assert_eq!(doc.chunks[0].offset_end, 60);
+
+        // far from the edges, or not loaded at all
+        assert_eq!(doc.prefetch_range(40, Direction::Down), None);
+        assert_eq!(doc.prefetch_range(45, Direction::Up), None);
+        assert_eq!(doc.prefetch_range(70, Direction::Down), None);
+        // close to the edge in the scrolling direction
+        assert_eq!(doc.prefetch_range(55, Direction::Down), Some((60, 100)));
+        assert_eq!(doc.prefetch_range(55, Direction::Up), None);
+        assert_eq!(doc.prefetch_range(30, Direction::Up), Some((0, 30)));
+        assert_eq!(doc.prefetch_range(30, Direction::Down), None);
+
+        doc.prefetch(55, Direction::Down).unwrap();
+        doc.prefetch(30, Direction::Up).unwrap();
+        assert_eq!(doc.chunks.len(), 1);
+        assert_eq!(doc.chunks[0].offset_begin, 0);
+        assert_eq!(doc.chunks[0].offset_end, 100);
+        assert_eq!(doc.chunks[0].row_count(), 20);
+        // nothing more to prefetch at both ends
+        assert_eq!(doc.prefetch_range(95, Direction::Down), None);
+        assert_eq!(doc.prefetch_range(0, Direction::Up), None);
+        assert_eq!(
+            doc.query_line_texts(90, 3).unwrap(),
+            vec!["1234", "1234", "remain"]
+        );
+    }
+
+    #[test]
+    fn test_load_chunk_at_line_start() {
+        let cursor = Cursor::new("1234\n\nabcd\n\n\n1234\nabcd\nremain");
+        let mut doc = Document::new(cursor).unwrap();
+        doc.max_merged_chunk_size = 0;
+        doc.chunks.clear();
+
+        // adjacent to the end of an existing chunk
+        doc.load_chunk(0, 6).unwrap();
+        assert_eq!(doc.chunks[0].rows().collect::<Vec<_>>(), vec!["1234", ""]);
+        doc.load_chunk(6, 11).unwrap();
+        assert_eq!(doc.chunks[1].offset_begin, 6);
+        assert_eq!(doc.chunks[1].rows().collect::<Vec<_>>(), vec!["abcd"]);
+
+        // at a line start without neighbour, including an empty line
+        doc.load_chunk(12, 13).unwrap();
+        assert_eq!(doc.chunks[2].offset_begin, 12);
+        assert_eq!(doc.chunks[2].rows().collect::<Vec<_>>(), vec![""]);
+        doc.load_chunk(18, 28).unwrap();
+        assert_eq!(doc.chunks[3].offset_begin, 18);
+        assert_eq!(doc.chunks[3].rows().collect::<Vec<_>>(), vec!["abcd"]);
+
+        // in the middle of a line
+        doc.load_chunk(14, 18).unwrap();
+        assert_eq!(doc.chunks.len(), 4);
+        doc.load_chunk(13, 18).unwrap();
+        assert_eq!(doc.chunks[3].offset_begin, 13);
+        assert_eq!(doc.chunks[3].rows().collect::<Vec<_>>(), vec!["1234"]);
+        for window in doc.chunks.windows(2) {
+            assert!(window[0].offset_end <= window[1].offset_begin);
+        }
+
+        assert_eq!(
+            doc.query_line_texts(0, 9).unwrap(),
+            vec!["1234", "", "abcd", "", "", "1234", "abcd", "remain"]
+        );
+        assert_eq!(doc.query_line_texts(11, 3).unwrap(), vec!["", "", "1234"]);
     }
 
     #[test]
     fn test_load_chunk_drain() {
         let cursor = Cursor::new("1234\n1234\n1234\n1234\n1234\n1234\n1234\n1234\n");
         let mut doc = Document::new(cursor.clone()).unwrap();
+        doc.max_merged_chunk_size = 0;
         assert_eq!(doc.chunks.len(), 1);
-        assert_eq!(&doc.last_line, "1234\n");
+        assert_eq!(doc.last_line_text().unwrap(), "1234");
+        assert!(!doc.lacks_final_line_delimiter());
         doc.chunks.pop();
 
         doc.load_chunk(0, 11).unwrap();
@@ -762,9 +2997,12 @@ mod tests {
         doc.load_chunk(35, 40).unwrap();
         assert_eq!(doc.chunks.len(), 3);
 
+        // existing chunks are kept, only the gap between them is filled
         doc.load_chunk(12, 32).unwrap();
-        assert_eq!(doc.chunks.len(), 2);
+        assert_eq!(doc.chunks.len(), 4);
         assert_eq!(doc.chunks[1].offset_begin, 15);
-        assert_eq!(doc.chunks[1].offset_end, 30);
+        assert_eq!(doc.chunks[2].offset_begin, 20);
+        assert_eq!(doc.chunks[2].offset_end, 25);
+        assert_eq!(doc.chunks[3].offset_begin, 25);
     }
 }
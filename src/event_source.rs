@@ -1,48 +1,81 @@
-use std::time::Duration;
+use std::{collections::VecDeque, time::Duration};
 
 use anyhow::{Ok, Result};
-use crossterm::event::{self, poll, read, KeyCode, KeyEvent, KeyModifiers};
-use log::info;
+use crossterm::event::{self, poll, read, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use log::debug;
+
+pub use loss_viewer::direction::Direction;
 
 use crate::{
     bookmark::{BookMarkMenu, BookmarkMenuAction},
+    command::complete_command,
     finder::{FinderAction, FinderEventParser},
+    match_list::MatchListAction,
     prompt::{Prompt, PromptAction},
+    selection::SelectAction,
 };
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
+// starts a count that multiplies the step of the next window move, e.g. `#12` then `Down`.
+// a leader is needed since digits alone switch Finder slots
+const COUNT_LEADER: char = '#';
+// further digits are ignored once a count would exceed it
+const MAX_COUNT: usize = 9999;
+
+/// The keys of a sequence waiting for more, shown on the status bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PendingInput {
+    // `+` or `-`, awaiting a slot digit
+    AddSlot,
+    RemoveSlot,
+    // `X`, awaiting a second one to confirm
+    ResetAll,
+    // the count typed so far after the count leader
+    Count(usize),
 }
 
-impl Direction {
-    #[allow(dead_code)]
-    pub fn is_horizontal(&self) -> bool {
-        *self == Direction::Left || *self == Direction::Right
+impl PendingInput {
+    pub fn text(self) -> String {
+        match self {
+            Self::AddSlot => "+…".to_string(),
+            Self::RemoveSlot => "-…".to_string(),
+            Self::ResetAll => "X…".to_string(),
+            Self::Count(0) => format!("{COUNT_LEADER}…"),
+            Self::Count(count) => format!("{COUNT_LEADER}{count}…"),
+        }
     }
+}
 
-    pub fn is_vertical(&self) -> bool {
-        *self == Direction::Up || *self == Direction::Down
-    }
+/// Rows, or columns for `Left` and `Right`, each window move key scrolls by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveSteps {
+    pub arrow: usize,
+    pub page: usize,
+    pub ctrl_arrow: usize,
+    pub ctrl_page: usize,
+}
 
-    pub fn above_or_below(&self) -> &str {
-        assert!(self.is_vertical());
-        if *self == Direction::Up {
-            "above"
-        } else {
-            "below"
+impl Default for MoveSteps {
+    fn default() -> Self {
+        Self {
+            arrow: 1,
+            page: 5,
+            ctrl_arrow: 5,
+            ctrl_page: 20,
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Event {
     WindowMove(Direction, usize),
+    // the count typed so far, `None` once it's cancelled
+    CountPrefix(Option<usize>),
     Exit,
     ToggleWrapLine,
+    ToggleFoldWrappedLines,
+    TogglePlainMode,
+    ToggleInvisibles,
+    ToggleScrollOff,
     Search(PromptAction),
     SearchNext,
     SearchPrevious,
@@ -56,48 +89,297 @@ pub enum Event {
     UndoWindowVerticalMove,
     RedoWindowVerticalMove,
     FinderOperation(FinderAction),
+    // edits the active slot's pattern from the finder menu
+    EditSlotPattern(PromptAction),
     Follow,
     ToggleHelperMenu,
+    ToggleStats,
+    ToggleRestoredPosition,
+    Command(PromptAction),
+    ToggleMacroRecording,
+    ReplayMacro,
+    ToggleCollapseContinuations,
+    // expands or collapses the first trace in the window
+    ToggleExpandTrace,
+    ToggleMinimap,
+    CycleStatusLayout,
+    CycleContextLines,
+    // the matches of the active slot, collected and listed in a menu
+    MatchList(MatchListAction),
+    // visible lines selected with `v` to copy or write
+    Select(SelectAction),
+    WriteSelection(PromptAction),
+    // `Esc` or `Ctrl-C` with nothing open to cancel, dismisses the topmost transient state
+    Dismiss,
+    // opens the command prompt with `filter` and the current expression
+    EditFilter,
+    // `Ctrl-Z`, back to the shell until resumed with `fg`
+    Suspend,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct EventSource {
     search_prompt: Prompt,
     timestamp_prompt: Prompt,
     jump_prompt: Prompt,
     new_bookmark_prompt: Prompt,
+    command_prompt: Prompt,
+    slot_edit_prompt: Prompt,
+    selection_path_prompt: Prompt,
     bookmark_menu: BookMarkMenu,
     finder_event_parser: FinderEventParser,
     helper_menu_active: bool,
+    stats_active: bool,
+    match_list_active: bool,
+    selection_active: bool,
+    move_steps: MoveSteps,
+    // whether repeats of a held key are handled like presses, releases never are
+    key_repeat: bool,
+    // `Some` after the count leader until a move or any other key
+    count: Option<usize>,
+    // read ahead while coalescing or checking for an interrupt, but not handled yet
+    pending_raw_events: VecDeque<event::Event>,
 }
 
-impl EventSource {
-    pub fn check_for_interrupt(&mut self) -> Result<bool> {
-        let has_event = poll(Duration::from_secs(0))?;
-        if has_event {
+impl Default for EventSource {
+    fn default() -> Self {
+        Self {
+            search_prompt: Prompt::default(),
+            timestamp_prompt: Prompt::default(),
+            jump_prompt: Prompt::default(),
+            new_bookmark_prompt: Prompt::default(),
+            command_prompt: Prompt::with_completer(complete_command),
+            slot_edit_prompt: Prompt::default(),
+            selection_path_prompt: Prompt::default(),
+            bookmark_menu: BookMarkMenu::default(),
+            finder_event_parser: FinderEventParser::default(),
+            helper_menu_active: false,
+            stats_active: false,
+            match_list_active: false,
+            selection_active: false,
+            move_steps: MoveSteps::default(),
+            key_repeat: false,
+            count: None,
+            pending_raw_events: VecDeque::new(),
+        }
+    }
+}
+
+/// Where the manager takes events from. Prompts and menus that keep state across keys are
+/// told when the manager ends them, which sources yielding whole events can ignore.
+pub trait EventStream {
+    // waits at most `timeout` for an event, forever if `None`. returns `None` on timeout or if
+    // what came in doesn't make an event
+    fn poll_event(&mut self, timeout: Option<Duration>) -> Result<Option<Event>>;
+
+    // whether a long operation, like a search, should be given up
+    fn check_for_interrupt(&mut self) -> Result<bool>;
+
+    fn wait_for_event(&mut self) -> Result<Event> {
+        loop {
+            if let Some(event) = self.poll_event(None)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn set_move_steps(&mut self, _steps: MoveSteps) {}
+
+    fn set_key_repeat(&mut self, _key_repeat: bool) {}
+
+    fn set_direct_slot_digits(&mut self, _direct: bool) {}
+
+    fn exit_search_prompt(&mut self) {}
+
+    // the prompt starts empty, the manager fills in the pattern being edited
+    fn prefill_slot_edit_prompt(&mut self, _text: &str) {}
+
+    fn exit_slot_edit_prompt(&mut self) {}
+
+    fn prefill_command_prompt(&mut self, _text: &str) {}
+
+    fn cancel_remove_finder_active_slot(&mut self) {}
+
+    // the keys of an unfinished sequence, until it's completed or cancelled
+    fn pending_input(&self) -> Option<PendingInput> {
+        None
+    }
+
+    // gives up the unfinished sequence, returning the event cancelling it as `Esc` would
+    fn cancel_pending_input(&mut self) -> Option<Event> {
+        None
+    }
+
+    // the matches couldn't be collected, so the list isn't shown
+    fn close_match_list(&mut self) {}
+
+    // there was nothing to select
+    fn cancel_selection(&mut self) {}
+}
+
+impl EventStream for EventSource {
+    fn check_for_interrupt(&mut self) -> Result<bool> {
+        while poll(Duration::from_secs(0))? {
             let raw_event = read()?;
             if let event::Event::Key(key) = raw_event {
-                if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('c') {
+                if key.kind == KeyEventKind::Press
+                    && key.modifiers == KeyModifiers::CONTROL
+                    && key.code == KeyCode::Char('c')
+                {
                     return Ok(true);
                 }
             }
+            // typed ahead during the long operation, handled once it's done
+            self.pending_raw_events.push_back(raw_event);
         }
         Ok(false)
     }
 
-    pub fn wait_for_event(&mut self) -> Result<Event> {
-        loop {
-            let raw_event = read()?;
-            let event = self.handle_raw_event(&raw_event);
-            if let Some(event) = event {
-                return Ok(event);
+    fn poll_event(&mut self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        let raw_event = match (self.pending_raw_events.pop_front(), timeout) {
+            (Some(raw_event), _) => raw_event,
+            (None, None) => read()?,
+            (None, Some(timeout)) if poll(timeout)? => read()?,
+            (None, Some(_)) => return Ok(None),
+        };
+        let Some(event) = self.handle_raw_event(&raw_event) else {
+            return Ok(None);
+        };
+        // events already read come before those still waiting in the terminal
+        let mut pending = std::mem::take(&mut self.pending_raw_events);
+        let event = self.coalesce_events(event, &raw_event, || {
+            if let Some(raw_event) = pending.pop_front() {
+                Ok(Some(raw_event))
+            } else if poll(Duration::from_secs(0))? {
+                Ok(Some(read()?))
+            } else {
+                Ok(None)
+            }
+        });
+        self.pending_raw_events.extend(pending);
+        event.map(Some)
+    }
+
+    fn set_move_steps(&mut self, steps: MoveSteps) {
+        self.move_steps = steps;
+    }
+
+    fn set_key_repeat(&mut self, key_repeat: bool) {
+        self.key_repeat = key_repeat;
+    }
+
+    fn set_direct_slot_digits(&mut self, direct: bool) {
+        self.finder_event_parser.set_direct_digits(direct);
+    }
+
+    fn exit_search_prompt(&mut self) {
+        self.search_prompt.finish();
+    }
+
+    fn prefill_slot_edit_prompt(&mut self, text: &str) {
+        self.slot_edit_prompt.start_with(text);
+    }
+
+    fn exit_slot_edit_prompt(&mut self) {
+        self.slot_edit_prompt.finish();
+    }
+
+    fn prefill_command_prompt(&mut self, text: &str) {
+        self.command_prompt.start_with(text);
+    }
+
+    fn cancel_remove_finder_active_slot(&mut self) {
+        self.finder_event_parser.set_state_to_normal();
+    }
+
+    fn close_match_list(&mut self) {
+        self.match_list_active = false;
+    }
+
+    fn cancel_selection(&mut self) {
+        self.selection_active = false;
+    }
+
+    fn pending_input(&self) -> Option<PendingInput> {
+        self.count
+            .map(PendingInput::Count)
+            .or_else(|| self.finder_event_parser.pending_input())
+    }
+
+    fn cancel_pending_input(&mut self) -> Option<Event> {
+        if self.count.take().is_some() {
+            return Some(Event::CountPrefix(None));
+        }
+        self.finder_event_parser
+            .cancel_pending()
+            .map(Event::FinderOperation)
+    }
+}
+
+impl EventSource {
+    // merges the same key already queued behind a window move into one larger move, so that
+    // holding a key doesn't leave a backlog of renders behind. likewise only the last one of a
+    // burst of resizes is applied. `next_pending` shouldn't block
+    fn coalesce_events<F>(
+        &mut self,
+        event: Event,
+        raw_event: &event::Event,
+        mut next_pending: F,
+    ) -> Result<Event>
+    where
+        F: FnMut() -> Result<Option<event::Event>>,
+    {
+        match event {
+            Event::WindowMove(direction, step) => {
+                let event::Event::Key(first_key) = raw_event else {
+                    unreachable!("window moves only come from keys");
+                };
+                let mut total_step = step;
+                while let Some(next_raw_event) = next_pending()? {
+                    let event::Event::Key(key) = next_raw_event else {
+                        self.pending_raw_events.push_front(next_raw_event);
+                        break;
+                    };
+                    // releases between presses of the held key would be ignored anyway
+                    if !self.is_handled_kind(key.kind) {
+                        continue;
+                    }
+                    // a repeat of the held key moves like its press
+                    if (key.code, key.modifiers) != (first_key.code, first_key.modifiers) {
+                        self.pending_raw_events.push_front(next_raw_event);
+                        break;
+                    }
+                    // window moves don't change any state, so the same key means the same move,
+                    // only without the count that may have come before the first one
+                    total_step += self.window_move(&key).unwrap().1;
+                }
+                Ok(Event::WindowMove(direction, total_step))
+            }
+            Event::TerminalResize(mut width, mut height) => {
+                while let Some(next_raw_event) = next_pending()? {
+                    if let event::Event::Resize(next_width, next_height) = next_raw_event {
+                        (width, height) = (next_width as usize, next_height as usize);
+                    } else {
+                        self.pending_raw_events.push_front(next_raw_event);
+                        break;
+                    }
+                }
+                Ok(Event::TerminalResize(width, height))
             }
+            _ => Ok(event),
         }
     }
 
+    // windows terminals report releases, and repeats as a kind of their own, besides presses.
+    // every parser below only ever sees the handled ones
+    fn is_handled_kind(&self, kind: KeyEventKind) -> bool {
+        kind == KeyEventKind::Press || (self.key_repeat && kind == KeyEventKind::Repeat)
+    }
+
     fn handle_raw_event(&mut self, raw_event: &event::Event) -> Option<Event> {
-        info!("raw event: {:?}", raw_event);
+        debug!("raw event: {:?}", raw_event);
         match raw_event {
+            event::Event::Key(key) if !self.is_handled_kind(key.kind) => None,
             event::Event::Key(key) => self.handle_key_press(key),
             event::Event::Resize(width, height) => {
                 Some(Event::TerminalResize(*width as usize, *height as usize))
@@ -107,6 +389,15 @@ impl EventSource {
     }
 
     fn handle_key_press(&mut self, key: &KeyEvent) -> Option<Event> {
+        // cancels whatever is open or pending as `Esc` does, in any state
+        if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('c') {
+            let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+            return self.handle_key_press(&esc).or(Some(Event::Dismiss));
+        }
+        // whatever is open or pending is kept for when it's resumed
+        if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('z') {
+            return Some(Event::Suspend);
+        }
         if self.helper_menu_active {
             if key.modifiers == KeyModifiers::NONE
                 && (key.code == KeyCode::Char('h') || key.code == KeyCode::Esc)
@@ -116,6 +407,15 @@ impl EventSource {
             }
             return None;
         }
+        if self.stats_active {
+            if key.modifiers == KeyModifiers::NONE || key.modifiers == KeyModifiers::SHIFT {
+                if let KeyCode::Char('I') | KeyCode::Esc = key.code {
+                    self.stats_active = false;
+                    return Some(Event::ToggleStats);
+                }
+            }
+            return None;
+        }
         if self.search_prompt.is_active() {
             return self.search_prompt.handle_raw_event(key).map(Event::Search);
         }
@@ -137,12 +437,46 @@ impl EventSource {
                 .handle_raw_event(key)
                 .map(Event::NewBookmark);
         }
+        if self.command_prompt.is_active() {
+            return self
+                .command_prompt
+                .handle_raw_event(key)
+                .map(Event::Command);
+        }
+        if self.slot_edit_prompt.is_active() {
+            return self
+                .slot_edit_prompt
+                .handle_raw_event(key)
+                .map(Event::EditSlotPattern);
+        }
+        if self.selection_path_prompt.is_active() {
+            return self
+                .selection_path_prompt
+                .handle_raw_event(key)
+                .map(Event::WriteSelection);
+        }
         if self.bookmark_menu.is_active() {
             return self
                 .bookmark_menu
                 .handle_raw_event(key)
                 .map(Event::GotoBookmark);
         }
+        if self.match_list_active {
+            return self.handle_match_list_key(key).map(Event::MatchList);
+        }
+        if self.selection_active {
+            return self.handle_selection_key(key);
+        }
+        if self.finder_event_parser.can_edit_in_menu()
+            && key.modifiers == KeyModifiers::NONE
+            && matches!(key.code, KeyCode::Enter | KeyCode::Char('i'))
+        {
+            self.slot_edit_prompt.start();
+            return Some(Event::EditSlotPattern(PromptAction::Start(None)));
+        }
+        if let Some(count) = self.count {
+            return Some(self.handle_count_key(key, count));
+        }
         if let Some(action) = self.finder_event_parser.try_parse_raw_event(key) {
             return Some(Event::FinderOperation(action));
         }
@@ -150,7 +484,19 @@ impl EventSource {
         if key.modifiers == KeyModifiers::NONE || key.modifiers == KeyModifiers::SHIFT {
             match key.code {
                 KeyCode::Char('q') => Some(Event::Exit),
+                KeyCode::Esc => Some(Event::Dismiss),
+                KeyCode::F(2) => Some(Event::ToggleMacroRecording),
+                KeyCode::F(3) => Some(Event::ReplayMacro),
                 KeyCode::Char('w') => Some(Event::ToggleWrapLine),
+                KeyCode::Char('W') => Some(Event::ToggleFoldWrappedLines),
+                KeyCode::Char('p') => Some(Event::TogglePlainMode),
+                KeyCode::Char('$') => Some(Event::ToggleInvisibles),
+                KeyCode::Char('z') => Some(Event::ToggleScrollOff),
+                KeyCode::Char('c') => Some(Event::ToggleCollapseContinuations),
+                KeyCode::Char('C') => Some(Event::ToggleExpandTrace),
+                KeyCode::Char('M') => Some(Event::ToggleMinimap),
+                KeyCode::Char('S') => Some(Event::CycleStatusLayout),
+                KeyCode::Char('K') => Some(Event::CycleContextLines),
                 KeyCode::Char('/') => {
                     self.search_prompt.start();
                     Some(Event::Search(PromptAction::Start(Some(Direction::Down))))
@@ -165,12 +511,10 @@ impl EventSource {
                 }
                 KeyCode::Char('n') => Some(Event::SearchNext),
                 KeyCode::Char('N') => Some(Event::SearchPrevious),
-                KeyCode::Down => Some(Event::WindowMove(Direction::Down, 1)),
-                KeyCode::Up => Some(Event::WindowMove(Direction::Up, 1)),
-                KeyCode::Right => Some(Event::WindowMove(Direction::Right, 1)),
-                KeyCode::Left => Some(Event::WindowMove(Direction::Left, 1)),
-                KeyCode::PageDown => Some(Event::WindowMove(Direction::Down, 5)),
-                KeyCode::PageUp => Some(Event::WindowMove(Direction::Up, 5)),
+                KeyCode::Char(COUNT_LEADER) => {
+                    self.count = Some(0);
+                    Some(Event::CountPrefix(Some(0)))
+                }
                 KeyCode::Home => Some(Event::SeekToHome),
                 KeyCode::End => Some(Event::SeekToEnd),
                 KeyCode::Char('j') => {
@@ -191,21 +535,62 @@ impl EventSource {
                     self.bookmark_menu.activate();
                     Some(Event::GotoBookmark(BookmarkMenuAction::Start))
                 }
+                KeyCode::Char('v') => {
+                    self.selection_active = true;
+                    Some(Event::Select(SelectAction::Start))
+                }
+                KeyCode::Char('L') => {
+                    self.match_list_active = true;
+                    Some(Event::MatchList(MatchListAction::Start))
+                }
                 KeyCode::Char(',') => Some(Event::UndoWindowVerticalMove),
                 KeyCode::Char('.') => Some(Event::RedoWindowVerticalMove),
                 KeyCode::Char('F') => Some(Event::Follow),
+                KeyCode::Char('\'') => Some(Event::ToggleRestoredPosition),
+                KeyCode::Char(':') => {
+                    self.command_prompt.start();
+                    Some(Event::Command(PromptAction::Start(None)))
+                }
+                KeyCode::Char('&') => {
+                    self.command_prompt.start();
+                    Some(Event::EditFilter)
+                }
                 KeyCode::Char('h') => {
                     self.helper_menu_active = true;
                     Some(Event::ToggleHelperMenu)
                 }
+                KeyCode::Char('I') => {
+                    self.stats_active = true;
+                    Some(Event::ToggleStats)
+                }
+                _ => self
+                    .window_move(key)
+                    .map(|(direction, step)| Event::WindowMove(direction, step)),
+            }
+        } else {
+            self.window_move(key)
+                .map(|(direction, step)| Event::WindowMove(direction, step))
+        }
+    }
+
+    fn window_move(&self, key: &KeyEvent) -> Option<(Direction, usize)> {
+        let steps = &self.move_steps;
+        if key.modifiers == KeyModifiers::NONE || key.modifiers == KeyModifiers::SHIFT {
+            match key.code {
+                KeyCode::Down => Some((Direction::Down, steps.arrow)),
+                KeyCode::Up => Some((Direction::Up, steps.arrow)),
+                KeyCode::Right => Some((Direction::Right, steps.arrow)),
+                KeyCode::Left => Some((Direction::Left, steps.arrow)),
+                KeyCode::PageDown => Some((Direction::Down, steps.page)),
+                KeyCode::PageUp => Some((Direction::Up, steps.page)),
                 _ => None,
             }
         } else if key.modifiers == KeyModifiers::CONTROL {
             match key.code {
-                KeyCode::Down => Some(Event::WindowMove(Direction::Down, 5)),
-                KeyCode::Up => Some(Event::WindowMove(Direction::Up, 5)),
-                KeyCode::PageDown => Some(Event::WindowMove(Direction::Down, 20)),
-                KeyCode::PageUp => Some(Event::WindowMove(Direction::Up, 20)),
+                KeyCode::Down => Some((Direction::Down, steps.ctrl_arrow)),
+                KeyCode::Up => Some((Direction::Up, steps.ctrl_arrow)),
+                KeyCode::PageDown => Some((Direction::Down, steps.ctrl_page)),
+                KeyCode::PageUp => Some((Direction::Up, steps.ctrl_page)),
                 _ => None,
             }
         } else {
@@ -213,12 +598,97 @@ impl EventSource {
         }
     }
 
-    pub fn exit_search_prompt(&mut self) {
-        self.search_prompt.finish();
+    fn handle_match_list_key(&mut self, key: &KeyEvent) -> Option<MatchListAction> {
+        if key.modifiers != KeyModifiers::NONE {
+            return None;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => Some(MatchListAction::Arrow(Direction::Up)),
+            KeyCode::Down | KeyCode::Char('j') => Some(MatchListAction::Arrow(Direction::Down)),
+            KeyCode::Enter => {
+                self.match_list_active = false;
+                Some(MatchListAction::Enter)
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.match_list_active = false;
+                Some(MatchListAction::Cancel)
+            }
+            _ => None,
+        }
     }
 
-    pub fn cancel_remove_finder_active_slot(&mut self) {
-        self.finder_event_parser.set_state_to_normal();
+    // arrows extend the selection, shifted or not, and other keys end it
+    fn handle_selection_key(&mut self, key: &KeyEvent) -> Option<Event> {
+        if key.modifiers != KeyModifiers::NONE && key.modifiers != KeyModifiers::SHIFT {
+            return None;
+        }
+        let action = match key.code {
+            KeyCode::Up | KeyCode::Char('k') => SelectAction::Extend(Direction::Up),
+            KeyCode::Down | KeyCode::Char('j') => SelectAction::Extend(Direction::Down),
+            KeyCode::Char('y') => {
+                self.selection_active = false;
+                SelectAction::Copy
+            }
+            KeyCode::Char('w') => {
+                self.selection_active = false;
+                self.selection_path_prompt.start();
+                return Some(Event::WriteSelection(PromptAction::Start(None)));
+            }
+            KeyCode::Esc | KeyCode::Char('v') => {
+                self.selection_active = false;
+                SelectAction::Cancel
+            }
+            _ => return None,
+        };
+        Some(Event::Select(action))
+    }
+
+    // digits add to the count and a move takes it, any other key cancels it and is swallowed
+    fn handle_count_key(&mut self, key: &KeyEvent, count: usize) -> Event {
+        if let KeyCode::Char(digit @ '0'..='9') = key.code {
+            let digit = digit as usize - '0' as usize;
+            let count = match count * 10 + digit {
+                next if next > MAX_COUNT => count,
+                next => next,
+            };
+            self.count = Some(count);
+            return Event::CountPrefix(Some(count));
+        }
+        self.count = None;
+        match self.window_move(key) {
+            Some((direction, step)) => {
+                Event::WindowMove(direction, step.saturating_mul(count.max(1)))
+            }
+            None => Event::CountPrefix(None),
+        }
+    }
+}
+
+/// Yields events given up front, as if typed in that order, then `Exit` once they run out.
+/// Nothing is interrupted.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ScriptedEventSource {
+    events: std::collections::VecDeque<Event>,
+}
+
+#[cfg(test)]
+impl ScriptedEventSource {
+    pub fn new(events: Vec<Event>) -> Self {
+        Self {
+            events: events.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl EventStream for ScriptedEventSource {
+    fn poll_event(&mut self, _timeout: Option<Duration>) -> Result<Option<Event>> {
+        Ok(Some(self.events.pop_front().unwrap_or(Event::Exit)))
+    }
+
+    fn check_for_interrupt(&mut self) -> Result<bool> {
+        Ok(false)
     }
 }
 
@@ -285,6 +755,265 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_edit_slot_pattern_event() {
+        let mut source = EventSource::default();
+        let mut press =
+            |code| source.handle_raw_event(&RawEvent::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        // `i` means nothing outside the finder menu
+        assert_eq!(press(KeyCode::Char('i')), None);
+        assert_eq!(
+            press(KeyCode::Char('m')),
+            Some(Event::FinderOperation(FinderAction::MenuOn))
+        );
+        assert_eq!(
+            press(KeyCode::Enter),
+            Some(Event::EditSlotPattern(PromptAction::Start(None)))
+        );
+        // keys go to the prompt, not the menu
+        assert_eq!(
+            press(KeyCode::Char('m')),
+            Some(Event::EditSlotPattern(PromptAction::Content(
+                "m".to_string()
+            )))
+        );
+        assert_eq!(
+            press(KeyCode::Esc),
+            Some(Event::EditSlotPattern(PromptAction::Cancel))
+        );
+
+        // the menu stays open after editing
+        source.handle_raw_event(&RawEvent::Key(KeyEvent::new(
+            KeyCode::Char('i'),
+            KeyModifiers::NONE,
+        )));
+        source.prefill_slot_edit_prompt("ab");
+        let mut press =
+            |code| source.handle_raw_event(&RawEvent::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        assert_eq!(
+            press(KeyCode::Char('c')),
+            Some(Event::EditSlotPattern(PromptAction::Content(
+                "abc".to_string()
+            )))
+        );
+        assert_eq!(
+            press(KeyCode::Enter),
+            Some(Event::EditSlotPattern(PromptAction::Enter(
+                "abc".to_string()
+            )))
+        );
+        assert_eq!(
+            press(KeyCode::Esc),
+            Some(Event::FinderOperation(FinderAction::MenuOff))
+        );
+        assert_eq!(press(KeyCode::Enter), None);
+    }
+
+    #[test]
+    fn test_match_list_event() {
+        let mut source = EventSource::default();
+        let press = |source: &mut EventSource, code| {
+            source.handle_raw_event(&RawEvent::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+        };
+        assert_eq!(
+            press(&mut source, KeyCode::Char('L')),
+            Some(Event::MatchList(MatchListAction::Start))
+        );
+        // `j` moves in the list instead of jumping by lines
+        assert_eq!(
+            press(&mut source, KeyCode::Char('j')),
+            Some(Event::MatchList(MatchListAction::Arrow(Direction::Down)))
+        );
+        assert_eq!(press(&mut source, KeyCode::Char('x')), None);
+        assert_eq!(
+            press(&mut source, KeyCode::Enter),
+            Some(Event::MatchList(MatchListAction::Enter))
+        );
+        assert_eq!(press(&mut source, KeyCode::Char('q')), Some(Event::Exit));
+
+        // closed by the manager when nothing was collected
+        press(&mut source, KeyCode::Char('L'));
+        source.close_match_list();
+        assert_eq!(press(&mut source, KeyCode::Char('q')), Some(Event::Exit));
+    }
+
+    #[test]
+    fn test_slot_digits() {
+        let mut source = EventSource::default();
+        let press = |source: &mut EventSource, c| {
+            source.handle_raw_event(&RawEvent::Key(KeyEvent::new(
+                KeyCode::Char(c),
+                KeyModifiers::NONE,
+            )))
+        };
+        let finder = |action| Some(Event::FinderOperation(action));
+        // only in the finder menu, or after `+` or `-`
+        assert_eq!(press(&mut source, '3'), None);
+        assert_eq!(press(&mut source, 'm'), finder(FinderAction::MenuOn));
+        assert_eq!(
+            press(&mut source, '3'),
+            finder(FinderAction::SwitchActiveSlot(3))
+        );
+        assert_eq!(press(&mut source, 'm'), finder(FinderAction::MenuOff));
+        assert_eq!(
+            press(&mut source, '+'),
+            finder(FinderAction::AddActiveSlotStart)
+        );
+        assert_eq!(
+            press(&mut source, '4'),
+            finder(FinderAction::AddActiveSlot(4))
+        );
+
+        // anywhere with direct digits
+        source.set_direct_slot_digits(true);
+        assert_eq!(
+            press(&mut source, '3'),
+            finder(FinderAction::SwitchActiveSlot(3))
+        );
+        source.set_direct_slot_digits(false);
+        assert_eq!(press(&mut source, '3'), None);
+    }
+
+    #[test]
+    fn test_count_prefix() {
+        let mut source = EventSource::default();
+        let mut press = |code, modifiers| {
+            source.handle_raw_event(&RawEvent::Key(KeyEvent::new(code, modifiers)))
+        };
+        assert_eq!(
+            press(KeyCode::Char('#'), KeyModifiers::SHIFT),
+            Some(Event::CountPrefix(Some(0)))
+        );
+        assert_eq!(
+            press(KeyCode::Char('1'), KeyModifiers::NONE),
+            Some(Event::CountPrefix(Some(1)))
+        );
+        assert_eq!(
+            press(KeyCode::Char('2'), KeyModifiers::NONE),
+            Some(Event::CountPrefix(Some(12)))
+        );
+        assert_eq!(
+            press(KeyCode::PageDown, KeyModifiers::NONE),
+            Some(Event::WindowMove(Direction::Down, 60))
+        );
+        // taken by the move, a digit alone means nothing outside the finder menu
+        assert_eq!(press(KeyCode::Char('2'), KeyModifiers::NONE), None);
+
+        // a count without digits moves once
+        press(KeyCode::Char('#'), KeyModifiers::NONE);
+        assert_eq!(
+            press(KeyCode::Up, KeyModifiers::CONTROL),
+            Some(Event::WindowMove(Direction::Up, 5))
+        );
+
+        // esc or any other key cancels, and is swallowed
+        press(KeyCode::Char('#'), KeyModifiers::NONE);
+        press(KeyCode::Char('3'), KeyModifiers::NONE);
+        assert_eq!(
+            press(KeyCode::Esc, KeyModifiers::NONE),
+            Some(Event::CountPrefix(None))
+        );
+        assert_eq!(
+            press(KeyCode::Down, KeyModifiers::NONE),
+            Some(Event::WindowMove(Direction::Down, 1))
+        );
+        press(KeyCode::Char('#'), KeyModifiers::NONE);
+        assert_eq!(
+            press(KeyCode::Char('q'), KeyModifiers::NONE),
+            Some(Event::CountPrefix(None))
+        );
+
+        // digits past the maximum are ignored
+        press(KeyCode::Char('#'), KeyModifiers::NONE);
+        for _ in 0..6 {
+            press(KeyCode::Char('9'), KeyModifiers::NONE);
+        }
+        assert_eq!(
+            press(KeyCode::Char('9'), KeyModifiers::NONE),
+            Some(Event::CountPrefix(Some(MAX_COUNT)))
+        );
+        assert_eq!(
+            press(KeyCode::Right, KeyModifiers::NONE),
+            Some(Event::WindowMove(Direction::Right, MAX_COUNT))
+        );
+    }
+
+    #[test]
+    fn test_pending_input() {
+        let mut source = EventSource::default();
+        let press = |source: &mut EventSource, keys: &str| {
+            for c in keys.chars() {
+                let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+                source.handle_raw_event(&RawEvent::Key(key));
+            }
+            source.pending_input().map(PendingInput::text)
+        };
+        for (keys, pending) in [
+            ("+", "+…"),
+            ("-", "-…"),
+            ("X", "X…"),
+            ("#", "#…"),
+            ("#12", "#12…"),
+        ] {
+            assert_eq!(press(&mut source, keys).as_deref(), Some(pending));
+            assert!(source.cancel_pending_input().is_some());
+            assert_eq!(source.pending_input(), None);
+        }
+        // cleared once the sequence completes
+        assert_eq!(press(&mut source, "+3"), None);
+        assert_eq!(press(&mut source, "X"), Some("X…".to_string()));
+        assert_eq!(press(&mut source, "X"), None);
+        assert_eq!(source.cancel_pending_input(), None);
+
+        // or is cancelled with the event `Esc` would have given
+        press(&mut source, "#4");
+        assert_eq!(
+            source.cancel_pending_input(),
+            Some(Event::CountPrefix(None))
+        );
+        press(&mut source, "X");
+        assert_eq!(
+            source.cancel_pending_input(),
+            Some(Event::FinderOperation(FinderAction::ResetAllCancel))
+        );
+        press(&mut source, "-");
+        source.handle_raw_event(&RawEvent::Key(KeyEvent::new(
+            KeyCode::Esc,
+            KeyModifiers::NONE,
+        )));
+        assert_eq!(source.pending_input(), None);
+    }
+
+    #[test]
+    fn test_move_steps() {
+        let mut source = EventSource::default();
+        source.set_move_steps(MoveSteps {
+            arrow: 2,
+            page: 10,
+            ctrl_arrow: 3,
+            ctrl_page: 30,
+        });
+        let mut press = |code, modifiers| {
+            source.handle_raw_event(&RawEvent::Key(KeyEvent::new(code, modifiers)))
+        };
+        assert_eq!(
+            press(KeyCode::Left, KeyModifiers::NONE),
+            Some(Event::WindowMove(Direction::Left, 2))
+        );
+        assert_eq!(
+            press(KeyCode::PageUp, KeyModifiers::NONE),
+            Some(Event::WindowMove(Direction::Up, 10))
+        );
+        assert_eq!(
+            press(KeyCode::Down, KeyModifiers::CONTROL),
+            Some(Event::WindowMove(Direction::Down, 3))
+        );
+        assert_eq!(
+            press(KeyCode::PageDown, KeyModifiers::CONTROL),
+            Some(Event::WindowMove(Direction::Down, 30))
+        );
+    }
+
     #[test]
     fn test_window_move_event() {
         let mut source = EventSource::default();
@@ -296,4 +1025,173 @@ mod tests {
             Some(Event::WindowMove(Direction::Down, 1))
         );
     }
+
+    #[test]
+    fn test_ctrl_c_cancels() {
+        let mut source = EventSource::default();
+        let mut press = |code, modifiers| {
+            source.handle_raw_event(&RawEvent::Key(KeyEvent::new(code, modifiers)))
+        };
+        let ctrl_c = (KeyCode::Char('c'), KeyModifiers::CONTROL);
+        let cases = [
+            ('/', Event::Search(PromptAction::Cancel)),
+            (':', Event::Command(PromptAction::Cancel)),
+            ('h', Event::ToggleHelperMenu),
+            ('I', Event::ToggleStats),
+            ('g', Event::GotoBookmark(BookmarkMenuAction::Cancel)),
+            ('L', Event::MatchList(MatchListAction::Cancel)),
+            ('v', Event::Select(SelectAction::Cancel)),
+            ('#', Event::CountPrefix(None)),
+            (
+                '+',
+                Event::FinderOperation(FinderAction::AddOrRemoveActiveSlotCancel),
+            ),
+            ('X', Event::FinderOperation(FinderAction::ResetAllCancel)),
+            ('m', Event::FinderOperation(FinderAction::MenuOff)),
+        ];
+        for (opening_key, cancel) in cases {
+            press(KeyCode::Char(opening_key), KeyModifiers::NONE);
+            assert_eq!(
+                press(ctrl_c.0, ctrl_c.1),
+                Some(cancel),
+                "after {opening_key}"
+            );
+            // back to the plain view, where `q` exits
+            assert_eq!(
+                press(KeyCode::Char('q'), KeyModifiers::NONE),
+                Some(Event::Exit)
+            );
+        }
+        assert_eq!(press(ctrl_c.0, ctrl_c.1), Some(Event::Dismiss));
+        assert_eq!(
+            press(KeyCode::Esc, KeyModifiers::NONE),
+            Some(Event::Dismiss)
+        );
+
+        // `Ctrl-Z` suspends in any state, and the prompt is still open afterwards
+        press(KeyCode::Char('/'), KeyModifiers::NONE);
+        assert_eq!(
+            press(KeyCode::Char('z'), KeyModifiers::CONTROL),
+            Some(Event::Suspend)
+        );
+        assert_eq!(
+            press(KeyCode::Char('x'), KeyModifiers::NONE),
+            Some(Event::Search(PromptAction::Content("x".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_key_event_kind() {
+        let key =
+            |code, kind| RawEvent::Key(KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind));
+        let mut source = EventSource::default();
+        // the release of `h` would close the menu its press opened
+        assert_eq!(
+            source.handle_raw_event(&key(KeyCode::Char('h'), KeyEventKind::Press)),
+            Some(Event::ToggleHelperMenu)
+        );
+        assert_eq!(
+            source.handle_raw_event(&key(KeyCode::Char('h'), KeyEventKind::Release)),
+            None
+        );
+        assert_eq!(
+            source.handle_raw_event(&key(KeyCode::Char('h'), KeyEventKind::Repeat)),
+            None
+        );
+        assert!(source.helper_menu_active);
+        assert_eq!(
+            source.handle_raw_event(&key(KeyCode::Char('+'), KeyEventKind::Release)),
+            None
+        );
+        assert_eq!(source.finder_event_parser.pending_input(), None);
+
+        // releases are skipped while coalescing, and repeats count once enabled
+        let mut source = EventSource::default();
+        source.set_key_repeat(true);
+        let mut queued = [
+            key(KeyCode::Down, KeyEventKind::Release),
+            key(KeyCode::Down, KeyEventKind::Repeat),
+            key(KeyCode::Down, KeyEventKind::Repeat),
+            key(KeyCode::Down, KeyEventKind::Release),
+        ]
+        .into_iter();
+        let first = key(KeyCode::Down, KeyEventKind::Press);
+        let event = source.handle_raw_event(&first).unwrap();
+        assert_eq!(
+            source
+                .coalesce_events(event, &first, || Ok(queued.next()))
+                .unwrap(),
+            Event::WindowMove(Direction::Down, 3)
+        );
+        assert!(source.pending_raw_events.is_empty());
+    }
+
+    #[test]
+    fn test_coalesce_events() {
+        let key = |code| RawEvent::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        let mut source = EventSource::default();
+        let coalesce = |source: &mut EventSource, queued: &[RawEvent]| {
+            let mut queued = queued.iter().cloned();
+            let raw_event = queued.next().unwrap();
+            let event = source.handle_raw_event(&raw_event).unwrap();
+            source
+                .coalesce_events(event, &raw_event, || Ok(queued.next()))
+                .unwrap()
+        };
+
+        let downs = vec![key(KeyCode::Down); 5];
+        assert_eq!(
+            coalesce(&mut source, &downs),
+            Event::WindowMove(Direction::Down, 5)
+        );
+        assert!(source.pending_raw_events.is_empty());
+        assert_eq!(
+            coalesce(&mut source, &vec![key(KeyCode::PageUp); 3]),
+            Event::WindowMove(Direction::Up, 15)
+        );
+
+        // stops at a different event, which is kept for later
+        let queued = [
+            key(KeyCode::Down),
+            key(KeyCode::Down),
+            key(KeyCode::Char('w')),
+            key(KeyCode::Down),
+        ];
+        assert_eq!(
+            coalesce(&mut source, &queued),
+            Event::WindowMove(Direction::Down, 2)
+        );
+        assert_eq!(source.pending_raw_events, [key(KeyCode::Char('w'))]);
+        source.pending_raw_events.clear();
+
+        // only the first move takes the count
+        coalesce(&mut source, &[key(KeyCode::Char('#'))]);
+        coalesce(&mut source, &[key(KeyCode::Char('3'))]);
+        assert_eq!(
+            coalesce(&mut source, &downs[..3]),
+            Event::WindowMove(Direction::Down, 5)
+        );
+
+        // other events are never merged
+        assert_eq!(
+            coalesce(
+                &mut source,
+                &[key(KeyCode::Char('n')), key(KeyCode::Char('n'))]
+            ),
+            Event::SearchNext
+        );
+
+        // only the last resize of a burst is applied
+        let queued = [
+            RawEvent::Resize(80, 24),
+            RawEvent::Resize(0, 0),
+            RawEvent::Resize(100, 40),
+            key(KeyCode::Down),
+        ];
+        assert_eq!(
+            coalesce(&mut source, &queued),
+            Event::TerminalResize(100, 40)
+        );
+        assert_eq!(source.pending_raw_events, [key(KeyCode::Down)]);
+    }
 }
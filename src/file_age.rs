@@ -0,0 +1,96 @@
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// How long ago something happened in the largest whole unit, e.g. `3 days ago`.
+pub fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    let (count, unit) = match seconds {
+        _ if seconds < MINUTE => return "just now".to_string(),
+        _ if seconds < HOUR => (seconds / MINUTE, "minute"),
+        _ if seconds < DAY => (seconds / HOUR, "hour"),
+        _ => (seconds / DAY, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// The modification time as shown in the status bar: relative within a day, the local date and
+/// time beyond. A time ahead of `now`, e.g. from a skewed clock, is shown absolute too.
+pub fn format_modified(modified: SystemTime, now: SystemTime) -> String {
+    match now.duration_since(modified) {
+        Ok(age) if age.as_secs() < DAY => format_age(age),
+        _ => DateTime::<Local>::from(modified)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+    }
+}
+
+/// A warning for a file not modified for longer than `stale_after`, `None` turns it off.
+pub fn stale_warning(
+    modified: SystemTime,
+    now: SystemTime,
+    stale_after: Option<Duration>,
+) -> Option<String> {
+    let age = now.duration_since(modified).ok()?;
+    (age > stale_after?).then(|| format!("File last modified {}", format_age(age)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_age() {
+        let age = |seconds| format_age(Duration::from_secs(seconds));
+        assert_eq!(age(0), "just now");
+        assert_eq!(age(59), "just now");
+        assert_eq!(age(60), "1 minute ago");
+        assert_eq!(age(59 * MINUTE + 59), "59 minutes ago");
+        assert_eq!(age(HOUR), "1 hour ago");
+        assert_eq!(age(23 * HOUR), "23 hours ago");
+        assert_eq!(age(DAY + HOUR), "1 day ago");
+        assert_eq!(age(3 * DAY + 23 * HOUR), "3 days ago");
+    }
+
+    #[test]
+    fn test_format_modified() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            format_modified(now - Duration::from_secs(5 * MINUTE), now),
+            "5 minutes ago"
+        );
+        let old = now - Duration::from_secs(3 * DAY);
+        let absolute = DateTime::<Local>::from(old)
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        assert_eq!(format_modified(old, now), absolute);
+        let ahead = now + Duration::from_secs(HOUR);
+        assert_eq!(format_modified(ahead, now).len(), "2023-11-14 22:13".len());
+    }
+
+    #[test]
+    fn test_stale_warning() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let day = Some(Duration::from_secs(DAY));
+        let modified = now - Duration::from_secs(3 * DAY);
+        assert_eq!(
+            stale_warning(modified, now, day).as_deref(),
+            Some("File last modified 3 days ago")
+        );
+        assert_eq!(stale_warning(modified, now, None), None);
+        // up to the threshold, or modified after now
+        assert_eq!(
+            stale_warning(now - Duration::from_secs(DAY), now, day),
+            None
+        );
+        assert_eq!(
+            stale_warning(now + Duration::from_secs(HOUR), now, day),
+            None
+        );
+    }
+}
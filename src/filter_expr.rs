@@ -0,0 +1,414 @@
+//! Filter expressions over line content, like
+//! `level=ERROR AND (svc=auth OR svc=billing) AND NOT msg~"timeout"`.
+//!
+//! A term is one of:
+//! - a word or a quoted string, contained in the line
+//! - `~` followed by a regex, found in the line
+//! - `key=value`, `key!=value` or `key~regex` on the value of a field, taken from `key=value`
+//!   pairs (logfmt) or `"key": value` members (JSON) in the line. A line without the field
+//!   satisfies none of them but `!=`
+//!
+//! Terms are combined with `NOT`, `AND` and `OR` in order of precedence, and grouped with
+//! parentheses. Quoted strings keep whitespace, parentheses and operators, with `\"` and `\\`
+//! standing for a quote and a backslash in them.
+
+use std::{borrow::Cow, fmt};
+
+use regex::Regex;
+
+/// Where and why an expression is invalid, `position` counts chars from 0.
+#[derive(Debug, PartialEq)]
+pub struct FilterParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FilterParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for FilterParseError {}
+
+/// A parsed expression, see the module docs for its syntax.
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    source: String,
+    root: Node,
+}
+
+impl FilterExpr {
+    pub fn parse(source: &str) -> Result<Self, FilterParseError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            source,
+            tokens,
+            index: 0,
+        };
+        let root = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error_at(token.start, "unexpected token"));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        self.root.matches(line)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Contains(String),
+    LineRegex(Regex),
+    FieldEq(String, String),
+    FieldRegex(String, Regex),
+}
+
+impl Node {
+    fn matches(&self, line: &str) -> bool {
+        match self {
+            Self::And(left, right) => left.matches(line) && right.matches(line),
+            Self::Or(left, right) => left.matches(line) || right.matches(line),
+            Self::Not(node) => !node.matches(line),
+            Self::Contains(text) => line.contains(text.as_str()),
+            Self::LineRegex(regex) => regex.is_match(line),
+            Self::FieldEq(key, value) => field_value(line, key).is_some_and(|v| v == *value),
+            Self::FieldRegex(key, regex) => {
+                field_value(line, key).is_some_and(|v| regex.is_match(&v))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Equal,
+    NotEqual,
+    Tilde,
+    Word(String),
+    Quoted(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    // byte offset in the source
+    start: usize,
+}
+
+// chars ending a word, besides whitespace
+const WORD_DELIMITERS: &str = "()=~\"";
+
+fn tokenize(source: &str) -> Result<Vec<Token>, FilterParseError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '=' => TokenKind::Equal,
+            '~' => TokenKind::Tilde,
+            '!' if chars.peek().is_some_and(|(_, next)| *next == '=') => {
+                chars.next();
+                TokenKind::NotEqual
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        // other escapes are left for regexes
+                        Some((_, '\\')) => match chars.peek() {
+                            Some((_, escaped @ ('"' | '\\'))) => {
+                                text.push(*escaped);
+                                chars.next();
+                            }
+                            _ => text.push('\\'),
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => {
+                            return Err(FilterParseError {
+                                position: source[..start].chars().count(),
+                                message: "unterminated quote".to_string(),
+                            })
+                        }
+                    }
+                }
+                TokenKind::Quoted(text)
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some((_, next)) = chars.peek() {
+                    if next.is_whitespace() || WORD_DELIMITERS.contains(*next) {
+                        break;
+                    }
+                    // `!=` ends a word, a `!` alone doesn't
+                    if *next == '!' && source[start + word.len() + 1..].starts_with('=') {
+                        break;
+                    }
+                    word.push(*next);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "AND" => TokenKind::And,
+                    "OR" => TokenKind::Or,
+                    "NOT" => TokenKind::Not,
+                    _ => TokenKind::Word(word),
+                }
+            }
+        };
+        tokens.push(Token { kind, start });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn accept(&mut self, kind: TokenKind) -> bool {
+        let accepted = self.peek().is_some_and(|token| token.kind == kind);
+        if accepted {
+            self.index += 1;
+        }
+        accepted
+    }
+
+    fn error_at(&self, byte_offset: usize, message: &str) -> FilterParseError {
+        FilterParseError {
+            position: self.source[..byte_offset].chars().count(),
+            message: message.to_string(),
+        }
+    }
+
+    // where the next token starts, or the end of the source
+    fn next_start(&self) -> usize {
+        self.peek().map_or(self.source.len(), |token| token.start)
+    }
+
+    fn parse_or(&mut self) -> Result<Node, FilterParseError> {
+        let mut node = self.parse_and()?;
+        while self.accept(TokenKind::Or) {
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node, FilterParseError> {
+        let mut node = self.parse_not()?;
+        while self.accept(TokenKind::And) {
+            node = Node::And(Box::new(node), Box::new(self.parse_not()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_not(&mut self) -> Result<Node, FilterParseError> {
+        if self.accept(TokenKind::Not) {
+            return Ok(Node::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_term()
+    }
+
+    fn parse_term(&mut self) -> Result<Node, FilterParseError> {
+        let start = self.next_start();
+        let Some(token) = self.next() else {
+            return Err(self.error_at(start, "expected a term"));
+        };
+        match token.kind {
+            TokenKind::LeftParen => {
+                let node = self.parse_or()?;
+                if !self.accept(TokenKind::RightParen) {
+                    return Err(self.error_at(self.next_start(), "expected `)`"));
+                }
+                Ok(node)
+            }
+            TokenKind::Tilde => {
+                let (pattern, pattern_start) = self.parse_value()?;
+                Ok(Node::LineRegex(self.compile(&pattern, pattern_start)?))
+            }
+            TokenKind::Word(word) => match self.peek().map(|token| token.kind.clone()) {
+                Some(TokenKind::Equal) | Some(TokenKind::NotEqual) => {
+                    let negated = self.next().unwrap().kind == TokenKind::NotEqual;
+                    let (value, _) = self.parse_value()?;
+                    let node = Node::FieldEq(word, value);
+                    Ok(if negated {
+                        Node::Not(Box::new(node))
+                    } else {
+                        node
+                    })
+                }
+                Some(TokenKind::Tilde) => {
+                    self.next();
+                    let (pattern, pattern_start) = self.parse_value()?;
+                    Ok(Node::FieldRegex(
+                        word,
+                        self.compile(&pattern, pattern_start)?,
+                    ))
+                }
+                _ => Ok(Node::Contains(word)),
+            },
+            TokenKind::Quoted(text) => Ok(Node::Contains(text)),
+            _ => Err(self.error_at(token.start, "expected a term")),
+        }
+    }
+
+    // a word or a quoted string, along with where it starts
+    fn parse_value(&mut self) -> Result<(String, usize), FilterParseError> {
+        let start = self.next_start();
+        match self.next().map(|token| token.kind) {
+            Some(TokenKind::Word(value) | TokenKind::Quoted(value)) => Ok((value, start)),
+            _ => Err(self.error_at(start, "expected a value")),
+        }
+    }
+
+    fn compile(&self, pattern: &str, start: usize) -> Result<Regex, FilterParseError> {
+        Regex::new(pattern).map_err(|_| self.error_at(start, "invalid regex"))
+    }
+}
+
+/// The value of field `key` in `line`, from a `key=value` pair or a `"key": value` member
+/// whose value may be quoted.
+pub fn field_value<'a>(line: &'a str, key: &str) -> Option<Cow<'a, str>> {
+    // logfmt, the key starts the line or follows whitespace
+    let pair = format!("{key}=");
+    for (index, _) in line.match_indices(&pair) {
+        if line[..index]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace)
+        {
+            return Some(read_value(&line[index + pair.len()..], char::is_whitespace));
+        }
+    }
+    // json
+    let member = format!("\"{key}\"");
+    for (index, _) in line.match_indices(&member) {
+        let rest = line[index + member.len()..].trim_start();
+        if let Some(rest) = rest.strip_prefix(':') {
+            return Some(read_value(rest.trim_start(), |c| {
+                c.is_whitespace() || c == ',' || c == '}' || c == ']'
+            }));
+        }
+    }
+    None
+}
+
+// a quoted value up to its closing quote, or an unquoted one up to `is_end`
+fn read_value(text: &str, is_end: impl Fn(char) -> bool) -> Cow<'_, str> {
+    let Some(quoted) = text.strip_prefix('"') else {
+        let end = text.find(is_end).unwrap_or(text.len());
+        return Cow::Borrowed(&text[..end]);
+    };
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => value.extend(chars.next()),
+            _ => value.push(c),
+        }
+    }
+    Cow::Owned(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(source: &str, line: &str) -> bool {
+        FilterExpr::parse(source).unwrap().matches(line)
+    }
+
+    fn error(source: &str) -> FilterParseError {
+        FilterExpr::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn test_field_value() {
+        let logfmt = r#"ts=1 level=ERROR msg="read timeout" svc=auth"#;
+        assert_eq!(field_value(logfmt, "level").as_deref(), Some("ERROR"));
+        assert_eq!(field_value(logfmt, "msg").as_deref(), Some("read timeout"));
+        assert_eq!(field_value(logfmt, "vc"), None);
+        assert_eq!(field_value(logfmt, "host"), None);
+
+        let json = r#"{"level": "WARN", "code":503, "msg":"say \"hi\""}"#;
+        assert_eq!(field_value(json, "level").as_deref(), Some("WARN"));
+        assert_eq!(field_value(json, "code").as_deref(), Some("503"));
+        assert_eq!(field_value(json, "msg").as_deref(), Some(r#"say "hi""#));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let expr = r#"level=ERROR AND (svc=auth OR svc=billing) AND NOT msg~"time.?out""#;
+        assert!(matches(expr, "level=ERROR svc=auth msg=denied"));
+        assert!(!matches(expr, "level=ERROR svc=auth msg=timeout"));
+        assert!(!matches(expr, "level=ERROR svc=search msg=denied"));
+        assert!(!matches(expr, "level=INFO svc=billing msg=denied"));
+
+        // AND binds tighter than OR
+        assert!(matches("a OR b AND c", "a"));
+        assert!(!matches("(a OR b) AND c", "a"));
+        assert!(matches("NOT NOT a", "a"));
+
+        // substrings, line regexes and quoted strings
+        assert!(matches(r#""disk full" AND ~"^\d+""#, "42 disk full"));
+        assert!(!matches(r#"~"^\d+""#, "disk 42"));
+        assert!(matches("wow!", "wow!"));
+        let quoted = r#"msg="say \"hi\"""#;
+        assert!(matches(quoted, quoted));
+        // a missing field is unequal to anything
+        assert!(matches("svc!=auth", "level=INFO"));
+        assert!(!matches("svc=auth", "level=INFO"));
+        assert!(matches(r#"code~"^5""#, r#"{"code": 503}"#));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            error("a AND (b OR c"),
+            FilterParseError {
+                position: 13,
+                message: "expected `)`".to_string(),
+            }
+        );
+        assert_eq!(error("a AND").position, 5);
+        assert_eq!(error("level=").message, "expected a value");
+        assert_eq!(error("a b").position, 2);
+        assert_eq!(error(r#"msg~"(""#).message, "invalid regex");
+        assert_eq!(error(r#"msg~"(""#).position, 4);
+        assert_eq!(error(r#"a AND "open"#).position, 6);
+        assert_eq!(error("OR a").to_string(), "expected a term at column 1");
+        assert_eq!(error("").message, "expected a term");
+    }
+}
@@ -1,14 +1,30 @@
-use std::{collections::BTreeSet, ops::Range};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Range,
+    time::{Duration, Instant},
+};
 
+use anyhow::{anyhow, Ok, Result};
 use crossterm::{
     event::{KeyCode, KeyEvent, KeyModifiers},
     style::{Color, Stylize},
 };
-use regex::Regex;
+use loss_viewer::{
+    filter_expr::FilterExpr,
+    pattern::{CompiledPattern, LineMatch, PatternType, RawPatternSet},
+};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     canvas::Canvas,
+    color::{Background, ColorSupport},
+    event_source::{Direction, PendingInput},
+    popup_menu::PopupMenu,
+    profile::FilterProfile,
     render::{LineWithRenderScheme, RenderScheme},
+    status_bar::SegmentSource,
 };
 
 #[derive(Debug, PartialEq)]
@@ -26,10 +42,45 @@ impl HighlightFlag {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// text attribute applied on top of the highlight colors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+enum HighlightStyle {
+    #[default]
+    Plain,
+    Bold,
+    Underline,
+    Reverse,
+}
+
+impl HighlightStyle {
+    fn cycle(&mut self) {
+        *self = match self {
+            Self::Plain => Self::Bold,
+            Self::Bold => Self::Underline,
+            Self::Underline => Self::Reverse,
+            Self::Reverse => Self::Plain,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::Bold => "bold",
+            Self::Underline => "underline",
+            Self::Reverse => "reverse",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct HighlightOption {
     foreground_color: Color,
     background_color: Color,
+    style: HighlightStyle,
+    // no color can be shown, so attributes stand in for the colors
+    monochrome: bool,
+    // a pattern still being typed, shown dimmed
+    provisional: bool,
 }
 
 impl HighlightOption {
@@ -37,10 +88,29 @@ impl HighlightOption {
         Self {
             foreground_color,
             background_color,
+            style: HighlightStyle::Plain,
+            monochrome: false,
+            provisional: false,
         }
     }
 
-    fn from_slot_index(slot_index: usize) -> Self {
+    // dark foregrounds on light backgrounds, as the bright ones would be unreadable there
+    fn from_slot_index(slot_index: usize, background: Background) -> Self {
+        if background == Background::Light {
+            return match slot_index {
+                1 => Self::new(Color::White, Color::DarkGrey),
+                2 => Self::new(Color::White, Color::DarkBlue),
+                3 => Self::new(Color::White, Color::DarkCyan),
+                4 => Self::new(Color::White, Color::DarkGreen),
+                5 => Self::new(Color::Black, Color::Yellow),
+                6 => Self::new(Color::DarkMagenta, Color::Reset),
+                7 => Self::new(Color::DarkBlue, Color::Reset),
+                8 => Self::new(Color::DarkCyan, Color::Reset),
+                9 => Self::new(Color::DarkGreen, Color::Reset),
+                0 => Self::new(Color::DarkYellow, Color::Reset),
+                _ => unreachable!(),
+            };
+        }
         match slot_index {
             1 => Self::new(Color::Black, Color::Grey),
             2 => Self::new(Color::Black, Color::Blue),
@@ -56,14 +126,44 @@ impl HighlightOption {
         }
     }
 
-    fn render_scheme(&self) -> RenderScheme {
-        RenderScheme::Highlight(*self)
+    fn adapted_to(&self, color_support: ColorSupport) -> Self {
+        if color_support == ColorSupport::None {
+            return Self {
+                monochrome: true,
+                ..*self
+            };
+        }
+        Self {
+            foreground_color: color_support.map(self.foreground_color),
+            background_color: color_support.map(self.background_color),
+            ..*self
+        }
+    }
+
+    fn render_scheme(&self, color_support: ColorSupport) -> RenderScheme {
+        RenderScheme::Highlight(self.adapted_to(color_support))
     }
 
     pub fn render(&self, raw: &str) -> String {
-        raw.with(self.foreground_color)
-            .on(self.background_color)
-            .to_string()
+        let colored = if !self.monochrome {
+            raw.with(self.foreground_color).on(self.background_color)
+        } else if self.background_color == Color::Reset {
+            raw.underlined()
+        } else {
+            // a background is the most prominent, like reverse video
+            raw.reverse()
+        };
+        let styled = match self.style {
+            HighlightStyle::Plain => colored,
+            HighlightStyle::Bold => colored.bold(),
+            HighlightStyle::Underline => colored.underlined(),
+            HighlightStyle::Reverse => colored.reverse(),
+        };
+        if self.provisional {
+            styled.dim().to_string()
+        } else {
+            styled.to_string()
+        }
     }
 }
 
@@ -90,21 +190,6 @@ impl AdvancedAction {
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum PatternType {
-    Raw,
-    Regex,
-}
-
-impl PatternType {
-    fn toggle(&mut self) {
-        match self {
-            Self::Raw => *self = Self::Regex,
-            Self::Regex => *self = Self::Raw,
-        }
-    }
-}
-
 fn array_index_to_slot_index(index: usize) -> usize {
     assert!(index <= 9);
     (index + 1) % 10
@@ -115,6 +200,30 @@ fn array_index_from_slot_index(slot_index: usize) -> usize {
     (slot_index + 9) % 10
 }
 
+/// Matches of a slot pattern over the whole document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchStats {
+    pub count: usize,
+    // position of the first match in percentage
+    pub first_match_ratio: Option<usize>,
+}
+
+impl MatchStats {
+    fn format(&self) -> String {
+        match self.first_match_ratio {
+            Some(ratio) => format!("{} hits, first @ {ratio}%", self.count),
+            None => format!("{} hits", self.count),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MatchStatsCache {
+    // stats are outdated once the document grows
+    document_size: usize,
+    stats: MatchStats,
+}
+
 #[derive(Debug)]
 struct FinderSlot {
     slot_index: usize,
@@ -123,6 +232,13 @@ struct FinderSlot {
     advanced_action: AdvancedAction,
     pattern_type: PatternType,
     pattern: Option<String>,
+    // `pattern` compiled, kept in step with it and `pattern_type` by `compile`
+    matcher: Option<CompiledPattern>,
+    match_stats: Option<MatchStatsCache>,
+    // highlighting is suspended after the pattern took too long to match for a few frames in a
+    // row, regardless of the highlight flag
+    suspended: bool,
+    slow_frames: usize,
 }
 
 impl FinderSlot {
@@ -131,10 +247,14 @@ impl FinderSlot {
         Self {
             slot_index,
             highlight_flag: HighlightFlag::On,
-            highlight_option: HighlightOption::from_slot_index(slot_index),
+            highlight_option: HighlightOption::from_slot_index(slot_index, Background::default()),
             advanced_action: AdvancedAction::Nothing,
             pattern_type: PatternType::Raw,
             pattern: None,
+            matcher: None,
+            match_stats: None,
+            suspended: false,
+            slow_frames: 0,
         }
     }
 
@@ -143,33 +263,168 @@ impl FinderSlot {
         self.advanced_action = AdvancedAction::Nothing;
         self.pattern_type = PatternType::Raw;
         self.pattern = None;
+        self.matcher = None;
+        self.match_stats = None;
+        self.highlight_option.style = HighlightStyle::Plain;
+        self.resume();
+    }
+
+    fn compile(&mut self) {
+        self.matcher = self
+            .pattern
+            .as_deref()
+            .map(|pattern| CompiledPattern::new(self.pattern_type, pattern));
+    }
+
+    // a changed pattern gets a fresh start as well
+    fn resume(&mut self) {
+        self.suspended = false;
+        self.slow_frames = 0;
     }
 
     fn find_range_of_match(&self, line: &str) -> Option<Range<usize>> {
-        let pattern = self.pattern.as_ref().unwrap();
-        match self.pattern_type {
-            PatternType::Raw => {
-                if let Some(start) = line.find(pattern) {
-                    return Some(start..start + pattern.len());
-                }
-            }
-            PatternType::Regex => {
-                if let Some(m) = Regex::new(pattern).unwrap().find(line) {
-                    return Some(m.start()..m.end());
-                }
-            }
-        }
-        None
+        self.matcher.as_ref().unwrap().find(line)
+    }
+
+    fn find_ranges_of_matches(&self, line: &str) -> Vec<Range<usize>> {
+        self.matcher.as_ref().unwrap().find_all(line)
+    }
+}
+
+/// Tells how long a slot took to highlight a line, which suspends slots that are too slow.
+/// Tests stand in one counting the lines matched or making a slot look slower.
+trait MatchMeter: std::fmt::Debug {
+    // `elapsed` is the time the slot actually took
+    fn measure(&mut self, slot_index: usize, elapsed: Duration) -> Duration;
+}
+
+#[derive(Debug)]
+struct WallClock;
+
+impl MatchMeter for WallClock {
+    fn measure(&mut self, _slot_index: usize, elapsed: Duration) -> Duration {
+        elapsed
     }
 }
 
 const FINDER_SLOT_COUNT: usize = 10;
+// render layer of the pattern being typed, above the slot highlights
+const PROVISIONAL_LAYER: u8 = 1;
+const RENDER_CACHE_CAPACITY: usize = 1024;
+// time a slot may spend matching the lines of one frame
+const SLOW_MATCH_BUDGET: Duration = Duration::from_millis(50);
+// frames over the budget in a row before the slot highlight is suspended
+const SLOW_FRAME_LIMIT: usize = 3;
+
+// render schemes of recently shown lines, keyed by content hash. entries computed before the
+// slots last changed are stale, so the whole cache is dropped then
+#[derive(Debug, Default)]
+struct RenderCache {
+    generation: u64,
+    entries: HashMap<u64, (LineWithRenderScheme, u64)>,
+    // bumped on every access, entries with the smallest value are evicted first
+    clock: u64,
+}
+
+impl RenderCache {
+    fn get(&mut self, generation: u64, line: &str) -> Option<LineWithRenderScheme> {
+        if self.generation != generation {
+            self.generation = generation;
+            self.entries.clear();
+            return None;
+        }
+        self.clock += 1;
+        let (cached, last_used) = self.entries.get_mut(&hash_line(line))?;
+        // guard against hash collision
+        if cached.raw_content() != line {
+            return None;
+        }
+        *last_used = self.clock;
+        Some(cached.clone())
+    }
+
+    fn insert(&mut self, line_with_scheme: &LineWithRenderScheme) {
+        if self.entries.len() >= RENDER_CACHE_CAPACITY {
+            let least_recently_used = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key)
+                .unwrap();
+            self.entries.remove(&least_recently_used);
+        }
+        self.clock += 1;
+        let key = hash_line(line_with_scheme.raw_content());
+        self.entries
+            .insert(key, (line_with_scheme.clone(), self.clock));
+    }
+}
+
+fn hash_line(line: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    line.hash(&mut hasher);
+    hasher.finish()
+}
+
+// raw patterns of several slots matched in a single pass, with the slots they're of in order
+#[derive(Debug)]
+struct SlotPatternSet {
+    set: RawPatternSet,
+    slot_indexes: Vec<usize>,
+}
+
+impl SlotPatternSet {
+    // `None` unless there are at least two patterns, as a single one is found as fast alone
+    fn of_slots<'a>(slots: impl Iterator<Item = &'a FinderSlot>) -> Option<Self> {
+        let (slot_indexes, patterns): (Vec<_>, Vec<_>) = slots
+            .filter_map(|slot| Some((slot.slot_index, slot.pattern.as_deref()?)))
+            .unzip();
+        if patterns.len() < 2 {
+            return None;
+        }
+        Some(Self {
+            set: RawPatternSet::new(&patterns)?,
+            slot_indexes,
+        })
+    }
+}
+
+// matchers combining the raw patterns of the active slots, when none of them is a regex, and
+// of the highlighted raw slots. they're rebuilt once the slots or the active ones changed
+#[derive(Debug, Default)]
+struct CombinedMatchers {
+    // the generation and the active slots as a bit mask they were built for
+    built_for: Option<(u64, u16)>,
+    active: Option<SlotPatternSet>,
+    highlighted: Option<SlotPatternSet>,
+}
 
 #[derive(Debug)]
 pub struct Finder {
     slots: [FinderSlot; FINDER_SLOT_COUNT],
     active_slots: BTreeSet<usize>,
     menu_active: bool,
+    // the pattern being edited in the menu, shown in place of the status bar
+    menu_prompt: Option<String>,
+    // `+` or `-` awaits a slot digit, the active slots stand out in the status bar
+    slot_choice_pending: bool,
+    // the pattern typed so far in the search prompt, highlighted before it's committed
+    provisional_pattern: Option<String>,
+    // `provisional_pattern` compiled as the pattern type of the active slot it's typed for
+    provisional_matcher: Option<CompiledPattern>,
+    // bumped whenever slots change in a way that affects rendering
+    generation: u64,
+    render_cache: RenderCache,
+    color_support: ColorSupport,
+    background: Background,
+    // time each slot spent matching since the last frame ended, `None` if it matched nothing
+    match_time: [Option<Duration>; FINDER_SLOT_COUNT],
+    // set with `:filter`, lines it doesn't match are hidden like folded ones
+    view_filter: Option<FilterExpr>,
+    combined: RefCell<CombinedMatchers>,
+    // lines not matching the active slots are dimmed rather than shown as they are
+    spotlight: bool,
+    match_meter: Box<dyn MatchMeter>,
 }
 
 impl Finder {
@@ -178,20 +433,216 @@ impl Finder {
             slots: core::array::from_fn(FinderSlot::from_slot_array_index),
             active_slots: BTreeSet::from_iter([1]),
             menu_active: false,
+            menu_prompt: None,
+            slot_choice_pending: false,
+            provisional_pattern: None,
+            provisional_matcher: None,
+            generation: 0,
+            render_cache: RenderCache::default(),
+            color_support: ColorSupport::default(),
+            background: Background::default(),
+            match_time: [None; FINDER_SLOT_COUNT],
+            view_filter: None,
+            combined: RefCell::default(),
+            spotlight: false,
+            match_meter: Box::new(WallClock),
+        }
+    }
+
+    pub fn set_color_support(&mut self, color_support: ColorSupport) {
+        if self.color_support != color_support {
+            self.color_support = color_support;
+            self.generation += 1;
         }
     }
 
+    // switches every slot to the palette for `background`, keeping their styles
+    pub fn set_background(&mut self, background: Background) {
+        if self.background == background {
+            return;
+        }
+        self.background = background;
+        for slot in self.slots.iter_mut() {
+            slot.highlight_option = HighlightOption {
+                style: slot.highlight_option.style,
+                ..HighlightOption::from_slot_index(slot.slot_index, background)
+            };
+        }
+        self.generation += 1;
+    }
+
     pub fn is_menu_active(&self) -> bool {
         self.menu_active
     }
 
+    /// Highlights matches of `pattern` like the active slot would, dimmed and without changing
+    /// the slot. An empty pattern highlights nothing.
+    pub fn set_provisional_pattern(&mut self, pattern: Option<&str>) {
+        let pattern = pattern.filter(|pattern| !pattern.is_empty());
+        if self.provisional_pattern.as_deref() != pattern {
+            self.provisional_pattern = pattern.map(str::to_string);
+            self.compile_provisional_pattern();
+            self.generation += 1;
+        }
+    }
+
+    fn compile_provisional_pattern(&mut self) {
+        let pattern_type = self
+            .active_slots
+            .first()
+            .map_or(PatternType::Raw, |slot_index| {
+                self.slots[array_index_from_slot_index(*slot_index)].pattern_type
+            });
+        self.provisional_matcher = self
+            .provisional_pattern
+            .as_deref()
+            .map(|pattern| CompiledPattern::new(pattern_type, pattern));
+    }
+
+    pub fn slot_strip(&self, last_search: Option<LastSearch>) -> SlotStrip<'_> {
+        SlotStrip {
+            finder: self,
+            last_search,
+        }
+    }
+
+    pub fn set_slot_choice_pending(&mut self, pending: bool) {
+        self.slot_choice_pending = pending;
+    }
+
+    pub fn set_menu_prompt(&mut self, text: Option<&str>) {
+        self.menu_prompt = text.map(|text| format!("Edit pattern: {text}"));
+    }
+
+    /// The pattern of the only active slot, `None` if it has none or several slots are active.
+    pub fn active_slot_pattern(&self) -> Option<&str> {
+        if self.active_slots.len() != 1 {
+            return None;
+        }
+        let index = array_index_from_slot_index(*self.active_slots.iter().next().unwrap());
+        self.slots[index].pattern.as_deref()
+    }
+
+    pub fn slot_pattern(&self, slot_index: usize) -> Option<(PatternType, &str)> {
+        let slot = &self.slots[array_index_from_slot_index(slot_index)];
+        Some((slot.pattern_type, slot.pattern.as_deref()?))
+    }
+
     pub fn update_search_pattern(&mut self, pattern: &str) {
         assert!(self.active_slots.len() == 1);
         let index = array_index_from_slot_index(*self.active_slots.iter().next().unwrap());
         self.slots[index].pattern = Some(pattern.to_string());
+        self.slots[index].compile();
+        self.slots[index].match_stats = None;
+        self.slots[index].resume();
+        self.generation += 1;
+    }
+
+    /// Drops the match stats of every slot, e.g. once another file is opened, since the size
+    /// of the new document may happen to equal the one the stats were counted against.
+    pub fn forget_match_stats(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.match_stats = None;
+        }
+        self.generation += 1;
+    }
+
+    /// Slots with a pattern whose match stats are missing or outdated.
+    pub fn slots_without_match_stats(&self, document_size: usize) -> Vec<usize> {
+        self.slots
+            .iter()
+            .filter(|slot| slot.pattern.is_some())
+            .filter(|slot| {
+                slot.match_stats
+                    .as_ref()
+                    .is_none_or(|cache| cache.document_size != document_size)
+            })
+            .map(|slot| slot.slot_index)
+            .collect()
+    }
+
+    pub fn set_match_stats(&mut self, slot_index: usize, document_size: usize, stats: MatchStats) {
+        self.slots[array_index_from_slot_index(slot_index)].match_stats = Some(MatchStatsCache {
+            document_size,
+            stats,
+        });
+    }
+
+    pub fn can_satisfy_slot_pattern(&self, slot_index: usize, line: &str) -> bool {
+        let slot = &self.slots[array_index_from_slot_index(slot_index)];
+        slot.pattern.is_some() && slot.find_range_of_match(line).is_some()
+    }
+
+    /// The active slots with a pattern matching `line`, along with their patterns.
+    pub fn active_slot_matches(&self, line: &str) -> Vec<(usize, &str)> {
+        self.active_slots
+            .iter()
+            .filter(|slot_index| self.can_satisfy_slot_pattern(**slot_index, line))
+            .filter_map(|slot_index| Some((*slot_index, self.slot_pattern(*slot_index)?.1)))
+            .collect()
+    }
+
+    pub fn has_any_pattern(&self) -> bool {
+        self.slots.iter().any(|slot| slot.pattern.is_some())
+    }
+
+    // any slot with a pattern, highlighted or not
+    pub fn can_satisfy_any_pattern(&self, line: &str) -> bool {
+        self.slots
+            .iter()
+            .any(|slot| slot.pattern.is_some() && slot.find_range_of_match(line).is_some())
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn has_active_search_pattern(&self) -> bool {
+        self.active_slots.iter().any(|slot_index| {
+            self.slots[array_index_from_slot_index(*slot_index)]
+                .pattern
+                .is_some()
+        })
+    }
+
+    // brings the combined matchers up to date with the slots
+    fn refresh_combined_matchers(&self) {
+        let active_mask = self
+            .active_slots
+            .iter()
+            .fold(0u16, |mask, slot_index| mask | 1 << slot_index);
+        let built_for = Some((self.generation, active_mask));
+        let mut combined = self.combined.borrow_mut();
+        if combined.built_for == built_for {
+            return;
+        }
+        let active: Vec<_> = self
+            .active_slots
+            .iter()
+            .map(|slot_index| &self.slots[array_index_from_slot_index(*slot_index)])
+            .filter(|slot| slot.pattern.is_some())
+            .collect();
+        let all_raw = active
+            .iter()
+            .all(|slot| slot.pattern_type == PatternType::Raw);
+        *combined = CombinedMatchers {
+            built_for,
+            active: all_raw
+                .then(|| SlotPatternSet::of_slots(active.into_iter()))
+                .flatten(),
+            highlighted: SlotPatternSet::of_slots(self.slots.iter().filter(|slot| {
+                slot.pattern_type == PatternType::Raw
+                    && slot.highlight_flag == HighlightFlag::On
+                    && !slot.suspended
+            })),
+        };
     }
 
     pub fn can_satisfy_active_search_patterns(&self, line: &str) -> bool {
+        self.refresh_combined_matchers();
+        if let Some(active) = self.combined.borrow().active.as_ref() {
+            return active.set.is_match(line);
+        }
         for slot_index in self.active_slots.iter() {
             let index = array_index_from_slot_index(*slot_index);
             let slot = &self.slots[index];
@@ -202,13 +653,57 @@ impl Finder {
         false
     }
 
+    /// The leftmost match of the active search patterns in `line`.
+    pub fn active_search_match(&self, line: &str) -> LineMatch {
+        self.refresh_combined_matchers();
+        if let Some(active) = self.combined.borrow().active.as_ref() {
+            return active.set.find(line).into();
+        }
+        self.active_slots
+            .iter()
+            .map(|slot_index| &self.slots[array_index_from_slot_index(*slot_index)])
+            .filter(|slot| slot.pattern.is_some())
+            .filter_map(|slot| slot.find_range_of_match(line))
+            .min_by_key(|range| range.start)
+            .into()
+    }
+
+    /// Matches of the active search patterns in `line` by ascending start, the longest one
+    /// where several start at the same byte.
+    pub fn active_search_match_ranges(&self, line: &str) -> Vec<Range<usize>> {
+        let mut ranges = BTreeMap::new();
+        for slot_index in self.active_slots.iter() {
+            let slot = &self.slots[array_index_from_slot_index(*slot_index)];
+            if slot.pattern.is_none() {
+                continue;
+            }
+            for range in slot.find_ranges_of_matches(line) {
+                let end = ranges.entry(range.start).or_insert(range.end);
+                *end = std::cmp::max(*end, range.end);
+            }
+        }
+        ranges.into_iter().map(|(start, end)| start..end).collect()
+    }
+
+    /// Where matches of the active search patterns start in `line`, in ascending order.
+    pub fn active_search_match_starts(&self, line: &str) -> Vec<usize> {
+        self.active_search_match_ranges(line)
+            .into_iter()
+            .map(|range| range.start)
+            .collect()
+    }
+
     pub fn handle_event(&mut self, action: FinderAction) {
+        if action != FinderAction::MenuOn && action != FinderAction::MenuOff {
+            self.generation += 1;
+        }
         match action {
             FinderAction::MenuOn => self.menu_active = true,
             FinderAction::MenuOff => self.menu_active = false,
             FinderAction::AddActiveSlotStart => unreachable!(),
             FinderAction::RemoveActiveSlotStart => unreachable!(),
             FinderAction::SwitchActiveSlot(index) => self.set_active_slot(index),
+            FinderAction::MoveActiveSlot(direction) => self.move_active_slot(direction),
             FinderAction::AddActiveSlot(index) => self.add_active_slot(index),
             FinderAction::RemoveActiveSlot(index) => self.remove_active_slot(index),
             FinderAction::AddOrRemoveActiveSlotCancel => {}
@@ -217,9 +712,18 @@ impl Finder {
             FinderAction::ToggleExclusiveAction => self.toggle_exclusive_action(),
             FinderAction::TogglePatternType => self.toggle_pattern_type(),
             FinderAction::ResetSlot => self.reset_active_slots(),
+            FinderAction::CycleHighlightStyle => self.cycle_highlight_style(),
+            FinderAction::ToggleSpotlight => self.spotlight = !self.spotlight,
+            FinderAction::ResetAllStart => unreachable!(),
+            FinderAction::ResetAll => self.reset_all_slots(),
+            FinderAction::ResetAllCancel => {}
         }
     }
 
+    pub fn is_spotlight(&self) -> bool {
+        self.spotlight
+    }
+
     pub fn active_slots(&self) -> &BTreeSet<usize> {
         &self.active_slots
     }
@@ -228,21 +732,51 @@ impl Finder {
         self.active_slots = BTreeSet::from_iter([slot_index]);
     }
 
+    // to the slot above or below the first active one in the menu, wrapping around
+    pub fn move_active_slot(&mut self, direction: Direction) {
+        let first = self
+            .active_slots
+            .iter()
+            .map(|slot_index| array_index_from_slot_index(*slot_index))
+            .min()
+            .unwrap();
+        let next = if direction == Direction::Up {
+            (first + FINDER_SLOT_COUNT - 1) % FINDER_SLOT_COUNT
+        } else {
+            (first + 1) % FINDER_SLOT_COUNT
+        };
+        self.set_active_slot(array_index_to_slot_index(next));
+    }
+
     pub fn add_active_slot(&mut self, slot_index: usize) {
         self.active_slots.insert(slot_index);
     }
 
+    // the last active slot stays, as some slot is always active
     pub fn remove_active_slot(&mut self, slot_index: usize) {
-        assert!(self.active_slots.len() > 1);
-        self.active_slots.remove(&slot_index);
-        assert!(!self.active_slots.is_empty());
+        if self.active_slots.len() > 1 {
+            self.active_slots.remove(&slot_index);
+        }
     }
 
+    // a suspended slot is resumed rather than toggled
     pub fn toggle_highlight_flag(&mut self) {
+        for index in self.active_slots.iter() {
+            let slot = &mut self.slots[array_index_from_slot_index(*index)];
+            if slot.suspended {
+                slot.resume();
+            } else {
+                slot.highlight_flag.toggle();
+            }
+        }
+    }
+
+    pub fn cycle_highlight_style(&mut self) {
         for index in self.active_slots.iter() {
             self.slots[array_index_from_slot_index(*index)]
-                .highlight_flag
-                .toggle();
+                .highlight_option
+                .style
+                .cycle();
         }
     }
 
@@ -262,10 +796,13 @@ impl Finder {
 
     pub fn toggle_pattern_type(&mut self) {
         for index in self.active_slots.iter() {
-            self.slots[array_index_from_slot_index(*index)]
-                .pattern_type
-                .toggle();
+            let slot = &mut self.slots[array_index_from_slot_index(*index)];
+            slot.pattern_type.toggle();
+            slot.compile();
+            slot.match_stats = None;
+            slot.resume();
         }
+        self.compile_provisional_pattern();
     }
 
     pub fn reset_active_slots(&mut self) {
@@ -274,125 +811,229 @@ impl Finder {
         }
     }
 
+    pub fn reset_all_slots(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.reset();
+        }
+        self.active_slots = BTreeSet::from_iter([1]);
+    }
+
+    /// Loads rules of `profile` into slots in order from slot 1, replacing all slots.
+    pub fn apply_filter_profile(&mut self, profile: &FilterProfile) -> Result<()> {
+        if profile.rules.len() > FINDER_SLOT_COUNT {
+            return Err(anyhow!(
+                "profile has {} rules, at most {FINDER_SLOT_COUNT} are supported",
+                profile.rules.len()
+            ));
+        }
+        self.reset_all_slots();
+        for (slot, rule) in self.slots.iter_mut().zip(profile.rules.iter()) {
+            slot.pattern = Some(rule.pattern.clone());
+            slot.compile();
+            slot.advanced_action = if rule.exclusive {
+                AdvancedAction::Exclusive
+            } else {
+                AdvancedAction::Fold
+            };
+        }
+        self.generation += 1;
+        Ok(())
+    }
+
     pub fn can_pass_advanced_action(&self, line: &str) -> bool {
-        let fold_patterns = self
-            .slots
+        !self.is_folded(line) && self.matches_exclusive(line) != Some(false)
+    }
+
+    pub fn view_filter(&self) -> Option<&FilterExpr> {
+        self.view_filter.as_ref()
+    }
+
+    pub fn set_view_filter(&mut self, view_filter: Option<FilterExpr>) {
+        self.view_filter = view_filter;
+    }
+
+    pub fn has_exclusive_rule(&self) -> bool {
+        self.slots
             .iter()
-            .filter_map(|s| {
-                if s.advanced_action == AdvancedAction::Fold {
-                    s.pattern.clone()
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        if fold_patterns.iter().any(|p| line.contains(p)) {
-            return false;
+            .any(|s| s.advanced_action == AdvancedAction::Exclusive && s.pattern.is_some())
+    }
+
+    /// Which of the consecutive `lines` pass the fold and exclusive rules, where lines within
+    /// `context` lines of an exclusive match pass too, like `grep -C`. Folded lines never pass.
+    pub fn pass_with_context(&self, lines: &[&str], context: usize) -> Vec<bool> {
+        let mut passed = vec![false; lines.len()];
+        for (index, line) in lines.iter().enumerate() {
+            if self.matches_exclusive(line) != Some(false) {
+                let end = std::cmp::min(index + context + 1, lines.len());
+                passed[index.saturating_sub(context)..end].fill(true);
+            }
+        }
+        for (index, line) in lines.iter().enumerate() {
+            passed[index] = passed[index] && !self.is_folded(line);
         }
+        passed
+    }
 
-        let exclusive_patterns = self
-            .slots
-            .iter()
-            .filter_map(|s| {
-                if s.advanced_action == AdvancedAction::Exclusive {
-                    s.pattern.clone()
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        if !exclusive_patterns.is_empty() && exclusive_patterns.iter().all(|ep| !line.contains(ep))
-        {
-            return false;
+    // hidden by a fold slot or the view filter, even as context of an exclusive match
+    fn is_folded(&self, line: &str) -> bool {
+        if self.view_filter.as_ref().is_some_and(|f| !f.matches(line)) {
+            return true;
         }
+        self.slots.iter().any(|s| {
+            s.advanced_action == AdvancedAction::Fold
+                && s.pattern.as_ref().is_some_and(|p| line.contains(p))
+        })
+    }
 
-        true
+    // `None` if there's no exclusive rule
+    fn matches_exclusive(&self, line: &str) -> Option<bool> {
+        let mut exclusive_patterns = self.slots.iter().filter_map(|s| {
+            if s.advanced_action == AdvancedAction::Exclusive {
+                s.pattern.as_ref()
+            } else {
+                None
+            }
+        });
+        let first = exclusive_patterns.next()?;
+        Some(line.contains(first) || exclusive_patterns.any(|ep| line.contains(ep)))
     }
 
-    pub fn attach_render_scheme(&self, line: &str) -> LineWithRenderScheme {
+    pub fn attach_render_scheme(&mut self, line: &str) -> LineWithRenderScheme {
+        if let Some(cached) = self.render_cache.get(self.generation, line) {
+            return cached;
+        }
         let mut line_with_scheme = LineWithRenderScheme::new(line);
+        self.refresh_combined_matchers();
+        // raw slots matched together, the time it took shared among them
+        let combined_ranges = self
+            .combined
+            .get_mut()
+            .highlighted
+            .as_ref()
+            .map(|highlighted| {
+                let started = Instant::now();
+                let ranges = highlighted.set.find_ranges_of_matches(line);
+                let elapsed = started.elapsed() / highlighted.slot_indexes.len() as u32;
+                let by_slot: HashMap<_, _> = highlighted
+                    .slot_indexes
+                    .iter()
+                    .copied()
+                    .zip(ranges)
+                    .collect();
+                (by_slot, elapsed)
+            });
         // active slots have higher priority than inactive ones
         let (active, inactive): (Vec<_>, Vec<_>) = self
             .slots
             .iter()
             .partition(|slot| self.active_slots.contains(&slot.slot_index));
         for slot in active.iter().chain(inactive.iter()) {
-            if slot.highlight_flag == HighlightFlag::Off || slot.pattern.is_none() {
+            if slot.highlight_flag == HighlightFlag::Off || slot.suspended || slot.pattern.is_none()
+            {
                 continue;
             }
-            let mut from_pos = 0;
-            while let Some(match_range) = slot.find_range_of_match(&line[from_pos..]) {
-                let start = match_range.start + from_pos;
-                let end = match_range.end + from_pos;
-                line_with_scheme
-                    .add_scheme_if_not_overlap(start..end, slot.highlight_option.render_scheme());
-                from_pos = end;
+            let combined = combined_ranges
+                .as_ref()
+                .and_then(|(by_slot, elapsed)| Some((by_slot.get(&slot.slot_index)?, *elapsed)));
+            let (ranges, elapsed) = match combined {
+                Some((ranges, elapsed)) => (ranges.clone(), elapsed),
+                None => {
+                    let started = Instant::now();
+                    let ranges = slot.find_ranges_of_matches(line);
+                    (ranges, started.elapsed())
+                }
+            };
+            let elapsed = self.match_meter.measure(slot.slot_index, elapsed);
+            let match_time = &mut self.match_time[array_index_from_slot_index(slot.slot_index)];
+            *match_time = Some(match_time.unwrap_or_default() + elapsed);
+            // an empty match has nothing to highlight
+            for range in ranges.into_iter().filter(|range| !range.is_empty()) {
+                line_with_scheme.add_scheme_if_not_overlap(
+                    range,
+                    slot.highlight_option.render_scheme(self.color_support),
+                );
+            }
+        }
+        // in spotlight, a line the active slots don't match is dimmed over its highlights. without
+        // an active pattern every line would be, so none is
+        if self.spotlight
+            && !line.is_empty()
+            && active.iter().any(|slot| slot.pattern.is_some())
+            && !self.can_satisfy_active_search_patterns(line)
+        {
+            line_with_scheme.add_scheme_layered(0..line.len(), RenderScheme::Dim, 0);
+        }
+        // and the pattern being typed over all of them, where it overlaps them
+        if let (Some(matcher), Some(slot)) = (self.provisional_matcher.as_ref(), active.first()) {
+            let scheme = RenderScheme::Highlight(HighlightOption {
+                provisional: true,
+                ..slot.highlight_option.adapted_to(self.color_support)
+            });
+            for range in matcher
+                .find_all(line)
+                .into_iter()
+                .filter(|range| !range.is_empty())
+            {
+                line_with_scheme.add_scheme_layered(range, scheme, PROVISIONAL_LAYER);
             }
         }
+        self.render_cache.insert(&line_with_scheme);
         line_with_scheme
     }
 
-    pub fn render_status_bar(&self, canvas: &mut Canvas, space_count: usize) {
-        if space_count < 40 {
-            return;
+    /// Ends the frame whose lines were attached render schemes since the last call, returning
+    /// the slots suspended for matching over budget too many frames in a row. Frames served
+    /// from the cache don't count either way.
+    pub fn end_frame(&mut self) -> Vec<usize> {
+        let mut suspended = vec![];
+        for (slot, match_time) in self.slots.iter_mut().zip(self.match_time.iter_mut()) {
+            match match_time.take() {
+                Some(time) if time >= SLOW_MATCH_BUDGET => slot.slow_frames += 1,
+                Some(_) => slot.slow_frames = 0,
+                None => continue,
+            }
+            if slot.slow_frames >= SLOW_FRAME_LIMIT {
+                slot.suspended = true;
+                slot.slow_frames = 0;
+                suspended.push(slot.slot_index);
+            }
         }
-        let mut raw_content = canvas.status_bar.raw_content().to_string();
-        let slots_section_end = raw_content.len() - 5;
-        let slots_section_start = slots_section_end - 32;
-        let mut current_slot_start = slots_section_start;
-        for slot in self.slots.iter() {
-            let maybe_cursor = if self.active_slots.contains(&slot.slot_index) {
-                '*'
-            } else {
-                ' '
-            };
-            raw_content.replace_range(
-                current_slot_start + 1..current_slot_start + 3,
-                &format!("{maybe_cursor}{}", slot.slot_index),
-            );
-            let scheme = if slot.pattern.is_some() {
-                slot.highlight_option.render_scheme()
-            } else {
-                RenderScheme::Dim
-            };
-            canvas
-                .status_bar
-                .add_scheme_if_not_overlap(current_slot_start + 2..current_slot_start + 3, scheme);
-            current_slot_start += 3;
+        if !suspended.is_empty() {
+            self.generation += 1;
         }
-        raw_content.replace_range(slots_section_end - 2..slots_section_end, " |");
-        canvas.status_bar.set_raw_content(&raw_content);
+        suspended
     }
 
     pub fn render_menu(&self, canvas: &mut Canvas, window_width: usize, window_height: usize) {
-        const MENU_HEIGHT: usize = 11;
-        const MENU_MIN_WIDTH: usize = 50;
-        const FINDER_MENU_STR: &str = " Finder Menu ";
-        let width = std::cmp::max(window_width, 20);
-        let mut title = "=".repeat(width);
-        let begin = (width - FINDER_MENU_STR.len()) / 2;
-        title.replace_range(begin..begin + FINDER_MENU_STR.len(), FINDER_MENU_STR);
-        title.truncate(window_width);
-        if window_height < MENU_HEIGHT + 5 || window_width < MENU_MIN_WIDTH {
-            canvas.status_bar = LineWithRenderScheme::new(&title);
-            canvas.cursor_pos_x = None;
-            return;
-        }
-        canvas.popup_menu.clear();
-        canvas.popup_menu.push(LineWithRenderScheme::new(&title));
+        let mut rows = vec![];
         for slot in self.slots.iter() {
             let maybe_cursor = if self.active_slots.contains(&slot.slot_index) {
                 '*'
             } else {
                 ' '
             };
-            let raw_line = &format!(
-                " {maybe_cursor} {} | On Off | Fold Exclusive | Raw Regex | {}",
+            let mut raw_line = format!(
+                " {maybe_cursor} {} | On Off | Fold Exclusive | Raw Regex | {:<9} | ",
                 slot.slot_index,
-                slot.pattern.as_ref().unwrap_or(&String::default())
+                slot.highlight_option.style.name()
             );
+            if slot.suspended {
+                raw_line.push_str("too slow, o resumes | ");
+            }
+            // stats are dropped rather than cut when the window is too narrow
+            if let Some(cache) = slot.match_stats.as_ref() {
+                let stats = format!("{} | ", cache.stats.format());
+                if raw_line.len() + stats.len() <= window_width {
+                    raw_line.push_str(&stats);
+                }
+            }
+            raw_line.push_str(slot.pattern.as_deref().unwrap_or_default());
+            let raw_line = &raw_line;
             let mut rendered_line = LineWithRenderScheme::new(raw_line).truncate(window_width);
-            rendered_line.add_scheme_if_not_overlap(3..4, slot.highlight_option.render_scheme());
+            rendered_line.add_scheme_if_not_overlap(
+                3..4,
+                slot.highlight_option.render_scheme(self.color_support),
+            );
             if slot.highlight_flag != HighlightFlag::On {
                 rendered_line.add_scheme_if_not_overlap(7..9, RenderScheme::Dim);
             }
@@ -411,17 +1052,25 @@ impl Finder {
             if slot.pattern_type != PatternType::Regex {
                 rendered_line.add_scheme_if_not_overlap(37..42, RenderScheme::Dim);
             }
-            canvas.popup_menu.push(rendered_line);
+            rows.push(rendered_line);
+        }
+        PopupMenu {
+            title: "Finder Menu",
+            height: rows.len(),
+            min_width: 50,
+            rows,
+            prompt: self.menu_prompt.clone(),
+            ..PopupMenu::default()
         }
-        assert!(canvas.popup_menu.len() == MENU_HEIGHT);
-        canvas.status_bar = LineWithRenderScheme::default();
-        canvas.cursor_pos_x = Some(0);
+        .render(canvas, window_width, window_height);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum FinderAction {
     SwitchActiveSlot(usize),
+    // the slot above or below in the menu, as an alternative to its digit
+    MoveActiveSlot(Direction),
     AddActiveSlotStart,
     AddActiveSlot(usize),
     RemoveActiveSlotStart,
@@ -432,6 +1081,12 @@ pub enum FinderAction {
     ToggleExclusiveAction,
     TogglePatternType,
     ResetSlot,
+    CycleHighlightStyle,
+    ToggleSpotlight,
+    // resetting all slots takes a second `X` to confirm
+    ResetAllStart,
+    ResetAll,
+    ResetAllCancel,
     MenuOn,
     MenuOff,
 }
@@ -442,20 +1097,65 @@ enum FinderEventParserState {
     Normal,
     ParsedAdd,
     ParsedRemove,
+    ParsedResetAll,
 }
 
+/// Turns keys into finder actions. A bare digit switches the active slot only while the menu
+/// is open, unless direct digits are set, so a stray digit doesn't change what's highlighted.
+/// Digits after `+` or `-` always pick a slot.
 #[derive(Debug, Default)]
 pub struct FinderEventParser {
     state: FinderEventParserState,
     menu_active: bool,
+    direct_digits: bool,
 }
 
 impl FinderEventParser {
+    pub fn set_direct_digits(&mut self, direct_digits: bool) {
+        self.direct_digits = direct_digits;
+    }
+
     pub fn set_state_to_normal(&mut self) {
         self.state = FinderEventParserState::Normal;
     }
 
+    pub fn pending_input(&self) -> Option<PendingInput> {
+        match self.state {
+            FinderEventParserState::Normal => None,
+            FinderEventParserState::ParsedAdd => Some(PendingInput::AddSlot),
+            FinderEventParserState::ParsedRemove => Some(PendingInput::RemoveSlot),
+            FinderEventParserState::ParsedResetAll => Some(PendingInput::ResetAll),
+        }
+    }
+
+    // back to normal, returning the action cancelling the sequence given up
+    pub fn cancel_pending(&mut self) -> Option<FinderAction> {
+        let action = match self.state {
+            FinderEventParserState::Normal => return None,
+            FinderEventParserState::ParsedAdd | FinderEventParserState::ParsedRemove => {
+                FinderAction::AddOrRemoveActiveSlotCancel
+            }
+            FinderEventParserState::ParsedResetAll => FinderAction::ResetAllCancel,
+        };
+        self.state = FinderEventParserState::Normal;
+        Some(action)
+    }
+
+    // whether a key may start editing the active slot's pattern from the menu
+    pub fn can_edit_in_menu(&self) -> bool {
+        self.menu_active && self.state == FinderEventParserState::Normal
+    }
+
     pub fn try_parse_raw_event(&mut self, key: &KeyEvent) -> Option<FinderAction> {
+        // any key other than a second `X` cancels, and is swallowed
+        if self.state == FinderEventParserState::ParsedResetAll {
+            self.state = FinderEventParserState::Normal;
+            return if key.code == KeyCode::Char('X') {
+                Some(FinderAction::ResetAll)
+            } else {
+                Some(FinderAction::ResetAllCancel)
+            };
+        }
         if key.modifiers != KeyModifiers::NONE && key.modifiers != KeyModifiers::SHIFT {
             return None;
         }
@@ -472,6 +1172,13 @@ impl FinderEventParser {
                     return Some(FinderAction::RemoveActiveSlotStart);
                 }
             }
+            KeyCode::Char('0'..='9')
+                if self.state == FinderEventParserState::Normal
+                    && !self.menu_active
+                    && !self.direct_digits =>
+            {
+                return None
+            }
             KeyCode::Char(index @ '0'..='9') => {
                 let index = index as usize - '0' as usize;
                 let state = self.state;
@@ -482,6 +1189,8 @@ impl FinderEventParser {
                     FinderEventParserState::ParsedRemove => {
                         Some(FinderAction::RemoveActiveSlot(index))
                     }
+                    // handled before anything else
+                    FinderEventParserState::ParsedResetAll => unreachable!(),
                 };
             }
             KeyCode::Esc => {
@@ -519,6 +1228,33 @@ impl FinderEventParser {
                     return Some(FinderAction::ResetSlot);
                 }
             }
+            // styles are picked while looking at the slots in the menu
+            KeyCode::Char('s') => {
+                if self.menu_active && self.state == FinderEventParserState::Normal {
+                    return Some(FinderAction::CycleHighlightStyle);
+                }
+            }
+            KeyCode::Char('d') => {
+                if self.state == FinderEventParserState::Normal {
+                    return Some(FinderAction::ToggleSpotlight);
+                }
+            }
+            KeyCode::Char('X') => {
+                if self.state == FinderEventParserState::Normal {
+                    self.state = FinderEventParserState::ParsedResetAll;
+                    return Some(FinderAction::ResetAllStart);
+                }
+            }
+            KeyCode::Up | KeyCode::Down => {
+                if self.menu_active && self.state == FinderEventParserState::Normal {
+                    let direction = if key.code == KeyCode::Up {
+                        Direction::Up
+                    } else {
+                        Direction::Down
+                    };
+                    return Some(FinderAction::MoveActiveSlot(direction));
+                }
+            }
             KeyCode::Char('m') => {
                 if self.state == FinderEventParserState::Normal {
                     return if self.menu_active {
@@ -535,3 +1271,678 @@ impl FinderEventParser {
         None
     }
 }
+
+/// The slot the last search was entered into and its direction, which `n` and `N` follow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LastSearch {
+    pub slot_index: usize,
+    pub direction: Direction,
+}
+
+/// The finder slots in the status bar, marking the slot of the last search.
+pub struct SlotStrip<'a> {
+    finder: &'a Finder,
+    last_search: Option<LastSearch>,
+}
+
+impl SegmentSource for SlotStrip<'_> {
+    // every slot like ` *1  2 |`, or only the active ones and those with a pattern, or
+    // those as `[*1 3]`, whichever is the first to fit in `room` columns. the slot of the last
+    // search, while it keeps a pattern, has `/` or `?` ahead for its direction, like `/*1`.
+    // while a slot digit is awaited, the active slots are reversed
+    fn render_segment(&self, room: usize) -> Option<LineWithRenderScheme> {
+        let finder = self.finder;
+        let cell = |slot: &FinderSlot| {
+            let is_active = finder.active_slots.contains(&slot.slot_index);
+            let glyph = match self.last_search {
+                Some(LastSearch {
+                    slot_index,
+                    direction,
+                }) if slot_index == slot.slot_index && slot.pattern.is_some() => {
+                    if direction == Direction::Up {
+                        "?"
+                    } else {
+                        "/"
+                    }
+                }
+                _ => "",
+            };
+            let cursor = if is_active { "*" } else { "" };
+            let scheme = if is_active && finder.slot_choice_pending {
+                RenderScheme::Reverse
+            } else if slot.pattern.is_some() {
+                slot.highlight_option.render_scheme(finder.color_support)
+            } else {
+                RenderScheme::Dim
+            };
+            (glyph, cursor, slot.slot_index.to_string(), scheme)
+        };
+        let in_use: Vec<&FinderSlot> = finder
+            .slots
+            .iter()
+            .filter(|slot| slot.pattern.is_some() || finder.active_slots.contains(&slot.slot_index))
+            .collect();
+
+        let columns = |slots: &[&FinderSlot]| {
+            let mut section = LineWithRenderScheme::new("");
+            for slot in slots {
+                let (glyph, cursor, index, scheme) = cell(slot);
+                section.push_str(&format!("{:>2}", format!("{glyph}{cursor}")));
+                section.push_str_with_scheme(&index, scheme);
+            }
+            section.push_str(" |");
+            section
+        };
+        let all: Vec<&FinderSlot> = finder.slots.iter().collect();
+        let mut compact = LineWithRenderScheme::new("[");
+        for (i, slot) in in_use.iter().enumerate() {
+            let (glyph, cursor, index, scheme) = cell(slot);
+            if i > 0 {
+                compact.push_str(" ");
+            }
+            compact.push_str(&format!("{glyph}{cursor}"));
+            compact.push_str_with_scheme(&index, scheme);
+        }
+        compact.push_str("]");
+
+        [columns(&all), columns(&in_use), compact]
+            .into_iter()
+            .find(|section| section.raw_content().len() <= room)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    // counts the lines highlighted by slots, and makes a slot take `delay` longer on each
+    #[derive(Debug, Default)]
+    struct TestMeter {
+        calls: Rc<Cell<usize>>,
+        slow_slot: Option<(usize, Duration)>,
+    }
+
+    impl MatchMeter for TestMeter {
+        fn measure(&mut self, slot_index: usize, elapsed: Duration) -> Duration {
+            self.calls.set(self.calls.get() + 1);
+            match self.slow_slot {
+                Some((slow_slot, delay)) if slow_slot == slot_index => elapsed + delay,
+                _ => elapsed,
+            }
+        }
+    }
+
+    const STATS: MatchStats = MatchStats {
+        count: 3,
+        first_match_ratio: Some(10),
+    };
+
+    #[test]
+    fn test_render_slots_segment() {
+        let mut finder = Finder::new();
+        finder.update_search_pattern("error");
+        finder.set_active_slot(3);
+        finder.update_search_pattern("warn");
+        finder.set_active_slot(1);
+        let segment = |room| {
+            finder
+                .slot_strip(None)
+                .render_segment(room)
+                .map(|segment| segment.raw_content().to_string())
+        };
+        let all = " *1  2  3  4  5  6  7  8  9  0 |";
+        assert_eq!(segment(200).as_deref(), Some(all));
+        assert_eq!(segment(all.len()).as_deref(), Some(all));
+        // only the active slot and those with a pattern
+        assert_eq!(segment(all.len() - 1).as_deref(), Some(" *1  3 |"));
+        assert_eq!(segment(7).as_deref(), Some("[*1 3]"));
+        assert_eq!(segment(6).as_deref(), Some("[*1 3]"));
+        assert_eq!(segment(5), None);
+        assert_eq!(segment(0), None);
+
+        // the active slots are reversed while `+` or `-` awaits a digit
+        finder.set_slot_choice_pending(true);
+        let marked = finder.slot_strip(None).render_segment(7).unwrap().marked();
+        assert!(marked.starts_with("[*<1> "));
+        finder.set_slot_choice_pending(false);
+        assert!(!finder
+            .slot_strip(None)
+            .render_segment(7)
+            .unwrap()
+            .marked()
+            .contains('<'));
+
+        // the last active slot isn't removed
+        finder.handle_event(FinderAction::RemoveActiveSlot(1));
+        assert_eq!(finder.active_slots(), &BTreeSet::from_iter([1]));
+        finder.add_active_slot(3);
+        finder.handle_event(FinderAction::RemoveActiveSlot(1));
+        assert_eq!(finder.active_slots(), &BTreeSet::from_iter([3]));
+    }
+
+    #[test]
+    fn test_render_last_search_in_slots() {
+        let mut finder = Finder::new();
+        finder.update_search_pattern("error");
+        finder.set_active_slot(3);
+        finder.update_search_pattern("warn");
+        let segment = |finder: &Finder, slot_index, direction, room| {
+            let last_search = LastSearch {
+                slot_index,
+                direction,
+            };
+            finder
+                .slot_strip(Some(last_search))
+                .render_segment(room)
+                .map(|segment| segment.raw_content().to_string())
+        };
+        // searched down in the active slot, then up in a slot no longer active
+        assert_eq!(
+            segment(&finder, 3, Direction::Down, 200).as_deref(),
+            Some("  1  2/*3  4  5  6  7  8  9  0 |")
+        );
+        assert_eq!(
+            segment(&finder, 1, Direction::Up, 200).as_deref(),
+            Some(" ?1  2 *3  4  5  6  7  8  9  0 |")
+        );
+        // the same width as without a search, down to the compact form with a column more
+        assert_eq!(
+            segment(&finder, 1, Direction::Up, 20).as_deref(),
+            Some(" ?1 *3 |")
+        );
+        assert_eq!(
+            segment(&finder, 1, Direction::Up, 7).as_deref(),
+            Some("[?1 *3]")
+        );
+        assert_eq!(segment(&finder, 1, Direction::Up, 6), None);
+
+        // not marked once the slot is cleared
+        finder.set_active_slot(1);
+        finder.handle_event(FinderAction::ResetSlot);
+        assert_eq!(
+            segment(&finder, 1, Direction::Up, 20).as_deref(),
+            Some(" *1  3 |")
+        );
+    }
+
+    #[test]
+    fn test_match_stats_invalidation() {
+        let mut finder = Finder::new();
+        assert!(finder.slots_without_match_stats(100).is_empty());
+
+        finder.update_search_pattern("error");
+        assert_eq!(finder.slots_without_match_stats(100), [1]);
+        finder.set_match_stats(1, 100, STATS);
+        assert!(finder.slots_without_match_stats(100).is_empty());
+        // document grows
+        assert_eq!(finder.slots_without_match_stats(200), [1]);
+
+        // pattern changes
+        finder.update_search_pattern("warn");
+        assert_eq!(finder.slots_without_match_stats(100), [1]);
+        finder.set_match_stats(1, 100, STATS);
+        finder.toggle_pattern_type();
+        assert_eq!(finder.slots_without_match_stats(100), [1]);
+
+        // other slots are kept apart
+        finder.set_match_stats(1, 100, STATS);
+        finder.set_active_slot(2);
+        finder.update_search_pattern("info");
+        assert_eq!(finder.slots_without_match_stats(100), [2]);
+        finder.reset_active_slots();
+        assert!(finder.slots_without_match_stats(100).is_empty());
+        assert!(finder.slots[array_index_from_slot_index(2)]
+            .match_stats
+            .is_none());
+    }
+
+    #[test]
+    fn test_reset_all_slots() {
+        let mut finder = Finder::new();
+        finder.update_search_pattern("error");
+        finder.set_active_slot(3);
+        finder.update_search_pattern("warn");
+        finder.toggle_fold_action();
+        finder.add_active_slot(5);
+        finder.toggle_highlight_flag();
+
+        finder.reset_all_slots();
+        assert_eq!(finder.active_slots(), &BTreeSet::from_iter([1]));
+        for slot in finder.slots.iter() {
+            assert_eq!(slot.pattern, None);
+            assert_eq!(slot.highlight_flag, HighlightFlag::On);
+            assert_eq!(slot.advanced_action, AdvancedAction::Nothing);
+        }
+    }
+
+    #[test]
+    fn test_move_active_slot_in_menu() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let mut parser = FinderEventParser::default();
+        // arrows are left to window moves outside the menu
+        assert_eq!(parser.try_parse_raw_event(&key(KeyCode::Down)), None);
+        parser.try_parse_raw_event(&key(KeyCode::Char('m')));
+        assert_eq!(
+            parser.try_parse_raw_event(&key(KeyCode::Down)),
+            Some(FinderAction::MoveActiveSlot(Direction::Down))
+        );
+        assert_eq!(
+            parser.try_parse_raw_event(&key(KeyCode::Up)),
+            Some(FinderAction::MoveActiveSlot(Direction::Up))
+        );
+        // as is cycling the highlight style
+        assert_eq!(
+            parser.try_parse_raw_event(&key(KeyCode::Char('s'))),
+            Some(FinderAction::CycleHighlightStyle)
+        );
+        parser.try_parse_raw_event(&key(KeyCode::Char('m')));
+        assert_eq!(parser.try_parse_raw_event(&key(KeyCode::Char('s'))), None);
+
+        // in menu order, from the first active slot
+        let mut finder = Finder::new();
+        let mut move_to = |direction| {
+            finder.handle_event(FinderAction::MoveActiveSlot(direction));
+            finder.active_slots().iter().copied().collect::<Vec<_>>()
+        };
+        assert_eq!(move_to(Direction::Down), [2]);
+        assert_eq!(move_to(Direction::Up), [1]);
+        assert_eq!(move_to(Direction::Up), [0]);
+        assert_eq!(move_to(Direction::Down), [1]);
+        finder.set_active_slot(3);
+        finder.add_active_slot(0);
+        finder.handle_event(FinderAction::MoveActiveSlot(Direction::Down));
+        assert_eq!(finder.active_slots(), &BTreeSet::from_iter([4]));
+
+        // closing and reopening the menu keeps the cursor where it was
+        finder.handle_event(FinderAction::MenuOff);
+        finder.handle_event(FinderAction::MenuOn);
+        assert_eq!(finder.active_slots(), &BTreeSet::from_iter([4]));
+    }
+
+    #[test]
+    fn test_parse_reset_all() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut parser = FinderEventParser::default();
+        assert_eq!(
+            parser.try_parse_raw_event(&key('X')),
+            Some(FinderAction::ResetAllStart)
+        );
+        assert_eq!(
+            parser.try_parse_raw_event(&key('X')),
+            Some(FinderAction::ResetAll)
+        );
+
+        // anything else cancels and gets swallowed
+        parser.try_parse_raw_event(&key('X'));
+        assert_eq!(
+            parser.try_parse_raw_event(&key('x')),
+            Some(FinderAction::ResetAllCancel)
+        );
+        assert_eq!(
+            parser.try_parse_raw_event(&key('x')),
+            Some(FinderAction::ResetSlot)
+        );
+    }
+
+    #[test]
+    fn test_render_highlight_style() {
+        let mut option = HighlightOption::new(Color::Black, Color::Blue);
+        let colors = "\x1b[48;5;12m\x1b[38;5;0m";
+        assert_eq!(option.render("x"), format!("{colors}x\x1b[49m\x1b[39m"));
+        // attributes come after the colors and everything is reset at the end
+        option.style.cycle();
+        assert_eq!(option.style, HighlightStyle::Bold);
+        assert_eq!(option.render("x"), format!("{colors}\x1b[1mx\x1b[0m"));
+        option.style.cycle();
+        assert_eq!(option.render("x"), format!("{colors}\x1b[4mx\x1b[0m"));
+        option.style.cycle();
+        assert_eq!(option.render("x"), format!("{colors}\x1b[7mx\x1b[0m"));
+        option.style.cycle();
+        assert_eq!(option.style, HighlightStyle::Plain);
+    }
+
+    #[test]
+    fn test_render_without_color() {
+        let option = HighlightOption::new(Color::Black, Color::Blue);
+        assert_eq!(
+            option.adapted_to(ColorSupport::None).render("x"),
+            "\x1b[7mx\x1b[0m"
+        );
+        let mut option = HighlightOption::new(Color::Yellow, Color::Reset);
+        option.style.cycle();
+        assert_eq!(
+            option.adapted_to(ColorSupport::None).render("x"),
+            "\x1b[1m\x1b[4mx\x1b[0m"
+        );
+
+        let mut finder = Finder::new();
+        finder.update_search_pattern("err");
+        let rendered = finder.attach_render_scheme("an err").render();
+        assert!(rendered.contains("\x1b[48;5;"));
+        finder.set_color_support(ColorSupport::None);
+        let rendered = finder.attach_render_scheme("an err").render();
+        assert_eq!(rendered, "an \x1b[7merr\x1b[0m");
+    }
+
+    #[test]
+    fn test_palette_of_background() {
+        let colors = |slot_index, background| {
+            let option = HighlightOption::from_slot_index(slot_index, background);
+            (option.foreground_color, option.background_color)
+        };
+        assert_eq!(colors(2, Background::Dark), (Color::Black, Color::Blue));
+        assert_eq!(
+            colors(2, Background::Light),
+            (Color::White, Color::DarkBlue)
+        );
+        assert_eq!(colors(0, Background::Dark), (Color::Yellow, Color::Reset));
+        assert_eq!(
+            colors(0, Background::Light),
+            (Color::DarkYellow, Color::Reset)
+        );
+
+        let mut finder = Finder::new();
+        finder.cycle_highlight_style();
+        let generation = finder.generation();
+        finder.set_background(Background::Light);
+        assert!(finder.generation() > generation);
+        let slot = &finder.slots[array_index_from_slot_index(1)];
+        assert_eq!(slot.highlight_option.background_color, Color::DarkGrey);
+        assert_eq!(slot.highlight_option.style, HighlightStyle::Bold);
+        // nothing to redraw when it stays the same
+        let generation = finder.generation();
+        finder.set_background(Background::Light);
+        assert_eq!(finder.generation(), generation);
+    }
+
+    #[test]
+    fn test_render_cache() {
+        let mut finder = Finder::new();
+        let meter = TestMeter::default();
+        let calls = meter.calls.clone();
+        let match_calls = || calls.get();
+        finder.match_meter = Box::new(meter);
+        finder.update_search_pattern("err");
+        finder.handle_event(FinderAction::SwitchActiveSlot(2));
+        finder.update_search_pattern("warn");
+        let lines: Vec<_> = (0..60).map(|index| format!("line {index} err")).collect();
+        let render_frame = |finder: &mut Finder| {
+            lines
+                .iter()
+                .map(|line| finder.attach_render_scheme(line).render())
+                .collect::<Vec<_>>()
+        };
+
+        let frame = render_frame(&mut finder);
+        let calls = match_calls();
+        assert!(calls > 0);
+        // repeated frame is served from the cache
+        assert_eq!(render_frame(&mut finder), frame);
+        assert_eq!(match_calls(), calls);
+        // opening the menu changes nothing rendered
+        finder.handle_event(FinderAction::MenuOn);
+        render_frame(&mut finder);
+        assert_eq!(match_calls(), calls);
+
+        finder.handle_event(FinderAction::SwitchActiveSlot(1));
+        finder.handle_event(FinderAction::ToggleHighlightFlag);
+        assert_ne!(render_frame(&mut finder), frame);
+        assert!(match_calls() > calls);
+    }
+
+    #[test]
+    fn test_provisional_pattern() {
+        let mut finder = Finder::new();
+        finder.set_color_support(ColorSupport::None);
+        finder.update_search_pattern("err");
+        assert_eq!(
+            finder.attach_render_scheme("an error").render(),
+            "an \x1b[7merr\x1b[0mor"
+        );
+        // shown dimmed over the committed pattern, which is left as is
+        finder.set_provisional_pattern(Some("error"));
+        assert_eq!(
+            finder.attach_render_scheme("an error").render(),
+            "an \x1b[2m\x1b[7merror\x1b[0m"
+        );
+        // which still shows where they don't overlap
+        finder.set_provisional_pattern(Some("n e"));
+        assert_eq!(
+            finder.attach_render_scheme("an error").render(),
+            "a\x1b[2m\x1b[7mn e\x1b[0m\x1b[7mrr\x1b[0mor"
+        );
+        assert_eq!(finder.active_slot_pattern(), Some("err"));
+        // a regex slot takes it as a regex, empty matches are skipped but not what follows them
+        finder.toggle_pattern_type();
+        finder.set_provisional_pattern(Some("e*"));
+        assert_eq!(
+            finder.attach_render_scheme("an error").render(),
+            "an \x1b[2m\x1b[7me\x1b[0m\x1b[7mrr\x1b[0mor"
+        );
+
+        // removed on cancel or enter
+        finder.toggle_pattern_type();
+        finder.set_provisional_pattern(None);
+        assert_eq!(
+            finder.attach_render_scheme("an error").render(),
+            "an \x1b[7merr\x1b[0mor"
+        );
+        finder.set_provisional_pattern(Some(""));
+        assert_eq!(finder.provisional_pattern, None);
+    }
+
+    #[test]
+    fn test_combined_raw_patterns() {
+        let mut finder = Finder::new();
+        for (slot_index, pattern) in [(1, "error"), (2, "err"), (3, "or")] {
+            finder.handle_event(FinderAction::SwitchActiveSlot(slot_index));
+            finder.update_search_pattern(pattern);
+        }
+        let line = "an error or errors";
+        let rendered = finder.attach_render_scheme(line).render();
+        assert!(finder.combined.borrow().highlighted.is_some());
+        assert!(finder.combined.borrow().active.is_none());
+        // each match keeps the color of its slot, the active slot 3 going first
+        let mut expected = LineWithRenderScheme::new(line);
+        let scheme = |slot_index| {
+            finder.slots[array_index_from_slot_index(slot_index)]
+                .highlight_option
+                .render_scheme(ColorSupport::default())
+        };
+        for (range, slot_index) in [(6..8, 3), (9..11, 3), (15..17, 3), (3..6, 2), (12..15, 2)] {
+            expected.add_scheme_if_not_overlap(range, scheme(slot_index));
+        }
+        assert_eq!(rendered, expected.render());
+
+        // several active raw slots are searched for together
+        finder.add_active_slot(2);
+        assert_eq!(
+            finder.active_slot_matches("an error"),
+            [(2, "err"), (3, "or")]
+        );
+        assert!(finder.active_slot_matches("a warning").is_empty());
+        assert!(finder.can_satisfy_active_search_patterns("an error"));
+        assert!(!finder.can_satisfy_active_search_patterns("a warning"));
+        assert!(finder.combined.borrow().active.is_some());
+        assert_eq!(
+            finder.active_search_match("or an error"),
+            LineMatch::MatchAt(0..2)
+        );
+        assert_eq!(
+            finder.active_search_match("an error"),
+            LineMatch::MatchAt(3..6)
+        );
+        // but one at a time once one of them is a regex
+        finder.handle_event(FinderAction::SwitchActiveSlot(2));
+        finder.update_search_pattern("e.r");
+        finder.handle_event(FinderAction::TogglePatternType);
+        finder.add_active_slot(3);
+        assert!(finder.can_satisfy_active_search_patterns("eXr"));
+        assert!(finder.combined.borrow().active.is_none());
+        assert_eq!(
+            finder.active_search_match("an eXr or"),
+            LineMatch::MatchAt(3..6)
+        );
+    }
+
+    #[test]
+    fn test_anchored_highlights() {
+        let mut finder = Finder::new();
+        finder.set_color_support(ColorSupport::None);
+        finder.toggle_pattern_type();
+        finder.update_search_pattern("^ERROR");
+        // only the start of the line matches, not where the previous match ended
+        assert_eq!(
+            finder.attach_render_scheme("ERRORERROR ERROR").render(),
+            "\x1b[7mERROR\x1b[0mERROR ERROR"
+        );
+        assert_eq!(
+            finder.active_search_match_starts("ERRORERROR ERROR"),
+            vec![0]
+        );
+        finder.update_search_pattern("foo$");
+        assert_eq!(
+            finder.attach_render_scheme("foofoo foo").render(),
+            "foofoo \x1b[7mfoo\x1b[0m"
+        );
+        finder.update_search_pattern(r"\bfoo\b");
+        assert_eq!(
+            finder.attach_render_scheme("foo xfoo foo").render(),
+            "\x1b[7mfoo\x1b[0m xfoo \x1b[7mfoo\x1b[0m"
+        );
+        assert_eq!(
+            finder.active_search_match_starts("foo xfoo foo"),
+            vec![0, 9]
+        );
+        // every occurrence of a raw pattern
+        finder.toggle_pattern_type();
+        finder.update_search_pattern("ERROR");
+        assert_eq!(
+            finder.active_search_match_starts("ERRORERROR ERROR"),
+            vec![0, 5, 11]
+        );
+    }
+
+    #[test]
+    fn test_suspend_slow_slot() {
+        let mut finder = Finder::new();
+        finder.update_search_pattern("err");
+        finder.handle_event(FinderAction::SwitchActiveSlot(4));
+        finder.update_search_pattern("(a+)+$");
+        finder.toggle_pattern_type();
+        finder.match_meter = Box::new(TestMeter {
+            slow_slot: Some((4, Duration::from_millis(10))),
+            ..TestMeter::default()
+        });
+        let highlighted = finder.attach_render_scheme("err aa").render();
+        // lines differ between frames, so that none is served from the cache
+        let render_frame = |finder: &mut Finder, frame: usize, line_count: usize| {
+            for index in 0..line_count {
+                finder.attach_render_scheme(&format!("{frame}.{index} err aaa"));
+            }
+            finder.end_frame()
+        };
+
+        // under budget in between, counting starts over
+        assert!(render_frame(&mut finder, 0, 5).is_empty());
+        assert!(render_frame(&mut finder, 1, 5).is_empty());
+        assert!(render_frame(&mut finder, 2, 2).is_empty());
+        assert!(render_frame(&mut finder, 3, 5).is_empty());
+        // a frame from the cache changes nothing
+        assert!(finder.end_frame().is_empty());
+        assert!(render_frame(&mut finder, 4, 5).is_empty());
+        assert_eq!(render_frame(&mut finder, 5, 5), [4]);
+
+        // the pattern is kept but no longer highlighted, unlike the other slots
+        let rendered = finder.attach_render_scheme("err aa").render();
+        assert_ne!(rendered, highlighted);
+        assert!(rendered.ends_with(" aa"));
+        assert_eq!(finder.active_slot_pattern(), Some("(a+)+$"));
+        let mut canvas = Canvas::default();
+        finder.render_menu(&mut canvas, 100, 20);
+        assert!(canvas.popup_menu[4]
+            .raw_content()
+            .contains("too slow, o resumes | (a+)+$"));
+        assert!(render_frame(&mut finder, 6, 5).is_empty());
+
+        // `o` resumes it, leaving the highlight flag as it was
+        finder.handle_event(FinderAction::ToggleHighlightFlag);
+        assert_eq!(
+            finder.slots[array_index_from_slot_index(4)].highlight_flag,
+            HighlightFlag::On
+        );
+        assert_eq!(finder.attach_render_scheme("err aa").render(), highlighted);
+    }
+
+    #[test]
+    fn test_render_cache_eviction() {
+        let mut cache = RenderCache::default();
+        let line = |index: usize| LineWithRenderScheme::new(&index.to_string());
+        for index in 0..RENDER_CACHE_CAPACITY {
+            cache.insert(&line(index));
+        }
+        // keep the oldest one recently used
+        assert!(cache.get(0, "0").is_some());
+        cache.insert(&line(RENDER_CACHE_CAPACITY));
+        assert_eq!(cache.entries.len(), RENDER_CACHE_CAPACITY);
+        assert!(cache.get(0, "0").is_some());
+        assert!(cache.get(0, "1").is_none());
+        // anything cached is dropped for a new generation
+        assert!(cache.get(1, "0").is_none());
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn test_pass_with_context() {
+        let mut finder = Finder::new();
+        let lines = [
+            "a", "b", "match 1", "c", "d", "e", "f", "match 2", "g", "match 3", "h",
+        ];
+        // everything passes without a rule
+        assert!(finder
+            .pass_with_context(&lines, 2)
+            .iter()
+            .all(|passed| *passed));
+
+        finder.update_search_pattern("match");
+        finder.toggle_exclusive_action();
+        let shown = |finder: &Finder, context| -> Vec<&str> {
+            let passed = finder.pass_with_context(&lines, context);
+            lines
+                .iter()
+                .zip(passed)
+                .filter_map(|(line, passed)| passed.then_some(*line))
+                .collect()
+        };
+        assert_eq!(shown(&finder, 0), ["match 1", "match 2", "match 3"]);
+        // two groups, the contexts of the last two matches overlap
+        assert_eq!(
+            shown(&finder, 1),
+            ["b", "match 1", "c", "f", "match 2", "g", "match 3", "h"]
+        );
+        // the groups merge, and are cut at both ends
+        assert_eq!(shown(&finder, 2).len(), 11);
+
+        // folded lines are hidden even within a context
+        finder.set_active_slot(2);
+        finder.update_search_pattern("g");
+        finder.toggle_fold_action();
+        assert_eq!(shown(&finder, 1)[4..], ["match 2", "match 3", "h"]);
+
+        // and so are lines the view filter doesn't match
+        finder.set_view_filter(Some(FilterExpr::parse("NOT 3").unwrap()));
+        assert_eq!(shown(&finder, 1)[4..], ["match 2", "h"]);
+        assert!(!finder.can_pass_advanced_action("match 3"));
+    }
+
+    #[test]
+    fn test_format_match_stats() {
+        assert_eq!(STATS.format(), "3 hits, first @ 10%");
+        let stats = MatchStats {
+            count: 0,
+            first_match_ratio: None,
+        };
+        assert_eq!(stats.format(), "0 hits");
+    }
+}
@@ -0,0 +1,691 @@
+// Test doubles running the manager end to end, without a terminal: documents are read from
+// memory, events come from a script and frames are captured as plain rows.
+
+use std::{
+    cell::RefCell,
+    io::{self, sink, Read, Seek, SeekFrom, Write},
+    rc::Rc,
+};
+
+use loss_viewer::document::Document;
+
+use crate::{
+    canvas::{CapturingCanvas, FrameSink},
+    config::Config,
+    event_source::{Event, EventStream, ScriptedEventSource},
+    input::DocumentSource,
+    manager::Manager,
+    suspend::FakeTerminal,
+    window::Window,
+};
+
+/// Appended to through a shared buffer, like a log file being written.
+pub struct GrowingSource {
+    content: Rc<RefCell<Vec<u8>>>,
+    position: usize,
+}
+
+impl GrowingSource {
+    pub fn new(content: Rc<RefCell<Vec<u8>>>) -> Self {
+        Self {
+            content,
+            position: 0,
+        }
+    }
+}
+
+impl Read for GrowingSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let content = self.content.borrow();
+        let begin = std::cmp::min(self.position, content.len());
+        let n = (&content[begin..]).read(buf)?;
+        self.position = begin + n;
+        std::io::Result::Ok(n)
+    }
+}
+
+impl Seek for GrowingSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset as usize,
+            SeekFrom::End(offset) => self
+                .content
+                .borrow()
+                .len()
+                .saturating_add_signed(offset as isize),
+            SeekFrom::Current(offset) => self.position.saturating_add_signed(offset as isize),
+        };
+        std::io::Result::Ok(self.position as u64)
+    }
+}
+
+impl DocumentSource for GrowingSource {}
+
+// nothing restored from or saved to the session of a real user
+fn scripted_config() -> Config {
+    Config {
+        restore_session: false,
+        scroll_off: None,
+        ..Config::default()
+    }
+}
+
+fn manager_over(
+    source: impl DocumentSource + 'static,
+    width: usize,
+    height: usize,
+    event_source: Box<dyn EventStream>,
+    render_sink: Box<dyn FrameSink>,
+) -> Manager {
+    let config = scripted_config();
+    let source: Box<dyn DocumentSource> = Box::new(source);
+    let document = Document::with_line_delimiter(source, &config.line_delimiter).unwrap();
+    let mut manager = Manager::new(
+        document,
+        "scripted",
+        config,
+        Window::with_terminal_size(width, height),
+        event_source,
+        render_sink,
+    );
+    manager.set_terminal_guard(Box::new(FakeTerminal {
+        size: (width, height),
+        ..FakeTerminal::default()
+    }));
+    manager
+}
+
+/// A manager over `source` with a terminal of `width` x `height`, driven by
+/// [`Manager::run_once`] and drawing nowhere.
+pub fn scripted_manager(
+    source: impl DocumentSource + 'static,
+    width: usize,
+    height: usize,
+) -> Manager {
+    manager_over(
+        source,
+        width,
+        height,
+        Box::new(ScriptedEventSource::default()),
+        Box::new(sink()),
+    )
+}
+
+/// Like [`scripted_manager`], but [`Manager::run`] plays `events` and every frame is captured.
+pub fn capturing_manager(
+    source: impl DocumentSource + 'static,
+    width: usize,
+    height: usize,
+    events: Vec<Event>,
+) -> (Manager, CapturingCanvas) {
+    let canvas = CapturingCanvas::default();
+    let manager = manager_over(
+        source,
+        width,
+        height,
+        Box::new(ScriptedEventSource::new(events)),
+        Box::new(canvas.clone()),
+    );
+    (manager, canvas)
+}
+
+/// The bytes written to the terminal, shared among clones like [`CapturingCanvas`].
+#[derive(Debug, Clone, Default)]
+pub struct TerminalOutput {
+    written: Rc<RefCell<Vec<u8>>>,
+}
+
+impl TerminalOutput {
+    /// What was written since the last call.
+    pub fn take(&self) -> String {
+        String::from_utf8(std::mem::take(&mut *self.written.borrow_mut())).unwrap()
+    }
+}
+
+impl Write for TerminalOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        std::io::Result::Ok(())
+    }
+}
+
+/// Like [`scripted_manager`], but frames are drawn as to a terminal, into the returned output.
+pub fn writing_manager(
+    source: impl DocumentSource + 'static,
+    width: usize,
+    height: usize,
+) -> (Manager, TerminalOutput) {
+    let output = TerminalOutput::default();
+    let manager = manager_over(
+        source,
+        width,
+        height,
+        Box::new(ScriptedEventSource::default()),
+        Box::new(output.clone()),
+    );
+    (manager, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        bookmark::BookmarkMenuAction, event_source::Direction, finder::FinderAction,
+        manager::StartPosition, match_list::MatchListAction, prompt::PromptAction,
+        selection::SelectAction,
+    };
+
+    const EXCEPTIONS_LOG: &str = include_str!("../tests/fixtures/exceptions.log");
+    const RESTART_LOG: &str = include_str!("../tests/fixtures/restart.log");
+
+    fn numbered_lines(count: usize) -> String {
+        (0..count).map(|index| format!("line {index}\n")).collect()
+    }
+
+    // rows of the body, without the `~` filling the rest
+    fn body(frame: &[String]) -> Vec<&str> {
+        frame[..frame.len() - 1]
+            .iter()
+            .map(String::as_str)
+            .filter(|row| *row != "~")
+            .collect()
+    }
+
+    fn status_bar(frame: &[String]) -> &str {
+        frame.last().unwrap()
+    }
+
+    fn search(pattern: &str) -> [Event; 2] {
+        [
+            Event::Search(PromptAction::Start(Some(Direction::Down))),
+            Event::Search(PromptAction::Enter(pattern.to_string())),
+        ]
+    }
+
+    #[test]
+    fn test_search_in_wrapped_lines() {
+        let long_line = format!("{}needle{}\n", "a".repeat(40), "b".repeat(20));
+        let content = numbered_lines(3) + &long_line + "line 4\n";
+        let mut events = vec![Event::ToggleWrapLine];
+        events.extend(search("needle"));
+        events.push(Event::SearchNext);
+        let (mut manager, canvas) = capturing_manager(Cursor::new(content), 30, 10, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+
+        // the prompt takes the status bar while typing
+        assert!(status_bar(&frames[2]).contains("Search: "));
+        let landed = &frames[3];
+        assert_eq!(
+            body(landed),
+            [
+                "a".repeat(30).as_str(),
+                "aaaaaaaaaa[needle]bbbbbbbbbbbbbb",
+                "bbbbbb",
+                "line 4"
+            ]
+        );
+        // nothing further down, the window stays
+        let last = canvas.last_frame();
+        assert_eq!(body(&last), body(landed));
+        assert!(status_bar(&last).contains("Not found"));
+    }
+
+    #[test]
+    fn test_follow_appended_lines() {
+        let content = Rc::new(RefCell::new(numbered_lines(2).into_bytes()));
+        let source = GrowingSource::new(content.clone());
+        let (mut manager, canvas) = capturing_manager(source, 30, 4, vec![]);
+        manager.run_once(Event::Follow).unwrap();
+        assert_eq!(body(&canvas.last_frame()), ["line 0", "line 1"]);
+
+        content.borrow_mut().extend(numbered_lines(6)[14..].bytes());
+        manager.run_idle().unwrap();
+        assert_eq!(body(&canvas.last_frame()), ["line 3", "line 4", "line 5"]);
+        // nothing appended, nothing drawn
+        let frame_count = canvas.frames().len();
+        manager.run_idle().unwrap();
+        assert_eq!(canvas.frames().len(), frame_count);
+    }
+
+    #[test]
+    fn test_bookmark_jump() {
+        let events = vec![
+            Event::JumpByLines(PromptAction::Start(Some(Direction::Down))),
+            Event::JumpByLines(PromptAction::Enter("40".to_string())),
+            Event::NewBookmark(PromptAction::Start(None)),
+            Event::NewBookmark(PromptAction::Enter("here".to_string())),
+            Event::SeekToHome,
+            Event::GotoBookmark(BookmarkMenuAction::Start),
+            Event::GotoBookmark(BookmarkMenuAction::Enter),
+        ];
+        let (mut manager, canvas) =
+            capturing_manager(Cursor::new(numbered_lines(100)), 30, 21, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+
+        assert_eq!(frames[2][0], "line 40");
+        assert_eq!(frames[5][0], "line 0");
+        // the menu covers the bottom of the body
+        let menu = &frames[6];
+        assert!(menu.iter().any(|row| row.contains("Bookmark")));
+        assert!(menu
+            .iter()
+            .any(|row| row.starts_with(" > ") && row.contains("here")));
+        let last = canvas.last_frame();
+        assert_eq!(last[0], "line 40");
+        assert!(status_bar(&last).contains("Jumped to bookmark: here"));
+    }
+
+    #[test]
+    fn test_finder_fold() {
+        let content = "DEBUG x\nINFO y\nDEBUG z\nWARN w\n";
+        let mut events = vec![Event::FinderOperation(FinderAction::SwitchActiveSlot(2))];
+        events.extend(search("DEBUG"));
+        events.push(Event::FinderOperation(FinderAction::ToggleFoldAction));
+        events.push(Event::FinderOperation(FinderAction::ToggleFoldAction));
+        let (mut manager, canvas) = capturing_manager(Cursor::new(content), 30, 10, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+
+        assert_eq!(
+            body(&frames[3]),
+            ["[DEBUG] x", "INFO y", "[DEBUG] z", "WARN w"]
+        );
+        // the slot searched in, downwards
+        assert!(status_bar(&frames[3]).contains("/*[2]"));
+        // folded lines are hidden until the fold is toggled off
+        assert_eq!(body(&frames[4]), ["INFO y", "WARN w"]);
+        assert_eq!(body(&frames[5]), body(&frames[3]));
+    }
+
+    #[test]
+    fn test_remove_last_active_slot() {
+        let events = [
+            FinderAction::RemoveActiveSlotStart,
+            // e.g. replayed by a macro
+            FinderAction::RemoveActiveSlot(1),
+            FinderAction::AddActiveSlotStart,
+            FinderAction::AddActiveSlot(3),
+            FinderAction::RemoveActiveSlotStart,
+            FinderAction::RemoveActiveSlot(1),
+        ]
+        .into_iter()
+        .map(Event::FinderOperation)
+        .collect();
+        let (mut manager, canvas) = capturing_manager(Cursor::new("a\nb\n"), 80, 4, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+        assert_eq!(status_bar(&frames[1]), "Cannot remove the last active slot");
+        assert_eq!(status_bar(&frames[2]), "Cannot remove the last active slot");
+        // the slots already active are listed while a digit is awaited
+        assert!(status_bar(&frames[3]).contains("Adding Finder active slot to 1 ..."));
+        assert!(status_bar(&frames[5]).contains("Removing Finder active slot from 1 3 ..."));
+        assert!(status_bar(&frames[6]).contains(" {1}  {2} *{3} "));
+    }
+
+    #[test]
+    fn test_jump_to_repeated_timestamp() {
+        let jump = |content: &str| Event::JumpToTimestamp(PromptAction::Enter(content.to_string()));
+        let events = vec![
+            jump("10:01:40"),
+            jump("> 10:01:40"),
+            jump("10:00"),
+            jump("<10:00"),
+            jump(">10:00"),
+            jump(">10:00"),
+        ];
+        let (mut manager, canvas) = capturing_manager(Cursor::new(RESTART_LOG), 80, 4, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+        let top = |index: usize| body(&frames[index])[0].to_string();
+
+        assert!(top(1).ends_with("[run 1] step 8"));
+        assert!(top(2).ends_with("[run 2] step 8"));
+        // nearest to the second run, then forced into the first one and back
+        assert!(top(3).ends_with("[run 2] step 3"));
+        assert!(top(4).ends_with("[run 1] step 3"));
+        assert!(top(5).ends_with("[run 2] step 3"));
+        assert!(top(6).ends_with("[run 2] step 3"));
+        assert_eq!(status_bar(&frames[6]), "No 10:00 below the top line");
+    }
+
+    #[test]
+    fn test_dump_frame() {
+        let path = std::env::temp_dir().join(format!("loss-frame-{}.json", std::process::id()));
+        let mut events = search("line 1").to_vec();
+        events.push(Event::Command(PromptAction::Enter(format!(
+            "dump-frame {}",
+            path.display()
+        ))));
+        let (mut manager, canvas) =
+            capturing_manager(Cursor::new(numbered_lines(20)), 30, 5, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+        // the path is cut in the middle to fit, its end is kept
+        assert!(status_bar(&frames[3]).starts_with("Frame dumped"));
+        assert!(status_bar(&frames[3]).ends_with(".json"));
+
+        // the frame on screen as the command was entered
+        let file = std::fs::File::open(&path).unwrap();
+        let dump = crate::canvas::FrameDump::read_json(file).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((dump.width, dump.height), (30, 4));
+        let rows: Vec<String> = dump
+            .canvas
+            .body_area
+            .iter()
+            .map(|line| line.marked().trim_end().to_string())
+            .collect();
+        assert_eq!(rows, frames[2][..4]);
+        assert_eq!(rows[0], "[line 1]");
+    }
+
+    #[test]
+    fn test_suspend_and_resume() {
+        let events = vec![
+            Event::WindowMove(Direction::Down, 2),
+            Event::Suspend,
+            Event::Suspend,
+        ];
+        let (mut manager, canvas) =
+            capturing_manager(Cursor::new(numbered_lines(20)), 30, 5, events);
+        let terminal = FakeTerminal {
+            size: (30, 8),
+            ..FakeTerminal::default()
+        };
+        manager.set_terminal_guard(Box::new(terminal.clone()));
+        manager.run().unwrap();
+        let frames = canvas.frames();
+        assert_eq!(
+            *terminal.calls.borrow(),
+            ["release", "stop", "reacquire"].repeat(2)
+        );
+        // drawn whole at the size resumed at, from where it was left
+        assert_eq!(frames[2].len(), 8);
+        assert_eq!(body(&frames[2])[0], "line 2");
+
+        let terminal = FakeTerminal {
+            fail_to_stop: true,
+            size: (30, 5),
+            ..FakeTerminal::default()
+        };
+        let (mut manager, canvas) =
+            capturing_manager(Cursor::new("a\n"), 30, 5, vec![Event::Suspend]);
+        manager.set_terminal_guard(Box::new(terminal));
+        manager.run().unwrap();
+        assert_eq!(status_bar(&canvas.last_frame()), "Cannot stop");
+    }
+
+    #[test]
+    fn test_resize_during_menu() {
+        let events = vec![
+            Event::ToggleHelperMenu,
+            Event::TerminalResize(80, 8),
+            Event::TerminalResize(80, 40),
+            Event::ToggleHelperMenu,
+        ];
+        let (mut manager, canvas) =
+            capturing_manager(Cursor::new(numbered_lines(100)), 80, 40, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+        let is_title = |row: &String| row.contains(" Helper Menu ");
+
+        assert_eq!(frames[0].len(), 40);
+        // shown over the body, then only its title once the window is too small for it
+        assert!(frames[1][..39].iter().any(is_title));
+        assert_eq!(frames[2].len(), 8);
+        assert!(is_title(&frames[2][7]));
+        assert_eq!(frames[2][0], "line 0");
+        assert!(frames[3][..39].iter().any(is_title));
+        assert!(!canvas.last_frame().iter().any(is_title));
+    }
+
+    #[test]
+    fn test_match_list() {
+        let path =
+            std::env::temp_dir().join(format!("loss-matches-test-{}.txt", std::process::id()));
+        let mut events = search("ERROR").to_vec();
+        events.extend([
+            Event::MatchList(MatchListAction::Start),
+            Event::MatchList(MatchListAction::Arrow(Direction::Down)),
+            Event::MatchList(MatchListAction::Enter),
+            Event::Command(PromptAction::Start(None)),
+            Event::Command(PromptAction::Enter(format!("matches {}", path.display()))),
+        ]);
+        let (mut manager, canvas) = capturing_manager(Cursor::new(EXCEPTIONS_LOG), 80, 21, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+
+        let menu = &frames[3];
+        assert!(menu
+            .iter()
+            .any(|row| row.contains(" Matches of Slot 1 (2) ")));
+        assert!(
+            menu.iter()
+                .any(|row| row.starts_with(" >         46  ")
+                    && row.ends_with("ERROR request failed"))
+        );
+        assert!(frames[4]
+            .iter()
+            .any(|row| row.starts_with(" >        273  ")));
+        // the second match is brought to the top
+        assert_eq!(frames[5][0], "2024-05-01 10:00:03.500 [ERROR] retry failed");
+        assert!(status_bar(&frames[5]).contains("Jumped to match 2 of 2"));
+
+        assert!(status_bar(&canvas.last_frame()).contains("Written 2 matches to"));
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            written,
+            "46\t2024-05-01 10:00:01.120 ERROR request failed\n\
+             273\t2024-05-01 10:00:03.500 ERROR retry failed\n"
+        );
+    }
+
+    #[test]
+    fn test_match_list_stale() {
+        let content = Rc::new(RefCell::new(b"ok\nfail 1\n".to_vec()));
+        let (mut manager, canvas) =
+            capturing_manager(GrowingSource::new(content.clone()), 80, 21, vec![]);
+        let run = |manager: &mut Manager, events: Vec<Event>| {
+            for event in events {
+                manager.run_once(event).unwrap();
+            }
+        };
+        run(&mut manager, search("fail").to_vec());
+        run(
+            &mut manager,
+            vec![
+                Event::MatchList(MatchListAction::Start),
+                Event::MatchList(MatchListAction::Cancel),
+                Event::Follow,
+            ],
+        );
+
+        // collected again once the file grows
+        content.borrow_mut().extend(b"fail 2\n");
+        manager.run_idle().unwrap();
+        run(&mut manager, vec![Event::MatchList(MatchListAction::Start)]);
+        assert!(canvas
+            .last_frame()
+            .iter()
+            .any(|row| row.contains(" Matches of Slot 1 (2) ")));
+        run(
+            &mut manager,
+            vec![
+                Event::MatchList(MatchListAction::Arrow(Direction::Up)),
+                Event::MatchList(MatchListAction::Enter),
+            ],
+        );
+        assert!(status_bar(&canvas.last_frame()).contains("Jumped to match 2 of 2"));
+    }
+
+    #[test]
+    fn test_select_lines() {
+        let path =
+            std::env::temp_dir().join(format!("loss-selection-test-{}.txt", std::process::id()));
+        let mut events = vec![Event::Select(SelectAction::Start)];
+        events.extend((0..6).map(|_| Event::Select(SelectAction::Extend(Direction::Down))));
+        events.extend([
+            Event::Select(SelectAction::Extend(Direction::Up)),
+            Event::WriteSelection(PromptAction::Start(None)),
+            Event::WriteSelection(PromptAction::Enter(path.display().to_string())),
+        ]);
+        let content = numbered_lines(20);
+        let (mut manager, canvas) = capturing_manager(Cursor::new(content), 40, 6, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+        let selected = |text: &str| format!("<{text:<40}>");
+
+        assert_eq!(body(&frames[1])[0], selected("line 0"));
+        assert_eq!(body(&frames[1])[1], "line 1");
+        assert!(status_bar(&frames[1]).contains("Selected 1 lines"));
+        // the window scrolls just enough to keep the last selected line shown
+        let scrolled = body(&frames[7]);
+        assert_eq!(scrolled[0], selected("line 2"));
+        assert_eq!(scrolled[4], selected("line 6"));
+        assert!(status_bar(&frames[7]).contains("Selected 7 lines"));
+        assert!(status_bar(&frames[8]).contains("Selected 6 lines"));
+        assert_eq!(body(&frames[8])[4], "line 6");
+
+        assert!(status_bar(&canvas.last_frame()).contains("Written 6 lines to"));
+        assert_eq!(body(&canvas.last_frame())[0], "line 2");
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, numbered_lines(6));
+    }
+
+    #[test]
+    fn test_stats_popup() {
+        let mut events = vec![Event::ToggleStats, Event::ToggleStats];
+        events.extend(search("ERROR"));
+        events.extend([Event::SearchNext, Event::SearchNext, Event::ToggleStats]);
+        let (mut manager, canvas) = capturing_manager(Cursor::new(EXCEPTIONS_LOG), 80, 21, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+        let stat = |frame: &[String], label: &str| {
+            let row = frame.iter().find(|row| row.trim_start().starts_with(label));
+            row.unwrap().trim_start()[label.len()..].trim().to_string()
+        };
+
+        let opened = &frames[1];
+        assert!(opened.iter().any(|row| row.contains(" Statistics ")));
+        assert_eq!(stat(opened, "Searches"), "0 (0 not found)");
+        assert_eq!(stat(opened, "Lines scanned"), "0");
+        assert_eq!(stat(opened, "Frames drawn"), "1");
+        assert_eq!(stat(opened, "Window top"), "line 1, offset 0");
+        assert_eq!(stat(opened, "Chunk loads"), "1");
+
+        // the third search runs past the last match
+        let reopened = canvas.last_frame();
+        assert_eq!(stat(&reopened, "Searches"), "3 (1 not found)");
+        assert_ne!(stat(&reopened, "Lines scanned"), "0");
+        assert_eq!(stat(&reopened, "Frames drawn"), "7");
+        assert_eq!(stat(&reopened, "Timestamp format"), "%Y-%m-%d %H:%M:%S%.f");
+    }
+
+    #[test]
+    fn test_filter_command() {
+        let content = "level=INFO svc=auth msg=ok\n\
+                       level=ERROR svc=auth msg=denied\n\
+                       level=ERROR svc=billing msg=\"read timeout\"\n\
+                       level=ERROR svc=search msg=denied\n";
+        let command = |text: &str| Event::Command(PromptAction::Enter(text.to_string()));
+        let events = vec![
+            command(r#"filter level=ERROR AND (svc=auth OR svc=billing) AND NOT msg~timeout"#),
+            Event::EditFilter,
+            Event::Command(PromptAction::Cancel),
+            command("filter level=ERROR AND (svc=auth"),
+            command("filter"),
+        ];
+        let (mut manager, canvas) = capturing_manager(Cursor::new(content), 60, 8, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+
+        assert_eq!(body(&frames[1]), ["level=ERROR svc=auth msg=denied"]);
+        assert!(status_bar(&frames[1]).contains(" &level=ERROR AND (svc=a… "));
+        // the expression is longer than the width, its end where the cursor is stays shown
+        assert_eq!(
+            status_bar(&frames[2]).trim_end(),
+            "(TOP) (END) :filter level=ERROR AND…ng) AND NOT msg~timeout"
+        );
+        // an invalid expression leaves the installed one
+        assert_eq!(
+            status_bar(&frames[4]),
+            "Invalid filter: expected `)` at column 26"
+        );
+        assert_eq!(body(&frames[4]), body(&frames[1]));
+        assert_eq!(body(&frames[5]).len(), 4);
+        assert!(!status_bar(&frames[5]).contains('&'));
+    }
+
+    #[test]
+    fn test_step_line_matches() {
+        let content = "start\nx ERR ERR ERR ERR ERR\nok\nERR last\n";
+        let mut events = vec![Event::Command(PromptAction::Enter(
+            "set linematches".to_string(),
+        ))];
+        events.extend(search("ERR"));
+        events.extend(vec![Event::SearchNext; 5]);
+        events.extend(vec![Event::SearchPrevious; 2]);
+        let (mut manager, canvas) = capturing_manager(Cursor::new(content), 40, 6, events);
+        manager.run().unwrap();
+        let frames = canvas.frames();
+
+        let statuses: Vec<_> = frames[3..]
+            .iter()
+            .map(|frame| status_bar(frame).split("  ").next().unwrap())
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                "Match 1 of 5 in line, column 3",
+                "Match 2 of 5 in line, column 7",
+                "Match 3 of 5 in line, column 11",
+                "Match 4 of 5 in line, column 15",
+                "Match 5 of 5 in line, column 19",
+                // a line with a single match isn't reported
+                "(END)",
+                // entered from below at its last match
+                "Match 5 of 5 in line, column 19",
+                "Match 4 of 5 in line, column 15",
+            ]
+        );
+        // the window stays while stepping within the line
+        assert_eq!(body(&frames[7])[0], "x [ERR] [ERR] [ERR] [ERR] [ERR]");
+        assert_eq!(body(&frames[8])[0], "[ERR] last");
+    }
+
+    #[test]
+    fn test_loading_frame() {
+        let canvas = CapturingCanvas::default();
+        let sink = canvas.clone();
+        let open_document = |config: &Config| {
+            // nothing is read until the loading frame is drawn
+            assert_eq!(sink.frames().len(), 1);
+            let source: Box<dyn DocumentSource> = Box::new(Cursor::new(numbered_lines(20)));
+            Document::with_line_delimiter(source, &config.line_delimiter)
+        };
+        let mut manager = Manager::load(
+            open_document,
+            "app.log",
+            scripted_config(),
+            Window::with_terminal_size(30, 6),
+            Box::new(ScriptedEventSource::new(vec![])),
+            Box::new(canvas.clone()),
+            StartPosition::LastLines(2),
+        )
+        .unwrap();
+        manager.run().unwrap();
+        let frames = canvas.frames();
+
+        assert_eq!(frames[0], ["", "", "       Loading app.log…", "", "", ""]);
+        assert_eq!(body(&frames[1]), ["line 18", "line 19"]);
+    }
+}
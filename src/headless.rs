@@ -0,0 +1,191 @@
+use std::io::{Read, Seek, Write};
+
+use anyhow::{Ok, Result};
+use loss_viewer::document::Document;
+
+use crate::finder::Finder;
+
+// lines queried from the document at a time
+const HEADLESS_BATCH_LINE_COUNT: usize = 1024;
+
+// writes lines passing the fold and exclusive rules of `finder` to `out`, like `cat` if there's
+// no rule. the output ends with a line delimiter only if the document does, even if its last
+// line is filtered out. with `color`, lines carry the highlights of `finder` as ANSI colors, for
+// `cat` or `less -R`
+pub fn stream_filtered<R: Read + Seek, W: Write>(
+    document: &mut Document<R>,
+    finder: &mut Finder,
+    line_delimiter: &str,
+    color: bool,
+    out: &mut W,
+) -> Result<()> {
+    let last_line_start_offset = document.last_line_start_offset();
+    let final_delimiter = document.document_size() > 0 && !document.lacks_final_line_delimiter();
+    // written ahead of the next line passing, so none trails the last one written
+    let mut delimiter_pending = false;
+    let mut written = false;
+    let mut offset = 0;
+    loop {
+        for line in document.query_lines(offset, HEADLESS_BATCH_LINE_COUNT)? {
+            if finder.can_pass_advanced_action(&line.text) {
+                if delimiter_pending {
+                    out.write_all(line_delimiter.as_bytes())?;
+                }
+                if color {
+                    let rendered = finder.attach_render_scheme(&line.text).render();
+                    out.write_all(rendered.as_bytes())?;
+                } else {
+                    out.write_all(line.text.as_bytes())?;
+                }
+                delimiter_pending = true;
+                written = true;
+            }
+            if line.offset == last_line_start_offset {
+                if written && final_delimiter {
+                    out.write_all(line_delimiter.as_bytes())?;
+                }
+                return Ok(out.flush()?);
+            }
+            offset = line.offset + line.text.len() + line_delimiter.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::profile::{FilterProfile, FilterRule};
+
+    use super::*;
+
+    fn fixture_path(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn stream(document: &mut Document<impl Read + Seek>, finder: &mut Finder) -> String {
+        let mut out = vec![];
+        stream_filtered(document, finder, "\n", false, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn rule(exclusive: bool, pattern: &str) -> FilterRule {
+        FilterRule {
+            exclusive,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_stream_without_rules() {
+        let path = fixture_path("access.log");
+        let mut document = Document::<std::fs::File>::open_file(&path, "\n").unwrap();
+        assert_eq!(
+            stream(&mut document, &mut Finder::new()),
+            std::fs::read_to_string(&path).unwrap()
+        );
+
+        for content in ["", "\n", "a", "a\nb", "a\nb\n", "a\n\n"] {
+            let mut document = Document::new(Cursor::new(content)).unwrap();
+            assert_eq!(stream(&mut document, &mut Finder::new()), content);
+        }
+    }
+
+    #[test]
+    fn test_stream_final_line_delimiter() {
+        let mut finder = Finder::new();
+        let profile = FilterProfile {
+            rules: vec![rule(true, "a")],
+        };
+        finder.apply_filter_profile(&profile).unwrap();
+        // kept as it is whether the last line passes or not
+        for (content, expected) in [
+            ("a1\nb\na2", "a1\na2"),
+            ("a1\nb\na2\n", "a1\na2\n"),
+            ("a1\nb", "a1"),
+            ("a1\nb\n", "a1\n"),
+            ("b\nb", ""),
+            ("b\nb\n", ""),
+        ] {
+            let mut document = Document::new(Cursor::new(content)).unwrap();
+            assert_eq!(stream(&mut document, &mut finder), expected, "{content:?}");
+        }
+    }
+
+    #[test]
+    fn test_stream_with_profile() {
+        let path = fixture_path("access.log");
+        let mut document = Document::<std::fs::File>::open_file(&path, "\n").unwrap();
+        let mut finder = Finder::new();
+        let profile = FilterProfile {
+            rules: vec![rule(false, "/health"), rule(true, " 500 ")],
+        };
+        finder.apply_filter_profile(&profile).unwrap();
+        assert_eq!(
+            stream(&mut document, &mut finder),
+            "10.0.0.2 - - [2024-05-01 10:00:02] \"GET /api/orders HTTP/1.1\" 500 12\n\
+             10.0.0.4 - - [2024-05-01 10:00:05] \"POST /api/orders HTTP/1.1\" 500 48\n"
+        );
+    }
+
+    #[test]
+    fn test_stream_across_batches() {
+        let content = (0..HEADLESS_BATCH_LINE_COUNT * 3)
+            .map(|index| format!("line {index}\n"))
+            .collect::<String>();
+        let mut document = Document::new(Cursor::new(content.as_str())).unwrap();
+        let mut finder = Finder::new();
+        let profile = FilterProfile {
+            rules: vec![rule(true, "7")],
+        };
+        finder.apply_filter_profile(&profile).unwrap();
+        let expected = content
+            .lines()
+            .filter(|line| line.contains('7'))
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        assert_eq!(stream(&mut document, &mut finder), expected);
+    }
+
+    // drops the `ESC [ ... m` sequences setting colors
+    fn strip_ansi(colored: &str) -> String {
+        let mut stripped = String::default();
+        let mut chars = colored.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                stripped.push(c);
+            }
+        }
+        stripped
+    }
+
+    #[test]
+    fn test_stream_with_color() {
+        let path = fixture_path("access.log");
+        let mut finder = Finder::new();
+        let profile = FilterProfile {
+            rules: vec![rule(false, "/health"), rule(true, " 500 ")],
+        };
+        finder.apply_filter_profile(&profile).unwrap();
+        let mut export = |color: bool| {
+            let mut document = Document::<std::fs::File>::open_file(&path, "\n").unwrap();
+            let mut out = vec![];
+            stream_filtered(&mut document, &mut finder, "\n", color, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let plain = export(false);
+        let colored = export(true);
+        assert_eq!(
+            plain,
+            "10.0.0.2 - - [2024-05-01 10:00:02] \"GET /api/orders HTTP/1.1\" 500 12\n\
+             10.0.0.4 - - [2024-05-01 10:00:05] \"POST /api/orders HTTP/1.1\" 500 48\n"
+        );
+        // the same lines and delimiters with the highlights around the matches
+        assert_eq!(strip_ansi(&colored), plain);
+        for line in colored.lines() {
+            assert!(line.contains("\x1b[") && !line.starts_with('\x1b'));
+        }
+    }
+}
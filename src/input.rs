@@ -0,0 +1,114 @@
+use std::{
+    fs::{self, File},
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use anyhow::{Ok, Result};
+use loss_viewer::{document::Document, spool::Spool};
+
+/// What the pager reads a document from. Appended data is picked up by seeking to the end, so a
+/// source grows by reporting a larger size there.
+pub trait DocumentSource: Read + Seek {
+    /// Whether data streams in rather than being there from the start.
+    fn is_streamed(&self) -> bool {
+        false
+    }
+
+    // streamed and not ended yet
+    fn is_streaming(&self) -> bool {
+        false
+    }
+}
+
+/// A document over any source, as the pager holds it.
+pub type SourceDocument = Document<Box<dyn DocumentSource>>;
+
+/// A file opened by path, a fifo is spooled into memory as it can't be seeked.
+#[derive(Debug)]
+pub enum Input {
+    File(File),
+    Spool(Spool),
+}
+
+impl Input {
+    pub fn open(filename: &str) -> Result<Self> {
+        let file = File::open(filename)?;
+        if is_fifo(&fs::metadata(filename)?) {
+            Ok(Self::Spool(Spool::new(file)))
+        } else {
+            Ok(Self::File(file))
+        }
+    }
+
+    // blocks until a streamed input ends
+    pub fn wait_until_complete(&self) {
+        if let Self::Spool(spool) = self {
+            spool.wait_until_finished();
+        }
+    }
+
+    // over the last `tail_bytes` only if given, see `--tail-bytes`
+    pub fn open_document(
+        self,
+        line_delimiter: &str,
+        tail_bytes: Option<usize>,
+    ) -> Result<Document<Self>> {
+        match tail_bytes {
+            Some(tail_bytes) => Document::with_tail(self, line_delimiter, tail_bytes),
+            None => Document::with_line_delimiter(self, line_delimiter),
+        }
+    }
+
+    pub fn open_source_document(
+        self,
+        line_delimiter: &str,
+        tail_bytes: Option<usize>,
+    ) -> Result<SourceDocument> {
+        let source: Box<dyn DocumentSource> = Box::new(self);
+        match tail_bytes {
+            Some(tail_bytes) => Document::with_tail(source, line_delimiter, tail_bytes),
+            None => Document::with_line_delimiter(source, line_delimiter),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_fifo(metadata: &fs::Metadata) -> bool {
+    std::os::unix::fs::FileTypeExt::is_fifo(&metadata.file_type())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+impl DocumentSource for Input {
+    fn is_streamed(&self) -> bool {
+        matches!(self, Self::Spool(_))
+    }
+
+    fn is_streaming(&self) -> bool {
+        matches!(self, Self::Spool(spool) if !spool.is_finished())
+    }
+}
+
+// content already in memory
+impl<T: AsRef<[u8]>> DocumentSource for Cursor<T> {}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Spool(spool) => spool.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Spool(spool) => spool.seek(pos),
+        }
+    }
+}
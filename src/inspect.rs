@@ -0,0 +1,296 @@
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use anyhow::{Ok, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use loss_viewer::document::{required_chunk_size, Document};
+
+/// Where a line sits in the document, `line` counting from 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+}
+
+/// Diagnostics of a file, gathered without the pager by `loss --inspect`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Inspection {
+    pub size: usize,
+    pub line_count: usize,
+    // in bytes, without the line delimiter
+    pub longest_line_len: usize,
+    pub longest_line: Option<Location>,
+    // the first invalid byte
+    pub invalid_utf8: Option<Location>,
+    pub required_chunk_size: usize,
+    // left empty if the document can't be opened with the default chunk size
+    pub timestamp_format: Option<String>,
+    pub default_date: Option<NaiveDate>,
+    pub first_timestamp: Option<NaiveDateTime>,
+    pub last_timestamp: Option<NaiveDateTime>,
+}
+
+impl Inspection {
+    // whether the document can be shown as is, which timestamps are looked up through
+    pub fn is_readable(&self) -> bool {
+        self.invalid_utf8.is_none() && self.required_chunk_size == required_chunk_size(0)
+    }
+
+    pub fn write_text<W: Write>(&self, out: &mut W) -> Result<()> {
+        let or_none = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+        writeln!(out, "size: {}", self.size)?;
+        writeln!(out, "lines: {}", self.line_count)?;
+        writeln!(
+            out,
+            "longest_line: {}",
+            or_none(self.longest_line.map(|location| format!(
+                "{} bytes at line {} (offset {})",
+                self.longest_line_len, location.line, location.offset
+            )))
+        )?;
+        writeln!(
+            out,
+            "invalid_utf8: {}",
+            or_none(
+                self.invalid_utf8
+                    .map(|location| format!("line {} (offset {})", location.line, location.offset))
+            )
+        )?;
+        writeln!(out, "required_chunk_size: {}", self.required_chunk_size)?;
+        writeln!(
+            out,
+            "timestamp_format: {}",
+            or_none(self.timestamp_format.clone())
+        )?;
+        writeln!(
+            out,
+            "default_date: {}",
+            or_none(self.default_date.map(|date| date.to_string()))
+        )?;
+        writeln!(
+            out,
+            "first_timestamp: {}",
+            or_none(self.first_timestamp.map(|datetime| datetime.to_string()))
+        )?;
+        writeln!(
+            out,
+            "last_timestamp: {}",
+            or_none(self.last_timestamp.map(|datetime| datetime.to_string()))
+        )?;
+        Ok(())
+    }
+
+    // a single json object on one line, absent values are null
+    pub fn write_json<W: Write>(&self, out: &mut W) -> Result<()> {
+        let location = |location: Option<Location>| match location {
+            Some(location) => format!(
+                "{{\"line\":{},\"offset\":{}}}",
+                location.line, location.offset
+            ),
+            None => "null".to_string(),
+        };
+        let string = |value: Option<String>| match value {
+            Some(value) => json_string(&value),
+            None => "null".to_string(),
+        };
+        let fields = [
+            ("size", self.size.to_string()),
+            ("lines", self.line_count.to_string()),
+            ("longest_line_len", self.longest_line_len.to_string()),
+            ("longest_line", location(self.longest_line)),
+            ("invalid_utf8", location(self.invalid_utf8)),
+            ("required_chunk_size", self.required_chunk_size.to_string()),
+            ("timestamp_format", string(self.timestamp_format.clone())),
+            (
+                "default_date",
+                string(self.default_date.map(|date| date.to_string())),
+            ),
+            (
+                "first_timestamp",
+                string(self.first_timestamp.map(|datetime| datetime.to_string())),
+            ),
+            (
+                "last_timestamp",
+                string(self.last_timestamp.map(|datetime| datetime.to_string())),
+            ),
+        ];
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|(key, value)| format!("\"{key}\":{value}"))
+            .collect();
+        writeln!(out, "{{{}}}", fields.join(","))?;
+        Ok(())
+    }
+}
+
+/// Scans every line of `reader`, then looks up timestamps through a [`Document`] over it if
+/// it can be read like the pager does.
+pub fn inspect<R: Read + Seek>(reader: R, line_delimiter: &str) -> Result<Inspection> {
+    let mut inspection = Inspection::default();
+    let delimiter = line_delimiter.as_bytes();
+    let mut reader = BufReader::new(reader);
+    let mut line = vec![];
+    loop {
+        let len = reader.read_until(*delimiter.last().unwrap(), &mut line)?;
+        let complete = line.ends_with(delimiter);
+        // the last byte of a longer delimiter may also show up alone
+        if len > 0 && !complete {
+            continue;
+        }
+        if line.is_empty() {
+            break;
+        }
+        let text = if complete {
+            &line[..line.len() - delimiter.len()]
+        } else {
+            &line[..]
+        };
+        inspection.line_count += 1;
+        let location = Location {
+            offset: inspection.size,
+            line: inspection.line_count,
+        };
+        if inspection.longest_line.is_none() || text.len() > inspection.longest_line_len {
+            inspection.longest_line_len = text.len();
+            inspection.longest_line = Some(location);
+        }
+        if inspection.invalid_utf8.is_none() {
+            if let Err(e) = std::str::from_utf8(text) {
+                inspection.invalid_utf8 = Some(Location {
+                    offset: location.offset + e.valid_up_to(),
+                    ..location
+                });
+            }
+        }
+        inspection.size += line.len();
+        if !complete {
+            break;
+        }
+        line.clear();
+    }
+    inspection.required_chunk_size =
+        required_chunk_size(inspection.longest_line_len + delimiter.len());
+
+    // the document would fail on invalid utf-8 or a line split across chunks
+    if inspection.is_readable() {
+        let mut reader = reader.into_inner();
+        reader.seek(SeekFrom::Start(0))?;
+        let mut document = Document::with_line_delimiter(reader, line_delimiter)?;
+        if let Some((first, last)) = document.query_first_and_last_timestamps()? {
+            inspection.first_timestamp = Some(first);
+            inspection.last_timestamp = Some(last);
+        }
+        inspection.timestamp_format = document.detect_log_timestamp_format(0)?;
+        inspection.default_date = document.log_default_date();
+    }
+    Ok(inspection)
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Cursor};
+
+    use super::*;
+
+    fn fixture_path(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn inspect_str(content: &str, line_delimiter: &str) -> Inspection {
+        inspect(Cursor::new(content), line_delimiter).unwrap()
+    }
+
+    #[test]
+    fn test_inspect_lines() {
+        let inspection = inspect_str("", "\n");
+        assert_eq!(inspection.line_count, 0);
+        assert_eq!(inspection.longest_line, None);
+
+        let inspection = inspect_str("ab\nabcd\n\nabc", "\n");
+        assert_eq!(inspection.size, 12);
+        assert_eq!(inspection.line_count, 4);
+        assert_eq!(inspection.longest_line_len, 4);
+        assert_eq!(
+            inspection.longest_line,
+            Some(Location { offset: 3, line: 2 })
+        );
+        assert_eq!(inspection.required_chunk_size, required_chunk_size(0));
+        assert!(inspection.is_readable());
+
+        // a lone `\r` doesn't end a line
+        let inspection = inspect_str("a\rb\r\nc\r\n", "\r\n");
+        assert_eq!(inspection.line_count, 2);
+        assert_eq!(inspection.longest_line_len, 3);
+
+        let long_line = "x".repeat(required_chunk_size(0));
+        let inspection = inspect_str(&format!("a\n{long_line}\n"), "\n");
+        assert_eq!(inspection.required_chunk_size, required_chunk_size(0) * 4);
+        assert!(!inspection.is_readable());
+    }
+
+    #[test]
+    fn test_inspect_log() {
+        let path = fixture_path("exceptions.log");
+        let inspection = inspect(File::open(&path).unwrap(), "\n").unwrap();
+        assert_eq!(inspection.size, 537);
+        assert_eq!(inspection.line_count, 14);
+        assert_eq!(inspection.invalid_utf8, None);
+        assert_eq!(
+            inspection.timestamp_format.as_deref(),
+            Some("%Y-%m-%d %H:%M:%S%.f")
+        );
+        assert_eq!(inspection.default_date, NaiveDate::from_ymd_opt(2024, 5, 1));
+        let date = inspection.default_date.unwrap();
+        assert_eq!(inspection.first_timestamp, date.and_hms_opt(10, 0, 0));
+        assert_eq!(inspection.last_timestamp, date.and_hms_opt(10, 0, 4));
+
+        let mut out = vec![];
+        inspection.write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.starts_with("{\"size\":537,\"lines\":14,"));
+        assert!(json.contains("\"invalid_utf8\":null,"));
+        assert!(json.ends_with("\"last_timestamp\":\"2024-05-01 10:00:04\"}\n"));
+    }
+
+    #[test]
+    fn test_inspect_binary() {
+        let path = fixture_path("binary.log");
+        let inspection = inspect(File::open(&path).unwrap(), "\n").unwrap();
+        assert_eq!(inspection.line_count, 3);
+        assert_eq!(
+            inspection.invalid_utf8,
+            Some(Location {
+                offset: 48,
+                line: 2
+            })
+        );
+        // timestamps aren't looked up in a file the pager can't read
+        assert!(!inspection.is_readable());
+        assert_eq!(inspection.timestamp_format, None);
+
+        let mut out = vec![];
+        inspection.write_text(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("invalid_utf8: line 2 (offset 48)\n"));
+        assert!(text.ends_with("last_timestamp: none\n"));
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("%Y-%m-%d"), "\"%Y-%m-%d\"");
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+}
@@ -0,0 +1,19 @@
+//! Chunked, lazily loaded view over a (possibly huge or growing) log document, together with
+//! the line-oriented querying, searching and timestamp helpers that the `loss` pager is built on.
+//!
+//! ```no_run
+//! use loss_viewer::document::Document;
+//!
+//! let mut document = Document::<std::fs::File>::open_file("app.log", "\n")?;
+//! let lines = document.query_lines(0, 10)?;
+//! let distance = document.query_distance_to_next_match(0, |line| line.contains("ERROR"))?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod chunk;
+pub mod direction;
+pub mod document;
+pub mod filter_expr;
+pub mod log_timestamp;
+pub mod pattern;
+pub mod spool;
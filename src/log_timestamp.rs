@@ -1,8 +1,15 @@
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
 
+/// Returns the `chrono` format of the timestamp which `line` starts with, if recognized.
 pub fn detect_log_timstamp_format(line: &str) -> Option<String> {
     // ordering should be strict -> loose
-    const LOG_TIMESTAMP_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.9f", "[%Y-%m-%d %H:%M:%S]"];
+    const LOG_TIMESTAMP_FORMATS: &[&str] = &[
+        "%Y-%m-%dT%H:%M:%S%.f%#z",
+        "%Y-%m-%d %H:%M:%S%.f%#z",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "[%Y-%m-%d %H:%M:%S]",
+    ];
     for &fmt in LOG_TIMESTAMP_FORMATS {
         if NaiveDateTime::parse_and_remainder(line, fmt).is_ok() {
             return Some(fmt.to_string());
@@ -11,14 +18,44 @@ pub fn detect_log_timstamp_format(line: &str) -> Option<String> {
     None
 }
 
-pub fn parse_log_timestamp(content: &str) -> (Option<NaiveDate>, Option<NaiveTime>) {
+/// Parses timezone like `Z`, `UTC`, `+02:00` or `-0530` into its offset from UTC.
+pub fn parse_timezone(content: &str) -> Option<FixedOffset> {
+    match content.to_ascii_uppercase().as_str() {
+        "Z" | "UTC" | "GMT" => FixedOffset::east_opt(0),
+        _ if content.starts_with(['+', '-']) => content.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses user input like `2024-01-02 8:12:50.123`, `8:12` or `21` into optional date and time,
+/// optionally followed by a timezone like `Z`, `UTC` or `+02:00`.
+pub fn parse_log_timestamp(
+    content: &str,
+) -> (Option<NaiveDate>, Option<NaiveTime>, Option<FixedOffset>) {
     let (date, time_str) =
         if let Ok((date, remain)) = NaiveDate::parse_and_remainder(content, "%Y-%m-%d") {
-            (Some(date), remain)
+            (Some(date), remain.strip_prefix('T').unwrap_or(remain))
         } else {
             (None, content)
         };
 
+    // the timezone starts with a sign or is the trailing word
+    let time_str = time_str.trim_end();
+    let split_at = time_str.find(['+', '-']).unwrap_or(
+        time_str
+            .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+            .len(),
+    );
+    let (time_str, timezone_str) = time_str.split_at(split_at);
+    let timezone = match timezone_str.trim() {
+        "" => None,
+        timezone_str => match parse_timezone(timezone_str) {
+            Some(timezone) => Some(timezone),
+            None => return (date, None, None),
+        },
+    };
+    let time_str = time_str.trim_end();
+
     // NaiveTime cannot parse from a single %H
     let time_str = if time_str.contains(':') {
         time_str.to_string()
@@ -27,7 +64,7 @@ pub fn parse_log_timestamp(content: &str) -> (Option<NaiveDate>, Option<NaiveTim
     };
 
     // ordering should be strict -> loose
-    const TIME_FORMATS: &[&str] = &["%H:%M:%S%.9f", "%H:%M"];
+    const TIME_FORMATS: &[&str] = &["%H:%M:%S%.f", "%H:%M"];
     let mut time = None;
     for &fmt in TIME_FORMATS {
         if let Ok(parsed_time) = NaiveTime::parse_from_str(&time_str, fmt) {
@@ -35,7 +72,7 @@ pub fn parse_log_timestamp(content: &str) -> (Option<NaiveDate>, Option<NaiveTim
             break;
         }
     }
-    (date, time)
+    (date, time, timezone)
 }
 
 #[cfg(test)]
@@ -47,16 +84,28 @@ mod tests {
     fn test_detect_log_timstamp_format() {
         assert_eq!(
             detect_log_timstamp_format("2024-01-01 12:00:00.123 [Info] hello"),
-            Some("%Y-%m-%d %H:%M:%S%.9f".to_string())
+            Some("%Y-%m-%d %H:%M:%S%.f".to_string())
         );
         assert_eq!(
             detect_log_timstamp_format("2024-01-01 12:00:00 [Info] hello"),
-            Some("%Y-%m-%d %H:%M:%S%.9f".to_string())
+            Some("%Y-%m-%d %H:%M:%S%.f".to_string())
         );
         assert_eq!(
             detect_log_timstamp_format("20240101 12:00:00 [Info] hello"),
             None
         );
+        assert_eq!(
+            detect_log_timstamp_format("2024-01-01T12:00:00.123+02:00 [Info] hello"),
+            Some("%Y-%m-%dT%H:%M:%S%.f%#z".to_string())
+        );
+        assert_eq!(
+            detect_log_timstamp_format("2024-01-01 12:00:00Z [Info] hello"),
+            Some("%Y-%m-%d %H:%M:%S%.f%#z".to_string())
+        );
+        assert_eq!(
+            detect_log_timstamp_format("2024-01-01T12:00:00 [Info] hello"),
+            Some("%Y-%m-%dT%H:%M:%S%.f".to_string())
+        );
         assert_eq!(
             detect_log_timstamp_format("[2024-01-01 12:00:00] [Info] hello"),
             Some("[%Y-%m-%d %H:%M:%S]".to_string())
@@ -65,49 +114,71 @@ mod tests {
 
     #[test]
     fn test_parse_log_timestamp() {
-        assert_eq!(
-            parse_log_timestamp("2024-01-02 8:12:50.1234567"),
+        let date = NaiveDate::from_ymd_opt(2024, 1, 2);
+        let time = |hour, min, sec, nano| NaiveTime::from_hms_nano_opt(hour, min, sec, nano);
+        let cases = [
             (
-                NaiveDate::from_ymd_opt(2024, 1, 2),
-                NaiveTime::from_hms_nano_opt(8, 12, 50, 123456700)
-            )
-        );
-        assert_eq!(
-            parse_log_timestamp("2024-01-02 8:12:20"),
-            (
-                NaiveDate::from_ymd_opt(2024, 1, 2),
-                NaiveTime::from_hms_nano_opt(8, 12, 20, 0)
-            )
-        );
-        assert_eq!(
-            parse_log_timestamp("2024-01-02 8:12"),
+                "2024-01-02 8:12:50.1234567",
+                date,
+                time(8, 12, 50, 123456700),
+            ),
+            ("2024-01-02 8:12:20", date, time(8, 12, 20, 0)),
+            ("2024-01-02 8:12", date, time(8, 12, 0, 0)),
+            ("2024-01-02 21", date, time(21, 0, 0, 0)),
+            ("8:12:50.1234567", None, time(8, 12, 50, 123456700)),
+            ("8:12:20", None, time(8, 12, 20, 0)),
+            ("8:12", None, time(8, 12, 0, 0)),
+            ("21", None, time(21, 0, 0, 0)),
+        ];
+        for (content, date, time) in cases {
+            assert_eq!(
+                parse_log_timestamp(content),
+                (date, time, None),
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_log_timestamp_with_timezone() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 2);
+        let time = |hour, min, sec, milli| NaiveTime::from_hms_milli_opt(hour, min, sec, milli);
+        let utc = FixedOffset::east_opt(0);
+        let cases = [
+            ("14:00 UTC", None, time(14, 0, 0, 0), utc),
+            ("14:00Z", None, time(14, 0, 0, 0), utc),
+            ("14 utc", None, time(14, 0, 0, 0), utc),
             (
-                NaiveDate::from_ymd_opt(2024, 1, 2),
-                NaiveTime::from_hms_nano_opt(8, 12, 0, 0)
-            )
-        );
-        assert_eq!(
-            parse_log_timestamp("2024-01-02 21"),
+                "2024-01-02T8:12:50.5+02:00",
+                date,
+                time(8, 12, 50, 500),
+                FixedOffset::east_opt(2 * 3600),
+            ),
             (
-                NaiveDate::from_ymd_opt(2024, 1, 2),
-                NaiveTime::from_hms_nano_opt(21, 0, 0, 0)
-            )
-        );
-        assert_eq!(
-            parse_log_timestamp("8:12:50.1234567"),
-            (None, NaiveTime::from_hms_nano_opt(8, 12, 50, 123456700))
-        );
-        assert_eq!(
-            parse_log_timestamp("8:12:20"),
-            (None, NaiveTime::from_hms_nano_opt(8, 12, 20, 0))
-        );
-        assert_eq!(
-            parse_log_timestamp("8:12"),
-            (None, NaiveTime::from_hms_nano_opt(8, 12, 0, 0))
-        );
-        assert_eq!(
-            parse_log_timestamp("21"),
-            (None, NaiveTime::from_hms_nano_opt(21, 0, 0, 0))
-        );
+                "2024-01-02 8:12 -0530",
+                date,
+                time(8, 12, 0, 0),
+                FixedOffset::west_opt(5 * 3600 + 30 * 60),
+            ),
+        ];
+        for (content, date, time, timezone) in cases {
+            assert_eq!(
+                parse_log_timestamp(content),
+                (date, time, timezone),
+                "{content}"
+            );
+        }
+        assert_eq!(parse_log_timestamp("14:00 Mars").1, None);
+        assert_eq!(parse_log_timestamp("14:00 +25:00").1, None);
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("Z"), FixedOffset::east_opt(0));
+        assert_eq!(parse_timezone("GMT"), FixedOffset::east_opt(0));
+        assert_eq!(parse_timezone("+08:00"), FixedOffset::east_opt(8 * 3600));
+        assert_eq!(parse_timezone("-0100"), FixedOffset::west_opt(3600));
+        assert_eq!(parse_timezone("local"), None);
+        assert_eq!(parse_timezone("08:00"), None);
     }
 }
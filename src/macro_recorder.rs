@@ -0,0 +1,195 @@
+use anyhow::Result;
+
+use crate::{event_source::Event, prompt::PromptAction};
+
+// guards against runaway replays, e.g. a macro searching for something that never moves on
+pub const MACRO_MAX_LEN: usize = 1000;
+
+/// Records the stream of events between two presses of the record key, for replaying later.
+#[derive(Debug, Default)]
+pub struct MacroRecorder {
+    recording: Option<Vec<Event>>,
+    recorded: Vec<Event>,
+}
+
+/// What a replayed event led to.
+#[derive(Debug, PartialEq)]
+pub enum ReplayStep {
+    Continue,
+    Exit,
+    // the event failed, e.g. a search found nothing
+    Abort(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayOutcome {
+    Finished(usize),
+    Exit,
+    // 1-based step which failed
+    Aborted(usize, String),
+}
+
+impl MacroRecorder {
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // starts recording, or stops it and keeps what's recorded
+    pub fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(events) => self.recorded = events,
+            None => self.recording = Some(vec![]),
+        }
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        if let Some(events) = self.recording.as_mut() {
+            if is_recordable(event) && events.len() < MACRO_MAX_LEN {
+                events.push(event.clone());
+            }
+        }
+    }
+
+    pub fn recorded(&self) -> &[Event] {
+        &self.recorded
+    }
+}
+
+// prompts are recorded by their start and final content only, and events which would re-enter
+// recording or leave the current view are skipped
+fn is_recordable(event: &Event) -> bool {
+    !matches!(
+        event,
+        Event::Exit
+            | Event::ToggleMacroRecording
+            | Event::ReplayMacro
+            | Event::TerminalResize(..)
+            | Event::ToggleHelperMenu
+            | Event::ToggleStats
+            | Event::Dismiss
+            | Event::Suspend
+            | Event::EditFilter
+            | Event::Follow
+            | Event::Search(PromptAction::Content(_))
+            | Event::JumpToTimestamp(PromptAction::Content(_))
+            | Event::JumpByLines(PromptAction::Content(_))
+            | Event::NewBookmark(PromptAction::Content(_))
+            | Event::Command(PromptAction::Content(_))
+            | Event::WriteSelection(PromptAction::Content(_))
+    )
+}
+
+// an error returned by `dispatch` aborts the replay as well
+pub fn replay<F>(events: Vec<Event>, mut dispatch: F) -> ReplayOutcome
+where
+    F: FnMut(Event) -> Result<ReplayStep>,
+{
+    let event_count = std::cmp::min(events.len(), MACRO_MAX_LEN);
+    for (index, event) in events.into_iter().take(event_count).enumerate() {
+        match dispatch(event) {
+            Ok(ReplayStep::Continue) => {}
+            Ok(ReplayStep::Exit) => return ReplayOutcome::Exit,
+            Ok(ReplayStep::Abort(reason)) => return ReplayOutcome::Aborted(index + 1, reason),
+            Err(e) => return ReplayOutcome::Aborted(index + 1, e.to_string()),
+        }
+    }
+    ReplayOutcome::Finished(event_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use anyhow::Ok;
+    use loss_viewer::document::Document;
+
+    use super::*;
+    use crate::event_source::Direction;
+
+    fn record_search_and_move() -> MacroRecorder {
+        let mut recorder = MacroRecorder::default();
+        recorder.record(&Event::WindowMove(Direction::Down, 1));
+        recorder.toggle_recording();
+        for event in [
+            Event::Search(PromptAction::Start(Some(Direction::Down))),
+            Event::Search(PromptAction::Content("e".to_string())),
+            Event::Search(PromptAction::Content("er".to_string())),
+            Event::Search(PromptAction::Enter("er".to_string())),
+            Event::TerminalResize(80, 24),
+            Event::WindowMove(Direction::Down, 1),
+            Event::ToggleMacroRecording,
+        ] {
+            recorder.record(&event);
+        }
+        recorder.toggle_recording();
+        recorder
+    }
+
+    #[test]
+    fn test_record_through_prompt() {
+        let recorder = record_search_and_move();
+        assert!(!recorder.is_recording());
+        assert_eq!(
+            recorder.recorded(),
+            [
+                Event::Search(PromptAction::Start(Some(Direction::Down))),
+                Event::Search(PromptAction::Enter("er".to_string())),
+                Event::WindowMove(Direction::Down, 1),
+            ]
+        );
+
+        let mut recorder = MacroRecorder::default();
+        recorder.toggle_recording();
+        for _ in 0..MACRO_MAX_LEN + 1 {
+            recorder.record(&Event::SearchNext);
+        }
+        recorder.toggle_recording();
+        assert_eq!(recorder.recorded().len(), MACRO_MAX_LEN);
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut document =
+            Document::new(Cursor::new("info\nerror 1\ninfo\ninfo\nerror 2\ninfo\n")).unwrap();
+        let recorded = record_search_and_move().recorded().to_vec();
+        let mut offset = 0;
+        let mut dispatch = |event: Event| {
+            match event {
+                Event::Search(PromptAction::Enter(pattern)) => {
+                    let predict = |line: &str| line.contains(&pattern);
+                    match document.query_distance_to_next_match(offset, predict)? {
+                        Some(distance) => offset += distance,
+                        None => return Ok(ReplayStep::Abort("Not found".to_string())),
+                    }
+                }
+                Event::WindowMove(Direction::Down, step) => {
+                    offset += document.query_distance_to_below_n_lines(offset, step)?
+                }
+                _ => {}
+            }
+            Ok(ReplayStep::Continue)
+        };
+        assert_eq!(
+            replay(recorded.clone(), &mut dispatch),
+            ReplayOutcome::Finished(3)
+        );
+        assert_eq!(
+            replay(recorded.clone(), &mut dispatch),
+            ReplayOutcome::Finished(3)
+        );
+        assert_eq!(
+            replay(recorded.clone(), &mut dispatch),
+            ReplayOutcome::Aborted(2, "Not found".to_string())
+        );
+        assert_eq!(offset, 31);
+
+        let endless = vec![Event::SearchNext; MACRO_MAX_LEN * 2];
+        let mut step_count = 0;
+        let outcome = replay(endless, |_| {
+            step_count += 1;
+            Ok(ReplayStep::Continue)
+        });
+        assert_eq!(outcome, ReplayOutcome::Finished(MACRO_MAX_LEN));
+        assert_eq!(step_count, MACRO_MAX_LEN);
+    }
+}
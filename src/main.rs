@@ -1,20 +1,55 @@
-use anyhow::{Ok, Result};
+use anyhow::{anyhow, Ok, Result};
+use canvas::FrameDump;
+use cli::{check_input_file, Command};
+use config::Config;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use finder::Finder;
+use input::Input;
+use log::LevelFilter;
 use manager::Manager;
-use std::env;
+use profile::FilterProfile;
+use std::{
+    env,
+    fs::{self, File},
+    io::{stdout, BufReader, IsTerminal},
+    path::Path,
+    process,
+};
 
 mod bookmark;
 mod canvas;
-mod chunk;
-mod document;
+mod cli;
+mod clipboard;
+mod color;
+mod command;
+mod config;
+mod continuation;
+#[cfg(unix)]
+mod control;
 mod event_source;
+mod file_age;
 mod finder;
+#[cfg(test)]
+mod harness;
+mod headless;
 mod helper;
-mod log_timestamp;
+mod input;
+mod inspect;
+mod macro_recorder;
 mod manager;
+mod match_list;
+mod minimap;
+mod notifier;
+mod popup_menu;
+mod profile;
 mod prompt;
 mod render;
+mod selection;
+mod session;
+mod stats;
 mod status_bar;
+mod suspend;
+mod timing;
 mod window;
 
 fn print_version() {
@@ -22,14 +57,43 @@ fn print_version() {
     println!("loss {VERSION}");
 }
 
-fn print_usage() {
-    println!("loss - A modern terminal pager and log viewer");
-    println!("usage: loss <filename>");
-}
+const USAGE: &str = "\
+loss - A modern terminal pager and log viewer
+usage: loss [options] <filename>
+
+options:
+  -h, --help      print this help
+  -v, --version   print version
+  --no-session    don't restore or save the position of last visit
+  -d, --delimiter <lf|crlf|rs|\\xHH|char>
+                  line delimiter, defaults to lf
+  -n, --lines <count>
+                  start at the last lines instead of the position of last visit, not with +F
+                  or +<line>
+  +F              start in follow mode, not with -n or +<line>
+  +<line>         start at a line, as does a `:<line>` suffix of the filename, not with -n
+  --tail-bytes <size>
+                  read only the last bytes of the file, e.g. 50M, as if it started there
+  --log[=<path>]  write a debug log, to the cache directory if no path is given
+  --control <path>
+                  accept `:` commands on a unix socket, one per line
+  --raw-output    print the file instead of paging, implied if stdout isn't a tty
+  --color         keep highlights as ANSI colors in raw output
+  --filter-profile <name>
+                  apply fold and exclusive rules saved in a profile
+  --inspect       print diagnostics of the file instead of paging
+  --json          print diagnostics as json, with --inspect
+  --render-frame <path>
+                  print a frame dumped by `:dump-frame` instead of paging
+";
+
+// exit code for arguments that don't make sense, as with most command line tools
+const USAGE_ERROR_EXIT_CODE: i32 = 2;
 
-fn init_logger() {
-    let logfile = "loss.log";
-    // let logfile = Utc::now().format("%Y%m%d-%H%M%S").to_string() + "-loss.log";
+fn init_logger(path: &Path, level: LevelFilter) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
     fern::Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
@@ -39,32 +103,108 @@ fn init_logger() {
                 message
             ))
         })
-        .level(log::LevelFilter::Info)
-        .chain(fern::log_file(logfile).unwrap())
-        .apply()
-        .unwrap();
+        .level(level)
+        .chain(fern::log_file(path)?)
+        .apply()?;
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    if let std::result::Result::Ok(enabled) = std::env::var("LOSS_LOG_ENABLED") {
-        if enabled == "1" {
-            init_logger();
-        }
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("loss: {e:#}");
+        process::exit(1);
     }
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        print_usage();
-    } else if args[1] == "-v" {
-        print_version();
-    } else {
-        let filename = args[1].as_str();
-        enable_raw_mode().unwrap();
+}
+
+fn usage_error() -> ! {
+    eprint!("{USAGE}");
+    process::exit(USAGE_ERROR_EXIT_CODE);
+}
 
-        // todo: catch error and make sure raw mode is disabled when exit
-        let mut manager = Manager::new(filename)?;
-        manager.run()?;
+fn run() -> Result<()> {
+    match cli::parse_args(env::args().skip(1)) {
+        Command::PrintHelp => print!("{USAGE}"),
+        Command::PrintVersion => print_version(),
+        Command::UsageError => usage_error(),
+        Command::Inspect(options) => {
+            check_input_file(Path::new(&options.filename))
+                .map_err(|e| anyhow!("{}: {e}", options.filename))?;
+            let line_delimiter = match options.line_delimiter {
+                Some(line_delimiter) => line_delimiter,
+                None => Config::load()?.line_delimiter,
+            };
+            let input = Input::open(&options.filename)?;
+            input.wait_until_complete();
+            let inspection = inspect::inspect(input, &line_delimiter)?;
+            let mut out = stdout().lock();
+            if options.json {
+                inspection.write_json(&mut out)?;
+            } else {
+                inspection.write_text(&mut out)?;
+            }
+        }
+        Command::RenderFrame(path) => {
+            let file = File::open(&path).map_err(|e| anyhow!("{path}: {e}"))?;
+            let dump =
+                FrameDump::read_json(BufReader::new(file)).map_err(|e| anyhow!("{path}: {e}"))?;
+            dump.write_rows(&mut stdout().lock())?;
+        }
+        Command::View(mut options) => {
+            options.resolve_line_suffix();
+            // the suffix is a line to start at as much as `+N`
+            if options.has_conflicting_start() {
+                usage_error();
+            }
+            // fail before touching the terminal
+            check_input_file(Path::new(&options.filename))
+                .map_err(|e| anyhow!("{}: {e}", options.filename))?;
+            let file_config = Config::load()?;
+            let mut config = file_config.clone();
+            options.apply_to(&mut config);
+            if let Some(path) = config.log_file.resolve() {
+                // logging is for debugging only, not worth refusing to start
+                if let Err(e) = init_logger(&path, config.log_level) {
+                    eprintln!("loss: cannot log to {}: {e}", path.display());
+                }
+            }
+            let profile = options
+                .filter_profile
+                .as_deref()
+                .map(FilterProfile::load)
+                .transpose()?;
+            if options.raw_output || !stdout().is_terminal() {
+                let input = Input::open(&options.filename)?;
+                // everything is printed at once, so wait for a stream to end
+                input.wait_until_complete();
+                let mut document =
+                    input.open_document(&config.line_delimiter, config.tail_bytes)?;
+                let mut finder = Finder::new();
+                if let Some(profile) = profile.as_ref() {
+                    finder.apply_filter_profile(profile)?;
+                }
+                return headless::stream_filtered(
+                    &mut document,
+                    &mut finder,
+                    &config.line_delimiter,
+                    options.color,
+                    &mut stdout().lock(),
+                );
+            }
 
-        disable_raw_mode().unwrap();
+            let mut manager = Manager::open(&options.filename, config, options.start_position())?;
+            manager.watch_config_file(file_config);
+            if let (Some(name), Some(profile)) = (options.filter_profile.as_ref(), profile.as_ref())
+            {
+                manager.apply_filter_profile(name, profile)?;
+            }
+            if let Some(path) = options.control_path.as_ref() {
+                manager.listen_on_control_channel(path)?;
+            }
+            enable_raw_mode()?;
+            let result = manager.run();
+            disable_raw_mode()?;
+            result?;
+        }
     }
     Ok(())
 }
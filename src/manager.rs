@@ -1,442 +1,3911 @@
-use std::fs::File;
+#[cfg(unix)]
+use std::path::Path;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{stdout, BufWriter, Read, Seek, Write},
+    ops::{Range, RangeInclusive},
+    time::{Duration, Instant, SystemTime},
+};
+
+#[cfg(unix)]
+use crate::control::ControlChannel;
 
 use crate::{
     bookmark::{BookmarkMenuAction, BookmarkStore, BOOKMARK_NAME_MAX_LEN},
-    canvas::{clear_screen_and_reset_cursor, Canvas},
-    document::Document,
-    event_source::{Direction, Event, EventSource},
-    finder::{Finder, FinderAction},
+    canvas::{Canvas, FrameDump, FrameSink},
+    clipboard::Clipboard,
+    color::{query_background, Background, ColorSupport},
+    command::{help_text, parse_command, Command, Setting},
+    config::{config_file_path, BoundaryFeedback, Config, ConfigChanges, FileStamp, ScrollOff},
+    continuation::ContinuationFolder,
+    event_source::{Direction, Event, EventSource, EventStream, PendingInput},
+    file_age::{format_modified, stale_warning},
+    finder::{Finder, FinderAction, LastSearch, MatchStats},
+    headless::stream_filtered,
     helper::HelperMenu,
-    log_timestamp::parse_log_timestamp,
+    input::{Input, SourceDocument},
+    macro_recorder::{replay, MacroRecorder, ReplayOutcome, ReplayStep},
+    match_list::{CollectKey, MatchList, MatchListAction},
+    minimap::{thumb_rows, Minimap},
+    notifier::{Notification, Notifier, NotifyEvent},
+    profile::FilterProfile,
     prompt::PromptAction,
-    render::LineWithRenderScheme,
-    status_bar::StatusBar,
+    render::{
+        width_with_invisibles, wrapped_row_count, wrapped_row_start, LineWithRenderScheme,
+        RenderScheme,
+    },
+    selection::{SelectAction, Selection},
+    session::{SessionState, SessionStore},
+    stats::{format_bytes, SessionStats, StatsPopup},
+    status_bar::{Boundary, StatusBar},
+    suspend::{suspend, Terminal, TerminalGuard},
+    timing::{FrameTiming, Phase},
     window::Window,
 };
 use anyhow::{Ok, Result};
-use log::info;
+use chrono::NaiveDateTime;
+use log::{debug, info, log_enabled, warn, Level};
+use loss_viewer::{
+    document::{Document, Line, PositionRatio, TimestampMatch},
+    filter_expr::FilterExpr,
+    log_timestamp::parse_log_timestamp,
+    pattern::LineMatch,
+};
 
 #[derive(Debug, Default)]
 struct Context {
-    raw_lines_buffer: Vec<String>,
+    raw_lines_buffer: Vec<Line>,
+    // continuation lines collapsed under each parent in `raw_lines_buffer`
+    hidden_continuations: HashMap<usize, usize>,
+    // lines in `raw_lines_buffer` after hidden ones, each starting a group of context lines
+    group_starts: HashSet<usize>,
     searching_direction: Option<Direction>,
+    // direction of the last executed search, which `n` and `N` are relative to
+    last_search_direction: Option<Direction>,
+    // slot the last search prompt was entered into, marked in the status bar
+    last_search_slot: Option<usize>,
     jumping_direction: Option<Direction>,
+    // a count is shown in the status bar until a window move takes it
+    counting: bool,
+    // visible lines selected with `v`, kept while its path is prompted for
+    selection: Option<Selection>,
+    // keys of an unfinished sequence and since when they're waiting for the next one
+    pending_input: Option<(PendingInput, Instant)>,
     wrap_lines: bool,
+    // wrapping is turned off for a horizontal move, until the next vertical one
+    wrap_paused: bool,
+    // show at most `wrap_row_limit` rows per line when wrapping
+    fold_wrapped_lines: bool,
+    wrap_row_limit: usize,
+    // raw content only, for copying with terminal selection
+    plain_mode: bool,
+    plain_mode_keeps_filters: bool,
+    // tabs, trailing whitespace and carriage returns drawn as glyphs
+    show_invisibles: bool,
+    // lines shown around each exclusive match
+    context_lines: usize,
+    // lines kept above the target of a search or jump, `None` when turned off
+    scroll_off: Option<ScrollOff>,
+    configured_scroll_off: Option<ScrollOff>,
+    boundary_feedback: BoundaryFeedback,
+    // window offset and the target offset it was revealed for
+    revealed: Option<(usize, usize)>,
+    // offset of the line the last search landed on, underlined until it's scrolled off or
+    // another search starts
+    current_match: Option<usize>,
+    // column the last search landed on in a single-line document, where every match is on
+    // the same line
+    single_line_match: Option<usize>,
+    // `n` and `N` step through the matches of the line the last search landed on, which is
+    // at `line_match_index` among them
+    step_line_matches: bool,
+    line_match_index: usize,
+    // an offset whose line number is known, lines are counted from it for the status bar
+    line_number_anchor: Option<(usize, usize)>,
     need_rerender: bool,
+    // whether the body may differ from the one on screen, e.g. after a move or a change to the
+    // filter or finder slots. otherwise the rows drawn last are kept and only the status bar
+    // is drawn again
+    body_dirty: bool,
     load_lines_multiple: usize,
+    // offset restored from last visit, if any
+    restored_offset: Option<usize>,
+    // bytes above the document start cut off by `:trim`, on top of those skipped by
+    // `--tail-bytes`
+    trimmed: usize,
+    last_vertical_direction: Option<Direction>,
+    macro_recorder: MacroRecorder,
+}
+
+impl Context {
+    fn last_search(&self) -> Option<LastSearch> {
+        Some(LastSearch {
+            slot_index: self.last_search_slot?,
+            direction: self.last_search_direction?,
+        })
+    }
+
+    // returns whether wrapping got paused
+    fn pause_wrap(&mut self) -> bool {
+        if !self.wrap_lines {
+            return false;
+        }
+        self.wrap_lines = false;
+        self.wrap_paused = true;
+        true
+    }
+
+    // returns whether wrapping got resumed
+    fn resume_wrap(&mut self) -> bool {
+        if !self.wrap_paused {
+            return false;
+        }
+        self.wrap_lines = true;
+        self.wrap_paused = false;
+        true
+    }
+
+    fn on_search_started(&mut self) {
+        self.current_match = None;
+        self.single_line_match = None;
+        self.line_match_index = 0;
+    }
+
+    fn on_search_landed(&mut self, offset: usize) {
+        self.current_match = Some(offset);
+    }
+
+    fn on_body_laid_out(&mut self, current_match_shown: bool) {
+        if !current_match_shown {
+            self.current_match = None;
+        }
+    }
+}
+
+// what `Esc` in the plain view dismisses, one at a time from the topmost
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transient {
+    StatusMessage,
+    CurrentMatch,
+}
+
+/// Whether to go on after an event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunOutcome {
+    Continue,
+    Exit,
 }
 
 #[derive(Debug, PartialEq)]
 enum Mode {
     Normal,
     Follow,
+    // following until a line appended matches the active search
+    FollowUntilMatch,
+}
+
+// how often the control channel and streamed input are checked while waiting for terminal events
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// how often the config file and the active profile are checked for changes
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// an unfinished key sequence is cancelled after this long without the next key
+const PENDING_INPUT_TIMEOUT: Duration = Duration::from_secs(3);
+// how long the terminal is given to tell its background color, slow over ssh but usually
+// answered at once
+const BACKGROUND_QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+// columns kept on the left of a match that a search scrolls horizontally to
+const SEARCH_COLUMN_MARGIN: usize = 8;
+// render layer of selected rows, above highlights and the pattern being typed
+const SELECTION_LAYER: u8 = 2;
+// bytes of a long line highlighted on both sides of what's shown of it, for matches reaching
+// into view. the rest of the line is left out
+const HIGHLIGHT_MARGIN: usize = 1024;
+// context lines around exclusive matches cycled through with `K`, 0 turns them off
+const CONTEXT_LINES_CYCLE: [usize; 3] = [1, 2, 5];
+
+// the same file opened by different paths shares its session
+fn session_key_of(filename: &str) -> String {
+    fs::canonicalize(filename)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or(filename.to_string())
+}
+
+/// Where the window starts when a file is opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StartPosition {
+    // restored from the session if enabled, otherwise the top
+    #[default]
+    LastVisit,
+    // the window top is at the nth line counted back from the end
+    LastLines(usize),
+    // 1-based line revealed as by `:N`
+    Line(usize),
+    Follow,
 }
 
 pub struct Manager {
-    document: Document<File>,
+    document: SourceDocument,
     window: Window,
     status_bar: StatusBar,
-    event_source: EventSource,
+    event_source: Box<dyn EventStream>,
     bookmark_store: BookmarkStore,
+    match_list: MatchList,
     finder: Finder,
     helper_menu: HelperMenu,
+    stats_popup: StatsPopup,
+    session_stats: SessionStats,
+    timing: FrameTiming,
     context: Context,
     canvas: Canvas,
     mode: Mode,
+    session_store: Option<SessionStore>,
+    session_key: String,
+    config: Config,
+    // as last read from the config file, without command line overrides
+    file_config: Config,
+    config_stamp: Option<FileStamp>,
+    active_profile: Option<(String, FileStamp)>,
+    last_file_check: Instant,
+    continuation_folder: ContinuationFolder,
+    minimap: Minimap,
+    notifier: Notifier,
+    clipboard: Clipboard,
+    // as answered by the terminal, used unless the config sets a background
+    detected_background: Option<Background>,
+    // of the file shown, unknown for a stream
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    control_channel: Option<ControlChannel>,
+    // where frames are drawn, stdout when running in the terminal
+    render_sink: Box<dyn FrameSink>,
+    terminal: Box<dyn TerminalGuard>,
 }
 
 impl Manager {
-    pub fn new(filename: &str) -> Result<Manager> {
+    /// Replaces the terminal handed back to the shell by `Ctrl-Z`, as a scripted run must not
+    /// stop the tests.
+    #[cfg(test)]
+    pub fn set_terminal_guard(&mut self, terminal: Box<dyn TerminalGuard>) {
+        self.terminal = terminal;
+    }
+
+    /// Opens `filename` for the terminal, the window starting at `start_position`.
+    pub fn open(filename: &str, config: Config, start_position: StartPosition) -> Result<Manager> {
+        let mut manager = Manager::load(
+            |config| {
+                Input::open(filename)?
+                    .open_source_document(&config.line_delimiter, config.tail_bytes)
+            },
+            filename,
+            config,
+            Window::new()?,
+            Box::new(EventSource::default()),
+            Box::new(stdout()),
+            start_position,
+        )?;
+        manager.detect_background();
+        Ok(manager)
+    }
+
+    /// Like [`Manager::new`], with the document opened by `open_document` as configured and the
+    /// window moved to `start_position` while a loading frame is shown, as reading the first
+    /// chunks may take seconds on a slow file system. The screen is cleared again if either
+    /// fails.
+    pub fn load<F>(
+        open_document: F,
+        filename: &str,
+        config: Config,
+        window: Window,
+        event_source: Box<dyn EventStream>,
+        mut render_sink: Box<dyn FrameSink>,
+        start_position: StartPosition,
+    ) -> Result<Manager>
+    where
+        F: FnOnce(&Config) -> Result<SourceDocument>,
+    {
+        render_sink.draw(&Canvas::loading(
+            filename,
+            window.width,
+            window.height,
+            window.is_single_line(),
+        ))?;
+        let document = match open_document(&config) {
+            std::result::Result::Ok(document) => document,
+            Err(e) => {
+                render_sink.clear()?;
+                return Err(e);
+            }
+        };
+        let mut manager = Manager::new(
+            document,
+            filename,
+            config,
+            window,
+            event_source,
+            render_sink,
+        );
+        if let Err(e) = manager.start_at(start_position) {
+            manager.render_sink.clear()?;
+            return Err(e);
+        }
+        Ok(manager)
+    }
+
+    /// A manager over an opened document, drawing each frame to `render_sink`. Events are
+    /// either read from `event_source` by [`Manager::run`] or fed through [`Manager::run_once`].
+    pub fn new(
+        document: SourceDocument,
+        filename: &str,
+        config: Config,
+        window: Window,
+        event_source: Box<dyn EventStream>,
+        render_sink: Box<dyn FrameSink>,
+    ) -> Manager {
         info!("[new] ===== manager created: {filename} =====");
-        Ok(Manager {
-            document: Document::<File>::open_file(filename)?,
-            window: Window::new()?,
+        let mut manager = Manager {
+            document,
+            window,
             status_bar: StatusBar::default(),
-            event_source: EventSource::default(),
+            event_source,
             bookmark_store: BookmarkStore::default(),
+            match_list: MatchList::default(),
             finder: Finder::new(),
             helper_menu: HelperMenu::default(),
+            stats_popup: StatsPopup::default(),
+            session_stats: SessionStats::default(),
+            timing: FrameTiming::new(timing_enabled(&config)),
             context: Context::default(),
             canvas: Canvas::default(),
             mode: Mode::Normal,
-        })
+            session_store: config.restore_session.then(SessionStore::open_default),
+            session_key: session_key_of(filename),
+            config: config.clone(),
+            file_config: config.clone(),
+            config_stamp: None,
+            active_profile: None,
+            last_file_check: Instant::now(),
+            continuation_folder: ContinuationFolder::default(),
+            minimap: Minimap::default(),
+            notifier: Notifier::new(config.notify),
+            clipboard: Clipboard::new(),
+            detected_background: None,
+            modified: None,
+            #[cfg(unix)]
+            control_channel: None,
+            render_sink,
+            terminal: Box::new(Terminal),
+        };
+        manager.document.set_log_timezone(config.log_timezone);
+        manager.context.need_rerender = true;
+        manager.context.body_dirty = true;
+        manager
+            .bookmark_store
+            .set_document_start(manager.document.bytes_skipped());
+        manager.context.load_lines_multiple = 1;
+        manager.context.wrap_row_limit = config.wrap_row_limit;
+        manager.context.plain_mode_keeps_filters = config.plain_mode_keeps_filters;
+        manager.context.context_lines = config.context_lines;
+        manager.context.scroll_off = config.scroll_off;
+        manager.context.configured_scroll_off = config.scroll_off;
+        manager.context.boundary_feedback = config.boundary_feedback;
+        manager.context.step_line_matches = config.step_line_matches;
+        manager.status_bar.set_layout(config.status_layout);
+        manager.status_bar.set_filename(filename);
+        manager.event_source.set_move_steps(config.move_steps);
+        manager.event_source.set_key_repeat(config.key_repeat);
+        manager
+            .event_source
+            .set_direct_slot_digits(config.direct_slot_digits);
+        manager
+            .finder
+            .set_color_support(config.color.unwrap_or_else(ColorSupport::from_env));
+        manager.finder.set_background(manager.background());
+        manager.modified = modified_time_of(filename, &manager.document);
+        let now = SystemTime::now();
+        if let Some(warning) = manager
+            .modified
+            .and_then(|modified| stale_warning(modified, now, config.stale_after))
+        {
+            manager.status_bar.set_oneoff_alert_text(&warning);
+        }
+        manager
+    }
+
+    // the answer is read from the terminal, so this must be done before reading events. it's
+    // only asked once, a config reloaded back to `auto` uses what was found then
+    fn detect_background(&mut self) {
+        if self.config.background.is_some() {
+            return;
+        }
+        self.detected_background = query_background(BACKGROUND_QUERY_TIMEOUT);
+        info!("[detect_background] {:?}", self.detected_background);
+        self.finder.set_background(self.background());
+    }
+
+    fn background(&self) -> Background {
+        self.config
+            .background
+            .or(self.detected_background)
+            .unwrap_or_default()
+    }
+
+    pub fn start_at(&mut self, start_position: StartPosition) -> Result<()> {
+        match start_position {
+            // a stream has no last visit to restore, what matters is the data still coming in
+            StartPosition::LastVisit if self.document.reader().is_streamed() => {
+                self.enter_follow_mode()?
+            }
+            StartPosition::LastVisit => self.restore_session()?,
+            StartPosition::LastLines(line_count) => {
+                let offset = self.document.query_offset_of_last_n_lines(line_count)?;
+                self.window.set_offset(offset);
+            }
+            StartPosition::Line(line_number) => {
+                self.goto_line(line_number)?;
+                self.status_bar
+                    .set_oneoff_info_text(&format!("Opened at line {line_number}"));
+            }
+            StartPosition::Follow => self.enter_follow_mode()?,
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    pub fn listen_on_control_channel(&mut self, path: &str) -> Result<()> {
+        self.control_channel = Some(ControlChannel::bind(Path::new(path))?);
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn listen_on_control_channel(&mut self, _path: &str) -> Result<()> {
+        Err(anyhow::anyhow!("control channel is only supported on unix"))
+    }
+
+    // returns whether any command was executed and whether to exit
+    #[cfg(unix)]
+    fn serve_control_channel(&mut self) -> Result<(bool, bool)> {
+        let Some(mut channel) = self.control_channel.take() else {
+            return Ok((false, false));
+        };
+        let mut should_exit = false;
+        let executed = channel.serve(|line| {
+            let (exit, response) = self.execute_control_command(line);
+            should_exit |= exit;
+            response
+        });
+        self.control_channel = Some(channel);
+        Ok((executed?, should_exit))
+    }
+
+    #[cfg(not(unix))]
+    fn serve_control_channel(&mut self) -> Result<(bool, bool)> {
+        Ok((false, false))
+    }
+
+    // responds with `ok` or `error` followed by the window offset and position
+    #[cfg(unix)]
+    fn execute_control_command(&mut self, line: &str) -> (bool, String) {
+        info!("[execute_control_command] {line}");
+        // left by the previous command of the same round
+        self.status_bar.clear_oneoff_text();
+        let executed = parse_command(line).and_then(|command| self.execute_command(command));
+        let (should_exit, error) = match executed {
+            std::result::Result::Ok(should_exit) => (
+                should_exit,
+                self.status_bar.oneoff_error().map(str::to_string),
+            ),
+            Err(e) => (false, Some(e.to_string())),
+        };
+        let offset = self.window.offset();
+        let position = match self.document.position_ratio(offset) {
+            PositionRatio::Empty => "empty".to_string(),
+            PositionRatio::Percent(ratio) => format!("{ratio}%"),
+            PositionRatio::End => "end".to_string(),
+        };
+        let state = format!("offset={offset} position={position}");
+        let response = match error {
+            Some(error) => format!("error {state} {error}"),
+            None => format!("ok {state}"),
+        };
+        (should_exit, response)
+    }
+
+    // reloads the config file once it changes, `file_config` is what it held at startup
+    pub fn watch_config_file(&mut self, file_config: Config) {
+        self.file_config = file_config;
+        self.config_stamp = config_file_path().map(FileStamp::new);
+    }
+
+    pub fn apply_filter_profile(&mut self, name: &str, profile: &FilterProfile) -> Result<()> {
+        self.finder.apply_filter_profile(profile)?;
+        self.watch_profile(name);
+        Ok(())
+    }
+
+    fn watch_profile(&mut self, name: &str) {
+        self.active_profile = FilterProfile::path_of(name)
+            .ok()
+            .map(|path| (name.to_string(), FileStamp::new(path)));
+    }
+
+    // finder slots are kept while positions specific to the previous file are dropped
+    fn open_file(&mut self, filename: &str) -> Result<()> {
+        let opened = Input::open(filename).and_then(|input| {
+            input.open_source_document(&self.config.line_delimiter, self.config.tail_bytes)
+        });
+        let document = match opened {
+            std::result::Result::Ok(document) => document,
+            Err(e) => {
+                self.status_bar
+                    .set_oneoff_error_text(&format!("Cannot open {filename}: {e}"));
+                return Ok(());
+            }
+        };
+        info!("[open_file] ===== switched to: {filename} =====");
+        self.save_session();
+        self.document = document;
+        self.document.set_log_timezone(self.config.log_timezone);
+        self.session_key = session_key_of(filename);
+        self.window.reset_position();
+        self.bookmark_store = BookmarkStore::default();
+        self.bookmark_store
+            .set_document_start(self.document.bytes_skipped());
+        self.match_list = MatchList::default();
+        // stats and minimap density are keyed on the document size, which says nothing of content
+        self.finder.forget_match_stats();
+        self.context.revealed = None;
+        self.context.current_match = None;
+        self.context.single_line_match = None;
+        self.context.line_number_anchor = None;
+        self.status_bar.set_filename(filename);
+        self.modified = modified_time_of(filename, &self.document);
+        // the format of the new file may differ
+        self.continuation_folder.disable();
+        self.context.restored_offset = None;
+        self.context.trimmed = 0;
+        self.update_indicator();
+        self.restore_session()?;
+        self.status_bar
+            .set_oneoff_info_text(&format!("Opened {filename}"));
+        Ok(())
+    }
+
+    fn restore_session(&mut self) -> Result<()> {
+        let Some(state) = self
+            .session_store
+            .as_ref()
+            .and_then(|store| store.get(&self.session_key))
+            .cloned()
+        else {
+            return Ok(());
+        };
+        info!("[restore_session] {:?}", state);
+        // the file may have changed since last visit
+        let trimmed = self.document.snap_offset_to_line_start(state.trimmed)?;
+        if trimmed > 0 && trimmed < self.document.last_line_start_offset() {
+            self.rebase_document(self.document.bytes_skipped() + trimmed, 0)?;
+            self.context.trimmed = trimmed;
+            self.update_indicator();
+        }
+        let offset = self.document.snap_offset_to_line_start(state.offset)?;
+        if offset > 0 {
+            self.window.set_offset(offset);
+            self.context.restored_offset = Some(offset);
+        }
+        self.context.wrap_lines = state.wrap_lines;
+        self.window.horizontal_shift = state.horizontal_shift;
+        self.window.set_wrapped(state.wrap_lines);
+        // a profile given on the command line is applied later and wins
+        if let Some(name) = state.profile {
+            let active = self
+                .active_profile
+                .as_ref()
+                .map(|(active, _)| active.as_str());
+            if active != Some(name.as_str()) {
+                let restored = FilterProfile::load(&name)
+                    .and_then(|profile| self.apply_filter_profile(&name, &profile));
+                if let Err(e) = restored {
+                    warn!("[restore_session] cannot restore profile {name}: {e}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn save_session(&mut self) {
+        let state = SessionState {
+            offset: self.window.offset(),
+            // a pause only lasts until the next vertical move
+            wrap_lines: self.context.wrap_lines || self.context.wrap_paused,
+            horizontal_shift: self.window.unwrapped_horizontal_shift(),
+            trimmed: self.context.trimmed,
+            profile: self.active_profile.as_ref().map(|(name, _)| name.clone()),
+        };
+        if let Some(store) = self.session_store.as_mut() {
+            store.set(&self.session_key, state);
+            if let Err(e) = store.save() {
+                warn!("[save_session] failed to save session: {e}");
+            }
+        }
     }
 
     pub fn run(&mut self) -> Result<()> {
-        self.context.need_rerender = true;
-        self.context.load_lines_multiple = 1;
+        self.refresh()?;
         loop {
-            self.fill_canvas_and_render()?;
-            let should_exit = self.listen_and_dispatch_event()?;
-            self.ensure_consistency()?;
-            if should_exit {
-                clear_screen_and_reset_cursor()?;
+            let outcome = match self.next_event()? {
+                Some(event) => self.run_once(event)?,
+                None => self.run_idle()?,
+            };
+            if outcome == RunOutcome::Exit {
+                self.save_session();
+                self.render_sink.clear()?;
                 return Ok(());
             }
         }
     }
 
+    /// Dispatches `event`, then brings the window to a consistent state and renders it, unless
+    /// the event asks to exit. The session isn't saved on exit, that's up to the caller.
+    pub fn run_once(&mut self, event: Event) -> Result<RunOutcome> {
+        debug!("[run_once] new event: {:?}", event);
+        if let Some(now) = self.timing.start() {
+            self.timing.begin_frame(now);
+        }
+        let load_time = self.document.stats().load_time;
+        self.context.macro_recorder.record(&event);
+        self.context.body_dirty |= !leaves_body_unchanged(&event);
+        if self.dispatch_event(event)? {
+            return Ok(RunOutcome::Exit);
+        }
+        self.refresh()?;
+        self.end_frame_timing(load_time)?;
+        Ok(RunOutcome::Continue)
+    }
+
+    // a slow frame is warned about in the log with where its time went, and told in the status
+    // bar, drawn again for it, with `show_slow_frames`
+    fn end_frame_timing(&mut self, load_time_at_start: Duration) -> Result<()> {
+        let Some(now) = self.timing.start() else {
+            return Ok(());
+        };
+        // a file opened meanwhile counts its loads from zero
+        let load_time = self.document.stats().load_time;
+        self.timing.add(
+            Phase::ChunkLoad,
+            load_time.saturating_sub(load_time_at_start),
+        );
+        let Some(slow_frame) = self.timing.end_frame(now) else {
+            return Ok(());
+        };
+        warn!("[slow_frame] {}", slow_frame.log_line());
+        if self.config.show_slow_frames {
+            self.status_bar.set_oneoff_alert_text(&slow_frame.summary());
+            self.fill_canvas_and_render()?;
+        }
+        Ok(())
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        self.sync_pending_input();
+        self.ensure_consistency()?;
+        self.fill_canvas_and_render()?;
+        self.prefetch_in_scroll_direction()
+    }
+
     fn fill_canvas_and_render(&mut self) -> Result<()> {
         if !self.context.need_rerender {
             self.context.need_rerender = true;
             return Ok(());
         }
-        self.context.raw_lines_buffer.clear();
-        self.load_raw_lines_buffer()?;
-        self.canvas.clear();
-        for line in self.context.raw_lines_buffer.iter() {
-            if !self.finder.can_pass_advanced_action(line) {
-                continue;
+        // popups are laid out along with the body
+        let body_dirty = std::mem::take(&mut self.context.body_dirty) || self.is_popup_active();
+        if body_dirty {
+            self.context.raw_lines_buffer.clear();
+            self.context.hidden_continuations.clear();
+            self.context.group_starts.clear();
+            let started = self.timing.start();
+            self.load_raw_lines_buffer()?;
+            self.timing.record(Phase::QueryLines, started);
+            self.canvas.clear();
+            let minimap_shown = self.is_minimap_shown();
+            let body_width = self.body_width();
+            // the window may have been resized or the line folded since
+            self.window.row_shift = std::cmp::min(self.window.row_shift, self.max_row_shift());
+            let layout = BodyLayout {
+                width: body_width,
+                height: self.window.height,
+                horizontal_shift: self.window.horizontal_shift,
+                row_shift: self.window.row_shift,
+                wrap_lines: self.context.wrap_lines,
+                max_rows: self
+                    .context
+                    .fold_wrapped_lines
+                    .then_some(self.context.wrap_row_limit),
+                wrap_indicator: self.wrap_indicator().to_string(),
+                plain_mode: self.context.plain_mode,
+                show_invisibles: self.context.show_invisibles,
+                selection: self.context.selection.as_ref().map(Selection::range),
+            };
+            let (rows, current_match_shown) = layout.lay_out(
+                &mut self.finder,
+                &self.context.raw_lines_buffer,
+                &self.context.hidden_continuations,
+                &self.context.group_starts,
+                self.context.current_match,
+            );
+            for slot_index in self.finder.end_frame() {
+                self.status_bar.set_oneoff_error_text(&format!(
+                    "Slot {slot_index} disabled: pattern too slow (press o to re-enable)"
+                ));
             }
-            let line_with_render_scheme = self.finder.attach_render_scheme(line);
-            if self.context.wrap_lines {
-                for idx in 0..=line.len() / self.window.width {
-                    let start = idx * self.window.width;
-                    let end = std::cmp::min((idx + 1) * self.window.width, line.len());
-                    let substr = line_with_render_scheme.substr(start..end);
-                    self.canvas.body_area.push(substr);
-                }
-            } else {
-                let start = self.window.horizontal_shift;
-                let end = start + self.window.width;
-                let substr = line_with_render_scheme.substr(start..end);
-                self.canvas.body_area.push(substr);
+            self.canvas.body_area.extend(rows);
+            self.context.on_body_laid_out(current_match_shown);
+            self.canvas
+                .body_area
+                .resize(self.window.height, LineWithRenderScheme::new("~"));
+            if minimap_shown {
+                self.render_minimap(body_width)?;
             }
+        } else {
+            self.canvas.status_bar.clear();
         }
-        self.canvas
-            .body_area
-            .resize(self.window.height, LineWithRenderScheme::new("~"));
 
+        if let Some(text) = self.status_bar.take_unnotified_error() {
+            self.notify(NotifyEvent::Error, &text);
+        }
         if self.bookmark_store.is_active() {
             self.bookmark_store
                 .render(&mut self.canvas, self.window.width, self.window.height);
+        } else if self.match_list.is_active() {
+            let mut lines = vec![];
+            for index in self.match_list.shown_range() {
+                let offset = self.match_list.offsets()[index];
+                let line = self.document.query_lines(offset, 1)?;
+                lines.push(
+                    line.first()
+                        .map(|line| line.text.clone())
+                        .unwrap_or_default(),
+                );
+            }
+            self.match_list.render(
+                &lines,
+                &mut self.canvas,
+                self.window.width,
+                self.window.height,
+            );
         } else if self.finder.is_menu_active() {
             self.finder
                 .render_menu(&mut self.canvas, self.window.width, self.window.height);
         } else if self.helper_menu.is_active() {
             self.helper_menu
                 .render(&mut self.canvas, self.window.width, self.window.height);
+        } else if self.stats_popup.is_active() {
+            let stats = self.stats_rows()?;
+            self.stats_popup.render(
+                &stats,
+                &mut self.canvas,
+                self.window.width,
+                self.window.height,
+            );
         } else {
-            let ratio = self.document.percent_ratio_of_offset(self.window.offset());
+            let ratio = if self.document.is_single_line() {
+                self.single_line_ratio()
+            } else {
+                self.document.position_ratio(self.window.offset())
+            };
             self.status_bar.set_ratio(ratio);
-            if let Some(space_count) = self.status_bar.render(&mut self.canvas, self.window.width) {
-                self.finder.render_status_bar(&mut self.canvas, space_count);
-            }
+            let boundary =
+                detect_boundary(&mut self.document, self.window.offset(), self.window.height)?;
+            self.status_bar.set_boundary(boundary);
+            self.update_status_top_line()?;
+            let modified = self.modified;
+            self.status_bar.set_modified(
+                modified.map(|modified| format_modified(modified, SystemTime::now())),
+            );
+            let last_search = self.context.last_search();
+            self.status_bar.render(
+                &mut self.canvas,
+                self.window.width,
+                &self.finder.slot_strip(last_search),
+            );
         }
-        self.canvas.render()?;
+        self.canvas.single_line = self.window.is_single_line();
+        let started = self.timing.start();
+        if body_dirty {
+            self.render_sink.draw(&self.canvas)?;
+        } else {
+            self.render_sink.draw_status_bar(&self.canvas)?;
+        }
+        self.timing.record(Phase::Render, started);
+        self.session_stats.frames += 1;
+        self.canvas.bell = false;
         Ok(())
     }
 
-    fn load_raw_lines_buffer(&mut self) -> Result<()> {
-        let offset = self.window.offset();
-        let line_count_to_query = self.window.height * self.context.load_lines_multiple;
-        let lines = self.document.query_lines(offset, line_count_to_query)?;
-        let filtered_lines = lines
-            .into_iter()
-            .filter(|line| self.finder.can_pass_advanced_action(line));
-        self.context.raw_lines_buffer.extend(filtered_lines);
-        Ok(())
+    fn is_popup_active(&self) -> bool {
+        self.bookmark_store.is_active()
+            || self.match_list.is_active()
+            || self.finder.is_menu_active()
+            || self.helper_menu.is_active()
+            || self.stats_popup.is_active()
     }
 
-    fn listen_and_dispatch_event(&mut self) -> Result<bool> {
-        if self.mode != Mode::Normal {
-            if self.event_source.check_for_interrupt()? {
-                self.mode = Mode::Normal;
-                self.status_bar.clear_text();
-            } else if self.mode == Mode::Follow {
-                let document_updated = self.document.update_docsize_and_lastline()?;
-                self.context.need_rerender = document_updated;
-                if document_updated {
-                    self.seek_to_end()?;
+    // shown by the stats popup, the frame being drawn is left out of the count
+    fn stats_rows(&mut self) -> Result<Vec<(&'static str, String)>> {
+        let document_stats = self.document.stats();
+        let offset = self.window.offset();
+        let line_number = self.top_line_number()?;
+        let timestamp_format = self.document.detect_log_timestamp_format(offset)?;
+        Ok(vec![
+            ("Document size", format_bytes(self.document.document_size())),
+            (
+                "Modified",
+                match self.modified {
+                    Some(modified) => format_modified(modified, SystemTime::now()),
+                    None => "unknown".to_string(),
+                },
+            ),
+            (
+                "Byte order mark",
+                if self.document.has_bom() {
+                    "UTF-8, skipped"
+                } else {
+                    "none"
                 }
-            }
-            return Ok(false);
-        }
-        let event = self.event_source.wait_for_event()?;
-        info!("[run] new event: {:?}", event);
-        match event {
-            Event::Exit => return Ok(true),
-            Event::ToggleWrapLine => self.context.wrap_lines = !self.context.wrap_lines,
-            Event::WindowMove(direction, step) => self.on_window_move_event(direction, step)?,
-            Event::Search(action) => self.on_search_event(action)?,
-            Event::SearchNext => self.search_next(Direction::Down, true)?,
-            Event::SearchPrevious => self.search_next(Direction::Up, true)?,
-            Event::SeekToEnd => self.seek_to_end()?,
-            Event::SeekToHome => self.window.set_offset(0),
-            Event::JumpToTimestamp(action) => self.on_jump_to_timestamp_event(action)?,
-            Event::JumpByLines(action) => self.on_jump_by_lines_event(action)?,
-            Event::TerminalResize(width, height) => self.window.resize(width, height),
-            Event::NewBookmark(action) => self.on_new_bookmark_event(action)?,
-            Event::GotoBookmark(action) => self.on_bookmark_menu_event(action)?,
-            Event::UndoWindowVerticalMove => self.window.goto_previous_offset(),
-            Event::RedoWindowVerticalMove => self.window.goto_next_offset(),
-            Event::FinderOperation(action) => self.on_finder_event(action)?,
-            Event::Follow => self.enter_follow_mode()?,
-            Event::ToggleHelperMenu => self.helper_menu.toggle_active(),
-        }
-        Ok(false)
+                .to_string(),
+            ),
+            ("Loaded chunks", self.document.chunk_count().to_string()),
+            ("Chunk bytes", format_bytes(self.document.chunk_bytes())),
+            ("Chunk loads", document_stats.chunk_loads.to_string()),
+            ("Bytes loaded", format_bytes(document_stats.bytes_loaded)),
+            ("Chunk merges", document_stats.chunk_merges.to_string()),
+            (
+                "Chunk heavy frames",
+                match document_stats.chunk_heavy_queries {
+                    0 => "none".to_string(),
+                    count => format!(
+                        "{count}, chunks of {} may be too small",
+                        format_bytes(self.document.chunk_size())
+                    ),
+                },
+            ),
+            (
+                "Timestamp format",
+                timestamp_format.unwrap_or_else(|| "none detected".to_string()),
+            ),
+            ("Window top", format!("line {line_number}, offset {offset}")),
+            (
+                "Searches",
+                format!(
+                    "{} ({} not found)",
+                    self.session_stats.searches, self.session_stats.search_misses
+                ),
+            ),
+            ("Lines scanned", document_stats.lines_scanned.to_string()),
+            ("Frames drawn", self.session_stats.frames.to_string()),
+        ])
     }
 
-    fn on_window_move_event(&mut self, direction: Direction, step: usize) -> Result<()> {
-        match direction {
-            Direction::Up => {
-                let distance = self
-                    .document
-                    .query_distance_to_above_n_lines(self.window.offset(), step)?;
-                self.window.move_offset_by(distance, direction);
-            }
-            Direction::Down => {
-                let distance = self
-                    .document
-                    .query_distance_to_below_n_lines(self.window.offset(), step)?;
-                self.window.move_offset_by(distance, direction);
-            }
-            Direction::Left => {
-                if !self.context.wrap_lines {
-                    self.window.horizontal_shift =
-                        self.window.horizontal_shift.saturating_sub(step);
-                }
-            }
-            Direction::Right => {
-                if !self.context.wrap_lines {
-                    let max_line_len = self
-                        .context
-                        .raw_lines_buffer
-                        .iter()
-                        .map(|line| line.len())
-                        .max()
-                        .unwrap();
-                    let max_window_shift = max_line_len.saturating_sub(self.window.width);
-                    self.window.horizontal_shift =
-                        std::cmp::min(self.window.horizontal_shift + step, max_window_shift);
-                }
-            }
+    fn update_status_top_line(&mut self) -> Result<()> {
+        let offset = self.window.offset();
+        if !self.status_bar.layout().shows_top_line_details() {
+            self.status_bar.set_top_line(offset, None, None);
+            self.status_bar.set_timestamp_format(None);
+            return Ok(());
         }
-        Ok(())
-    }
-
-    fn on_search_event(&mut self, action: PromptAction) -> Result<()> {
-        match action {
-            PromptAction::Start(direction) => {
-                assert!(direction.unwrap().is_vertical());
-                if self.finder.active_slots().len() > 1 {
-                    self.event_source.exit_search_prompt();
-                    self.status_bar.set_oneoff_error_text(
-                        "Cannot search with more than one active Finder slot",
-                    );
-                } else {
-                    self.context.searching_direction = direction;
-                    self.status_bar.set_text("Search: ");
-                }
-            }
-            PromptAction::Content(content) => {
-                self.status_bar.set_text(&format!("Search: {content}"));
-            }
-            PromptAction::Cancel => {
-                self.context.searching_direction = None;
-                self.status_bar.clear_text();
-            }
-            PromptAction::Enter(content) => {
-                assert_eq!(self.finder.active_slots().len(), 1);
-                self.status_bar.clear_text();
-                if content.is_empty() {
-                    self.status_bar.set_oneoff_error_text("Nothing to search");
-                } else {
-                    self.finder.update_search_pattern(&content);
-                    self.search_next(self.context.searching_direction.unwrap(), false)?;
-                    self.context.searching_direction = None;
-                }
+        let line_number = self.top_line_number()?;
+        let timestamp_format = self.document.detect_log_timestamp_format(offset)?;
+        self.status_bar
+            .set_timestamp_format(timestamp_format.clone());
+        let timestamp = match timestamp_format {
+            Some(fmt) => {
+                let line = self.document.query_lines(offset, 1)?.remove(0);
+                NaiveDateTime::parse_and_remainder(&line.text, &fmt)
+                    .ok()
+                    .map(|(_, remainder)| {
+                        line.text[..line.text.len() - remainder.len()].to_string()
+                    })
             }
-        }
+            None => None,
+        };
+        self.status_bar
+            .set_top_line(offset, Some(line_number), timestamp);
         Ok(())
     }
 
-    fn search_next(&mut self, direction: Direction, from_next_event: bool) -> Result<()> {
-        assert!(direction.is_vertical());
-        let search_predict = |line: &str| self.finder.can_satisfy_active_search_patterns(line);
-        let mut extra_distance = 0;
-        let distance = if direction == Direction::Up {
-            self.document
-                .query_distance_to_prev_match(self.window.offset(), search_predict)?
+    // counted from the nearest of the document start and the last window top looked up
+    fn top_line_number(&mut self) -> Result<usize> {
+        let offset = self.window.offset();
+        let (anchor_offset, anchor_line_number) = match self.context.line_number_anchor {
+            Some((anchor_offset, _)) if offset < anchor_offset.saturating_sub(offset) => (0, 1),
+            Some(anchor) => anchor,
+            None => (0, 1),
+        };
+        let line_number = if offset >= anchor_offset {
+            anchor_line_number + self.document.count_lines_between(anchor_offset, offset)?
         } else {
-            if from_next_event {
-                extra_distance = self
-                    .document
-                    .query_distance_to_below_n_lines(self.window.offset(), 1)?;
-            }
-            self.document.query_distance_to_next_match(
-                self.window.offset() + extra_distance,
-                search_predict,
-            )?
+            anchor_line_number - self.document.count_lines_between(offset, anchor_offset)?
         };
-        if let Some(distance) = distance {
-            self.window
-                .move_offset_by(distance + extra_distance, direction);
+        self.context.line_number_anchor = Some((offset, line_number));
+        Ok(line_number)
+    }
+
+    // plain mode is for copying content only
+    fn is_minimap_shown(&self) -> bool {
+        !self.context.plain_mode && self.minimap.is_shown(self.window.width)
+    }
+
+    fn body_width(&self) -> usize {
+        if self.is_minimap_shown() {
+            self.window.width - 1
         } else {
-            self.status_bar.set_oneoff_error_text("Not found");
+            self.window.width
         }
-        Ok(())
     }
 
-    fn seek_to_end(&mut self) -> Result<()> {
-        let distance = self.document.query_distance_to_above_n_lines(
-            self.document.last_line_start_offset(),
-            self.window.height.saturating_sub(1),
-        )?;
-        self.window.set_offset(
-            self.document
-                .last_line_start_offset()
-                .saturating_sub(distance),
-        );
-        Ok(())
+    // plain mode keeps the indicator out of what is selected
+    fn wrap_indicator(&self) -> &str {
+        if self.context.plain_mode {
+            ""
+        } else {
+            &self.config.wrap_indicator
+        }
     }
 
-    fn on_jump_to_timestamp_event(&mut self, action: PromptAction) -> Result<()> {
-        match action {
-            PromptAction::Start(direction) => {
-                assert!(direction.is_none());
-                self.status_bar.set_text("Jump to timestamp: ");
-            }
-            PromptAction::Content(content) => {
+    // rows the line of a single-line document takes beyond the window height when wrapped
+    fn max_row_shift(&self) -> usize {
+        if !self.context.wrap_lines || !self.document.is_single_line() {
+            return 0;
+        }
+        let line_len = self
+            .context
+            .raw_lines_buffer
+            .first()
+            .map_or(0, |line| line.text.len());
+        let mut row_count = wrapped_row_count(line_len, self.body_width(), self.wrap_indicator());
+        if self.context.fold_wrapped_lines && !self.context.plain_mode {
+            row_count = std::cmp::min(row_count, self.context.wrap_row_limit);
+        }
+        row_count.saturating_sub(self.window.height)
+    }
+
+    // the horizontal position over the line of a single-line document, which rows of the
+    // wrapped line or columns of the shifted one are shown
+    fn single_line_ratio(&self) -> PositionRatio {
+        let line_len = self
+            .context
+            .raw_lines_buffer
+            .first()
+            .map_or(0, |line| line.text.len());
+        let (column, shown_len) = if self.context.wrap_lines {
+            let row_start = |row| wrapped_row_start(row, self.body_width(), self.wrap_indicator());
+            let column = row_start(self.window.row_shift);
+            (
+                column,
+                row_start(self.window.row_shift + self.window.height) - column,
+            )
+        } else {
+            (self.window.horizontal_shift, self.body_width())
+        };
+        column_ratio(column, shown_len, line_len)
+    }
+
+    // appends the minimap column to the body rows, scanning for matches first if slots changed,
+    // or only the appended data if the document grew
+    fn render_minimap(&mut self, body_width: usize) -> Result<()> {
+        let scan_from = self.minimap.start_scan(
+            self.finder.generation(),
+            self.document.document_size(),
+            self.document.last_line_start_offset(),
+        );
+        if let Some(from) = scan_from.filter(|_| self.finder.has_any_pattern()) {
+            let finder = &self.finder;
+            let event_source = &mut self.event_source;
+            let minimap = &mut self.minimap;
+            let finished = self.document.scan_matches_from(
+                from,
+                |line| finder.can_satisfy_any_pattern(line),
+                || event_source.check_for_interrupt().unwrap_or(true),
+                |offset| minimap.count_match(offset),
+            )?;
+            if !finished {
+                self.minimap.interrupt_scan();
                 self.status_bar
-                    .set_text(&format!("Jump to timestamp: {content}"));
+                    .set_oneoff_error_text("Minimap scan interrupted");
             }
-            PromptAction::Cancel => {
-                self.status_bar.clear_text();
+        }
+        let height = self.window.height;
+        let bottom_offset = self
+            .context
+            .raw_lines_buffer
+            .get(std::cmp::min(height, self.context.raw_lines_buffer.len()).saturating_sub(1))
+            .map_or(self.window.offset(), |line| line.offset);
+        let thumb = thumb_rows(
+            self.document.percent_ratio_of_offset(self.window.offset()),
+            self.document.percent_ratio_of_offset(bottom_offset),
+            height,
+        );
+        let column = self.minimap.column(thumb, height);
+        for (row, (cell, scheme)) in self.canvas.body_area.iter_mut().zip(column) {
+            row.pad_to(body_width);
+            match scheme {
+                Some(scheme) => row.push_str_with_scheme(&cell.to_string(), scheme),
+                None => row.push_str(&cell.to_string()),
             }
-            PromptAction::Enter(content) => {
-                self.status_bar.clear_text();
-                let (date, time) = parse_log_timestamp(&content);
-                if let Some(time) = time {
-                    if let Some(offset) = self.document.query_offset_by_timestamp(date, time)? {
-                        self.window.set_offset(offset)
-                    } else {
-                        self.status_bar
-                            .set_oneoff_error_text("Cannot jump to timestamp");
-                    }
-                } else {
-                    self.status_bar.set_oneoff_error_text("Invalid timestamp");
+        }
+        Ok(())
+    }
+
+    fn prefetch_in_scroll_direction(&mut self) -> Result<()> {
+        if self.mode != Mode::Normal {
+            return Ok(());
+        }
+        if let Some(direction) = self.context.last_vertical_direction {
+            self.document.prefetch(self.window.offset(), direction)?;
+        }
+        Ok(())
+    }
+
+    fn load_raw_lines_buffer(&mut self) -> Result<()> {
+        let offset = self.window.offset();
+        // plain mode may show everything as is, see `plain_mode_keeps_filters`
+        let apply_filters = !self.context.plain_mode || self.context.plain_mode_keeps_filters;
+        let context_lines = if apply_filters && self.finder.has_exclusive_rule() {
+            self.context.context_lines
+        } else {
+            0
+        };
+        // lines below the window may make those at its bottom context of a match
+        let line_count_to_query =
+            self.window.height * self.context.load_lines_multiple + context_lines;
+        let lines = if self.continuation_folder.is_enabled() {
+            self.query_lines_collapsing_continuations(offset, line_count_to_query)?
+        } else {
+            self.document.query_lines(offset, line_count_to_query)?
+        };
+        if !apply_filters {
+            self.context.raw_lines_buffer.extend(lines);
+            return Ok(());
+        }
+        // and so may lines above the window for those at its top
+        let mut lines_above = vec![];
+        if context_lines > 0 {
+            let distance = self
+                .document
+                .query_distance_to_above_n_lines(offset, context_lines)?;
+            if distance > 0 {
+                lines_above = self
+                    .document
+                    .query_lines(offset - distance, context_lines)?;
+                lines_above.retain(|line| line.offset < offset);
+            }
+        }
+        let above_count = lines_above.len();
+        let lines: Vec<Line> = lines_above.into_iter().chain(lines).collect();
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        let passed = self.finder.pass_with_context(&texts, context_lines);
+        for (index, line) in lines.into_iter().enumerate() {
+            if !passed[index] || index < above_count {
+                continue;
+            }
+            if context_lines > 0 && index > 0 && !passed[index - 1] {
+                self.context.group_starts.insert(line.offset);
+            }
+            self.context.raw_lines_buffer.push(line);
+        }
+        Ok(())
+    }
+
+    // queries until `line_count` lines are left after collapsing, or the document ends
+    fn query_lines_collapsing_continuations(
+        &mut self,
+        mut offset: usize,
+        line_count: usize,
+    ) -> Result<Vec<Line>> {
+        let mut shown = vec![];
+        loop {
+            let lines = self.document.query_lines(offset, line_count)?;
+            let last_line = lines.last().unwrap();
+            let reached_end = last_line.offset == self.document.last_line_start_offset();
+            offset = last_line.offset + last_line.text.len() + self.config.line_delimiter.len();
+            self.continuation_folder.collapse_into(
+                lines,
+                &mut shown,
+                &mut self.context.hidden_continuations,
+            );
+            if reached_end || shown.len() >= line_count {
+                return Ok(shown);
+            }
+        }
+    }
+
+    // waits for the next event from the terminal, `None` if there's none to dispatch, while
+    // following or when other sources are due to be checked
+    fn next_event(&mut self) -> Result<Option<Event>> {
+        if self.mode != Mode::Normal {
+            return Ok(None);
+        }
+        match self.poll_interval() {
+            Some(interval) => self.event_source.poll_event(Some(interval)),
+            None => self.event_source.wait_for_event().map(Some),
+        }
+    }
+
+    /// Checks on sources other than the terminal events, like a followed file growing, then
+    /// renders if anything changed.
+    pub fn run_idle(&mut self) -> Result<RunOutcome> {
+        if let Some(event) = self.expire_pending_input() {
+            return self.run_once(event);
+        }
+        if self.mode != Mode::Normal {
+            self.follow()?;
+        } else if self.on_poll_timeout()? {
+            return Ok(RunOutcome::Exit);
+        }
+        self.refresh()?;
+        Ok(RunOutcome::Continue)
+    }
+
+    fn follow(&mut self) -> Result<()> {
+        if self.event_source.check_for_interrupt()? {
+            self.mode = Mode::Normal;
+            self.status_bar.clear_text();
+        } else {
+            let appended = self.document.update_docsize_and_lastline()?;
+            self.context.need_rerender = appended.is_some();
+            self.context.body_dirty |= appended.is_some();
+            if let Some(appended) = appended {
+                self.seek_to_end()?;
+                if self.mode == Mode::FollowUntilMatch {
+                    self.stop_following_at_appended_match(appended)?;
                 }
             }
         }
         Ok(())
     }
 
-    fn on_jump_by_lines_event(&mut self, action: PromptAction) -> Result<()> {
-        match action {
-            PromptAction::Start(direction) => {
-                assert!(direction.unwrap().is_vertical());
-                self.context.jumping_direction = direction;
-                let s = direction.as_ref().unwrap().above_or_below();
-                self.status_bar.set_text(&format!("Jump to {s} N lines: "));
+    // how long to wait for terminal events before checking on other sources or giving up an
+    // unfinished key sequence, `None` to wait indefinitely
+    fn poll_interval(&self) -> Option<Duration> {
+        let pending_timeout = self
+            .context
+            .pending_input
+            .map(|(_, since)| PENDING_INPUT_TIMEOUT.saturating_sub(since.elapsed()));
+        [self.source_poll_interval(), pending_timeout]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    // how often sources other than the terminal need checking
+    fn source_poll_interval(&self) -> Option<Duration> {
+        #[cfg(unix)]
+        if self.control_channel.is_some() {
+            return Some(POLL_INTERVAL);
+        }
+        if self.document.reader().is_streaming() {
+            return Some(POLL_INTERVAL);
+        }
+        (self.config_stamp.is_some() || self.active_profile.is_some())
+            .then_some(FILE_CHECK_INTERVAL)
+    }
+
+    // the timeout restarts whenever the sequence moves on
+    fn sync_pending_input(&mut self) {
+        let pending_input = self.event_source.pending_input();
+        if self.context.pending_input.map(|(input, _)| input) != pending_input {
+            self.context.pending_input = pending_input.map(|input| (input, Instant::now()));
+        }
+        self.status_bar
+            .set_pending_input(pending_input.map(PendingInput::text));
+        self.finder.set_slot_choice_pending(matches!(
+            pending_input,
+            Some(PendingInput::AddSlot | PendingInput::RemoveSlot)
+        ));
+    }
+
+    // the event cancelling a key sequence left unfinished for too long
+    fn expire_pending_input(&mut self) -> Option<Event> {
+        let (_, since) = self.context.pending_input?;
+        if since.elapsed() < PENDING_INPUT_TIMEOUT {
+            return None;
+        }
+        self.context.pending_input = None;
+        self.event_source.cancel_pending_input()
+    }
+
+    // returns whether to exit
+    fn on_poll_timeout(&mut self) -> Result<bool> {
+        // skip rendering unless something changed
+        self.context.need_rerender = self.document.update_docsize_and_lastline()?.is_some();
+        self.context.need_rerender |= self.reload_changed_files();
+        let (executed, should_exit) = self.serve_control_channel()?;
+        self.context.need_rerender |= executed;
+        self.context.body_dirty |= self.context.need_rerender;
+        Ok(should_exit)
+    }
+
+    // returns whether anything was reloaded
+    fn reload_changed_files(&mut self) -> bool {
+        if self.last_file_check.elapsed() < FILE_CHECK_INTERVAL {
+            return false;
+        }
+        self.last_file_check = Instant::now();
+        let mut reloaded = false;
+        if self.config_stamp.as_mut().is_some_and(FileStamp::refresh) {
+            match self.reload_config() {
+                std::result::Result::Ok(changes) => self
+                    .status_bar
+                    .set_oneoff_info_text(&config_reloaded_text(&changes)),
+                Err(e) => self
+                    .status_bar
+                    .set_oneoff_error_text(&format!("Config not reloaded: {e}")),
             }
-            PromptAction::Content(content) => {
-                let s = self
-                    .context
-                    .jumping_direction
-                    .as_ref()
-                    .unwrap()
-                    .above_or_below();
-                self.status_bar
-                    .set_text(&format!("Jump to {s} N lines: {content}"));
+            reloaded = true;
+        }
+        if let Some((name, stamp)) = self.active_profile.as_mut() {
+            if stamp.refresh() {
+                let name = name.clone();
+                match self.reload_profile(&name) {
+                    std::result::Result::Ok(()) => self
+                        .status_bar
+                        .set_oneoff_info_text(&format!("Reloaded profile {name}")),
+                    Err(e) => self
+                        .status_bar
+                        .set_oneoff_error_text(&format!("Profile not reloaded: {e}")),
+                }
+                reloaded = true;
             }
-            PromptAction::Cancel => {
-                self.context.jumping_direction = None;
-                self.status_bar.clear_text();
+        }
+        reloaded
+    }
+
+    // the current settings are kept if the file doesn't parse, e.g. when caught half-written
+    fn reload_config(&mut self) -> Result<ConfigChanges> {
+        let reloaded = Config::load()?;
+        let changes = self.config.apply_reloaded(&self.file_config, &reloaded);
+        self.file_config = reloaded;
+        info!("[reload_config] {:?}", changes);
+        for key in changes.applied.iter() {
+            match *key {
+                "log_timezone" => self.document.set_log_timezone(self.config.log_timezone),
+                "wrap_row_limit" => self.context.wrap_row_limit = self.config.wrap_row_limit,
+                "plain_mode_keeps_filters" => {
+                    self.context.plain_mode_keeps_filters = self.config.plain_mode_keeps_filters
+                }
+                "context_lines" => self.context.context_lines = self.config.context_lines,
+                "scroll_off" => {
+                    self.context.scroll_off = self.config.scroll_off;
+                    if self.config.scroll_off.is_some() {
+                        self.context.configured_scroll_off = self.config.scroll_off;
+                    }
+                }
+                "boundary_feedback" => {
+                    self.context.boundary_feedback = self.config.boundary_feedback
+                }
+                "color" => self
+                    .finder
+                    .set_color_support(self.config.color.unwrap_or_else(ColorSupport::from_env)),
+                "background" => self.finder.set_background(self.background()),
+                "status_layout" => self.status_bar.set_layout(self.config.status_layout),
+                "step_arrow" | "step_page" | "step_ctrl_arrow" | "step_ctrl_page" => {
+                    self.event_source.set_move_steps(self.config.move_steps)
+                }
+                "key_repeat" => self.event_source.set_key_repeat(self.config.key_repeat),
+                "direct_slot_digits" => self
+                    .event_source
+                    .set_direct_slot_digits(self.config.direct_slot_digits),
+                "step_line_matches" => {
+                    self.context.step_line_matches = self.config.step_line_matches
+                }
+                "notify_not_found" | "notify_follow_match" | "notify_error" => {
+                    self.notifier.set_policy(self.config.notify)
+                }
+                "show_slow_frames" => self.timing.set_enabled(timing_enabled(&self.config)),
+                _ => {}
             }
-            PromptAction::Enter(content) => {
-                self.status_bar.clear_text();
-                if let std::result::Result::Ok(step) = content.parse::<usize>() {
-                    self.on_window_move_event(self.context.jumping_direction.unwrap(), step)?;
+        }
+        Ok(changes)
+    }
+
+    // like the config, the current rules are kept if the profile doesn't parse
+    fn reload_profile(&mut self, name: &str) -> Result<()> {
+        let profile = FilterProfile::load(name)?;
+        self.finder.apply_filter_profile(&profile)
+    }
+
+    // returns whether to exit
+    fn dispatch_event(&mut self, event: Event) -> Result<bool> {
+        match event {
+            Event::Exit => return Ok(true),
+            Event::ToggleWrapLine => self.set_wrap_lines(!self.context.wrap_lines),
+            Event::ToggleScrollOff => self.toggle_scroll_off(),
+            Event::TogglePlainMode => self.set_plain_mode(!self.context.plain_mode),
+            Event::ToggleCollapseContinuations => self.toggle_collapse_continuations()?,
+            Event::ToggleExpandTrace => self.toggle_expand_trace(),
+            Event::CycleContextLines => {
+                let context_lines = next_context_lines(self.context.context_lines);
+                self.set_context_lines(context_lines);
+            }
+            Event::CycleStatusLayout => {
+                let layout = self.status_bar.layout().cycle();
+                self.status_bar.set_layout(layout);
+                self.status_bar
+                    .set_oneoff_info_text(&format!("Status bar: {}", layout.name()));
+            }
+            Event::ToggleMinimap => {
+                let text = if self.minimap.toggle() {
+                    "Minimap on"
                 } else {
-                    self.status_bar.set_oneoff_error_text("Invalid line count");
+                    "Minimap off"
+                };
+                self.status_bar.set_oneoff_info_text(text);
+            }
+            Event::ToggleInvisibles => self.context.show_invisibles = !self.context.show_invisibles,
+            Event::ToggleFoldWrappedLines => {
+                self.context.fold_wrapped_lines = !self.context.fold_wrapped_lines;
+                if !self.context.wrap_lines {
+                    self.status_bar
+                        .set_oneoff_error_text("Folding takes effect when wrapping lines");
                 }
-                self.context.jumping_direction = None;
             }
+            Event::WindowMove(direction, step) => {
+                if std::mem::take(&mut self.context.counting) {
+                    self.status_bar.clear_text();
+                }
+                self.on_window_move_event(direction, step)?
+            }
+            Event::CountPrefix(count) => {
+                self.context.counting = count.is_some();
+                match count {
+                    Some(0) => self.status_bar.set_text("Count: "),
+                    Some(count) => self.status_bar.set_text(&format!("Count: {count}")),
+                    None => self.status_bar.clear_text(),
+                }
+            }
+            Event::Search(action) => self.on_search_event(action)?,
+            Event::SearchNext => {
+                let direction = resolve_search_direction(self.context.last_search_direction, false);
+                self.search_next(direction, true)?
+            }
+            Event::SearchPrevious => {
+                let direction = resolve_search_direction(self.context.last_search_direction, true);
+                self.search_next(direction, true)?
+            }
+            Event::SeekToEnd => self.seek_to_end()?,
+            Event::SeekToHome => {
+                self.window.set_offset(0);
+                self.window.row_shift = 0;
+            }
+            Event::JumpToTimestamp(action) => self.on_jump_to_timestamp_event(action)?,
+            Event::JumpByLines(action) => self.on_jump_by_lines_event(action)?,
+            Event::TerminalResize(width, height) => {
+                self.window.resize(width, height);
+                self.context.body_dirty = true;
+            }
+            Event::NewBookmark(action) => self.on_new_bookmark_event(action)?,
+            Event::GotoBookmark(action) => self.on_bookmark_menu_event(action)?,
+            Event::MatchList(action) => self.on_match_list_event(action)?,
+            Event::Select(action) => self.on_select_event(action)?,
+            Event::WriteSelection(action) => self.on_write_selection_event(action)?,
+            Event::UndoWindowVerticalMove => self.window.goto_previous_offset(),
+            Event::RedoWindowVerticalMove => self.window.goto_next_offset(),
+            Event::FinderOperation(action) => self.on_finder_event(action)?,
+            Event::EditSlotPattern(action) => self.on_edit_slot_pattern_event(action)?,
+            Event::Follow => self.enter_follow_mode()?,
+            Event::ToggleHelperMenu => self.helper_menu.toggle_active(),
+            Event::ToggleStats => self.stats_popup.toggle_active(),
+            Event::Dismiss => self.dismiss(),
+            Event::Suspend => self.suspend()?,
+            Event::EditFilter => {
+                let text = match self.finder.view_filter() {
+                    Some(filter) => format!("filter {}", filter.source()),
+                    None => "filter ".to_string(),
+                };
+                self.event_source.prefill_command_prompt(&text);
+                self.status_bar.set_text(&format!(":{text}"));
+            }
+            Event::ToggleRestoredPosition => self.toggle_restored_position(),
+            Event::Command(action) => return self.on_command_event(action),
+            Event::ToggleMacroRecording => self.toggle_macro_recording(),
+            Event::ReplayMacro => return Ok(self.replay_macro()),
         }
-        Ok(())
+        Ok(false)
     }
 
-    fn on_new_bookmark_event(&mut self, action: PromptAction) -> Result<()> {
+    fn set_context_lines(&mut self, context_lines: usize) {
+        self.context.context_lines = context_lines;
+        let text = if self.finder.has_exclusive_rule() {
+            format!("Context lines: {context_lines}")
+        } else {
+            format!("Context lines: {context_lines} (no exclusive slot)")
+        };
+        self.status_bar.set_oneoff_info_text(&text);
+    }
+
+    fn toggle_macro_recording(&mut self) {
+        let recorder = &mut self.context.macro_recorder;
+        recorder.toggle_recording();
+        if !recorder.is_recording() {
+            let text = format!("Macro recorded ({} events)", recorder.recorded().len());
+            self.status_bar.set_oneoff_info_text(&text);
+        }
+        self.update_indicator();
+    }
+
+    // returns whether to exit
+    fn replay_macro(&mut self) -> bool {
+        if self.context.macro_recorder.is_recording() {
+            self.status_bar
+                .set_oneoff_error_text("Cannot replay while recording");
+            return false;
+        }
+        let events = self.context.macro_recorder.recorded().to_vec();
+        if events.is_empty() {
+            self.status_bar.set_oneoff_error_text("No macro recorded");
+            return false;
+        }
+        let outcome = replay(events, |event| {
+            if self.dispatch_event(event)? {
+                return Ok(ReplayStep::Exit);
+            }
+            Ok(match self.status_bar.oneoff_error() {
+                Some(text) => ReplayStep::Abort(text.to_string()),
+                None => ReplayStep::Continue,
+            })
+        });
+        match outcome {
+            ReplayOutcome::Finished(event_count) => self
+                .status_bar
+                .set_oneoff_info_text(&format!("Macro replayed ({event_count} events)")),
+            ReplayOutcome::Exit => return true,
+            ReplayOutcome::Aborted(step, reason) => self
+                .status_bar
+                .set_oneoff_error_text(&format!("Macro aborted at step {step}: {reason}")),
+        }
+        false
+    }
+
+    fn set_plain_mode(&mut self, plain_mode: bool) {
+        self.context.plain_mode = plain_mode;
+        self.update_indicator();
+    }
+
+    // the horizontal shift is remembered while wrapping, a pause discards the columns moved
+    // during it
+    fn set_wrap_lines(&mut self, wrap_lines: bool) {
+        if std::mem::take(&mut self.context.wrap_paused) {
+            self.window.horizontal_shift = 0;
+        }
+        self.context.wrap_lines = wrap_lines;
+        self.window.set_wrapped(wrap_lines);
+        self.update_indicator();
+    }
+
+    fn update_indicator(&mut self) {
+        let flags = [
+            (self.context.plain_mode, "[plain] "),
+            (self.context.macro_recorder.is_recording(), "[recording] "),
+            (self.context.wrap_paused, "[wrap paused] "),
+            (self.finder.is_spotlight(), "[spotlight] "),
+        ];
+        let mut indicator: String = flags
+            .into_iter()
+            .filter_map(|(on, text)| on.then_some(text))
+            .collect();
+        if self.context.trimmed > 0 {
+            indicator.push_str(&format!(
+                "[trimmed {}] ",
+                format_bytes(self.context.trimmed)
+            ));
+        }
+        self.status_bar.set_indicator(&indicator);
+    }
+
+    // returns whether to exit
+    fn on_command_event(&mut self, action: PromptAction) -> Result<bool> {
         match action {
             PromptAction::Start(direction) => {
                 assert!(direction.is_none());
-                self.status_bar.set_text("New bookmark: ");
-            }
-            PromptAction::Content(content) => {
-                self.status_bar
-                    .set_text(&format!("New bookmark: {content}"));
-            }
-            PromptAction::Cancel => {
-                self.status_bar.clear_text();
+                self.status_bar.set_text(":");
             }
+            PromptAction::Content(content) => self.status_bar.set_text(&format!(":{content}")),
+            PromptAction::Cancel => self.status_bar.clear_text(),
             PromptAction::Enter(content) => {
                 self.status_bar.clear_text();
-                if content.is_empty() {
-                    self.status_bar
-                        .set_oneoff_error_text("Bookmark name shouldn't be empty");
-                } else if content.len() > BOOKMARK_NAME_MAX_LEN {
-                    self.status_bar.set_oneoff_error_text(&format!(
-                        "Bookmark name should have no more than {BOOKMARK_NAME_MAX_LEN} chars"
-                    ));
-                } else {
-                    let line = &self.document.query_lines(self.window.offset(), 1)?[0];
-                    self.bookmark_store
-                        .new_bookmark(&content, self.window.offset(), line);
-                    self.status_bar
-                        .set_oneoff_error_text(&format!("Bookmark saved: {content}"));
+                match parse_command(&content) {
+                    std::result::Result::Ok(command) => return self.execute_command(command),
+                    Err(e) => self.status_bar.set_oneoff_error_text(&e.to_string()),
                 }
             }
         }
-        Ok(())
+        Ok(false)
     }
 
-    fn on_bookmark_menu_event(&mut self, action: BookmarkMenuAction) -> Result<()> {
-        if action == BookmarkMenuAction::Enter {
-            if let Some((bookmark_name, offset, _)) = self.bookmark_store.handle_enter_event() {
-                self.window.set_offset(*offset);
-                self.status_bar
-                    .set_oneoff_error_text(&format!("Jumped to bookmark: {bookmark_name}"));
+    fn execute_command(&mut self, command: Command) -> Result<bool> {
+        info!("[execute_command] {:?}", command);
+        match command {
+            Command::Edit(filename) => self.open_file(&filename)?,
+            Command::Write(filename, color) => {
+                let written = File::create(&filename).and_then(|file| {
+                    let mut writer = BufWriter::new(file);
+                    stream_filtered(
+                        &mut self.document,
+                        &mut self.finder,
+                        &self.config.line_delimiter,
+                        color,
+                        &mut writer,
+                    )
+                    .map_err(std::io::Error::other)
+                });
+                match written {
+                    std::result::Result::Ok(()) => self
+                        .status_bar
+                        .set_oneoff_info_text(&format!("Written to {filename}")),
+                    Err(e) => self
+                        .status_bar
+                        .set_oneoff_error_text(&format!("Cannot write {filename}: {e}")),
+                }
             }
-        } else {
-            self.bookmark_store.handle_other_event(action);
+            Command::Filter(expr) => self.set_view_filter(expr.as_deref()),
+            Command::WriteMatches(filename) => {
+                if self.collect_matches()? {
+                    self.write_matches(&filename)?;
+                }
+            }
+            Command::Set(Setting::Wrap(wrap_lines)) => self.set_wrap_lines(wrap_lines),
+            Command::Set(Setting::Plain(plain_mode)) => self.set_plain_mode(plain_mode),
+            Command::Set(Setting::Invisibles(show_invisibles)) => {
+                self.context.show_invisibles = show_invisibles
+            }
+            Command::Set(Setting::ContextLines(context_lines)) => {
+                self.set_context_lines(context_lines)
+            }
+            Command::Set(Setting::StepLineMatches(step_line_matches)) => {
+                self.context.step_line_matches = step_line_matches
+            }
+            Command::Set(Setting::ScrollOff(scroll_off)) => {
+                self.context.scroll_off = scroll_off;
+                if scroll_off.is_some() {
+                    self.context.configured_scroll_off = scroll_off;
+                }
+            }
+            Command::LoadProfile(name) => {
+                let loaded = FilterProfile::load(&name)
+                    .and_then(|profile| self.finder.apply_filter_profile(&profile));
+                match loaded {
+                    std::result::Result::Ok(()) => {
+                        self.watch_profile(&name);
+                        self.status_bar
+                            .set_oneoff_info_text(&format!("Loaded profile {name}"))
+                    }
+                    Err(e) => self.status_bar.set_oneoff_error_text(&e.to_string()),
+                }
+            }
+            Command::Trim => self.trim_above_top()?,
+            Command::Untrim => self.untrim()?,
+            Command::ReloadConfig => {
+                // changes seen here aren't reloaded again on the next check
+                if let Some(stamp) = self.config_stamp.as_mut() {
+                    stamp.refresh();
+                }
+                if let Some((_, stamp)) = self.active_profile.as_mut() {
+                    stamp.refresh();
+                }
+                let mut text = match self.reload_config() {
+                    std::result::Result::Ok(changes) => config_reloaded_text(&changes),
+                    Err(e) => {
+                        self.status_bar
+                            .set_oneoff_error_text(&format!("Config not reloaded: {e}"));
+                        return Ok(false);
+                    }
+                };
+                if let Some((name, _)) = self.active_profile.as_ref() {
+                    let name = name.clone();
+                    if let Err(e) = self.reload_profile(&name) {
+                        self.status_bar
+                            .set_oneoff_error_text(&format!("Profile not reloaded: {e}"));
+                        return Ok(false);
+                    }
+                    text.push_str(&format!(", reloaded profile {name}"));
+                }
+                self.status_bar.set_oneoff_info_text(&text);
+            }
+            Command::DumpFrame(filename) => self.dump_frame(&filename),
+            Command::FollowUntil(pattern) => {
+                self.enter_follow_until_match_mode(pattern.as_deref())?
+            }
+            Command::JumpToTimestamp(timestamp) => self.jump_to_timestamp(&timestamp)?,
+            Command::GotoLine(line_number) => self.goto_line(line_number)?,
+            Command::Help => self.status_bar.set_oneoff_info_text(&help_text()),
+            Command::Quit => return Ok(true),
         }
-        Ok(())
+        Ok(false)
     }
 
-    fn on_finder_event(&mut self, action: FinderAction) -> Result<()> {
-        if action == FinderAction::AddActiveSlotStart {
-            self.status_bar.set_text("Adding Finder active slot ...");
-        } else if action == FinderAction::RemoveActiveSlotStart {
-            if self.finder.active_slots().len() == 1 {
-                self.event_source.cancel_remove_finder_active_slot();
-                self.status_bar
-                    .set_oneoff_error_text("Cannot remove active slot when there is only one");
-            } else {
-                self.status_bar.set_text("Removing Finder active slot ...");
-            }
-        } else {
-            self.status_bar.clear_text();
-            self.finder.handle_event(action);
+    fn trim_above_top(&mut self) -> Result<()> {
+        let top = self.window.offset();
+        if top == 0 {
+            self.status_bar
+                .set_oneoff_error_text("Nothing above the top line to trim");
+            return Ok(());
         }
+        self.rebase_document(self.document.bytes_skipped() + top, 0)?;
+        self.context.trimmed += top;
+        self.update_indicator();
+        self.status_bar
+            .set_oneoff_info_text(&format!("Trimmed {} above the top line", format_bytes(top)));
         Ok(())
     }
 
-    fn enter_follow_mode(&mut self) -> Result<()> {
-        assert_eq!(self.mode, Mode::Normal);
-        self.seek_to_end()?;
-        self.mode = Mode::Follow;
+    fn untrim(&mut self) -> Result<()> {
+        let trimmed = std::mem::take(&mut self.context.trimmed);
+        if trimmed == 0 {
+            self.status_bar.set_oneoff_error_text("Nothing trimmed");
+            return Ok(());
+        }
+        let top = self.window.offset() + trimmed;
+        self.rebase_document(self.document.bytes_skipped() - trimmed, top)?;
+        self.update_indicator();
         self.status_bar
-            .set_text("Waiting for data... (interrupt to abort)");
+            .set_oneoff_info_text(&format!("Untrimmed {}", format_bytes(trimmed)));
         Ok(())
     }
 
-    fn ensure_consistency(&mut self) -> Result<()> {
-        assert!(self.window.offset() <= self.document.last_line_start_offset());
-        if self.window.offset() < self.document.last_line_start_offset() {
-            self.document
-                .assert_offset_is_at_line_start(self.window.offset())?;
-        }
+    // moves the document start to `bytes_skipped` into the file, keeping what's still above it
+    // at its place and dropping the positions which no longer are
+    fn rebase_document(&mut self, bytes_skipped: usize, top: usize) -> Result<()> {
+        let old_start = self.document.bytes_skipped();
+        self.document.set_bytes_skipped(bytes_skipped)?;
+        self.continuation_folder.rebase(old_start, bytes_skipped);
+        self.bookmark_store.set_document_start(bytes_skipped);
+        self.window.rebase_offset(top);
+        self.minimap.forget_density();
+        self.match_list = MatchList::default();
+        self.context.revealed = None;
+        self.context.current_match = None;
+        self.context.single_line_match = None;
+        self.context.line_number_anchor = None;
+        self.context.restored_offset = None;
         Ok(())
     }
+
+    // 1-based, the last line if there are fewer
+    fn goto_line(&mut self, line_number: usize) -> Result<()> {
+        let offset = self
+            .document
+            .query_distance_to_below_n_lines(0, line_number.saturating_sub(1))?;
+        self.reveal_offset(offset)
+    }
+
+    fn toggle_collapse_continuations(&mut self) -> Result<()> {
+        if self.continuation_folder.is_enabled() {
+            self.continuation_folder.disable();
+            self.status_bar
+                .set_oneoff_info_text("Continuation lines shown");
+            return Ok(());
+        }
+        match self
+            .document
+            .detect_log_timestamp_format(self.window.offset())?
+        {
+            Some(fmt) => {
+                self.continuation_folder.enable(&fmt);
+                self.status_bar
+                    .set_oneoff_info_text("Lines without timestamp collapsed");
+            }
+            None => self
+                .status_bar
+                .set_oneoff_error_text("Cannot collapse: no timestamp format detected"),
+        }
+        Ok(())
+    }
+
+    // the first trace in the window, collapsed or not
+    fn toggle_expand_trace(&mut self) {
+        let parent_offset = self
+            .context
+            .raw_lines_buffer
+            .iter()
+            .take(self.window.height)
+            .map(|line| line.offset)
+            .find(|offset| {
+                self.context.hidden_continuations.contains_key(offset)
+                    || self.continuation_folder.is_expanded(*offset)
+            });
+        match parent_offset {
+            Some(parent_offset) => self.continuation_folder.toggle_expanded(parent_offset),
+            None => self
+                .status_bar
+                .set_oneoff_error_text("No trace in the window"),
+        }
+    }
+
+    fn on_window_move_event(&mut self, direction: Direction, step: usize) -> Result<()> {
+        if direction.is_vertical() {
+            self.context.last_vertical_direction = Some(direction);
+            if self.context.resume_wrap() {
+                self.window.horizontal_shift = 0;
+                self.update_indicator();
+            }
+        }
+        if direction.is_vertical() && self.max_row_shift() > 0 {
+            self.scroll_single_line_rows(direction, step);
+            return Ok(());
+        }
+        match direction {
+            Direction::Up => {
+                let distance = self.continuation_folder.query_distance_to_above_n_lines(
+                    &mut self.document,
+                    self.window.offset(),
+                    step,
+                )?;
+                if distance == 0 {
+                    self.give_boundary_feedback("Already at top");
+                }
+                self.window.move_offset_by(distance, direction);
+            }
+            Direction::Down => {
+                let distance = self.continuation_folder.query_distance_to_below_n_lines(
+                    &mut self.document,
+                    self.window.offset(),
+                    step,
+                )?;
+                if distance == 0 {
+                    self.give_boundary_feedback("Already at end");
+                }
+                self.window.move_offset_by(distance, direction);
+            }
+            Direction::Left => {
+                // nothing to reveal on the left of the first column
+                if self.window.horizontal_shift > 0 && self.context.pause_wrap() {
+                    self.update_indicator();
+                }
+                if !self.context.wrap_lines {
+                    self.window.horizontal_shift =
+                        self.window.horizontal_shift.saturating_sub(step);
+                    if self.window.horizontal_shift == 0 && self.context.resume_wrap() {
+                        self.update_indicator();
+                    }
+                }
+            }
+            Direction::Right => {
+                if self.context.pause_wrap() {
+                    self.update_indicator();
+                }
+                if !self.context.wrap_lines {
+                    let max_line_len = self
+                        .context
+                        .raw_lines_buffer
+                        .iter()
+                        .map(|line| {
+                            if self.context.show_invisibles {
+                                width_with_invisibles(&line.text)
+                            } else {
+                                line.text.len()
+                            }
+                        })
+                        .max()
+                        .unwrap();
+                    let max_window_shift = max_line_len.saturating_sub(self.window.width);
+                    self.window.horizontal_shift =
+                        std::cmp::min(self.window.horizontal_shift + step, max_window_shift);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // scrolls through the rows of a single wrapped line, as there's no other line to move to
+    fn scroll_single_line_rows(&mut self, direction: Direction, step: usize) {
+        let row_shift = std::cmp::min(self.window.row_shift, self.max_row_shift());
+        let new_row_shift = if direction == Direction::Up {
+            row_shift.saturating_sub(step)
+        } else {
+            std::cmp::min(row_shift + step, self.max_row_shift())
+        };
+        if new_row_shift == row_shift {
+            self.give_boundary_feedback(if direction == Direction::Up {
+                "Already at top"
+            } else {
+                "Already at end"
+            });
+        }
+        self.window.row_shift = new_row_shift;
+    }
+
+    // tell that a scroll has hit the document start or end, rather than silently doing nothing
+    fn give_boundary_feedback(&mut self, text: &str) {
+        match self.context.boundary_feedback {
+            BoundaryFeedback::Flash => {
+                self.status_bar.set_oneoff_error_text(text);
+                self.status_bar.mark_oneoff_notified();
+            }
+            BoundaryFeedback::Bell => self.canvas.bell = true,
+            BoundaryFeedback::Off => {}
+        }
+    }
+
+    fn on_search_event(&mut self, action: PromptAction) -> Result<()> {
+        match action {
+            PromptAction::Start(direction) => {
+                assert!(direction.unwrap().is_vertical());
+                if self.finder.active_slots().len() > 1 {
+                    self.event_source.exit_search_prompt();
+                    self.status_bar.set_oneoff_error_text(
+                        "Cannot search with more than one active Finder slot",
+                    );
+                } else {
+                    self.context.searching_direction = direction;
+                    self.context.on_search_started();
+                    self.status_bar.set_text("Search: ");
+                }
+            }
+            PromptAction::Content(content) => {
+                self.status_bar.set_text(&format!("Search: {content}"));
+                self.finder.set_provisional_pattern(Some(&content));
+            }
+            PromptAction::Cancel => {
+                self.context.searching_direction = None;
+                self.status_bar.clear_text();
+                self.finder.set_provisional_pattern(None);
+            }
+            PromptAction::Enter(content) => {
+                assert_eq!(self.finder.active_slots().len(), 1);
+                self.status_bar.clear_text();
+                self.finder.set_provisional_pattern(None);
+                if content.is_empty() {
+                    self.status_bar.set_oneoff_error_text("Nothing to search");
+                } else {
+                    self.finder.update_search_pattern(&content);
+                    let direction = self.context.searching_direction.take().unwrap();
+                    self.context.last_search_direction = Some(direction);
+                    self.context.last_search_slot = self.finder.active_slots().first().copied();
+                    self.search_next(direction, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn search_next(&mut self, direction: Direction, from_next_event: bool) -> Result<()> {
+        let started = self.timing.start();
+        let result = self.search_next_untimed(direction, from_next_event);
+        self.timing.record(Phase::Search, started);
+        result
+    }
+
+    fn search_next_untimed(&mut self, direction: Direction, from_next_event: bool) -> Result<()> {
+        assert!(direction.is_vertical());
+        self.session_stats.searches += 1;
+        if self.document.is_single_line() {
+            return self.search_in_single_line(direction, from_next_event);
+        }
+        if from_next_event && self.context.step_line_matches && self.step_in_line(direction)? {
+            return Ok(());
+        }
+        // continue from the last match rather than the window top, which may be above it
+        let offset = match self.context.revealed {
+            Some((window_offset, target_offset))
+                if from_next_event && window_offset == self.window.offset() =>
+            {
+                target_offset
+            }
+            _ => self.window.offset(),
+        };
+        let search_predict = |line: &str| self.finder.can_satisfy_active_search_patterns(line);
+        let mut extra_distance = 0;
+        let distance = if direction == Direction::Up {
+            self.document
+                .query_distance_to_prev_match(offset, search_predict)?
+        } else {
+            if from_next_event {
+                extra_distance = self.document.query_distance_to_below_n_lines(offset, 1)?;
+            }
+            self.document
+                .query_distance_to_next_match(offset + extra_distance, search_predict)?
+        };
+        if let Some(distance) = distance {
+            let target_offset = if direction == Direction::Up {
+                offset - distance
+            } else {
+                offset + extra_distance + distance
+            };
+            self.land_on_match(target_offset, direction)?;
+        } else {
+            self.session_stats.search_misses += 1;
+            self.status_bar.set_oneoff_error_text("Not found");
+            self.notify(NotifyEvent::NotFound, "Not found");
+        }
+        Ok(())
+    }
+
+    // lands on the first match of the line, or the last one when stepping through them upwards
+    fn land_on_match(&mut self, target_offset: usize, direction: Direction) -> Result<()> {
+        // a match inside a collapsed trace is shown rather than jumped over
+        if let Some(parent_offset) = self
+            .continuation_folder
+            .query_parent_offset(&mut self.document, target_offset)?
+        {
+            if self.continuation_folder.is_collapsed(parent_offset) {
+                self.continuation_folder.toggle_expanded(parent_offset);
+            }
+        }
+        self.reveal_offset(target_offset)?;
+        self.context.on_search_landed(target_offset);
+        let line = self.document.query_lines(target_offset, 1)?.remove(0).text;
+        if !self.context.step_line_matches {
+            if let LineMatch::MatchAt(range) = self.finder.active_search_match(&line) {
+                self.reveal_match_column(range);
+            }
+            return Ok(());
+        }
+        let ranges = self.finder.active_search_match_ranges(&line);
+        self.context.line_match_index = match direction {
+            Direction::Up => ranges.len().saturating_sub(1),
+            _ => 0,
+        };
+        if let Some(range) = ranges.get(self.context.line_match_index) {
+            self.reveal_match_column(range.clone());
+            self.report_line_match(&ranges);
+        }
+        Ok(())
+    }
+
+    // moves to the next match in `direction` on the line the last search landed on, if it's
+    // still where the search left the window and has one
+    fn step_in_line(&mut self, direction: Direction) -> Result<bool> {
+        let target_offset = match (self.context.revealed, self.context.current_match) {
+            (Some((window_offset, target_offset)), Some(current_match))
+                if window_offset == self.window.offset() && target_offset == current_match =>
+            {
+                target_offset
+            }
+            _ => return Ok(false),
+        };
+        let line = self.document.query_lines(target_offset, 1)?.remove(0).text;
+        let ranges = self.finder.active_search_match_ranges(&line);
+        let index = self.context.line_match_index;
+        let index = match direction {
+            Direction::Up => match std::cmp::min(index, ranges.len()).checked_sub(1) {
+                Some(index) => index,
+                None => return Ok(false),
+            },
+            _ if index + 1 < ranges.len() => index + 1,
+            _ => return Ok(false),
+        };
+        self.context.line_match_index = index;
+        self.reveal_match_column(ranges[index].clone());
+        self.report_line_match(&ranges);
+        Ok(true)
+    }
+
+    // on a line with several matches, which of them the search is at
+    fn report_line_match(&mut self, ranges: &[Range<usize>]) {
+        if ranges.len() < 2 {
+            return;
+        }
+        let index = self.context.line_match_index;
+        self.status_bar.set_oneoff_info_text(&format!(
+            "Match {} of {} in line, column {}",
+            index + 1,
+            ranges.len(),
+            ranges[index].start + 1
+        ));
+    }
+
+    // a match beyond the right edge is scrolled to when lines are not wrapped
+    fn reveal_match_column(&mut self, range: Range<usize>) {
+        if !self.context.wrap_lines {
+            self.window.horizontal_shift =
+                reveal_column_shift(range, self.window.horizontal_shift, self.body_width());
+        }
+    }
+
+    // searches among the columns of the only line, scrolling the match into view
+    fn search_in_single_line(&mut self, direction: Direction, from_next_event: bool) -> Result<()> {
+        let line = self.document.query_lines(0, 1)?.remove(0).text;
+        let starts = self.finder.active_search_match_starts(&line);
+        let width = self.body_width();
+        // from the first column shown, or just past the last match for `n` and `N`
+        let from = match self.context.single_line_match {
+            Some(column) if from_next_event && direction == Direction::Down => column + 1,
+            Some(column) if from_next_event => column,
+            _ if self.context.wrap_lines => {
+                wrapped_row_start(self.window.row_shift, width, self.wrap_indicator())
+            }
+            _ => self.window.horizontal_shift,
+        };
+        let Some(column) = next_match_column(&starts, from, direction) else {
+            self.session_stats.search_misses += 1;
+            self.status_bar.set_oneoff_error_text("Not found");
+            self.notify(NotifyEvent::NotFound, "Not found");
+            return Ok(());
+        };
+        self.context.single_line_match = Some(column);
+        if self.context.wrap_lines {
+            let row = wrapped_row_count(column + 1, width, self.wrap_indicator()) - 1;
+            if row < self.window.row_shift || row >= self.window.row_shift + self.window.height {
+                self.window.row_shift = row;
+            }
+        } else {
+            self.window.horizontal_shift =
+                reveal_column_shift(column..column + 1, self.window.horizontal_shift, width);
+        }
+        self.status_bar
+            .set_oneoff_info_text(&format!("Match at column {}", column + 1));
+        Ok(())
+    }
+
+    // jumps so that the target line has some context above it, see `scroll_off`. the target may
+    // be computed or stale, e.g. a bookmark in a file changed since, so it's snapped first
+    fn reveal_offset(&mut self, target_offset: usize) -> Result<()> {
+        let target_offset = self.document.snap_offset_to_line_start(target_offset)?;
+        let margin = match self.context.scroll_off {
+            Some(ScrollOff::Lines(line_count)) => line_count,
+            Some(ScrollOff::Center) => self.window.height / 2,
+            None => 0,
+        };
+        let margin = std::cmp::min(margin, self.window.height.saturating_sub(1));
+        let window_offset = reveal_window_offset(&mut self.document, target_offset, margin)?;
+        self.window.set_offset(window_offset);
+        self.context.revealed = Some((window_offset, target_offset));
+        Ok(())
+    }
+
+    fn toggle_scroll_off(&mut self) {
+        self.context.scroll_off = match self.context.scroll_off {
+            Some(_) => None,
+            // center when nothing is configured
+            None => Some(
+                self.context
+                    .configured_scroll_off
+                    .unwrap_or(ScrollOff::Center),
+            ),
+        };
+        let text = match self.context.scroll_off {
+            Some(ScrollOff::Lines(line_count)) => format!("Scroll-off: {line_count} lines"),
+            Some(ScrollOff::Center) => "Scroll-off: center".to_string(),
+            None => "Scroll-off: off".to_string(),
+        };
+        self.status_bar.set_oneoff_info_text(&text);
+    }
+
+    fn seek_to_end(&mut self) -> Result<()> {
+        // a collapsed trace at the end is shown as its parent
+        let last_line_start_offset = self.document.last_line_start_offset();
+        let last_shown_offset = match self
+            .continuation_folder
+            .query_parent_offset(&mut self.document, last_line_start_offset)?
+        {
+            Some(parent_offset) if self.continuation_folder.is_collapsed(parent_offset) => {
+                parent_offset
+            }
+            _ => last_line_start_offset,
+        };
+        let distance = self.continuation_folder.query_distance_to_above_n_lines(
+            &mut self.document,
+            last_shown_offset,
+            self.window.height.saturating_sub(1),
+        )?;
+        self.window
+            .set_offset(last_shown_offset.saturating_sub(distance));
+        // clamped once laid out
+        self.window.row_shift = usize::MAX;
+        Ok(())
+    }
+
+    fn on_jump_to_timestamp_event(&mut self, action: PromptAction) -> Result<()> {
+        match action {
+            PromptAction::Start(direction) => {
+                assert!(direction.is_none());
+                self.status_bar.set_text("Jump to timestamp: ");
+            }
+            PromptAction::Content(content) => {
+                self.status_bar
+                    .set_text(&format!("Jump to timestamp: {content}"));
+            }
+            PromptAction::Cancel => {
+                self.status_bar.clear_text();
+            }
+            PromptAction::Enter(content) => {
+                self.status_bar.clear_text();
+                self.jump_to_timestamp(&content)?;
+            }
+        }
+        Ok(())
+    }
+
+    // `<` or `>` before the timestamp only looks above or below the current line
+    fn jump_to_timestamp(&mut self, content: &str) -> Result<()> {
+        let content = content.trim_start();
+        let (direction, timestamp) = if let Some(rest) = content.strip_prefix('<') {
+            (Some(Direction::Up), rest)
+        } else if let Some(rest) = content.strip_prefix('>') {
+            (Some(Direction::Down), rest)
+        } else {
+            (None, content)
+        };
+        let (date, time, timezone) = parse_log_timestamp(timestamp.trim_start());
+        let Some(time) = time else {
+            self.status_bar.set_oneoff_error_text("Invalid timestamp");
+            return Ok(());
+        };
+        // the head of the file may be a banner without timestamps, so also look around what is
+        // currently shown
+        let offset = self.window.offset();
+        let Some(fmt) = self.document.detect_log_timestamp_format(offset)? else {
+            self.status_bar
+                .set_oneoff_error_text("Cannot detect timestamp format");
+            return Ok(());
+        };
+        if let Some(found) = self
+            .document
+            .query_nearest_offset_by_timestamp(offset, direction, date, time, timezone)?
+        {
+            self.reveal_offset(found.offset())?;
+            let clamped = match found {
+                TimestampMatch::Found(_) => None,
+                TimestampMatch::ClampedToFirst(_, datetime) => Some(("first", datetime)),
+                TimestampMatch::ClampedToLast(_, datetime) => Some(("last", datetime)),
+            };
+            if let Some((which, datetime)) = clamped {
+                self.status_bar.set_oneoff_info_text(&format!(
+                    "Clamped to {which} timestamp {}",
+                    datetime.format("%H:%M:%S")
+                ));
+            }
+        } else if let Some(direction) = direction {
+            self.status_bar.set_oneoff_error_text(&format!(
+                "No {} {} the top line",
+                timestamp.trim(),
+                direction.above_or_below()
+            ));
+        } else {
+            self.status_bar
+                .set_oneoff_error_text(&format!("Cannot jump to timestamp (timestamps: {fmt})"));
+        }
+        Ok(())
+    }
+
+    fn on_jump_by_lines_event(&mut self, action: PromptAction) -> Result<()> {
+        match action {
+            PromptAction::Start(direction) => {
+                assert!(direction.unwrap().is_vertical());
+                self.context.jumping_direction = direction;
+                let s = direction.as_ref().unwrap().above_or_below();
+                self.status_bar.set_text(&format!("Jump to {s} N lines: "));
+            }
+            PromptAction::Content(content) => {
+                let s = self
+                    .context
+                    .jumping_direction
+                    .as_ref()
+                    .unwrap()
+                    .above_or_below();
+                self.status_bar
+                    .set_text(&format!("Jump to {s} N lines: {content}"));
+            }
+            PromptAction::Cancel => {
+                self.context.jumping_direction = None;
+                self.status_bar.clear_text();
+            }
+            PromptAction::Enter(content) => {
+                self.status_bar.clear_text();
+                if let std::result::Result::Ok(step) = content.parse::<usize>() {
+                    self.on_window_move_event(self.context.jumping_direction.unwrap(), step)?;
+                } else {
+                    self.status_bar.set_oneoff_error_text("Invalid line count");
+                }
+                self.context.jumping_direction = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_new_bookmark_event(&mut self, action: PromptAction) -> Result<()> {
+        match action {
+            PromptAction::Start(direction) => {
+                assert!(direction.is_none());
+                self.status_bar.set_text("New bookmark: ");
+            }
+            PromptAction::Content(content) => {
+                self.status_bar
+                    .set_text(&format!("New bookmark: {content}"));
+            }
+            PromptAction::Cancel => {
+                self.status_bar.clear_text();
+            }
+            PromptAction::Enter(content) => {
+                self.status_bar.clear_text();
+                if content.is_empty() {
+                    self.status_bar
+                        .set_oneoff_error_text("Bookmark name shouldn't be empty");
+                } else if content.len() > BOOKMARK_NAME_MAX_LEN {
+                    self.status_bar.set_oneoff_error_text(&format!(
+                        "Bookmark name should have no more than {BOOKMARK_NAME_MAX_LEN} chars"
+                    ));
+                } else {
+                    let line = &self.document.query_lines(self.window.offset(), 1)?[0];
+                    let slot_matches = self.finder.active_slot_matches(&line.text);
+                    self.bookmark_store.new_bookmark(
+                        &content,
+                        line.offset,
+                        &line.text,
+                        &slot_matches,
+                    );
+                    self.status_bar
+                        .set_oneoff_info_text(&format!("Bookmark saved: {content}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn on_bookmark_menu_event(&mut self, action: BookmarkMenuAction) -> Result<()> {
+        if action == BookmarkMenuAction::Enter {
+            if let Some(bookmark) = self.bookmark_store.handle_enter_event() {
+                let (bookmark_name, offset) = (bookmark.name.clone(), bookmark.offset);
+                self.reveal_offset(offset)?;
+                self.status_bar
+                    .set_oneoff_info_text(&format!("Jumped to bookmark: {bookmark_name}"));
+            }
+        } else {
+            self.bookmark_store.handle_other_event(action);
+        }
+        Ok(())
+    }
+
+    fn on_match_list_event(&mut self, action: MatchListAction) -> Result<()> {
+        match action {
+            MatchListAction::Start => {
+                if self.collect_matches()? {
+                    self.match_list.open();
+                } else {
+                    self.event_source.close_match_list();
+                }
+            }
+            MatchListAction::Arrow(direction) => self.match_list.move_selection(direction),
+            MatchListAction::Enter => {
+                if let Some((index, offset)) = self.match_list.close() {
+                    self.reveal_offset(offset)?;
+                    self.status_bar.set_oneoff_info_text(&format!(
+                        "Jumped to match {} of {}",
+                        index + 1,
+                        self.match_list.offsets().len()
+                    ));
+                }
+            }
+            MatchListAction::Cancel => {
+                self.match_list.close();
+            }
+        }
+        Ok(())
+    }
+
+    // scans for the lines matching the only active slot, unless the list is still fresh.
+    // returns false with an error shown if there's nothing to collect or ctrl-c is pressed
+    fn collect_matches(&mut self) -> Result<bool> {
+        let active_slots = self.finder.active_slots();
+        if active_slots.len() != 1 {
+            self.status_bar
+                .set_oneoff_error_text("Matches are collected from a single active slot");
+            return Ok(false);
+        }
+        let slot_index = *active_slots.first().unwrap();
+        let Some((pattern_type, pattern)) = self.finder.slot_pattern(slot_index) else {
+            self.status_bar
+                .set_oneoff_error_text(&format!("No pattern in slot {slot_index}"));
+            return Ok(false);
+        };
+        let key = CollectKey {
+            slot_index,
+            pattern_type,
+            pattern: pattern.to_string(),
+            document_size: self.document.document_size(),
+        };
+        if self.match_list.is_fresh(&key) {
+            return Ok(true);
+        }
+        let finder = &self.finder;
+        let event_source = &mut self.event_source;
+        let offsets = self.document.match_offsets(
+            |line| finder.can_satisfy_slot_pattern(slot_index, line),
+            || event_source.check_for_interrupt().unwrap_or(true),
+        )?;
+        let Some(offsets) = offsets else {
+            self.status_bar
+                .set_oneoff_error_text("Collecting matches interrupted");
+            return Ok(false);
+        };
+        self.match_list.set(key, offsets);
+        Ok(true)
+    }
+
+    // one line per match: its offset, a tab and its text
+    fn write_matches(&mut self, filename: &str) -> Result<()> {
+        let written = File::create(filename).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for offset in self.match_list.offsets() {
+                let text = self
+                    .document
+                    .query_lines(*offset, 1)
+                    .map_err(std::io::Error::other)?
+                    .first()
+                    .map(|line| line.text.clone())
+                    .unwrap_or_default();
+                writeln!(writer, "{offset}\t{text}")?;
+            }
+            writer.flush()
+        });
+        match written {
+            std::result::Result::Ok(()) => self.status_bar.set_oneoff_info_text(&format!(
+                "Written {} matches to {filename}",
+                self.match_list.offsets().len()
+            )),
+            Err(e) => self
+                .status_bar
+                .set_oneoff_error_text(&format!("Cannot write {filename}: {e}")),
+        }
+        Ok(())
+    }
+
+    // a message is over the current match in the status bar, so it goes first
+    fn topmost_transient(&self) -> Option<Transient> {
+        if self.status_bar.has_oneoff_text() {
+            Some(Transient::StatusMessage)
+        } else if self.context.current_match.is_some() {
+            Some(Transient::CurrentMatch)
+        } else {
+            None
+        }
+    }
+
+    fn dismiss(&mut self) {
+        match self.topmost_transient() {
+            Some(Transient::StatusMessage) => self.status_bar.clear_oneoff_text(),
+            Some(Transient::CurrentMatch) => self.context.current_match = None,
+            None => {}
+        }
+    }
+
+    // the screen is left empty for the shell, and drawn whole again once resumed
+    fn suspend(&mut self) -> Result<()> {
+        self.render_sink.clear()?;
+        self.context.body_dirty = true;
+        match suspend(self.terminal.as_mut()) {
+            std::result::Result::Ok((width, height)) => self.window.resize(width, height),
+            Err(e) => self.status_bar.set_oneoff_error_text(&e.to_string()),
+        }
+        Ok(())
+    }
+
+    // the frame last drawn, i.e. the one on screen as the command is entered
+    fn dump_frame(&mut self, filename: &str) {
+        let dump = FrameDump {
+            width: self.window.width,
+            height: self.window.height,
+            canvas: self.canvas.clone(),
+        };
+        let written = File::create(filename)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| dump.write_json(BufWriter::new(file)));
+        match written {
+            std::result::Result::Ok(()) => self
+                .status_bar
+                .set_oneoff_info_text(&format!("Frame dumped to {filename}")),
+            Err(e) => self
+                .status_bar
+                .set_oneoff_error_text(&format!("Cannot write {filename}: {e}")),
+        }
+    }
+
+    fn on_select_event(&mut self, action: SelectAction) -> Result<()> {
+        match action {
+            SelectAction::Start => {
+                let Some(top_line) = self.context.raw_lines_buffer.first() else {
+                    self.event_source.cancel_selection();
+                    self.status_bar.set_oneoff_error_text("Nothing to select");
+                    return Ok(());
+                };
+                self.context.selection = Some(Selection::new(top_line.offset));
+                self.update_selection_text();
+            }
+            SelectAction::Extend(direction) => {
+                let Some(mut selection) = self.context.selection else {
+                    return Ok(());
+                };
+                match self.next_visible_line(selection.cursor(), direction)? {
+                    Some(offset) => {
+                        selection.extend_to(offset);
+                        self.context.selection = Some(selection);
+                        self.scroll_to_line(offset)?;
+                    }
+                    None if direction == Direction::Up => {
+                        self.give_boundary_feedback("Already at top")
+                    }
+                    None => self.give_boundary_feedback("Already at end"),
+                }
+                self.update_selection_text();
+            }
+            SelectAction::Copy => {
+                let text = self.selected_lines()?.join("\n");
+                self.end_selection();
+                match self.clipboard.copy(&text) {
+                    std::result::Result::Ok(()) => self
+                        .status_bar
+                        .set_oneoff_info_text(&format!("Copied {} lines", text.lines().count())),
+                    Err(e) => self
+                        .status_bar
+                        .set_oneoff_error_text(&format!("Cannot copy selection: {e}")),
+                }
+            }
+            SelectAction::Cancel => self.end_selection(),
+        }
+        Ok(())
+    }
+
+    fn on_write_selection_event(&mut self, action: PromptAction) -> Result<()> {
+        match action {
+            PromptAction::Start(_) => self.status_bar.set_text("Write selection to: "),
+            PromptAction::Content(content) => self
+                .status_bar
+                .set_text(&format!("Write selection to: {content}")),
+            PromptAction::Cancel => self.end_selection(),
+            PromptAction::Enter(filename) => {
+                let lines = self.selected_lines()?;
+                self.end_selection();
+                let delimiter = &self.config.line_delimiter;
+                let written = File::create(&filename).and_then(|file| {
+                    let mut writer = BufWriter::new(file);
+                    for line in lines.iter() {
+                        writer.write_all(line.as_bytes())?;
+                        writer.write_all(delimiter.as_bytes())?;
+                    }
+                    writer.flush()
+                });
+                match written {
+                    std::result::Result::Ok(()) => self.status_bar.set_oneoff_info_text(&format!(
+                        "Written {} lines to {filename}",
+                        lines.len()
+                    )),
+                    Err(e) => self
+                        .status_bar
+                        .set_oneoff_error_text(&format!("Cannot write {filename}: {e}")),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_view_filter(&mut self, expr: Option<&str>) {
+        let Some(expr) = expr else {
+            self.finder.set_view_filter(None);
+            self.status_bar.set_filter(None);
+            self.status_bar.set_oneoff_info_text("Filter removed");
+            return;
+        };
+        match FilterExpr::parse(expr) {
+            std::result::Result::Ok(filter) => {
+                self.status_bar
+                    .set_filter(Some(filter.source().to_string()));
+                self.finder.set_view_filter(Some(filter));
+            }
+            Err(e) => self
+                .status_bar
+                .set_oneoff_error_text(&format!("Invalid filter: {e}")),
+        }
+    }
+
+    fn update_selection_text(&mut self) {
+        if let Some(selection) = self.context.selection {
+            self.status_bar.set_text(&format!(
+                "Selected {} lines (y: copy, w: write, Esc: cancel)",
+                selection.line_count()
+            ));
+        }
+    }
+
+    fn end_selection(&mut self) {
+        self.context.selection = None;
+        self.status_bar.clear_text();
+    }
+
+    // a line passing the filters unless plain mode shows everything, as shown in the body
+    // apart from context lines
+    fn is_line_visible(&self, line: &str) -> bool {
+        let apply_filters = !self.context.plain_mode || self.context.plain_mode_keeps_filters;
+        !apply_filters || self.finder.can_pass_advanced_action(line)
+    }
+
+    // start offset of the closest visible line above or below the one at `offset`
+    fn next_visible_line(
+        &mut self,
+        mut offset: usize,
+        direction: Direction,
+    ) -> Result<Option<usize>> {
+        loop {
+            let distance = if direction == Direction::Up {
+                self.document.query_distance_to_above_n_lines(offset, 1)?
+            } else {
+                self.document.query_distance_to_below_n_lines(offset, 1)?
+            };
+            if distance == 0 {
+                return Ok(None);
+            }
+            offset = if direction == Direction::Up {
+                offset - distance
+            } else {
+                offset + distance
+            };
+            let text = self.document.query_lines(offset, 1)?[0].text.clone();
+            if self.is_line_visible(&text) {
+                return Ok(Some(offset));
+            }
+        }
+    }
+
+    // the visible lines from the first to the last selected one
+    fn selected_lines(&mut self) -> Result<Vec<String>> {
+        let Some(selection) = self.context.selection else {
+            return Ok(vec![]);
+        };
+        let mut lines = vec![];
+        let mut offset = *selection.range().start();
+        loop {
+            let text = self.document.query_lines(offset, 1)?[0].text.clone();
+            if self.is_line_visible(&text) {
+                lines.push(text);
+            }
+            let distance = self.document.query_distance_to_below_n_lines(offset, 1)?;
+            offset += distance;
+            if distance == 0 || offset > *selection.range().end() {
+                return Ok(lines);
+            }
+        }
+    }
+
+    // scrolls just enough for the line at `offset` to be shown, the lines above it in the
+    // window are taken from the last render
+    fn scroll_to_line(&mut self, offset: usize) -> Result<()> {
+        if offset < self.window.offset() {
+            self.window.set_offset(offset);
+            return Ok(());
+        }
+        let mut lines: Vec<Line> = self
+            .context
+            .raw_lines_buffer
+            .iter()
+            .take_while(|line| line.offset < offset)
+            .cloned()
+            .collect();
+        lines.extend(self.document.query_lines(offset, 1)?);
+        let width = self.body_width();
+        let indicator = self.wrap_indicator();
+        let rows_of = |line: &Line| {
+            if self.context.wrap_lines {
+                wrapped_row_count(line.text.len(), width, indicator)
+            } else {
+                1
+            }
+        };
+        let mut rows: usize = lines.iter().map(rows_of).sum();
+        let mut top = 0;
+        while rows > self.window.height && top + 1 < lines.len() {
+            rows -= rows_of(&lines[top]);
+            top += 1;
+        }
+        if top > 0 {
+            self.window.set_offset(lines[top].offset);
+        }
+        Ok(())
+    }
+
+    fn on_finder_event(&mut self, action: FinderAction) -> Result<()> {
+        let active_slots = self
+            .finder
+            .active_slots()
+            .iter()
+            .map(|slot_index| slot_index.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if action == FinderAction::AddActiveSlotStart {
+            self.status_bar
+                .set_text(&format!("Adding Finder active slot to {active_slots} ..."));
+        } else if action == FinderAction::RemoveActiveSlotStart {
+            if self.finder.active_slots().len() == 1 {
+                self.event_source.cancel_remove_finder_active_slot();
+                self.status_bar
+                    .set_oneoff_error_text("Cannot remove the last active slot");
+            } else {
+                self.status_bar.set_text(&format!(
+                    "Removing Finder active slot from {active_slots} ..."
+                ));
+            }
+        } else if matches!(action, FinderAction::RemoveActiveSlot(_))
+            && self.finder.active_slots().len() == 1
+        {
+            // e.g. replayed by a macro recorded with more slots active
+            self.status_bar.clear_text();
+            self.status_bar
+                .set_oneoff_error_text("Cannot remove the last active slot");
+        } else if action == FinderAction::ResetAllStart {
+            self.status_bar
+                .set_text("Reset all Finder slots? Press X again to confirm");
+        } else {
+            self.status_bar.clear_text();
+            if action == FinderAction::ResetAll {
+                self.status_bar.set_oneoff_info_text("Finder reset");
+            }
+            let toggles_spotlight = action == FinderAction::ToggleSpotlight;
+            self.finder.handle_event(action);
+            if toggles_spotlight {
+                self.update_indicator();
+            }
+            if self.finder.is_menu_active() {
+                self.update_finder_match_stats()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_edit_slot_pattern_event(&mut self, action: PromptAction) -> Result<()> {
+        match action {
+            PromptAction::Start(_) => {
+                if self.finder.active_slots().len() > 1 {
+                    self.event_source.exit_slot_edit_prompt();
+                    self.status_bar
+                        .set_oneoff_error_text("Cannot edit with more than one active Finder slot");
+                } else {
+                    let pattern = self
+                        .finder
+                        .active_slot_pattern()
+                        .unwrap_or_default()
+                        .to_string();
+                    self.event_source.prefill_slot_edit_prompt(&pattern);
+                    self.finder.set_menu_prompt(Some(&pattern));
+                }
+            }
+            PromptAction::Content(content) => self.finder.set_menu_prompt(Some(&content)),
+            PromptAction::Cancel => self.finder.set_menu_prompt(None),
+            PromptAction::Enter(content) => {
+                self.finder.set_menu_prompt(None);
+                if content.is_empty() {
+                    self.status_bar
+                        .set_oneoff_error_text("Empty pattern, press x to clear the slot");
+                } else {
+                    self.finder.update_search_pattern(&content);
+                    self.update_finder_match_stats()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // counted lazily for the finder menu, ctrl-c skips the remaining slots
+    fn update_finder_match_stats(&mut self) -> Result<()> {
+        let document_size = self.document.document_size();
+        for slot_index in self.finder.slots_without_match_stats(document_size) {
+            let finder = &self.finder;
+            let event_source = &mut self.event_source;
+            let match_count = self.document.count_matches(
+                |line| finder.can_satisfy_slot_pattern(slot_index, line),
+                || event_source.check_for_interrupt().unwrap_or(true),
+            )?;
+            let Some(match_count) = match_count else {
+                self.status_bar
+                    .set_oneoff_error_text("Counting matches interrupted");
+                return Ok(());
+            };
+            let stats = MatchStats {
+                count: match_count.count,
+                first_match_ratio: match_count
+                    .first_offset
+                    .map(|offset| self.document.percent_ratio_of_offset(offset)),
+            };
+            self.finder
+                .set_match_stats(slot_index, document_size, stats);
+        }
+        Ok(())
+    }
+
+    fn toggle_restored_position(&mut self) {
+        let Some(restored_offset) = self.context.restored_offset else {
+            self.status_bar
+                .set_oneoff_error_text("No position restored from last visit");
+            return;
+        };
+        if self.window.offset() == restored_offset {
+            self.window.set_offset(0);
+            self.status_bar
+                .set_oneoff_info_text("Jumped to start of file");
+        } else {
+            self.window.set_offset(restored_offset);
+            self.status_bar
+                .set_oneoff_info_text("Jumped to restored position");
+        }
+    }
+
+    fn enter_follow_mode(&mut self) -> Result<()> {
+        self.start_following(Mode::Follow, "Waiting for data... (interrupt to abort)")
+    }
+
+    // follows until the given pattern, or the active one if `None`, shows up in appended lines
+    fn enter_follow_until_match_mode(&mut self, pattern: Option<&str>) -> Result<()> {
+        if self.finder.active_slots().len() > 1 {
+            self.status_bar
+                .set_oneoff_error_text("Cannot search with more than one active Finder slot");
+            return Ok(());
+        }
+        match pattern {
+            Some(pattern) => self.finder.update_search_pattern(pattern),
+            None if !self.finder.has_active_search_pattern() => {
+                self.status_bar.set_oneoff_error_text("No search pattern");
+                return Ok(());
+            }
+            None => {}
+        }
+        self.start_following(
+            Mode::FollowUntilMatch,
+            "Waiting for a match... (interrupt to abort)",
+        )
+    }
+
+    fn start_following(&mut self, mode: Mode, text: &str) -> Result<()> {
+        assert_eq!(self.mode, Mode::Normal);
+        self.seek_to_end()?;
+        self.mode = mode;
+        self.status_bar.set_text(text);
+        Ok(())
+    }
+
+    fn stop_following_at_appended_match(&mut self, appended: Range<usize>) -> Result<()> {
+        let search_predict = |line: &str| self.finder.can_satisfy_active_search_patterns(line);
+        let Some(target_offset) =
+            query_appended_match(&mut self.document, appended, search_predict)?
+        else {
+            return Ok(());
+        };
+        self.mode = Mode::Normal;
+        self.status_bar.clear_text();
+        self.land_on_match(target_offset, Direction::Down)?;
+        self.context.last_search_direction = Some(Direction::Down);
+        let text = "Pattern found, stopped following";
+        self.status_bar.set_oneoff_alert_text(text);
+        self.notify(NotifyEvent::FollowMatch, text);
+        Ok(())
+    }
+
+    // on top of the status bar text already set for the event
+    fn notify(&mut self, event: NotifyEvent, text: &str) {
+        self.status_bar.mark_oneoff_notified();
+        match self.notifier.notify(event, text) {
+            std::result::Result::Ok(Notification::Flash) => self.status_bar.flash(),
+            std::result::Result::Ok(_) => {}
+            Err(e) => warn!("[notify] {e}"),
+        }
+    }
+
+    // window offset should always be at a line start within the document. recover it instead of
+    // crashing in case it's broken by a bug or file change
+    fn ensure_consistency(&mut self) -> Result<()> {
+        let offset = self.window.offset();
+        let adjusted_offset = self.document.snap_offset_to_line_start(offset)?;
+        if adjusted_offset != offset {
+            warn!("[ensure_consistency] window offset {offset} adjusted to {adjusted_offset}");
+            self.window.correct_offset(adjusted_offset);
+            self.status_bar.set_oneoff_error_text("Position adjusted");
+        }
+        Ok(())
+    }
+}
+
+// which ends of the document are visible in a window of `height` lines at `offset`
+fn detect_boundary<R: Read + Seek>(
+    document: &mut Document<R>,
+    offset: usize,
+    height: usize,
+) -> Result<Boundary> {
+    let distance = document.query_distance_to_below_n_lines(offset, height.saturating_sub(1))?;
+    let at_end = offset + distance >= document.last_line_start_offset();
+    Ok(Boundary {
+        at_top: offset == 0,
+        at_end,
+        missing_final_newline: at_end && document.lacks_final_line_delimiter(),
+    })
+}
+
+/// Offset of the first line touched by the `appended` bytes that satisfies `search_predict`.
+/// The line they start in is checked whole, since they may have completed it.
+fn query_appended_match<R: Read + Seek, P: Fn(&str) -> bool>(
+    document: &mut Document<R>,
+    appended: Range<usize>,
+    search_predict: P,
+) -> Result<Option<usize>> {
+    let offset = document.snap_offset_to_line_start(appended.start)?;
+    let distance = document.query_distance_to_next_match(offset, search_predict)?;
+    Ok(distance.map(|distance| offset + distance))
+}
+
+// window offset that puts the line at `target_offset` `margin` lines below the window top, or
+// as far as the document start allows
+fn reveal_window_offset<R: Read + Seek>(
+    document: &mut Document<R>,
+    target_offset: usize,
+    margin: usize,
+) -> Result<usize> {
+    let distance = document.query_distance_to_above_n_lines(target_offset, margin)?;
+    Ok(target_offset - distance)
+}
+
+// how raw lines are laid out into rows of the body area
+struct BodyLayout {
+    width: usize,
+    height: usize,
+    horizontal_shift: usize,
+    // rows of the first line skipped when wrapping
+    row_shift: usize,
+    wrap_lines: bool,
+    max_rows: Option<usize>,
+    wrap_indicator: String,
+    plain_mode: bool,
+    show_invisibles: bool,
+    // start offsets of the first and last selected lines, whose rows are reversed
+    selection: Option<RangeInclusive<usize>>,
+}
+
+impl BodyLayout {
+    // rows filling the body, where those of the line at `marked_offset` are underlined. also
+    // returns whether that line is shown
+    fn lay_out(
+        &self,
+        finder: &mut Finder,
+        lines: &[Line],
+        hidden_continuations: &HashMap<usize, usize>,
+        group_starts: &HashSet<usize>,
+        marked_offset: Option<usize>,
+    ) -> (Vec<LineWithRenderScheme>, bool) {
+        let mut rows = vec![];
+        let mut marked_shown = false;
+        for (index, line) in lines.iter().enumerate() {
+            if rows.len() >= self.height {
+                break;
+            }
+            // rows below the body aren't built
+            let first_row = if index == 0 { self.row_shift } else { 0 };
+            let row_count = self.height - rows.len();
+            let mut line_rows =
+                self.rows_of_line(finder, &line.text, first_row..first_row + row_count);
+            if group_starts.contains(&line.offset) {
+                rows.push(self.marker_row("──".to_string()));
+            }
+            if Some(line.offset) == marked_offset {
+                marked_shown = true;
+                if !self.plain_mode {
+                    line_rows
+                        .iter_mut()
+                        .for_each(|row| row.underline(self.width));
+                }
+            }
+            if self
+                .selection
+                .as_ref()
+                .is_some_and(|selection| selection.contains(&line.offset))
+            {
+                for row in line_rows.iter_mut() {
+                    row.pad_to(self.width);
+                    let len = row.raw_content().len();
+                    row.add_scheme_layered(0..len, RenderScheme::Reverse, SELECTION_LAYER);
+                }
+            }
+            rows.extend(line_rows);
+            if let Some(count) = hidden_continuations.get(&line.offset) {
+                rows.push(self.marker_row(format!("… +{count} lines")));
+            }
+        }
+        (rows, marked_shown)
+    }
+
+    // a dimmed row between lines, like the count of collapsed continuations
+    fn marker_row(&self, marker: String) -> LineWithRenderScheme {
+        let marker: String = marker.chars().take(self.width).collect();
+        if self.plain_mode {
+            return LineWithRenderScheme::new(&marker);
+        }
+        let mut row = LineWithRenderScheme::default();
+        row.push_str_with_scheme(&marker, RenderScheme::Dim);
+        row
+    }
+
+    // the rows of `line` in `rows` when wrapping, or its only row otherwise
+    fn rows_of_line(
+        &self,
+        finder: &mut Finder,
+        line: &str,
+        rows: Range<usize>,
+    ) -> Vec<LineWithRenderScheme> {
+        let part = self.shown_part(line, &rows);
+        let text = &line[part.clone()];
+        let cut_len = line.len() - part.end;
+        // plain mode drops highlights and fold markers, but keeps wrapping and shifting so that
+        // what is selected matches the file
+        let line_with_render_scheme = if self.plain_mode {
+            LineWithRenderScheme::new(text).with_cut_len(cut_len)
+        } else if self.show_invisibles {
+            finder
+                .attach_render_scheme(text)
+                .with_cut_len(cut_len)
+                .with_invisibles()
+        } else {
+            finder.attach_render_scheme(text).with_cut_len(cut_len)
+        };
+        if self.wrap_lines {
+            let max_rows = if self.plain_mode { None } else { self.max_rows };
+            line_with_render_scheme.wrap(self.width, max_rows, &self.wrap_indicator, rows)
+        } else {
+            let start = self.horizontal_shift - part.start;
+            let end = start + self.width;
+            vec![line_with_render_scheme.substr(start..end)]
+        }
+    }
+
+    // the bytes of `line` which may be shown in `rows`, with a margin on both sides. a match
+    // anchored to the line ends may be found at the ends of the part as well, but only within
+    // the margins, which aren't shown. wrapped rows and invisibles count columns from the
+    // start of the line, which is kept for them
+    fn shown_part(&self, line: &str, rows: &Range<usize>) -> Range<usize> {
+        let (start, end) = if self.wrap_lines {
+            let end = wrapped_row_start(rows.end, self.width, &self.wrap_indicator);
+            (0, end)
+        } else if self.show_invisibles {
+            (0, self.horizontal_shift + self.width)
+        } else {
+            let start = self.horizontal_shift.saturating_sub(HIGHLIGHT_MARGIN);
+            (start, self.horizontal_shift + self.width)
+        };
+        let mut start = std::cmp::min(start, line.len());
+        let mut end = std::cmp::min(end + HIGHLIGHT_MARGIN, line.len());
+        while !line.is_char_boundary(start) {
+            start -= 1;
+        }
+        while !line.is_char_boundary(end) {
+            end += 1;
+        }
+        start..end
+    }
+}
+
+// the context lines cycled through, or the first above `current` if it was set otherwise
+fn next_context_lines(current: usize) -> usize {
+    CONTEXT_LINES_CYCLE
+        .into_iter()
+        .find(|context_lines| *context_lines > current)
+        .unwrap_or(0)
+}
+
+// the position of `column` over a line of `line_len`, `End` once the last column is shown
+fn column_ratio(column: usize, shown_len: usize, line_len: usize) -> PositionRatio {
+    if column + shown_len >= line_len {
+        PositionRatio::End
+    } else {
+        PositionRatio::Percent(column * 100 / line_len)
+    }
+}
+
+// horizontal shift showing the match at `range` in a window of `width`, back to no shift if
+// it's within the first screenful, and kept if it's already shown
+fn reveal_column_shift(range: Range<usize>, horizontal_shift: usize, width: usize) -> usize {
+    if range.end <= width {
+        0
+    } else if range.start >= horizontal_shift && range.end <= horizontal_shift + width {
+        horizontal_shift
+    } else {
+        range
+            .start
+            .saturating_sub(std::cmp::min(SEARCH_COLUMN_MARGIN, width / 4))
+    }
+}
+
+// the first match starting at `from` or after it downward, the last one before it upward
+fn next_match_column(starts: &[usize], from: usize, direction: Direction) -> Option<usize> {
+    if direction == Direction::Up {
+        starts.iter().rev().find(|start| **start < from).copied()
+    } else {
+        starts.iter().find(|start| **start >= from).copied()
+    }
+}
+
+fn config_reloaded_text(changes: &ConfigChanges) -> String {
+    if changes.restart_required.is_empty() {
+        return "Config reloaded".to_string();
+    }
+    format!(
+        "Config reloaded, restart to apply: {}",
+        changes.restart_required.join(", ")
+    )
+}
+
+// events only shown in the status bar, like typing into a prompt which doesn't preview anything
+fn leaves_body_unchanged(event: &Event) -> bool {
+    let typing = |action: &PromptAction| !matches!(action, PromptAction::Enter(_));
+    match event {
+        Event::CycleStatusLayout | Event::ToggleMacroRecording => true,
+        Event::Command(action) | Event::JumpToTimestamp(action) | Event::JumpByLines(action) => {
+            typing(action)
+        }
+        _ => false,
+    }
+}
+
+// like less, `n` repeats the last search in its direction while `N` reverses it
+fn resolve_search_direction(last_search_direction: Option<Direction>, reverse: bool) -> Direction {
+    let direction = last_search_direction.unwrap_or(Direction::Down);
+    if reverse {
+        direction.reverse()
+    } else {
+        direction
+    }
+}
+
+// a stream is modified as it comes in, its file times tell nothing
+fn modified_time_of(filename: &str, document: &SourceDocument) -> Option<SystemTime> {
+    if document.reader().is_streamed() {
+        return None;
+    }
+    fs::metadata(filename)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// frames are only timed when a slow one would be told somewhere
+fn timing_enabled(config: &Config) -> bool {
+    config.show_slow_frames || log_enabled!(Level::Warn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        harness::{scripted_manager, writing_manager, GrowingSource},
+        prompt::PromptAction,
+    };
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    fn top_row(manager: &Manager) -> &str {
+        manager.canvas.body_area[0].raw_content().trim_end()
+    }
+
+    fn status_text(manager: &Manager) -> &str {
+        manager.canvas.status_bar.raw_content()
+    }
+
+    #[test]
+    fn test_detect_boundary() {
+        let content = "l0\nl1\nl2\nl3\nl4\nl5";
+        let mut document = Document::new(Cursor::new(content)).unwrap();
+        let boundary = |document: &mut Document<_>, offset, height| {
+            let boundary = detect_boundary(document, offset, height).unwrap();
+            (boundary.at_top, boundary.at_end)
+        };
+        // the whole file fits in one screen
+        assert_eq!(boundary(&mut document, 0, 6), (true, true));
+        assert_eq!(boundary(&mut document, 0, 24), (true, true));
+        assert_eq!(boundary(&mut document, 0, 5), (true, false));
+        assert_eq!(boundary(&mut document, 3, 5), (false, true));
+        assert_eq!(boundary(&mut document, 3, 4), (false, false));
+        assert_eq!(boundary(&mut document, 15, 1), (false, true));
+
+        let mut document = Document::new(Cursor::new("")).unwrap();
+        assert_eq!(boundary(&mut document, 0, 24), (true, true));
+    }
+
+    #[test]
+    fn test_reveal_window_offset() {
+        let content = "l0\nl1\nl2\nl3\nl4\nl5";
+        let mut document = Document::new(Cursor::new(content)).unwrap();
+        let offset_of = |line_index: usize| line_index * 3;
+        assert_eq!(
+            reveal_window_offset(&mut document, offset_of(4), 2).unwrap(),
+            offset_of(2)
+        );
+        assert_eq!(
+            reveal_window_offset(&mut document, offset_of(5), 0).unwrap(),
+            offset_of(5)
+        );
+        assert_eq!(
+            reveal_window_offset(&mut document, offset_of(5), 1).unwrap(),
+            offset_of(4)
+        );
+        // the file is shorter than the margin
+        assert_eq!(
+            reveal_window_offset(&mut document, offset_of(2), 10).unwrap(),
+            0
+        );
+        assert_eq!(reveal_window_offset(&mut document, 0, 3).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_body_layout_in_plain_mode() {
+        let mut finder = Finder::new();
+        finder.update_search_pattern("err");
+        let line = "an err here and another err there";
+        let mut layout = BodyLayout {
+            width: 16,
+            height: 5,
+            horizontal_shift: 3,
+            row_shift: 0,
+            wrap_lines: false,
+            max_rows: None,
+            wrap_indicator: String::default(),
+            plain_mode: false,
+            show_invisibles: false,
+            selection: None,
+        };
+        let is_plain = |row: &LineWithRenderScheme| row.render() == row.raw_content();
+
+        let rows = layout.rows_of_line(&mut finder, line, 0..5);
+        assert_eq!(rows[0].raw_content(), "err here and ano");
+        assert!(!is_plain(&rows[0]));
+        layout.plain_mode = true;
+        let rows = layout.rows_of_line(&mut finder, line, 0..5);
+        assert_eq!(rows[0].raw_content(), "err here and ano");
+        assert!(is_plain(&rows[0]));
+
+        // wrapped lines are not folded in plain mode
+        layout.wrap_lines = true;
+        layout.max_rows = Some(1);
+        let rows = layout.rows_of_line(&mut finder, line, 0..5);
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(is_plain));
+        layout.plain_mode = false;
+        let rows = layout.rows_of_line(&mut finder, line, 0..5);
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].raw_content().contains("more cols"));
+    }
+
+    #[test]
+    fn test_long_line_slice() {
+        let mut finder = Finder::new();
+        finder.update_search_pattern("err");
+        let mut line = "x".repeat(200_000);
+        // one match straddling the left edge, one shown and one cut off on the right
+        for column in [99_999, 100_010, 100_030] {
+            line.replace_range(column..column + 3, "err");
+        }
+        let mut layout = BodyLayout {
+            width: 20,
+            height: 10,
+            horizontal_shift: 100_000,
+            row_shift: 0,
+            wrap_lines: false,
+            max_rows: None,
+            wrap_indicator: String::default(),
+            plain_mode: false,
+            show_invisibles: false,
+            selection: None,
+        };
+        let rows = layout.rows_of_line(&mut finder, &line, 0..10);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].raw_content(), &line[100_000..100_020]);
+        assert_eq!(
+            rows[0].marked(),
+            format!("[rr]{}[err]{}", "x".repeat(8), "x".repeat(7))
+        );
+
+        // a wrapped line gives no more rows than asked for, however long it is
+        layout.wrap_lines = true;
+        layout.horizontal_shift = 0;
+        let rows = layout.rows_of_line(&mut finder, &line, 5000..5010);
+        assert_eq!(rows.len(), 10);
+        assert_eq!(rows[0].raw_content(), &line[100_000..100_020]);
+        assert!(rows[0].marked().starts_with("[rr]"));
+        assert_eq!(rows[9].raw_content(), &line[100_180..100_200]);
+    }
+
+    #[test]
+    fn test_current_match_marker() {
+        let mut finder = Finder::new();
+        let lines: Vec<_> = ["a", "an err", "b", "another err", "c"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| Line {
+                offset: index * 100,
+                text: text.to_string(),
+            })
+            .collect();
+        let mut layout = BodyLayout {
+            width: 4,
+            height: 3,
+            horizontal_shift: 0,
+            row_shift: 0,
+            wrap_lines: false,
+            max_rows: None,
+            wrap_indicator: String::default(),
+            plain_mode: false,
+            show_invisibles: false,
+            selection: None,
+        };
+        let is_underlined = |row: &LineWithRenderScheme| row.render() != row.raw_content();
+        let mut context = Context::default();
+
+        // a new search lands on the second line
+        context.on_search_started();
+        context.on_search_landed(100);
+        let (rows, shown) = layout.lay_out(
+            &mut finder,
+            &lines,
+            &HashMap::new(),
+            &HashSet::new(),
+            context.current_match,
+        );
+        context.on_body_laid_out(shown);
+        assert_eq!(context.current_match, Some(100));
+        assert!(!is_underlined(&rows[0]) && is_underlined(&rows[1]));
+
+        // kept when wrapping or shifting
+        layout.horizontal_shift = 3;
+        let (rows, _) = layout.lay_out(
+            &mut finder,
+            &lines,
+            &HashMap::new(),
+            &HashSet::new(),
+            context.current_match,
+        );
+        assert_eq!(rows[1].raw_content(), "err ");
+        assert!(is_underlined(&rows[1]));
+        layout.horizontal_shift = 0;
+        layout.wrap_lines = true;
+        let (rows, shown) = layout.lay_out(
+            &mut finder,
+            &lines,
+            &HashMap::new(),
+            &HashSet::new(),
+            context.current_match,
+        );
+        assert!(shown);
+        assert_eq!(rows.len(), 3);
+        assert!(is_underlined(&rows[1]) && is_underlined(&rows[2]));
+
+        // `n` moves it to the next match, below the window bottom once wrapped
+        context.on_search_landed(300);
+        let (_, shown) = layout.lay_out(
+            &mut finder,
+            &lines,
+            &HashMap::new(),
+            &HashSet::new(),
+            context.current_match,
+        );
+        context.on_body_laid_out(shown);
+        assert_eq!(context.current_match, None);
+
+        // scrolled past
+        context.on_search_landed(100);
+        let (_, shown) = layout.lay_out(
+            &mut finder,
+            &lines[2..],
+            &HashMap::new(),
+            &HashSet::new(),
+            context.current_match,
+        );
+        context.on_body_laid_out(shown);
+        assert_eq!(context.current_match, None);
+
+        // a search started then cancelled leaves no marker
+        context.on_search_landed(100);
+        context.on_search_started();
+        let (rows, shown) = layout.lay_out(
+            &mut finder,
+            &lines,
+            &HashMap::new(),
+            &HashSet::new(),
+            context.current_match,
+        );
+        assert!(!shown);
+        assert!(!rows.iter().any(is_underlined));
+
+        // continuations collapsed under the first line
+        layout.wrap_lines = false;
+        layout.width = 8;
+        let hidden = HashMap::from([(0, 17)]);
+        let (rows, _) = layout.lay_out(&mut finder, &lines, &hidden, &HashSet::new(), None);
+        assert_eq!(rows[1].raw_content(), "… +17 li");
+        assert_eq!(rows[2].raw_content(), "an err");
+
+        // a separator before each group of context lines
+        let group_starts = HashSet::from([200]);
+        let (rows, _) = layout.lay_out(&mut finder, &lines, &HashMap::new(), &group_starts, None);
+        assert_eq!(rows[2].raw_content(), "──");
+        assert_eq!(rows[3].raw_content(), "b");
+    }
+
+    #[test]
+    fn test_next_context_lines() {
+        assert_eq!(next_context_lines(0), 1);
+        assert_eq!(next_context_lines(2), 5);
+        assert_eq!(next_context_lines(3), 5);
+        assert_eq!(next_context_lines(5), 0);
+        assert_eq!(next_context_lines(10), 0);
+    }
+
+    #[test]
+    fn test_pause_and_resume_wrap() {
+        let mut context = Context::default();
+        // not wrapping, nothing to pause
+        assert!(!context.pause_wrap());
+        assert!(!context.resume_wrap());
+
+        context.wrap_lines = true;
+        assert!(context.pause_wrap());
+        assert!(!context.wrap_lines && context.wrap_paused);
+        // further horizontal moves keep it paused
+        assert!(!context.pause_wrap());
+        assert!(context.resume_wrap());
+        assert!(context.wrap_lines && !context.wrap_paused);
+        assert!(!context.resume_wrap());
+
+        // turned off by hand while paused, a later move doesn't bring it back
+        context.pause_wrap();
+        context.wrap_lines = false;
+        context.wrap_paused = false;
+        assert!(!context.resume_wrap());
+        assert!(!context.wrap_lines);
+    }
+
+    #[test]
+    fn test_reveal_match_column() {
+        let path = format!("{}/tests/fixtures/wide.log", env!("CARGO_MANIFEST_DIR"));
+        let mut document = Document::<std::fs::File>::open_file(&path, "\n").unwrap();
+        let mut finder = Finder::new();
+        finder.update_search_pattern("needle");
+        let predict = |line: &str| finder.can_satisfy_active_search_patterns(line);
+        let mut shift = 0;
+        let search_from = |document: &mut Document<_>, offset: usize, shift: &mut usize| {
+            let distance = document
+                .query_distance_to_next_match(offset, predict)
+                .unwrap()
+                .unwrap();
+            let line = document
+                .query_lines(offset + distance, 1)
+                .unwrap()
+                .remove(0);
+            let LineMatch::MatchAt(range) = finder.active_search_match(&line.text) else {
+                panic!("no match in {}", line.text);
+            };
+            *shift = reveal_column_shift(range, *shift, 80);
+            line.offset + line.text.len() + 1
+        };
+
+        // near the start, nothing to shift
+        let offset = search_from(&mut document, 0, &mut shift);
+        assert_eq!(shift, 0);
+        // at column 500 with a margin on its left
+        let offset = search_from(&mut document, offset, &mut shift);
+        assert_eq!(shift, 492);
+        // at the same column on a later line, the shift is kept
+        shift = 450;
+        let offset = search_from(&mut document, offset, &mut shift);
+        assert_eq!(shift, 450);
+        // within the first screenful again
+        search_from(&mut document, offset, &mut shift);
+        assert_eq!(shift, 0);
+
+        // a narrow window keeps a smaller margin
+        assert_eq!(reveal_column_shift(500..506, 0, 20), 495);
+    }
+
+    #[test]
+    fn test_query_appended_match() {
+        let path =
+            std::env::temp_dir().join(format!("loss-follow-until-test-{}.log", std::process::id()));
+        fs::write(&path, "boot\nready\n").unwrap();
+        let mut document = Document::<File>::open_file(path.to_str().unwrap(), "\n").unwrap();
+        let mut finder = Finder::new();
+        finder.update_search_pattern("FATAL");
+        let append = |document: &mut Document<File>, data: &str| {
+            let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+            std::io::Write::write_all(&mut file, data.as_bytes()).unwrap();
+            let appended = document.update_docsize_and_lastline().unwrap().unwrap();
+            query_appended_match(document, appended, |line| {
+                finder.can_satisfy_active_search_patterns(line)
+            })
+            .unwrap()
+        };
+
+        assert_eq!(document.update_docsize_and_lastline().unwrap(), None);
+        assert_eq!(append(&mut document, "tick\nFA"), None);
+        // the pattern is completed by the next append, in a line started before it
+        assert_eq!(append(&mut document, "TAL: oom\ntick\n"), Some(16));
+        // lines before the appended bytes aren't scanned again
+        assert_eq!(append(&mut document, "tick\n"), None);
+        assert_eq!(append(&mut document, "tick\ntick\nFATAL\n"), Some(47));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_single_line_navigation() {
+        const LINE_LEN: usize = 1 << 20;
+        let mut content = "0123456789abcdef".repeat(LINE_LEN / 16);
+        for column in [100, 70000, LINE_LEN - 10] {
+            content.replace_range(column..column + 6, "needle");
+        }
+        let mut document = Document::new(Cursor::new(content.as_str())).unwrap();
+        assert!(document.is_single_line());
+        let line = document.query_lines(0, 1).unwrap().remove(0);
+
+        let mut finder = Finder::new();
+        finder.update_search_pattern("needle");
+        let starts = finder.active_search_match_starts(&line.text);
+        assert_eq!(starts, [100, 70000, LINE_LEN - 10]);
+        assert_eq!(next_match_column(&starts, 0, Direction::Down), Some(100));
+        assert_eq!(
+            next_match_column(&starts, 101, Direction::Down),
+            Some(70000)
+        );
+        assert_eq!(
+            next_match_column(&starts, LINE_LEN - 9, Direction::Down),
+            None
+        );
+        assert_eq!(next_match_column(&starts, 70000, Direction::Up), Some(100));
+        assert_eq!(next_match_column(&starts, 100, Direction::Up), None);
+
+        // the wrapped line scrolls by rows
+        let layout = BodyLayout {
+            width: 80,
+            height: 24,
+            horizontal_shift: 0,
+            row_shift: 70000 / 80 - 1,
+            wrap_lines: true,
+            max_rows: None,
+            wrap_indicator: String::default(),
+            plain_mode: false,
+            show_invisibles: false,
+            selection: None,
+        };
+        let (rows, _) = layout.lay_out(
+            &mut finder,
+            std::slice::from_ref(&line),
+            &HashMap::new(),
+            &HashSet::new(),
+            None,
+        );
+        // only the rows fitting in the window are built
+        assert_eq!(rows.len(), 24);
+        assert_eq!(rows[0].raw_content(), &line.text[69920..70000]);
+        assert!(rows[1].raw_content().starts_with("needle"));
+
+        // the ratio follows the first column shown
+        assert_eq!(column_ratio(0, 80, LINE_LEN), PositionRatio::Percent(0));
+        assert_eq!(
+            column_ratio(LINE_LEN / 2, 80, LINE_LEN),
+            PositionRatio::Percent(50)
+        );
+        assert_eq!(
+            column_ratio(LINE_LEN - 80, 80, LINE_LEN),
+            PositionRatio::End
+        );
+        assert_eq!(column_ratio(0, 80, 40), PositionRatio::End);
+    }
+
+    #[test]
+    fn test_resolve_search_direction() {
+        // nothing searched yet
+        assert_eq!(resolve_search_direction(None, false), Direction::Down);
+        assert_eq!(resolve_search_direction(None, true), Direction::Up);
+        // `?` then `n` then `N`
+        let last = Some(Direction::Up);
+        assert_eq!(resolve_search_direction(last, false), Direction::Up);
+        assert_eq!(resolve_search_direction(last, true), Direction::Down);
+        // `/` then `N` then `n`
+        let last = Some(Direction::Down);
+        assert_eq!(resolve_search_direction(last, true), Direction::Up);
+        assert_eq!(resolve_search_direction(last, false), Direction::Down);
+    }
+
+    #[test]
+    fn test_run_once() {
+        let content: String = (0..100).map(|index| format!("line {index}\n")).collect();
+        let mut manager = scripted_manager(Cursor::new(content), 30, 21);
+        let run = |manager: &mut Manager, event| manager.run_once(event).unwrap();
+        assert_eq!(
+            run(&mut manager, Event::TerminalResize(30, 21)),
+            RunOutcome::Continue
+        );
+        assert_eq!(top_row(&manager), "line 0");
+        assert_eq!(manager.canvas.body_area.len(), 20);
+
+        // search
+        run(
+            &mut manager,
+            Event::Search(PromptAction::Start(Some(Direction::Down))),
+        );
+        run(
+            &mut manager,
+            Event::Search(PromptAction::Content("line 4".to_string())),
+        );
+        assert!(status_text(&manager).contains("Search: line 4"));
+        run(
+            &mut manager,
+            Event::Search(PromptAction::Enter("line 42".to_string())),
+        );
+        assert_eq!(top_row(&manager), "line 42");
+        run(&mut manager, Event::SearchNext);
+        assert_eq!(top_row(&manager), "line 42");
+
+        // jump
+        run(
+            &mut manager,
+            Event::JumpByLines(PromptAction::Start(Some(Direction::Down))),
+        );
+        run(
+            &mut manager,
+            Event::JumpByLines(PromptAction::Enter("5".to_string())),
+        );
+        assert_eq!(top_row(&manager), "line 47");
+
+        // bookmark
+        run(&mut manager, Event::NewBookmark(PromptAction::Start(None)));
+        run(
+            &mut manager,
+            Event::NewBookmark(PromptAction::Enter("here".to_string())),
+        );
+        assert!(status_text(&manager).contains("Bookmark saved: here"));
+        run(&mut manager, Event::SeekToHome);
+        assert_eq!(top_row(&manager), "line 0");
+        run(&mut manager, Event::GotoBookmark(BookmarkMenuAction::Start));
+        assert!(manager.canvas.popup_menu[1].raw_content().contains("here"));
+        run(&mut manager, Event::GotoBookmark(BookmarkMenuAction::Enter));
+        assert_eq!(top_row(&manager), "line 47");
+        assert!(status_text(&manager).contains("Jumped to bookmark: here"));
+
+        assert_eq!(run(&mut manager, Event::Exit), RunOutcome::Exit);
+    }
+
+    #[test]
+    fn test_start_at_line() {
+        let content: String = (1..=100).map(|index| format!("line {index}\n")).collect();
+        let mut manager = scripted_manager(Cursor::new(content), 30, 11);
+        manager.start_at(StartPosition::Line(42)).unwrap();
+        manager.run_once(Event::TerminalResize(30, 11)).unwrap();
+        assert_eq!(top_row(&manager), "line 42");
+        assert!(status_text(&manager).starts_with("Opened at line 42"));
+    }
+
+    #[test]
+    fn test_start_streamed_input() {
+        let spool = loss_viewer::spool::Spool::new(Cursor::new("line 1\nline 2\n"));
+        let mut manager = scripted_manager(Input::Spool(spool), 30, 11);
+        manager.start_at(StartPosition::LastVisit).unwrap();
+        assert_eq!(manager.mode, Mode::Follow);
+
+        // an explicit start position is still honored
+        let spool = loss_viewer::spool::Spool::new(Cursor::new("line 1\nline 2\n"));
+        let mut manager = scripted_manager(Input::Spool(spool), 30, 11);
+        manager.start_at(StartPosition::Line(1)).unwrap();
+        assert_eq!(manager.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_dismiss() {
+        let content: String = (0..100).map(|index| format!("line {index}\n")).collect();
+        let mut manager = scripted_manager(Cursor::new(content), 30, 11);
+        let run = |manager: &mut Manager, event| manager.run_once(event).unwrap();
+        run(&mut manager, Event::TerminalResize(30, 11));
+        run(
+            &mut manager,
+            Event::Search(PromptAction::Start(Some(Direction::Down))),
+        );
+        run(
+            &mut manager,
+            Event::Search(PromptAction::Enter("line 4".to_string())),
+        );
+        let current_match = manager.context.current_match;
+        assert!(current_match.is_some());
+        run(&mut manager, Event::CycleStatusLayout);
+        assert!(status_text(&manager).contains("Status bar: "));
+        assert_eq!(manager.topmost_transient(), Some(Transient::StatusMessage));
+
+        // the message goes first, then the match marker, then nothing is left to dismiss
+        run(&mut manager, Event::Dismiss);
+        assert!(!status_text(&manager).contains("Status bar: "));
+        assert_eq!(manager.context.current_match, current_match);
+        run(&mut manager, Event::Dismiss);
+        assert_eq!(manager.topmost_transient(), None);
+        let top = top_row(&manager).to_string();
+        run(&mut manager, Event::Dismiss);
+        assert_eq!(top_row(&manager), top);
+
+        // a message without a match, and a match without a message
+        run(&mut manager, Event::CycleStatusLayout);
+        run(&mut manager, Event::Dismiss);
+        assert_eq!(manager.topmost_transient(), None);
+        run(&mut manager, Event::SearchNext);
+        assert_eq!(manager.topmost_transient(), Some(Transient::CurrentMatch));
+        run(&mut manager, Event::Dismiss);
+        assert_eq!(manager.topmost_transient(), None);
+    }
+
+    #[test]
+    fn test_redraw_status_bar_only() {
+        let content: String = (0..20).map(|index| format!("line {index}\n")).collect();
+        let (mut manager, output) = writing_manager(Cursor::new(content), 30, 4);
+        let clear_all = "\x1b[2J";
+        manager.run_once(Event::TerminalResize(30, 4)).unwrap();
+        let written = output.take();
+        assert!(written.contains(clear_all) && written.contains("line 2"));
+
+        // a message shown and expiring leaves the body as it is
+        manager.run_once(Event::CycleStatusLayout).unwrap();
+        let written = output.take();
+        assert!(written.starts_with("\x1b[4;1H\x1b[2K"), "{written:?}");
+        assert!(written.contains("Status bar: "));
+        assert!(!written.contains(clear_all) && !written.contains("line"));
+        manager.run_once(Event::ToggleMacroRecording).unwrap();
+        manager.run_once(Event::ToggleMacroRecording).unwrap();
+        let written = output.take();
+        assert!(!written.contains(clear_all) && !written.contains("line"));
+
+        // typing into the command prompt doesn't lay out the body again
+        manager.canvas.body_area[0] = LineWithRenderScheme::new("kept");
+        let typing = |content: &str| Event::Command(PromptAction::Content(content.to_string()));
+        manager.run_once(typing("tri")).unwrap();
+        let written = output.take();
+        assert!(written.contains(":tri") && !written.contains(clear_all));
+        assert_eq!(top_row(&manager), "kept");
+
+        // anything else is drawn whole
+        manager
+            .run_once(Event::WindowMove(Direction::Down, 1))
+            .unwrap();
+        assert!(output.take().contains(clear_all));
+        manager.run_once(Event::TerminalResize(30, 4)).unwrap();
+        assert!(output.take().contains(clear_all));
+    }
+
+    #[test]
+    fn test_spotlight() {
+        let content = "info a\nerror b\n\ninfo error c\nwarn d\n";
+        let mut manager = scripted_manager(Cursor::new(content), 30, 6);
+        manager.finder.update_search_pattern("error");
+        manager
+            .finder
+            .handle_event(FinderAction::SwitchActiveSlot(2));
+        manager.finder.update_search_pattern("warn");
+        manager
+            .finder
+            .handle_event(FinderAction::SwitchActiveSlot(1));
+        let toggle = Event::FinderOperation(FinderAction::ToggleSpotlight);
+        manager.run_once(toggle.clone()).unwrap();
+        assert!(status_text(&manager).starts_with("[spotlight] "));
+        let rows = |manager: &Manager| {
+            manager.canvas.body_area[..5]
+                .iter()
+                .map(|row| row.marked().trim_end().to_string())
+                .collect::<Vec<_>>()
+        };
+        // the highlights of inactive slots are dimmed along with the rest of their line
+        assert_eq!(
+            rows(&manager),
+            ["{info a}", "[error] b", "", "info [error] c", "{warn d}"]
+        );
+
+        // without an active pattern nothing is dimmed
+        manager
+            .run_once(Event::FinderOperation(FinderAction::SwitchActiveSlot(3)))
+            .unwrap();
+        assert_eq!(
+            rows(&manager),
+            ["info a", "[error] b", "", "info [error] c", "[warn] d"]
+        );
+        manager.run_once(toggle).unwrap();
+        assert!(!status_text(&manager).contains("[spotlight]"));
+    }
+
+    #[test]
+    fn test_recover_broken_offset() {
+        let content = "line 0\nline 1\nline 2\nline 3\nline 4\n";
+        let mut manager = scripted_manager(Cursor::new(content), 30, 4);
+        // in the middle of "line 2"
+        manager.window.set_offset(17);
+        manager.run_once(Event::ToggleWrapLine).unwrap();
+        assert_eq!(manager.window.offset(), 14);
+        assert_eq!(top_row(&manager), "line 2");
+        assert!(status_text(&manager).contains("Position adjusted"));
+
+        // past the end, e.g. after the file shrank
+        manager.window.set_offset(content.len() + 100);
+        manager.run_once(Event::ToggleWrapLine).unwrap();
+        assert_eq!(manager.window.offset(), 28);
+        assert_eq!(top_row(&manager), "line 4");
+        assert!(status_text(&manager).contains("Position adjusted"));
+    }
+
+    #[test]
+    fn test_open_file_of_same_size() {
+        let dir = std::env::temp_dir();
+        let first = dir.join(format!("loss-open-first-{}.log", std::process::id()));
+        let second = dir.join(format!("loss-open-second-{}.log", std::process::id()));
+        fs::write(&first, "error\nok\nok\n").unwrap();
+        fs::write(&second, "ok\nok\nerror\n").unwrap();
+        let mut manager = scripted_manager(Cursor::new(""), 30, 6);
+        let command = |manager: &mut Manager, command: &str| {
+            let event = Event::Command(PromptAction::Enter(command.to_string()));
+            manager.run_once(event).unwrap();
+        };
+        command(&mut manager, &format!("e {}", first.display()));
+        manager.finder.update_search_pattern("error");
+        manager.update_finder_match_stats().unwrap();
+        let document_size = manager.document.document_size();
+        assert!(manager
+            .finder
+            .slots_without_match_stats(document_size)
+            .is_empty());
+        let generation = manager.finder.generation();
+
+        command(&mut manager, &format!("e {}", second.display()));
+        assert_eq!(manager.document.document_size(), document_size);
+        // counted again for the new content, and the minimap scanned again
+        assert_eq!(manager.finder.slots_without_match_stats(document_size), [1]);
+        assert!(manager.finder.generation() > generation);
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn test_trim() {
+        let content: String = (0..100).map(|index| format!("line {index:02}\n")).collect();
+        let mut manager = scripted_manager(Cursor::new(content), 40, 6);
+        let command = |manager: &mut Manager, command: &str| {
+            let event = Event::Command(PromptAction::Enter(command.to_string()));
+            manager.run_once(event).unwrap();
+        };
+        command(&mut manager, "trim");
+        assert!(status_text(&manager).contains("Nothing above the top line to trim"));
+        manager
+            .bookmark_store
+            .new_bookmark("early", 8 * 10, "line 10", &[]);
+        manager
+            .bookmark_store
+            .new_bookmark("late", 8 * 80, "line 80", &[]);
+        manager.continuation_folder.toggle_expanded(8 * 60);
+
+        command(&mut manager, "50");
+        command(&mut manager, "trim");
+        // an expanded trace stays expanded at its line
+        assert!(manager.continuation_folder.is_expanded(8 * 60 - 392));
+        assert!(!manager.continuation_folder.is_expanded(8 * 60));
+        assert_eq!(manager.window.offset(), 0);
+        assert_eq!(top_row(&manager), "line 49");
+        assert!(status_text(&manager).contains("Trimmed 392 B above"));
+        // bookmarks above the start are hidden, the rest keep their lines
+        assert!(manager.bookmark_store.handle_enter_event().is_none());
+        manager
+            .run_once(Event::GotoBookmark(BookmarkMenuAction::Start))
+            .unwrap();
+        let bookmark = manager.bookmark_store.handle_enter_event().unwrap();
+        assert_eq!((bookmark.name.as_str(), bookmark.offset), ("late", 8 * 31));
+
+        // percentages go from the new start to the last line
+        command(&mut manager, "26");
+        assert_eq!(top_row(&manager), "line 74");
+        assert!(status_text(&manager).starts_with("[trimmed 392 B] "));
+        assert!(status_text(&manager).contains("50%"));
+        // searches stop at the start, as do moves to the top
+        manager.finder.update_search_pattern("line 1");
+        manager.run_once(Event::SearchPrevious).unwrap();
+        assert_eq!(top_row(&manager), "line 74");
+        manager.run_once(Event::SeekToHome).unwrap();
+        assert_eq!(top_row(&manager), "line 49");
+
+        command(&mut manager, "2");
+        command(&mut manager, "untrim");
+        assert!(manager.continuation_folder.is_expanded(8 * 60));
+        assert_eq!(manager.window.offset(), 8 * 50);
+        assert_eq!(top_row(&manager), "line 50");
+        assert!(!status_text(&manager).contains("[trimmed"));
+        manager.run_once(Event::SearchPrevious).unwrap();
+        assert_eq!(top_row(&manager), "line 19");
+        command(&mut manager, "untrim");
+        assert!(status_text(&manager).contains("Nothing trimmed"));
+
+        // and bookmarks above the trim come back
+        manager
+            .run_once(Event::GotoBookmark(BookmarkMenuAction::Start))
+            .unwrap();
+        manager
+            .run_once(Event::GotoBookmark(BookmarkMenuAction::First))
+            .unwrap();
+        let bookmark = manager.bookmark_store.handle_enter_event().unwrap();
+        assert_eq!((bookmark.name.as_str(), bookmark.offset), ("early", 8 * 10));
+    }
+
+    #[test]
+    fn test_follow_appended_lines() {
+        let content = Rc::new(RefCell::new(b"line 0\nline 1\n".to_vec()));
+        let source = GrowingSource::new(content.clone());
+        let mut manager = scripted_manager(source, 30, 4);
+        manager.run_once(Event::Follow).unwrap();
+        assert_eq!(manager.mode, Mode::Follow);
+        assert_eq!(top_row(&manager), "line 0");
+
+        for index in 2..6 {
+            content
+                .borrow_mut()
+                .extend_from_slice(format!("line {index}\n").as_bytes());
+        }
+        manager.run_idle().unwrap();
+        assert_eq!(manager.document.document_size(), 42);
+        // the window follows to the end
+        assert_eq!(top_row(&manager), "line 3");
+        // nothing appended, nothing changed
+        manager.run_idle().unwrap();
+        assert_eq!(top_row(&manager), "line 3");
+    }
+
+    #[test]
+    fn test_final_newline_indicator() {
+        for (content, missing) in [("line 0\nline 1", true), ("line 0\nline 1\n", false)] {
+            let mut manager = scripted_manager(Cursor::new(content), 40, 4);
+            manager.run_once(Event::SeekToEnd).unwrap();
+            assert_eq!(top_row(&manager), "line 0");
+            assert_eq!(manager.canvas.body_area[1].raw_content(), "line 1");
+            assert_eq!(status_text(&manager).contains("⏎ missing"), missing);
+        }
+
+        // following until the last line is completed
+        let content = Rc::new(RefCell::new(b"line 0\nline".to_vec()));
+        let source = GrowingSource::new(content.clone());
+        let mut manager = scripted_manager(source, 80, 4);
+        manager.run_once(Event::Follow).unwrap();
+        assert_eq!(manager.canvas.body_area[1].raw_content(), "line");
+        assert!(status_text(&manager).contains("⏎ missing"));
+        content.borrow_mut().extend_from_slice(b" 1\n");
+        manager.run_idle().unwrap();
+        assert_eq!(manager.canvas.body_area[1].raw_content(), "line 1");
+        assert!(!status_text(&manager).contains("⏎ missing"));
+    }
+
+    #[test]
+    fn test_toggle_wrap_keeps_horizontal_shift() {
+        let content = "0123456789".repeat(5) + "\n";
+        let mut manager = scripted_manager(Cursor::new(content.repeat(10)), 20, 6);
+        manager.config.wrap_indicator = "↪".to_string();
+        let run = |manager: &mut Manager, event| manager.run_once(event).unwrap();
+        run(&mut manager, Event::TerminalResize(20, 6));
+        run(&mut manager, Event::WindowMove(Direction::Right, 7));
+        assert_eq!(top_row(&manager), "78901234567890123456");
+
+        // wrapping starts from the first column, with the indicator on continuation rows
+        run(&mut manager, Event::ToggleWrapLine);
+        assert_eq!(manager.window.horizontal_shift, 0);
+        assert_eq!(top_row(&manager), "01234567890123456789");
+        assert_eq!(
+            manager.canvas.body_area[1].raw_content(),
+            "↪ 012345678901234567"
+        );
+        run(&mut manager, Event::ToggleWrapLine);
+        assert_eq!(top_row(&manager), "78901234567890123456");
+
+        // moving while wrapping is paused doesn't replace the remembered shift
+        run(&mut manager, Event::ToggleWrapLine);
+        run(&mut manager, Event::WindowMove(Direction::Right, 3));
+        assert!(manager.context.wrap_paused);
+        assert_eq!(top_row(&manager), "34567890123456789012");
+        run(&mut manager, Event::WindowMove(Direction::Down, 1));
+        assert!(manager.context.wrap_lines);
+        assert_eq!(top_row(&manager), "01234567890123456789");
+        run(&mut manager, Event::WindowMove(Direction::Right, 3));
+        // toggling while paused turns wrapping back on
+        run(&mut manager, Event::ToggleWrapLine);
+        assert!(manager.context.wrap_lines && !manager.context.wrap_paused);
+        assert_eq!(top_row(&manager), "01234567890123456789");
+        run(&mut manager, Event::ToggleWrapLine);
+        assert_eq!(top_row(&manager), "78901234567890123456");
+
+        // a horizontal move without wrapping is remembered in turn
+        run(&mut manager, Event::WindowMove(Direction::Left, 5));
+        run(&mut manager, Event::ToggleWrapLine);
+        run(&mut manager, Event::ToggleWrapLine);
+        assert_eq!(manager.window.horizontal_shift, 2);
+    }
 }
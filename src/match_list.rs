@@ -0,0 +1,175 @@
+use std::ops::Range;
+
+use loss_viewer::pattern::PatternType;
+
+use crate::{
+    canvas::Canvas,
+    event_source::Direction,
+    popup_menu::{viewport, PopupMenu},
+    render::LineWithRenderScheme,
+};
+
+// rows of the menu below its title
+const MENU_HEIGHT: usize = 9;
+
+/// What the matches were collected for, they are stale once any of it changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectKey {
+    pub slot_index: usize,
+    pub pattern_type: PatternType,
+    pub pattern: String,
+    pub document_size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchListAction {
+    Start,
+    Arrow(Direction),
+    Enter,
+    Cancel,
+}
+
+/// Offsets of every line matching a slot pattern, listed in a menu to jump to.
+#[derive(Debug, Default)]
+pub struct MatchList {
+    key: Option<CollectKey>,
+    offsets: Vec<usize>,
+    // the menu is shown while there's a selection
+    selected: Option<usize>,
+}
+
+impl MatchList {
+    pub fn is_fresh(&self, key: &CollectKey) -> bool {
+        self.key.as_ref() == Some(key)
+    }
+
+    pub fn set(&mut self, key: CollectKey, offsets: Vec<usize>) {
+        self.key = Some(key);
+        self.offsets = offsets;
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.selected.is_some()
+    }
+
+    pub fn open(&mut self) {
+        self.selected = Some(0);
+    }
+
+    // returns the selected offset along with its index, if there's any match
+    pub fn close(&mut self) -> Option<(usize, usize)> {
+        let selected = self.selected.take()?;
+        self.offsets.get(selected).map(|offset| (selected, *offset))
+    }
+
+    // the selection wraps around at either end
+    pub fn move_selection(&mut self, direction: Direction) {
+        let (Some(selected), len) = (self.selected, self.offsets.len()) else {
+            return;
+        };
+        if len == 0 {
+            return;
+        }
+        self.selected = Some(match direction {
+            Direction::Up => (selected + len - 1) % len,
+            _ => (selected + 1) % len,
+        });
+    }
+
+    /// Indexes of the matches shown in the menu, whose lines are handed to
+    /// [`MatchList::render`].
+    pub fn shown_range(&self) -> Range<usize> {
+        viewport(self.selected, self.offsets.len(), MENU_HEIGHT)
+    }
+
+    pub fn render(
+        &self,
+        lines: &[String],
+        canvas: &mut Canvas,
+        window_width: usize,
+        window_height: usize,
+    ) {
+        let shown = self.shown_range();
+        let rows = self.offsets[shown.clone()]
+            .iter()
+            .zip(lines)
+            .map(|(offset, line)| LineWithRenderScheme::new(&format!("{offset:>10}  {line}")))
+            .collect();
+        let slot_index = self.key.as_ref().map_or(0, |key| key.slot_index);
+        let title = format!("Matches of Slot {slot_index} ({})", self.offsets.len());
+        PopupMenu {
+            title: &title,
+            height: MENU_HEIGHT,
+            min_width: 20,
+            rows,
+            // relative to the shown rows, which the popup scrolls no further
+            selected: self.selected.map(|selected| selected - shown.start),
+            prompt: None,
+        }
+        .render(canvas, window_width, window_height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(pattern: &str, document_size: usize) -> CollectKey {
+        CollectKey {
+            slot_index: 1,
+            pattern_type: PatternType::Raw,
+            pattern: pattern.to_string(),
+            document_size,
+        }
+    }
+
+    #[test]
+    fn test_freshness() {
+        let mut list = MatchList::default();
+        assert!(!list.is_fresh(&key("err", 100)));
+        list.set(key("err", 100), vec![0, 10]);
+        assert!(list.is_fresh(&key("err", 100)));
+        // the pattern changed or the document grew
+        assert!(!list.is_fresh(&key("warn", 100)));
+        assert!(!list.is_fresh(&key("err", 120)));
+        assert!(!list.is_fresh(&CollectKey {
+            pattern_type: PatternType::Regex,
+            ..key("err", 100)
+        }));
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut list = MatchList::default();
+        list.set(key("err", 100), (0..20).map(|index| index * 5).collect());
+        list.open();
+        list.move_selection(Direction::Up);
+        assert_eq!(list.shown_range(), 11..20);
+        list.move_selection(Direction::Down);
+        list.move_selection(Direction::Down);
+        assert_eq!(list.shown_range(), 1..10);
+        assert_eq!(list.close(), Some((1, 5)));
+        assert!(!list.is_active());
+
+        let mut canvas = Canvas::default();
+        list.open();
+        let lines: Vec<_> = list
+            .shown_range()
+            .map(|index| format!("line {index}"))
+            .collect();
+        list.render(&lines, &mut canvas, 40, 20);
+        assert!(canvas.popup_menu[0]
+            .raw_content()
+            .contains(" Matches of Slot 1 (20) "));
+        assert_eq!(canvas.popup_menu[1].raw_content(), " >          0  line 0");
+
+        // nothing to jump to without matches
+        list.set(key("none", 100), vec![]);
+        list.move_selection(Direction::Down);
+        assert_eq!(list.close(), None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::ops::Range;
+
+use crate::render::RenderScheme;
+
+// buckets of the density scan, spread over the rows of the column when rendered
+const MINIMAP_BUCKET_COUNT: usize = 1000;
+// narrower windows keep every column for content
+const MINIMAP_MIN_WIDTH: usize = 40;
+
+#[derive(Debug)]
+struct DensityCache {
+    finder_generation: u64,
+    document_size: usize,
+    // bytes each bucket covers, doubled as the document outgrows the buckets so that appended
+    // data is counted without scanning the rest again
+    bucket_len: usize,
+    // `None` if the scan was interrupted, it's not retried until slots or the document change
+    buckets: Option<Vec<usize>>,
+    // the last line is scanned again along with appended data, as it may have grown
+    last_line_start: usize,
+    last_line_bucket: Option<usize>,
+}
+
+/// A scrollbar column on the right edge of the body, with ticks where finder patterns match.
+#[derive(Debug, Default)]
+pub struct Minimap {
+    enabled: bool,
+    density: Option<DensityCache>,
+}
+
+impl Minimap {
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        self.enabled
+    }
+
+    pub fn is_shown(&self, window_width: usize) -> bool {
+        self.enabled && window_width >= MINIMAP_MIN_WIDTH
+    }
+
+    /// Offset of the line to scan the document from with [`Minimap::count_match`] for the
+    /// density to be up to date, `None` if it already is. Only appended data is scanned if slots
+    /// didn't change since the last scan.
+    pub fn start_scan(
+        &mut self,
+        finder_generation: u64,
+        document_size: usize,
+        last_line_start: usize,
+    ) -> Option<usize> {
+        match self.density.as_mut() {
+            Some(cache)
+                if cache.finder_generation == finder_generation
+                    && cache.document_size == document_size =>
+            {
+                None
+            }
+            Some(DensityCache {
+                finder_generation: cached_generation,
+                document_size: cached_size,
+                bucket_len,
+                buckets: Some(buckets),
+                last_line_start: cached_last_line_start,
+                last_line_bucket,
+            }) if *cached_generation == finder_generation && *cached_size < document_size => {
+                if let Some(bucket) = last_line_bucket.take() {
+                    buckets[bucket] -= 1;
+                }
+                while last_line_start >= *bucket_len * MINIMAP_BUCKET_COUNT {
+                    for index in 0..MINIMAP_BUCKET_COUNT {
+                        buckets[index] = if index < MINIMAP_BUCKET_COUNT / 2 {
+                            buckets[index * 2] + buckets[index * 2 + 1]
+                        } else {
+                            0
+                        };
+                    }
+                    *bucket_len *= 2;
+                }
+                *cached_size = document_size;
+                Some(std::mem::replace(cached_last_line_start, last_line_start))
+            }
+            _ => {
+                self.density = Some(DensityCache {
+                    finder_generation,
+                    document_size,
+                    bucket_len: last_line_start / MINIMAP_BUCKET_COUNT + 1,
+                    buckets: Some(vec![0; MINIMAP_BUCKET_COUNT]),
+                    last_line_start,
+                    last_line_bucket: None,
+                });
+                Some(0)
+            }
+        }
+    }
+
+    // counts a line matching at `offset`, found by the scan started last
+    pub fn count_match(&mut self, offset: usize) {
+        let Some(cache) = self.density.as_mut() else {
+            return;
+        };
+        let Some(buckets) = cache.buckets.as_mut() else {
+            return;
+        };
+        let bucket = std::cmp::min(offset / cache.bucket_len, MINIMAP_BUCKET_COUNT - 1);
+        buckets[bucket] += 1;
+        if offset == cache.last_line_start {
+            cache.last_line_bucket = Some(bucket);
+        }
+    }
+
+    pub fn interrupt_scan(&mut self) {
+        if let Some(cache) = self.density.as_mut() {
+            cache.buckets = None;
+        }
+    }
+
+    // offsets no longer point to the lines counted, e.g. after a trim
+    pub fn forget_density(&mut self) {
+        self.density = None;
+    }
+
+    // one cell per row, the thumb reversed over the dim track while ticks stand out
+    pub fn column(&self, thumb: Range<usize>, height: usize) -> Vec<(char, Option<RenderScheme>)> {
+        let ticks = match self.density.as_ref() {
+            // the buckets past the last line are spare room for appended data
+            Some(DensityCache {
+                bucket_len,
+                buckets: Some(buckets),
+                last_line_start,
+                ..
+            }) => {
+                let used = std::cmp::min(last_line_start / bucket_len + 1, buckets.len());
+                bucket_ticks(&buckets[..used], height)
+            }
+            _ => vec![false; height],
+        };
+        ticks
+            .into_iter()
+            .enumerate()
+            .map(|(row, tick)| match (thumb.contains(&row), tick) {
+                (true, true) => ('•', Some(RenderScheme::Reverse)),
+                (true, false) => (' ', Some(RenderScheme::Reverse)),
+                (false, true) => ('•', None),
+                (false, false) => ('│', Some(RenderScheme::Dim)),
+            })
+            .collect()
+    }
+}
+
+/// Rows covered by the thumb, given the percent ratios of the first and last lines in the
+/// window. At least one row is covered.
+pub fn thumb_rows(top_ratio: usize, bottom_ratio: usize, height: usize) -> Range<usize> {
+    if height == 0 {
+        return 0..0;
+    }
+    let start = std::cmp::min(top_ratio * height / 100, height - 1);
+    let end = (bottom_ratio * height)
+        .div_ceil(100)
+        .clamp(start + 1, height);
+    start..end
+}
+
+/// Whether any match falls into the buckets each of `height` rows covers.
+pub fn bucket_ticks(buckets: &[usize], height: usize) -> Vec<bool> {
+    (0..height)
+        .map(|row| {
+            let start = row * buckets.len() / height;
+            let end = std::cmp::max((row + 1) * buckets.len() / height, start + 1);
+            buckets[start..std::cmp::min(end, buckets.len())]
+                .iter()
+                .any(|count| *count > 0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumb_rows() {
+        assert_eq!(thumb_rows(0, 100, 10), 0..10);
+        assert_eq!(thumb_rows(0, 5, 10), 0..1);
+        assert_eq!(thumb_rows(50, 59, 10), 5..6);
+        assert_eq!(thumb_rows(50, 61, 10), 5..7);
+        // at the end the thumb stays on the last row
+        assert_eq!(thumb_rows(100, 100, 10), 9..10);
+        assert_eq!(thumb_rows(37, 37, 200), 74..75);
+        assert_eq!(thumb_rows(0, 0, 0), 0..0);
+    }
+
+    #[test]
+    fn test_bucket_ticks() {
+        let mut buckets = vec![0; 10];
+        buckets[0] = 1;
+        buckets[7] = 3;
+        assert_eq!(bucket_ticks(&buckets, 5), [true, false, false, true, false]);
+        // more rows than buckets, each bucket spans several rows
+        assert_eq!(
+            bucket_ticks(&buckets[..4], 8),
+            [true, true, false, false, false, false, false, false]
+        );
+        assert_eq!(bucket_ticks(&buckets, 1), [true]);
+    }
+
+    #[test]
+    fn test_column() {
+        let mut minimap = Minimap::default();
+        assert!(!minimap.is_shown(80));
+        minimap.toggle();
+        assert!(minimap.is_shown(80));
+        assert!(!minimap.is_shown(20));
+
+        assert_eq!(minimap.start_scan(1, 4000, 3999), Some(0));
+        minimap.count_match(1000);
+        assert_eq!(minimap.start_scan(1, 4000, 3999), None);
+        let cells: String = minimap.column(0..1, 4).iter().map(|cell| cell.0).collect();
+        assert_eq!(cells, " •││");
+
+        // an interrupted scan shows no tick
+        minimap.interrupt_scan();
+        let cells: String = minimap.column(3..4, 4).iter().map(|cell| cell.0).collect();
+        assert_eq!(cells, "│││ ");
+        // and isn't retried until slots or the document change
+        assert_eq!(minimap.start_scan(1, 4000, 3999), None);
+        assert_eq!(minimap.start_scan(2, 4000, 3999), Some(0));
+    }
+
+    #[test]
+    fn test_scan_appended_data() {
+        let mut minimap = Minimap::default();
+        assert_eq!(minimap.start_scan(1, 1000, 999), Some(0));
+        minimap.count_match(0);
+        minimap.count_match(999);
+        // the last line is scanned again as it grew, and counted once
+        assert_eq!(minimap.start_scan(1, 1500, 999), Some(999));
+        minimap.count_match(999);
+        assert_eq!(minimap.column(0..1, 2)[1].0, '•');
+        assert_eq!(minimap.start_scan(1, 1600, 1500), Some(999));
+        minimap.count_match(999);
+        minimap.count_match(1500);
+        let buckets = minimap.density.as_ref().unwrap().buckets.clone().unwrap();
+        assert_eq!(buckets.iter().sum::<usize>(), 3);
+
+        // buckets are merged as the document outgrows them
+        assert_eq!(minimap.start_scan(1, 5000, 4999), Some(1500));
+        minimap.count_match(1500);
+        let cache = minimap.density.as_ref().unwrap();
+        assert_eq!(cache.bucket_len, 8);
+        let buckets = cache.buckets.as_ref().unwrap();
+        assert_eq!((buckets[0], buckets[124], buckets[187]), (1, 1, 1));
+        assert_eq!(buckets.iter().sum::<usize>(), 3);
+
+        // anything but appended data is scanned from the start
+        assert_eq!(minimap.start_scan(2, 5000, 4999), Some(0));
+        minimap.forget_density();
+        assert_eq!(minimap.start_scan(2, 5000, 4999), Some(0));
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{stdout, Stdout, Write};
+
+use anyhow::{anyhow, Ok, Result};
+
+/// How to tell about an event besides the status bar message.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Notification {
+    #[default]
+    None,
+    Bell,
+    // the status bar inverted for one frame
+    Flash,
+    // OSC 9, shown as a desktop notification by terminals supporting it
+    Desktop,
+}
+
+impl Notification {
+    // `none`, `bell`, `flash` or `osc9`
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "none" => Ok(Self::None),
+            "bell" => Ok(Self::Bell),
+            "flash" => Ok(Self::Flash),
+            "osc9" => Ok(Self::Desktop),
+            _ => Err(anyhow!("invalid notification `{value}`")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotifyEvent {
+    // a search found no match
+    NotFound,
+    // follow mode stopped at a matching line
+    FollowMatch,
+    Error,
+}
+
+/// The notification of each event, all silent by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NotifyPolicy {
+    pub not_found: Notification,
+    pub follow_match: Notification,
+    pub error: Notification,
+}
+
+impl NotifyPolicy {
+    pub fn of(&self, event: NotifyEvent) -> Notification {
+        match event {
+            NotifyEvent::NotFound => self.not_found,
+            NotifyEvent::FollowMatch => self.follow_match,
+            NotifyEvent::Error => self.error,
+        }
+    }
+}
+
+/// Writes the escape sequences of notifications, to stdout unless another writer is given.
+pub struct Notifier<W: Write = Stdout> {
+    policy: NotifyPolicy,
+    writer: W,
+}
+
+impl Notifier {
+    pub fn new(policy: NotifyPolicy) -> Self {
+        Self::with_writer(policy, stdout())
+    }
+}
+
+impl<W: Write> Notifier<W> {
+    pub fn with_writer(policy: NotifyPolicy, writer: W) -> Self {
+        Self { policy, writer }
+    }
+
+    pub fn set_policy(&mut self, policy: NotifyPolicy) {
+        self.policy = policy;
+    }
+
+    /// Emits what the policy picks for `event`, which is returned since a flash is left to the
+    /// status bar.
+    pub fn notify(&mut self, event: NotifyEvent, text: &str) -> Result<Notification> {
+        let notification = self.policy.of(event);
+        match notification {
+            Notification::Bell => write!(self.writer, "\x07")?,
+            Notification::Desktop => write!(self.writer, "\x1b]9;{}\x07", osc_text(text))?,
+            Notification::None | Notification::Flash => return Ok(notification),
+        }
+        self.writer.flush()?;
+        Ok(notification)
+    }
+}
+
+// control chars would end the sequence early
+fn osc_text(text: &str) -> String {
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    format!("loss: {text}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify() {
+        let policy = NotifyPolicy {
+            not_found: Notification::Bell,
+            follow_match: Notification::Desktop,
+            error: Notification::Flash,
+        };
+        let mut notifier = Notifier::with_writer(policy, vec![]);
+        assert_eq!(
+            notifier.notify(NotifyEvent::NotFound, "Not found").unwrap(),
+            Notification::Bell
+        );
+        assert_eq!(notifier.writer, b"\x07");
+        notifier
+            .notify(NotifyEvent::FollowMatch, "Pattern\x1b found")
+            .unwrap();
+        assert_eq!(notifier.writer, b"\x07\x1b]9;loss: Pattern found\x07");
+        // a flash is up to the caller
+        assert_eq!(
+            notifier.notify(NotifyEvent::Error, "oops").unwrap(),
+            Notification::Flash
+        );
+        assert_eq!(notifier.writer.len(), 25);
+
+        // silent by default
+        let mut notifier = Notifier::with_writer(NotifyPolicy::default(), vec![]);
+        for event in [
+            NotifyEvent::NotFound,
+            NotifyEvent::FollowMatch,
+            NotifyEvent::Error,
+        ] {
+            assert_eq!(notifier.notify(event, "").unwrap(), Notification::None);
+        }
+        assert!(notifier.writer.is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Notification::parse("osc9").unwrap(), Notification::Desktop);
+        assert_eq!(Notification::parse("none").unwrap(), Notification::None);
+        assert!(Notification::parse("loud").is_err());
+    }
+}
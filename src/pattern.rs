@@ -0,0 +1,269 @@
+//! Pattern matching shared by searching, highlighting and filtering.
+
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use regex::Regex;
+
+/// How the content of a pattern is interpreted.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PatternType {
+    /// Plain substring.
+    Raw,
+    /// Regular expression, an invalid one never matches.
+    Regex,
+}
+
+impl PatternType {
+    pub fn toggle(&mut self) {
+        match self {
+            Self::Raw => *self = Self::Regex,
+            Self::Regex => *self = Self::Raw,
+        }
+    }
+}
+
+/// What searching a line for a pattern found.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LineMatch {
+    NoMatch,
+    /// Byte range of the leftmost match.
+    MatchAt(Range<usize>),
+}
+
+impl LineMatch {
+    pub fn is_match(&self) -> bool {
+        matches!(self, Self::MatchAt(_))
+    }
+}
+
+impl From<Option<Range<usize>>> for LineMatch {
+    fn from(range: Option<Range<usize>>) -> Self {
+        range.map_or(Self::NoMatch, Self::MatchAt)
+    }
+}
+
+/// A pattern compiled once for matching many lines, e.g. every line of a scan.
+#[derive(Debug, Clone)]
+pub enum CompiledPattern {
+    Raw(String),
+    /// `None` for an invalid regular expression, which never matches.
+    Regex(Option<Regex>),
+}
+
+impl CompiledPattern {
+    pub fn new(pattern_type: PatternType, pattern: &str) -> Self {
+        match pattern_type {
+            PatternType::Raw => Self::Raw(pattern.to_string()),
+            PatternType::Regex => Self::Regex(Regex::new(pattern).ok()),
+        }
+    }
+
+    /// Returns byte range of the first match in `line`.
+    pub fn find(&self, line: &str) -> Option<Range<usize>> {
+        match self {
+            Self::Raw(pattern) => line.find(pattern).map(|start| start..start + pattern.len()),
+            Self::Regex(regex) => regex.as_ref()?.find(line).map(|m| m.start()..m.end()),
+        }
+    }
+
+    /// Returns byte ranges of all non-overlapping matches in `line`, from left to right.
+    /// Matching always runs over the whole line, so `^` and `$` anchor to its ends only.
+    pub fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            Self::Raw(pattern) => line
+                .match_indices(pattern.as_str())
+                .map(|(start, _)| start..start + pattern.len())
+                .collect(),
+            Self::Regex(Some(regex)) => regex.find_iter(line).map(|m| m.start()..m.end()).collect(),
+            Self::Regex(None) => vec![],
+        }
+    }
+}
+
+/// Returns byte range of the first match of `pattern` in `line`. The pattern is compiled on
+/// every call, [`CompiledPattern`] is for matching it against many lines.
+pub fn find_range_of_match(
+    pattern_type: PatternType,
+    pattern: &str,
+    line: &str,
+) -> Option<Range<usize>> {
+    CompiledPattern::new(pattern_type, pattern).find(line)
+}
+
+/// Returns byte ranges of all non-overlapping matches of `pattern` in `line`, see
+/// [`CompiledPattern::find_all`]. The pattern is compiled on every call.
+pub fn find_ranges_of_matches(
+    pattern_type: PatternType,
+    pattern: &str,
+    line: &str,
+) -> Vec<Range<usize>> {
+    CompiledPattern::new(pattern_type, pattern).find_all(line)
+}
+
+/// Raw patterns matched together in a single pass over a line, rather than one pass each,
+/// with every match still told apart by the pattern it's of.
+#[derive(Debug, Clone)]
+pub struct RawPatternSet {
+    // leftmost match of any pattern, the earliest given one where several start together
+    leftmost: AhoCorasick,
+    // every match of every distinct pattern, for telling them apart
+    overlapping: AhoCorasick,
+    // the given patterns of each distinct one, by index
+    owners: Vec<Vec<usize>>,
+    pattern_count: usize,
+}
+
+impl RawPatternSet {
+    /// `None` when a pattern is empty, which matches nowhere in particular.
+    pub fn new(patterns: &[&str]) -> Option<Self> {
+        if patterns.iter().any(|pattern| pattern.is_empty()) {
+            return None;
+        }
+        let mut distinct: Vec<&str> = vec![];
+        let mut owners: Vec<Vec<usize>> = vec![];
+        for (index, pattern) in patterns.iter().enumerate() {
+            match distinct.iter().position(|known| known == pattern) {
+                Some(position) => owners[position].push(index),
+                None => {
+                    distinct.push(pattern);
+                    owners.push(vec![index]);
+                }
+            }
+        }
+        let leftmost = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .build(&distinct)
+            .ok()?;
+        let overlapping = AhoCorasick::new(&distinct).ok()?;
+        Some(Self {
+            leftmost,
+            overlapping,
+            owners,
+            pattern_count: patterns.len(),
+        })
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.leftmost.is_match(line)
+    }
+
+    /// Byte range of the leftmost match of any pattern, like the first start of
+    /// [`find_range_of_match`] over the patterns one by one.
+    pub fn find(&self, line: &str) -> Option<Range<usize>> {
+        self.leftmost.find(line).map(|m| m.range())
+    }
+
+    /// Byte ranges of the matches of each pattern, by the index it was given at, the same as
+    /// [`find_ranges_of_matches`] finds them for each pattern alone.
+    pub fn find_ranges_of_matches(&self, line: &str) -> Vec<Vec<Range<usize>>> {
+        let mut ranges = vec![vec![]; self.owners.len()];
+        // matches come by where they end, which for a single pattern is where they start too
+        for m in self.overlapping.find_overlapping_iter(line) {
+            let of_pattern: &mut Vec<Range<usize>> = &mut ranges[m.pattern().as_usize()];
+            if of_pattern.last().is_none_or(|last| last.end <= m.start()) {
+                of_pattern.push(m.range());
+            }
+        }
+        let mut by_index = vec![vec![]; self.pattern_count];
+        for (owners, ranges) in self.owners.iter().zip(ranges) {
+            for owner in owners {
+                by_index[*owner] = ranges.clone();
+            }
+        }
+        by_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_range_of_match() {
+        assert_eq!(
+            find_range_of_match(PatternType::Raw, "bc", "abcbc"),
+            Some(1..3)
+        );
+        assert_eq!(find_range_of_match(PatternType::Raw, "x", "abc"), None);
+        assert_eq!(
+            find_range_of_match(PatternType::Regex, "b+c", "abbc"),
+            Some(1..4)
+        );
+        assert_eq!(
+            find_range_of_match(PatternType::Regex, "a.c", "a.c"),
+            Some(0..3)
+        );
+        assert_eq!(find_range_of_match(PatternType::Raw, "a.c", "abc"), None);
+        assert_eq!(find_range_of_match(PatternType::Regex, "(", "(abc"), None);
+
+        // compiled once, matched against many lines
+        let compiled = CompiledPattern::new(PatternType::Regex, r"id=\d+");
+        assert_eq!(compiled.find("x id=12"), Some(2..7));
+        assert_eq!(compiled.find_all("id=1 id=2"), vec![0..4, 5..9]);
+        assert_eq!(compiled.find("id="), None);
+        let invalid = CompiledPattern::new(PatternType::Regex, "(");
+        assert_eq!(invalid.find("(abc"), None);
+        assert!(invalid.find_all("(abc").is_empty());
+    }
+
+    #[test]
+    fn test_find_ranges_of_matches() {
+        assert_eq!(
+            find_ranges_of_matches(PatternType::Raw, "bc", "abcbcxbc"),
+            vec![1..3, 3..5, 6..8]
+        );
+        assert_eq!(
+            find_ranges_of_matches(PatternType::Raw, "aa", "aaa"),
+            vec![0..2]
+        );
+        // anchors hold against the whole line, not the rest of it after a match
+        assert_eq!(
+            find_ranges_of_matches(PatternType::Regex, "^ERROR", "ERRORERROR ERROR"),
+            vec![0..5]
+        );
+        assert_eq!(
+            find_ranges_of_matches(PatternType::Regex, "foo$", "foofoo foo"),
+            vec![7..10]
+        );
+        assert_eq!(
+            find_ranges_of_matches(PatternType::Regex, r"\bid=\d+", "id=1 xid=2 id=34"),
+            vec![0..4, 11..16]
+        );
+        assert_eq!(
+            find_ranges_of_matches(PatternType::Regex, "x*", "ab"),
+            vec![0..0, 1..1, 2..2]
+        );
+        assert!(find_ranges_of_matches(PatternType::Regex, "(", "(abc").is_empty());
+    }
+
+    #[test]
+    fn test_raw_pattern_set() {
+        let patterns = ["err", "error", "or", "err", "x"];
+        let set = RawPatternSet::new(&patterns).unwrap();
+        let line = "an error, or errerr";
+        assert!(set.is_match(line));
+        assert!(!set.is_match("warn"));
+        // the earliest given pattern where several start together
+        assert_eq!(set.find(line), Some(3..6));
+        assert_eq!(
+            set.find("no errors"),
+            find_range_of_match(PatternType::Raw, "err", "no errors")
+        );
+        let ranges = set.find_ranges_of_matches(line);
+        for (pattern, ranges) in patterns.iter().zip(ranges) {
+            assert_eq!(
+                ranges,
+                find_ranges_of_matches(PatternType::Raw, pattern, line),
+                "{pattern}"
+            );
+        }
+        // matches of a pattern don't overlap each other
+        let ranges = RawPatternSet::new(&["aa", "a"])
+            .unwrap()
+            .find_ranges_of_matches("aaaaa");
+        assert_eq!(ranges[0], [0..2, 2..4]);
+        assert_eq!(ranges[1], [0..1, 1..2, 2..3, 3..4, 4..5]);
+        assert!(RawPatternSet::new(&["a", ""]).is_none());
+    }
+}
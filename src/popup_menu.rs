@@ -0,0 +1,152 @@
+use std::ops::Range;
+
+use crate::{
+    canvas::Canvas,
+    render::LineWithRenderScheme,
+    status_bar::{cursor_column, truncate_middle},
+};
+
+// body rows kept visible above a menu, the menu isn't shown on shorter windows
+const MIN_BODY_HEIGHT: usize = 5;
+const TITLE_MIN_WIDTH: usize = 20;
+
+/// A menu over the bottom of the body: a title bar followed by rows, scrolled to show the
+/// selected one. On a window too small for it, only the title is shown in the status bar.
+#[derive(Debug, Default)]
+pub struct PopupMenu<'a> {
+    pub title: &'a str,
+    // rows below the title, fewer are padded with empty ones and more are scrolled
+    pub height: usize,
+    pub min_width: usize,
+    pub rows: Vec<LineWithRenderScheme>,
+    // scrolled into view and marked with `>`, rows aren't marked without a selection
+    pub selected: Option<usize>,
+    // shown in the status bar with the cursor at its end, the status bar is left empty
+    // without it
+    pub prompt: Option<String>,
+}
+
+impl PopupMenu<'_> {
+    pub fn render(&self, canvas: &mut Canvas, window_width: usize, window_height: usize) {
+        let title = title_bar(self.title, window_width);
+        if window_height < self.height + 1 + MIN_BODY_HEIGHT || window_width < self.min_width {
+            canvas.status_bar = LineWithRenderScheme::new(&title);
+            canvas.cursor_pos_x = None;
+            return;
+        }
+        canvas.popup_menu.clear();
+        canvas.popup_menu.push(LineWithRenderScheme::new(&title));
+        for index in viewport(self.selected, self.rows.len(), self.height) {
+            let row = match self.selected {
+                Some(selected) => {
+                    let cursor = if index == selected { '>' } else { ' ' };
+                    let mut row = LineWithRenderScheme::new(&format!(" {cursor} "));
+                    row.push_line(&self.rows[index]);
+                    row
+                }
+                None => self.rows[index].clone(),
+            };
+            canvas.popup_menu.push(row.truncate(window_width));
+        }
+        canvas
+            .popup_menu
+            .resize(self.height + 1, LineWithRenderScheme::default());
+
+        let prompt = self.prompt.as_deref().unwrap_or_default();
+        let prompt = truncate_middle(prompt, window_width.saturating_sub(1));
+        canvas.cursor_pos_x = Some(cursor_column(&prompt, window_width));
+        canvas.status_bar = LineWithRenderScheme::new(&prompt);
+    }
+}
+
+// the title centered in a bar of `=`
+fn title_bar(title: &str, window_width: usize) -> String {
+    let width = std::cmp::max(window_width, TITLE_MIN_WIDTH);
+    let mut bar = "=".repeat(width);
+    let title = format!(" {title} ");
+    let begin = width.saturating_sub(title.len()) / 2;
+    let end = std::cmp::min(begin + title.len(), width);
+    bar.replace_range(begin..end, &title[..end - begin]);
+    bar.truncate(window_width);
+    bar
+}
+
+/// Indexes of the rows shown in `height` rows, starting from the selected one unless that
+/// leaves rows at the end unused.
+pub fn viewport(selected: Option<usize>, len: usize, height: usize) -> Range<usize> {
+    let start = std::cmp::min(selected.unwrap_or(0), len.saturating_sub(height));
+    start..std::cmp::min(start + height, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewport() {
+        // a list shorter than the viewport is shown whole
+        assert_eq!(viewport(Some(0), 3, 9), 0..3);
+        assert_eq!(viewport(Some(2), 3, 9), 0..3);
+        assert_eq!(viewport(None, 0, 9), 0..0);
+        // near the top the selection leads
+        assert_eq!(viewport(Some(0), 20, 9), 0..9);
+        assert_eq!(viewport(Some(5), 20, 9), 5..14);
+        // near the bottom the last rows stay in view
+        assert_eq!(viewport(Some(15), 20, 9), 11..20);
+        assert_eq!(viewport(Some(19), 20, 9), 11..20);
+        assert_eq!(viewport(None, 20, 9), 0..9);
+    }
+
+    #[test]
+    fn test_title_bar() {
+        assert_eq!(title_bar("Menu", 20), "======= Menu =======");
+        assert_eq!(title_bar("Menu", 10), "======= Me");
+        assert_eq!(
+            title_bar("A rather long menu title", 20),
+            " A rather long menu "
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let rows: Vec<_> = (0..20)
+            .map(|i| LineWithRenderScheme::new(&format!("row {i}")))
+            .collect();
+        let mut menu = PopupMenu {
+            title: "Menu",
+            height: 4,
+            min_width: 20,
+            rows,
+            selected: Some(17),
+            prompt: Some("Filter: ".to_string()),
+        };
+        let mut canvas = Canvas::default();
+        menu.render(&mut canvas, 30, 20);
+        let shown: Vec<_> = canvas
+            .popup_menu
+            .iter()
+            .skip(1)
+            .map(|row| row.raw_content().to_string())
+            .collect();
+        assert_eq!(shown, ["   row 16", " > row 17", "   row 18", "   row 19"]);
+        assert_eq!(canvas.status_bar.raw_content(), "Filter: ");
+        assert_eq!(canvas.cursor_pos_x, Some(8));
+
+        // padded when the list is short
+        menu.rows.truncate(2);
+        menu.selected = Some(1);
+        menu.render(&mut canvas, 30, 20);
+        assert_eq!(canvas.popup_menu.len(), 5);
+        assert_eq!(canvas.popup_menu[2].raw_content(), " > row 1");
+        assert_eq!(canvas.popup_menu[4].raw_content(), "");
+
+        // too small, only the title is shown
+        let mut canvas = Canvas::default();
+        menu.render(&mut canvas, 30, 9);
+        assert!(canvas.popup_menu.is_empty());
+        assert_eq!(canvas.status_bar.raw_content(), title_bar("Menu", 30));
+        assert_eq!(canvas.cursor_pos_x, None);
+        menu.render(&mut canvas, 19, 20);
+        assert!(canvas.popup_menu.is_empty());
+    }
+}
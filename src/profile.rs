@@ -0,0 +1,90 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::config::config_dir;
+
+/// Fold and exclusive rules of finder slots, saved under a name.
+#[derive(Debug, Default, PartialEq)]
+pub struct FilterProfile {
+    pub rules: Vec<FilterRule>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FilterRule {
+    // hides lines matching `pattern` if false, keeps only them otherwise
+    pub exclusive: bool,
+    pub pattern: String,
+}
+
+impl FilterProfile {
+    // profiles live in `<config dir>/profiles/<name>`
+    pub fn path_of(name: &str) -> Result<PathBuf> {
+        config_dir()
+            .map(|dir| dir.join("profiles").join(name))
+            .ok_or_else(|| anyhow!("cannot locate config directory for profile `{name}`"))
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::path_of(name)?;
+        let content = fs::read_to_string(&path)
+            .map_err(|e| anyhow!("cannot read profile {}: {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| anyhow!("invalid profile {}: {e}", path.display()))
+    }
+
+    // one `fold <pattern>` or `exclusive <pattern>` per line, blank lines and lines starting
+    // with `#` are skipped. patterns may contain `#` and spaces, only the one space after the
+    // action is stripped
+    fn parse(content: &str) -> Result<Self> {
+        let mut rules = vec![];
+        for (line_number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let rule = match line.trim_start().split_once(' ') {
+                Some(("fold", pattern)) if !pattern.is_empty() => FilterRule {
+                    exclusive: false,
+                    pattern: pattern.to_string(),
+                },
+                Some(("exclusive", pattern)) if !pattern.is_empty() => FilterRule {
+                    exclusive: true,
+                    pattern: pattern.to_string(),
+                },
+                _ => {
+                    return Err(anyhow!(
+                        "line {}: expect `fold <pattern>` or `exclusive <pattern>`",
+                        line_number + 1
+                    ))
+                }
+            };
+            rules.push(rule);
+        }
+        Ok(Self { rules })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!(
+            FilterProfile::parse("# nginx\n\nfold GET /health\nexclusive  status=5\n").unwrap(),
+            FilterProfile {
+                rules: vec![
+                    FilterRule {
+                        exclusive: false,
+                        pattern: "GET /health".to_string(),
+                    },
+                    FilterRule {
+                        exclusive: true,
+                        pattern: " status=5".to_string(),
+                    },
+                ]
+            }
+        );
+        assert!(FilterProfile::parse("fold").is_err());
+        assert!(FilterProfile::parse("hide x").is_err());
+    }
+}
@@ -2,7 +2,7 @@ use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use crate::event_source::Direction;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PromptAction {
     Start(Option<Direction>),
     Content(String),
@@ -15,11 +15,25 @@ pub struct Prompt {
     prompt_text: Option<String>,
     prompt_history: Vec<String>,
     history_index: usize,
+    // replaces the prompt text on tab, if there's a completion
+    completer: Option<fn(&str) -> Option<String>>,
 }
 
 impl Prompt {
+    pub fn with_completer(completer: fn(&str) -> Option<String>) -> Self {
+        Self {
+            completer: Some(completer),
+            ..Self::default()
+        }
+    }
+
     pub fn start(&mut self) {
-        self.prompt_text = Some(String::default());
+        self.start_with("");
+    }
+
+    // starts with `text` already typed, as when editing an existing value
+    pub fn start_with(&mut self, text: &str) {
+        self.prompt_text = Some(text.to_string());
         self.history_index = self.prompt_history.len();
     }
 
@@ -79,6 +93,11 @@ impl Prompt {
                     self.finish();
                     Some(PromptAction::Enter(prompt_text))
                 }
+                KeyCode::Tab => {
+                    let completed = (self.completer?)(prompt_text)?;
+                    *prompt_text = completed;
+                    Some(PromptAction::Content(prompt_text.to_string()))
+                }
                 KeyCode::Esc => {
                     self.finish();
                     Some(PromptAction::Cancel)
@@ -132,4 +151,21 @@ mod tests {
         prompt.push_history("123");
         assert_eq!(prompt.prompt_history.len(), 3);
     }
+
+    #[test]
+    fn test_prompt_completion() {
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+        let mut prompt = Prompt::default();
+        prompt.start();
+        assert_eq!(prompt.handle_raw_event(&tab), None);
+
+        let mut prompt = Prompt::with_completer(|text| (text == "a").then(|| "abc".to_string()));
+        prompt.start();
+        assert_eq!(prompt.handle_raw_event(&tab), None);
+        prompt.handle_raw_event(&KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        assert_eq!(
+            prompt.handle_raw_event(&tab),
+            Some(PromptAction::Content("abc".to_string()))
+        );
+    }
 }
@@ -1,19 +1,47 @@
-use std::{ops::Range, vec};
+use std::{
+    ops::{Bound, Range, RangeBounds},
+    vec,
+};
 
-use crossterm::style::Stylize;
+use crossterm::style::{Attribute, SetAttribute, Stylize};
+use serde::{Deserialize, Serialize};
 
 use crate::finder::HighlightOption;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RenderScheme {
     Dim,
+    Reverse,
     Highlight(HighlightOption),
 }
 
-#[derive(Debug, Clone, Default)]
+// a scheme over a byte range. where spans overlap, the one on the higher layer is shown, or the
+// one added later on the same layer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SchemeSpan {
+    range: Range<usize>,
+    scheme: RenderScheme,
+    layer: u8,
+}
+
+// columns a tab advances to the next multiple of when invisibles are shown
+const TAB_WIDTH: usize = 8;
+const TAB_GLYPH: char = '→';
+const TRAILING_SPACE_GLYPH: char = '·';
+const CARRIAGE_RETURN_GLYPH: char = '␍';
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct LineWithRenderScheme {
     content: String,
-    render_schemes: Vec<(Range<usize>, RenderScheme)>,
+    render_schemes: Vec<SchemeSpan>,
+    // the whole row, on top of the schemes
+    underlined: bool,
+    // chars drawn in place of the single-byte placeholders at these positions of the content,
+    // which keeps a byte a column for shifting and wrapping
+    glyphs: Vec<(usize, char)>,
+    // bytes of the line left out after the content as they're far from what's shown, still
+    // counted when wrapping
+    cut_len: usize,
 }
 
 impl LineWithRenderScheme {
@@ -21,11 +49,92 @@ impl LineWithRenderScheme {
         Self {
             content: content.to_string(),
             render_schemes: vec![],
+            underlined: false,
+            glyphs: vec![],
+            cut_len: 0,
         }
     }
 
+    /// Marks the content as the start of a line `cut_len` bytes longer.
+    pub fn with_cut_len(mut self, cut_len: usize) -> Self {
+        self.cut_len = cut_len;
+        self
+    }
+
+    /// The line with tabs expanded to the next tab stop and shown as `→`, trailing whitespace
+    /// dimmed with spaces shown as `·` and carriage returns at the end as `␍`. Schemes are
+    /// moved along with the content they cover, which still is what searches match.
+    pub fn with_invisibles(&self) -> LineWithRenderScheme {
+        // the end of a cut line isn't known to be blank
+        let (body, trailing_start) = if self.cut_len > 0 {
+            (self.content.as_str(), self.content.len())
+        } else {
+            let body = self.content.trim_end_matches('\r');
+            (body, body.trim_end_matches([' ', '\t']).len())
+        };
+        let mut shown = LineWithRenderScheme::default();
+        // where each byte of the content went
+        let mut new_positions = Vec::with_capacity(self.content.len() + 1);
+        let mut column = 0;
+        for (index, c) in self.content.char_indices() {
+            new_positions.extend(std::iter::repeat_n(shown.content.len(), c.len_utf8()));
+            let glyph = match c {
+                '\t' => Some(TAB_GLYPH),
+                '\r' if index >= body.len() => Some(CARRIAGE_RETURN_GLYPH),
+                ' ' if index >= trailing_start => Some(TRAILING_SPACE_GLYPH),
+                _ => None,
+            };
+            let Some(glyph) = glyph else {
+                shown.content.push(c);
+                column += 1;
+                continue;
+            };
+            shown.glyphs.push((shown.content.len(), glyph));
+            let width = if c == '\t' {
+                TAB_WIDTH - column % TAB_WIDTH
+            } else {
+                1
+            };
+            shown.content.extend(std::iter::repeat_n(' ', width));
+            column += width;
+        }
+        new_positions.push(shown.content.len());
+        shown.render_schemes = self
+            .render_schemes
+            .iter()
+            .map(|span| SchemeSpan {
+                range: new_positions[std::cmp::min(span.range.start, self.content.len())]
+                    ..new_positions[std::cmp::min(span.range.end, self.content.len())],
+                ..*span
+            })
+            .collect();
+        shown.underlined = self.underlined;
+        shown.cut_len = self.cut_len;
+        // each run of trailing blanks not covered by a scheme already
+        let mut dimmed: Vec<Range<usize>> = vec![];
+        for index in trailing_start..self.content.len() {
+            let range = new_positions[index]..new_positions[index + 1];
+            if shown
+                .render_schemes
+                .iter()
+                .any(|span| ranges_have_overlap(range.clone(), span.range.clone()))
+            {
+                continue;
+            }
+            match dimmed.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => dimmed.push(range),
+            }
+        }
+        for range in dimmed {
+            shown.add_scheme_layered(range, RenderScheme::Dim, 0);
+        }
+        shown
+    }
+
     pub fn truncate(mut self, width: usize) -> Self {
         self.content.truncate(width);
+        self.glyphs.retain(|(position, _)| *position < width);
         self
     }
 
@@ -33,20 +142,27 @@ impl LineWithRenderScheme {
         &self.content
     }
 
-    pub fn set_raw_content(&mut self, raw_content: &str) {
-        self.content = raw_content.to_string();
-    }
-
+    /// Adds `scheme` on the lowest layer, unless it overlaps any scheme already added.
     pub fn add_scheme_if_not_overlap(&mut self, range: Range<usize>, scheme: RenderScheme) {
         if self
             .render_schemes
             .iter()
-            .all(|(existing_range, _)| !ranges_have_overlap(range.clone(), existing_range.clone()))
+            .all(|span| !ranges_have_overlap(range.clone(), span.range.clone()))
         {
-            self.render_schemes.push((range, scheme));
+            self.add_scheme_layered(range, scheme, 0);
         }
     }
 
+    /// Adds `scheme` on `layer`, shown over schemes on lower layers where they overlap and
+    /// under those on higher ones. On the same layer, the scheme added last is shown.
+    pub fn add_scheme_layered(&mut self, range: Range<usize>, scheme: RenderScheme, layer: u8) {
+        self.render_schemes.push(SchemeSpan {
+            range,
+            scheme,
+            layer,
+        });
+    }
+
     pub fn substr(&self, width_range: Range<usize>) -> LineWithRenderScheme {
         let content = if width_range.start >= self.content.len() {
             String::default()
@@ -55,45 +171,529 @@ impl LineWithRenderScheme {
             self.content[width_range.start..end].to_string()
         };
         let mut sub_schemes = vec![];
-        for (range, scheme) in self.render_schemes.iter() {
-            let new_start = std::cmp::max(range.start, width_range.start);
-            let new_end = std::cmp::min(range.end, width_range.end);
+        for span in self.render_schemes.iter() {
+            let new_start = std::cmp::max(span.range.start, width_range.start);
+            let new_end = std::cmp::min(span.range.end, width_range.end);
             if new_start < new_end {
                 let s = new_start - width_range.start;
                 let e = new_end - width_range.start;
-                sub_schemes.push((s..e, *scheme));
+                sub_schemes.push(SchemeSpan {
+                    range: s..e,
+                    ..*span
+                });
             }
         }
+        let glyphs = self
+            .glyphs
+            .iter()
+            .filter(|(position, _)| width_range.contains(position))
+            .map(|(position, glyph)| (position - width_range.start, *glyph))
+            .collect();
         LineWithRenderScheme {
             content,
             render_schemes: sub_schemes,
+            underlined: self.underlined,
+            glyphs,
+            cut_len: 0,
         }
     }
 
-    pub fn render(&self) -> String {
-        let mut render_schemes = self.render_schemes.clone();
-        render_schemes.sort_by(|a, b| a.0.start.cmp(&b.0.start));
-        for window in render_schemes.windows(2) {
-            assert!(window[0].0.end <= window[1].0.start);
+    /// Underlines the row padded to `width`, so that the line stands out even when it's short.
+    pub fn underline(&mut self, width: usize) {
+        self.pad_to(width);
+        self.underlined = true;
+    }
+
+    pub fn pad_to(&mut self, width: usize) {
+        let padding = width.saturating_sub(self.content.chars().count());
+        self.content.extend(std::iter::repeat_n(' ', padding));
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.content.push_str(s);
+    }
+
+    /// Appends `other` along with its schemes.
+    pub fn push_line(&mut self, other: &LineWithRenderScheme) {
+        let start = self.content.len();
+        self.content.push_str(&other.content);
+        for span in other.render_schemes.iter() {
+            self.render_schemes.push(SchemeSpan {
+                range: span.range.start + start..span.range.end + start,
+                ..*span
+            });
         }
-        let mut rendered_line = self.content.to_string();
-        for (range, scheme) in render_schemes.into_iter().rev() {
-            let raw = self.content[range.clone()].to_string();
-            let rendered = match scheme {
+        self.glyphs.extend(
+            other
+                .glyphs
+                .iter()
+                .map(|(position, glyph)| (position + start, *glyph)),
+        );
+    }
+
+    pub fn push_str_with_scheme(&mut self, s: &str, scheme: RenderScheme) {
+        let start = self.content.len();
+        self.content.push_str(s);
+        self.add_scheme_layered(start..self.content.len(), scheme, 0);
+    }
+
+    /// Splits into rows of `width`, those after the first led by a dim `indicator` followed by
+    /// a space, unless that leaves no room. With `max_rows`, rows beyond it are folded and the
+    /// last row ends with a dim marker telling how many columns are not shown. Only the rows
+    /// in `rows` are built, at least the last one if they're all beyond it.
+    pub fn wrap(
+        &self,
+        width: usize,
+        max_rows: Option<usize>,
+        indicator: &str,
+        rows: impl RangeBounds<usize>,
+    ) -> Vec<LineWithRenderScheme> {
+        let lead = wrap_lead(indicator, width);
+        let lead_width = lead.chars().count();
+        let len = self.content.len() + self.cut_len;
+        let row_count = wrapped_row_count(len, width, indicator);
+        let max_rows = max_rows.unwrap_or(row_count).max(1);
+        let row_start = |idx: usize| wrapped_row_start(idx, width, indicator);
+        let row = |idx: usize, len: usize| {
+            let start = row_start(idx);
+            let mut row = LineWithRenderScheme::default();
+            if idx > 0 && !lead.is_empty() {
+                row.push_str_with_scheme(&lead, RenderScheme::Dim);
+            }
+            row.push_line(&self.substr(start..start + len));
+            row
+        };
+        let row_width = |idx: usize| if idx == 0 { width } else { width - lead_width };
+        let shown_row_count = std::cmp::min(row_count, max_rows);
+        let end = match rows.end_bound() {
+            Bound::Included(end) => std::cmp::min(end + 1, shown_row_count),
+            Bound::Excluded(end) => std::cmp::min(*end, shown_row_count),
+            Bound::Unbounded => shown_row_count,
+        };
+        let start = match rows.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let start = std::cmp::min(start, shown_row_count - 1);
+        let end = std::cmp::max(end, start + 1);
+        let mut rows: Vec<_> = (start..end).map(|idx| row(idx, row_width(idx))).collect();
+        if row_count > max_rows && end == max_rows {
+            let idx = max_rows - 1;
+            let start = row_start(idx);
+            let marker_width = |folded: usize| fold_marker(folded).chars().count();
+            // the marker only gets shorter once some columns are shown before it
+            if let Some(shown) = row_width(idx).checked_sub(marker_width(len - start)) {
+                let mut last_row = row(idx, shown);
+                let folded = len - start - shown;
+                last_row.push_str_with_scheme(&fold_marker(folded), RenderScheme::Dim);
+                *rows.last_mut().unwrap() = last_row;
+            }
+        }
+        rows
+    }
+
+    pub fn render(&self) -> String {
+        let mut rendered_line = String::default();
+        let mut end = 0;
+        for (range, scheme) in self.resolve_schemes() {
+            rendered_line.push_str(&self.shown(end..range.start));
+            end = range.end;
+            let raw = self.shown(range);
+            let mut rendered = match scheme {
                 RenderScheme::Dim => raw.dim().to_string(),
+                RenderScheme::Reverse => raw.reverse().to_string(),
                 RenderScheme::Highlight(option) => option.render(&raw),
             };
-            rendered_line.replace_range(range, &rendered);
+            if self.underlined {
+                // a styled range ends with a reset, which drops the underline as well
+                rendered = format!("{}{rendered}", SetAttribute(Attribute::Underlined));
+                rendered.push_str(&SetAttribute(Attribute::Underlined).to_string());
+            }
+            rendered_line.push_str(&rendered);
+        }
+        rendered_line.push_str(&self.shown(end..self.content.len()));
+        if self.underlined {
+            rendered_line = format!(
+                "{}{rendered_line}{}",
+                SetAttribute(Attribute::Underlined),
+                SetAttribute(Attribute::NoUnderline)
+            );
         }
         rendered_line
     }
 
+    /// The content with each shown scheme marked in plain text: `[highlight]`, `<reverse>` and
+    /// `{dim}`. Underlining is left out.
+    #[cfg(test)]
+    pub fn marked(&self) -> String {
+        let mut marked = String::default();
+        let mut end = 0;
+        for (range, scheme) in self.resolve_schemes() {
+            let (open, close) = match scheme {
+                RenderScheme::Dim => ('{', '}'),
+                RenderScheme::Reverse => ('<', '>'),
+                RenderScheme::Highlight(_) => ('[', ']'),
+            };
+            marked.push_str(&self.shown(end..range.start));
+            end = range.end;
+            marked.push(open);
+            marked.push_str(&self.shown(range));
+            marked.push(close);
+        }
+        marked.push_str(&self.shown(end..self.content.len()));
+        marked
+    }
+
+    // the content in `range` with the glyphs in place of their placeholders
+    fn shown(&self, range: Range<usize>) -> String {
+        let mut shown = self.content[range.clone()].to_string();
+        for (position, glyph) in self.glyphs.iter().rev() {
+            if range.contains(position) {
+                let position = position - range.start;
+                shown.replace_range(position..position + 1, glyph.encode_utf8(&mut [0; 4]));
+            }
+        }
+        shown
+    }
+
+    // the shown scheme of each part of the content, as ranges in order that don't overlap. a
+    // span only split by others is shown as one range on both sides of them
+    fn resolve_schemes(&self) -> Vec<(Range<usize>, RenderScheme)> {
+        let len = self.content.len();
+        let mut bounds: Vec<usize> = self
+            .render_schemes
+            .iter()
+            .flat_map(|span| [span.range.start, span.range.end])
+            .map(|bound| std::cmp::min(bound, len))
+            .collect();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let mut resolved: Vec<(Range<usize>, usize)> = vec![];
+        for part in bounds.windows(2) {
+            let (start, end) = (part[0], part[1]);
+            // the last of the highest, as `max_by_key` picks the last of equal keys
+            let shown = self
+                .render_schemes
+                .iter()
+                .enumerate()
+                .filter(|(_, span)| span.range.start <= start && end <= span.range.end)
+                .max_by_key(|(_, span)| span.layer)
+                .map(|(index, _)| index);
+            let Some(index) = shown else {
+                continue;
+            };
+            match resolved.last_mut() {
+                Some((range, last)) if *last == index && range.end == start => range.end = end,
+                _ => resolved.push((start..end, index)),
+            }
+        }
+        resolved
+            .into_iter()
+            .map(|(range, index)| (range, self.render_schemes[index].scheme))
+            .collect()
+    }
+
     pub fn clear(&mut self) {
         self.content.clear();
         self.render_schemes.clear();
+        self.underlined = false;
+        self.glyphs.clear();
+        self.cut_len = 0;
+    }
+}
+
+// what leads rows after the first of a wrapped line, nothing if the indicator is empty or
+// leaves no room in `width`
+fn wrap_lead(indicator: &str, width: usize) -> String {
+    if indicator.is_empty() || indicator.chars().count() + 1 >= width {
+        return String::default();
     }
+    format!("{indicator} ")
+}
+
+/// Rows a line of `len` columns takes when wrapped in `width` columns, see
+/// [`LineWithRenderScheme::wrap`].
+pub fn wrapped_row_count(len: usize, width: usize, indicator: &str) -> usize {
+    let lead_width = wrap_lead(indicator, width).chars().count();
+    if len <= width {
+        1
+    } else {
+        1 + (len - width).div_ceil(width - lead_width)
+    }
+}
+
+/// Column of the line the row at `row` starts with when wrapped in `width` columns, the first
+/// row takes the whole width and the others what the indicator leaves.
+pub fn wrapped_row_start(row: usize, width: usize, indicator: &str) -> usize {
+    let lead_width = wrap_lead(indicator, width).chars().count();
+    match row {
+        0 => 0,
+        _ => width + (row - 1) * (width - lead_width),
+    }
+}
+
+/// Columns `line` takes with invisibles shown, where tabs are expanded to the next tab stop.
+pub fn width_with_invisibles(line: &str) -> usize {
+    line.chars().fold(0, |column, c| match c {
+        '\t' => column + TAB_WIDTH - column % TAB_WIDTH,
+        _ => column + 1,
+    })
+}
+
+fn fold_marker(folded: usize) -> String {
+    format!("… +{folded} more cols")
 }
 
 fn ranges_have_overlap(r1: Range<usize>, r2: Range<usize>) -> bool {
     r1.start < r2.end && r1.end > r2.start
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(rows: &[LineWithRenderScheme]) -> Vec<&str> {
+        rows.iter().map(|row| row.raw_content()).collect()
+    }
+
+    #[test]
+    fn test_wrap() {
+        let line = LineWithRenderScheme::new("0123456789abcdefghij");
+        assert_eq!(
+            contents(&line.wrap(8, None, "", ..)),
+            ["01234567", "89abcdef", "ghij"]
+        );
+        assert_eq!(
+            contents(&line.wrap(10, None, "", ..)),
+            ["0123456789", "abcdefghij"]
+        );
+        assert_eq!(contents(&line.wrap(8, Some(3), "", ..)).len(), 3);
+        assert_eq!(
+            contents(&LineWithRenderScheme::new("").wrap(8, Some(3), "", ..)),
+            [""]
+        );
+    }
+
+    #[test]
+    fn test_wrap_with_row_limit() {
+        let line = LineWithRenderScheme::new(&"x".repeat(100));
+        let rows = line.wrap(20, Some(3), "", ..);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].raw_content(), "x".repeat(20));
+        // 40 columns in the first two rows, 5 before the marker, 55 folded
+        assert_eq!(
+            rows[2].raw_content(),
+            format!("{}… +55 more cols", "x".repeat(5))
+        );
+        assert_eq!(rows[2].raw_content().chars().count(), 20);
+        assert!(matches!(
+            rows[2].render_schemes[..],
+            [SchemeSpan { ref range, scheme: RenderScheme::Dim, .. }] if range.start == 5
+        ));
+
+        // rows consumed by each line, mixing short and long ones
+        let lines = ["short", &"y".repeat(45), "", &"z".repeat(1000)];
+        let row_counts: Vec<_> = lines
+            .iter()
+            .map(|line| {
+                LineWithRenderScheme::new(line)
+                    .wrap(20, Some(2), "", ..)
+                    .len()
+            })
+            .collect();
+        assert_eq!(row_counts, [1, 2, 1, 2]);
+
+        // too narrow for the marker, rows are just cut
+        let rows = line.wrap(10, Some(2), "", ..);
+        assert_eq!(contents(&rows), ["x".repeat(10), "x".repeat(10)]);
+    }
+
+    #[test]
+    fn test_wrap_some_rows() {
+        let line = LineWithRenderScheme::new("0123456789abcdefghij");
+        assert_eq!(contents(&line.wrap(8, None, "", 1..2)), ["89abcdef"]);
+        assert_eq!(contents(&line.wrap(8, None, "", 2..)), ["ghij"]);
+        // a start past the end still shows the last row
+        assert_eq!(contents(&line.wrap(8, None, "", 5..9)), ["ghij"]);
+
+        // the cut part of a line counts towards its folded columns
+        let line = LineWithRenderScheme::new(&"x".repeat(45)).with_cut_len(55);
+        let rows = line.wrap(20, Some(3), "", 2..3);
+        assert_eq!(
+            contents(&rows),
+            [format!("{}… +55 more cols", "x".repeat(5))]
+        );
+    }
+
+    #[test]
+    fn test_wrap_with_indicator() {
+        let line = LineWithRenderScheme::new("0123456789abcdefghij");
+        let rows = line.wrap(8, None, "↪", ..);
+        assert_eq!(contents(&rows), ["01234567", "↪ 89abcd", "↪ efghij"]);
+        assert!(rows[0].render_schemes.is_empty());
+        assert!(matches!(
+            rows[1].render_schemes[..],
+            [SchemeSpan { ref range, scheme: RenderScheme::Dim, .. }] if *range == (0..4)
+        ));
+        assert_eq!(wrapped_row_count(20, 8, "↪"), 3);
+        assert_eq!(wrapped_row_count(21, 8, "↪"), 4);
+        assert_eq!(wrapped_row_count(8, 8, "↪"), 1);
+        assert_eq!(wrapped_row_start(2, 8, "↪"), 14);
+
+        // the marker of a folded line goes after the indicator
+        let rows = LineWithRenderScheme::new(&"x".repeat(100)).wrap(20, Some(2), ">>", ..);
+        assert_eq!(
+            rows[1].raw_content(),
+            format!(">> {}… +78 more cols", "x".repeat(2))
+        );
+
+        // dropped when it leaves no room
+        assert_eq!(contents(&line.wrap(3, Some(2), ">>", ..)), ["012", "345"]);
+        assert_eq!(wrapped_row_count(20, 3, ">>"), 7);
+    }
+
+    #[test]
+    fn test_with_invisibles() {
+        let mut line = LineWithRenderScheme::new("a\tbc  \r");
+        line.add_scheme_layered(2..4, RenderScheme::Reverse, 0);
+        let shown = line.with_invisibles();
+        // the tab reaches the next tab stop, the match moves along and trailing blanks are dim
+        assert_eq!(shown.raw_content(), format!("a{}bc   ", " ".repeat(7)));
+        assert_eq!(shown.marked(), "a→      <bc>{··␍}");
+        assert_eq!(width_with_invisibles("a\tbc  \r"), 13);
+
+        // a match over the tab covers all of its columns, and a highlighted trailing blank
+        // isn't dimmed over
+        let mut line = LineWithRenderScheme::new("ab\tc \t");
+        line.add_scheme_layered(1..4, RenderScheme::Reverse, 0);
+        line.add_scheme_layered(4..5, RenderScheme::Reverse, 0);
+        let shown = line.with_invisibles();
+        assert_eq!(shown.marked(), "a<b→     c><·>{→     }");
+        // inner blanks and carriage returns are left as they are
+        let shown = LineWithRenderScheme::new("a b\rc").with_invisibles();
+        assert_eq!(shown.marked(), "a b\rc");
+
+        // glyphs go along when the line is shifted or wrapped
+        let shown = LineWithRenderScheme::new("x\ty ").with_invisibles();
+        assert_eq!(shown.substr(1..4).marked(), "→  ");
+        assert_eq!(shown.substr(8..12).marked(), "y{·}");
+        let rows: Vec<_> = shown
+            .wrap(6, None, "", ..)
+            .iter()
+            .map(|row| row.marked())
+            .collect();
+        assert_eq!(rows, ["x→    ", "  y{·}"]);
+    }
+
+    #[test]
+    fn test_marked() {
+        let mut line = LineWithRenderScheme::new("an err and a warn");
+        line.add_scheme_layered(3..6, RenderScheme::Reverse, 0);
+        line.add_scheme_layered(13..17, RenderScheme::Dim, 0);
+        assert_eq!(line.marked(), "an <err> and a {warn}");
+        assert_eq!(LineWithRenderScheme::new("plain").marked(), "plain");
+    }
+
+    // the shown schemes as `(range, name)`, where highlights are told apart by style
+    fn resolved(line: &LineWithRenderScheme) -> Vec<(Range<usize>, &'static str)> {
+        line.resolve_schemes()
+            .into_iter()
+            .map(|(range, scheme)| {
+                let name = match scheme {
+                    RenderScheme::Dim => "dim",
+                    RenderScheme::Reverse => "reverse",
+                    RenderScheme::Highlight(_) => "highlight",
+                };
+                (range, name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_add_scheme_if_not_overlap() {
+        let mut line = LineWithRenderScheme::new("0123456789");
+        line.add_scheme_if_not_overlap(2..5, RenderScheme::Dim);
+        // dropped as it overlaps, adjacent ones are kept
+        line.add_scheme_if_not_overlap(4..6, RenderScheme::Reverse);
+        line.add_scheme_if_not_overlap(5..7, RenderScheme::Reverse);
+        line.add_scheme_if_not_overlap(0..2, RenderScheme::Reverse);
+        assert_eq!(
+            resolved(&line),
+            [(0..2, "reverse"), (2..5, "dim"), (5..7, "reverse")]
+        );
+        // also blocked by a layered one
+        line.add_scheme_layered(8..10, RenderScheme::Dim, 2);
+        line.add_scheme_if_not_overlap(9..10, RenderScheme::Reverse);
+        assert_eq!(resolved(&line).last(), Some(&(8..10, "dim")));
+    }
+
+    #[test]
+    fn test_add_scheme_layered() {
+        // nested, the outer one shown on both sides of the inner one
+        let mut line = LineWithRenderScheme::new("0123456789");
+        line.add_scheme_layered(0..10, RenderScheme::Dim, 0);
+        line.add_scheme_layered(3..5, RenderScheme::Reverse, 1);
+        assert_eq!(
+            resolved(&line),
+            [(0..3, "dim"), (3..5, "reverse"), (5..10, "dim")]
+        );
+        // nested under a higher layer, hidden entirely
+        line.add_scheme_layered(0..10, RenderScheme::Dim, 2);
+        assert_eq!(resolved(&line), [(0..10, "dim")]);
+
+        // partially overlapping, whichever order they're added in
+        for flipped in [false, true] {
+            let mut line = LineWithRenderScheme::new("0123456789");
+            let mut spans = vec![
+                (2..6, RenderScheme::Dim, 1),
+                (4..8, RenderScheme::Reverse, 0),
+            ];
+            if flipped {
+                spans.reverse();
+            }
+            for (range, scheme, layer) in spans {
+                line.add_scheme_layered(range, scheme, layer);
+            }
+            assert_eq!(resolved(&line), [(2..6, "dim"), (6..8, "reverse")]);
+        }
+
+        // adjacent ones stay apart
+        let mut line = LineWithRenderScheme::new("0123456789");
+        line.add_scheme_layered(0..3, RenderScheme::Dim, 1);
+        line.add_scheme_layered(3..6, RenderScheme::Dim, 0);
+        assert_eq!(resolved(&line), [(0..3, "dim"), (3..6, "dim")]);
+
+        // identical ones, the higher layer wins, then the one added last
+        let mut line = LineWithRenderScheme::new("0123456789");
+        line.add_scheme_layered(1..4, RenderScheme::Reverse, 1);
+        line.add_scheme_layered(1..4, RenderScheme::Dim, 0);
+        assert_eq!(resolved(&line), [(1..4, "reverse")]);
+        line.add_scheme_layered(1..4, RenderScheme::Dim, 1);
+        assert_eq!(resolved(&line), [(1..4, "dim")]);
+
+        // rendered without styles spilling over
+        let mut line = LineWithRenderScheme::new("abcdef");
+        line.add_scheme_layered(0..4, RenderScheme::Dim, 0);
+        line.add_scheme_layered(2..6, RenderScheme::Reverse, 1);
+        assert_eq!(line.render(), format!("{}{}", "ab".dim(), "cdef".reverse()));
+        // cut at the end of the content
+        let line = line.truncate(3);
+        assert_eq!(resolved(&line), [(0..2, "dim"), (2..3, "reverse")]);
+    }
+
+    #[test]
+    fn test_underline() {
+        let mut row = LineWithRenderScheme::new("abc");
+        row.add_scheme_if_not_overlap(0..1, RenderScheme::Dim);
+        row.underline(6);
+        assert_eq!(row.raw_content(), "abc   ");
+        let underlined = SetAttribute(Attribute::Underlined).to_string();
+        let rendered = row.render();
+        assert!(rendered.starts_with(&underlined));
+        // turned on again right after the dim range resets styles
+        assert!(rendered.contains(&format!("{}{underlined}bc   ", "a".dim())));
+        assert!(rendered.ends_with(&SetAttribute(Attribute::NoUnderline).to_string()));
+    }
+}
@@ -0,0 +1,91 @@
+use std::ops::RangeInclusive;
+
+use crate::event_source::Direction;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectAction {
+    Start,
+    Extend(Direction),
+    Copy,
+    Cancel,
+}
+
+/// Visible lines from where the selection started to where it's been extended to, in either
+/// order. Lines are kept by their start offsets, so the selection survives scrolling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    anchor: usize,
+    cursor: usize,
+    line_count: usize,
+}
+
+impl Selection {
+    pub fn new(offset: usize) -> Self {
+        Self {
+            anchor: offset,
+            cursor: offset,
+            line_count: 1,
+        }
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    /// Start offsets of the first and the last selected line.
+    pub fn range(&self) -> RangeInclusive<usize> {
+        std::cmp::min(self.anchor, self.cursor)..=std::cmp::max(self.anchor, self.cursor)
+    }
+
+    /// Moves the cursor to the next visible line on either side of it, which is at `offset`.
+    pub fn extend_to(&mut self, offset: usize) {
+        assert!(offset != self.cursor);
+        let moving_away = if offset > self.cursor {
+            self.cursor >= self.anchor
+        } else {
+            self.cursor <= self.anchor
+        };
+        if moving_away {
+            self.line_count += 1;
+        } else {
+            self.line_count -= 1;
+        }
+        self.cursor = offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extend() {
+        // lines start every 10 bytes
+        let mut selection = Selection::new(50);
+        assert_eq!(selection.range(), 50..=50);
+        selection.extend_to(60);
+        selection.extend_to(70);
+        assert_eq!(selection.range(), 50..=70);
+        assert_eq!(selection.line_count(), 3);
+
+        // back across the anchor, which stays selected
+        for offset in [60, 50, 40, 30] {
+            selection.extend_to(offset);
+        }
+        assert_eq!(selection.range(), 30..=50);
+        assert_eq!(selection.line_count(), 3);
+        assert_eq!(selection.cursor(), 30);
+
+        selection.extend_to(40);
+        selection.extend_to(50);
+        assert_eq!(selection.line_count(), 1);
+        // lines hidden by filters are skipped over, and not counted
+        selection.extend_to(90);
+        assert_eq!(selection.range(), 50..=90);
+        assert_eq!(selection.line_count(), 2);
+    }
+}
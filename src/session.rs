@@ -0,0 +1,216 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{Ok, Result};
+use log::warn;
+
+use crate::config::state_dir;
+
+// oldest records are dropped once the store grows beyond this
+const SESSION_RECORD_MAX_COUNT: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionState {
+    pub offset: usize,
+    pub wrap_lines: bool,
+    pub horizontal_shift: usize,
+    // bytes trimmed off the start with `:trim`, the offset is counted after them
+    pub trimmed: usize,
+    // filter profile loaded into the finder slots, if any
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct SessionRecord {
+    // seconds since epoch, used to evict the least recently saved records
+    saved_at: i64,
+    state: SessionState,
+}
+
+#[derive(Debug)]
+pub struct SessionStore {
+    path: Option<PathBuf>,
+    records: BTreeMap<String, SessionRecord>,
+}
+
+impl SessionStore {
+    pub fn open_default() -> Self {
+        let path = state_dir().map(|dir| dir.join("sessions"));
+        let records = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| parse_records(&content))
+            .unwrap_or_default();
+        Self { path, records }
+    }
+
+    pub fn get(&self, file_key: &str) -> Option<&SessionState> {
+        self.records.get(file_key).map(|record| &record.state)
+    }
+
+    pub fn set(&mut self, file_key: &str, state: SessionState) {
+        let record = SessionRecord {
+            saved_at: chrono::Local::now().timestamp(),
+            state,
+        };
+        self.records.insert(file_key.to_string(), record);
+        while self.records.len() > SESSION_RECORD_MAX_COUNT {
+            let oldest = self
+                .records
+                .iter()
+                .min_by_key(|(_, record)| record.saved_at)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            self.records.remove(&oldest);
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = self.path.as_ref() else {
+            warn!("[save] cannot locate state directory, session is not saved");
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, format_records(&self.records))?;
+        Ok(())
+    }
+}
+
+// one record per line: saved_at, offset, wrap_lines, horizontal_shift, trimmed, profile name
+// (empty without one) and file path separated by tabs. file path goes last so that it may
+// contain tabs itself
+fn parse_records(content: &str) -> BTreeMap<String, SessionRecord> {
+    let mut records = BTreeMap::new();
+    for line in content.lines() {
+        let fields: Vec<_> = line.splitn(5, '\t').collect();
+        if fields.len() != 5 {
+            warn!("[parse_records] skip malformed line: {line}");
+            continue;
+        }
+        // records saved before trims and profiles were kept go without, a path never is all
+        // digits
+        let (trimmed, profile, file_key) = match fields[4].split_once('\t') {
+            Some((trimmed, rest))
+                if !trimmed.is_empty() && trimmed.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                match rest.split_once('\t') {
+                    Some((profile, file_key)) => (trimmed.parse::<usize>(), profile, file_key),
+                    None => (std::result::Result::Ok(0), "", fields[4]),
+                }
+            }
+            _ => (std::result::Result::Ok(0), "", fields[4]),
+        };
+        let parsed = (
+            fields[0].parse::<i64>(),
+            fields[1].parse::<usize>(),
+            fields[2].parse::<u8>(),
+            fields[3].parse::<usize>(),
+            trimmed,
+        );
+        if let (
+            std::result::Result::Ok(saved_at),
+            std::result::Result::Ok(offset),
+            std::result::Result::Ok(wrap_lines),
+            std::result::Result::Ok(horizontal_shift),
+            std::result::Result::Ok(trimmed),
+        ) = parsed
+        {
+            let state = SessionState {
+                offset,
+                wrap_lines: wrap_lines != 0,
+                horizontal_shift,
+                trimmed,
+                profile: (!profile.is_empty()).then(|| profile.to_string()),
+            };
+            records.insert(file_key.to_string(), SessionRecord { saved_at, state });
+        } else {
+            warn!("[parse_records] skip malformed line: {line}");
+        }
+    }
+    records
+}
+
+fn format_records(records: &BTreeMap<String, SessionRecord>) -> String {
+    records
+        .iter()
+        .map(|(file_key, record)| {
+            format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{file_key}\n",
+                record.saved_at,
+                record.state.offset,
+                record.state.wrap_lines as u8,
+                record.state.horizontal_shift,
+                record.state.trimmed,
+                record.state.profile.as_deref().unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_records() {
+        let content = "200\t0\t0\t0\t0\t\t/tmp/with\ttab.log\n\
+                       100\t42\t1\t3\t4096\tnginx\t/var/log/a.log\n";
+        let records = parse_records(content);
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records["/var/log/a.log"],
+            SessionRecord {
+                saved_at: 100,
+                state: SessionState {
+                    offset: 42,
+                    wrap_lines: true,
+                    horizontal_shift: 3,
+                    trimmed: 4096,
+                    profile: Some("nginx".to_string()),
+                }
+            }
+        );
+        assert_eq!(records["/tmp/with\ttab.log"].saved_at, 200);
+        assert_eq!(records["/tmp/with\ttab.log"].state.profile, None);
+        assert_eq!(format_records(&records), content);
+
+        let records = parse_records("broken line\n1\tx\t0\t0\t/a\n1\t2\t0\t0\t/b\n");
+        assert_eq!(records.len(), 1);
+        assert!(records.contains_key("/b"));
+
+        // saved without the trimmed bytes and the profile
+        let records = parse_records("100\t42\t1\t3\t/var/log/a.log\n1\t2\t0\t0\t/with\t5\n");
+        assert_eq!(records["/var/log/a.log"].state.offset, 42);
+        assert_eq!(records["/var/log/a.log"].state.trimmed, 0);
+        assert_eq!(records["/with\t5"].state.trimmed, 0);
+    }
+
+    #[test]
+    fn test_session_store_eviction() {
+        let mut store = SessionStore {
+            path: None,
+            records: BTreeMap::new(),
+        };
+        let state = SessionState {
+            offset: 0,
+            wrap_lines: false,
+            horizontal_shift: 0,
+            trimmed: 0,
+            profile: None,
+        };
+        for index in 0..SESSION_RECORD_MAX_COUNT {
+            store.records.insert(
+                format!("/file{index}"),
+                SessionRecord {
+                    saved_at: index as i64,
+                    state: state.clone(),
+                },
+            );
+        }
+        store.set("/new", state.clone());
+        assert_eq!(store.records.len(), SESSION_RECORD_MAX_COUNT);
+        assert!(store.get("/file0").is_none());
+        assert_eq!(store.get("/new"), Some(&state));
+    }
+}
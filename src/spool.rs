@@ -0,0 +1,175 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+const SPOOL_READ_SIZE: usize = 65536;
+
+#[derive(Debug, Default)]
+struct SpoolData {
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+#[derive(Debug, Default)]
+struct SpoolShared {
+    data: Mutex<SpoolData>,
+    finished: Condvar,
+}
+
+/// A seekable reader over data from a non-seekable source such as a fifo, which is read
+/// into memory by a background thread as it arrives.
+///
+/// The size seen through [`Seek`] grows along with the data received so far, so a
+/// [`Document`](crate::document::Document) over it picks up new data with
+/// [`update_docsize_and_lastline`](crate::document::Document::update_docsize_and_lastline)
+/// like it does for a growing file. Reads never block.
+#[derive(Debug)]
+pub struct Spool {
+    shared: Arc<SpoolShared>,
+    position: u64,
+}
+
+impl Spool {
+    pub fn new<R: Read + Send + 'static>(mut source: R) -> Self {
+        let shared = Arc::new(SpoolShared::default());
+        let writer = Arc::clone(&shared);
+        thread::spawn(move || {
+            let mut read_buffer = vec![0; SPOOL_READ_SIZE];
+            loop {
+                match source.read(&mut read_buffer) {
+                    Ok(0) => break,
+                    Ok(len) => {
+                        let mut data = writer.data.lock().unwrap();
+                        data.buffer.extend_from_slice(&read_buffer[..len]);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    // what's received so far is still worth showing
+                    Err(_) => break,
+                }
+            }
+            writer.data.lock().unwrap().finished = true;
+            writer.finished.notify_all();
+        });
+        Self {
+            shared,
+            position: 0,
+        }
+    }
+
+    /// Whether the source has reached its end, so the spool won't grow any more.
+    pub fn is_finished(&self) -> bool {
+        self.shared.data.lock().unwrap().finished
+    }
+
+    pub fn wait_until_finished(&self) {
+        let mut data = self.shared.data.lock().unwrap();
+        while !data.finished {
+            data = self.shared.finished.wait(data).unwrap();
+        }
+    }
+
+    fn len(&self) -> u64 {
+        self.shared.data.lock().unwrap().buffer.len() as u64
+    }
+}
+
+impl Read for Spool {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.shared.data.lock().unwrap();
+        let begin = std::cmp::min(self.position as usize, data.buffer.len());
+        let len = std::cmp::min(buf.len(), data.buffer.len() - begin);
+        buf[..len].copy_from_slice(&data.buffer[begin..begin + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for Spool {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::End(offset) => (self.len(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+        self.position = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::*;
+    use crate::document::Document;
+
+    // a non-seekable source handing out what's sent through the channel, ends once the sender
+    // is dropped
+    struct ChannelReader {
+        receiver: Receiver<Vec<u8>>,
+    }
+
+    impl Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.receiver.recv() {
+                Ok(piece) => {
+                    buf[..piece.len()].copy_from_slice(&piece);
+                    Ok(piece.len())
+                }
+                Err(_) => Ok(0),
+            }
+        }
+    }
+
+    fn wait_for_len(spool: &mut Spool, len: u64) {
+        while spool.seek(SeekFrom::End(0)).unwrap() < len {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_spool_grows_as_data_arrives() {
+        let (sender, receiver) = mpsc::channel();
+        let mut spool = Spool::new(ChannelReader { receiver });
+        assert_eq!(spool.seek(SeekFrom::End(0)).unwrap(), 0);
+        let mut buf = [0; 8];
+        assert_eq!(spool.read(&mut buf).unwrap(), 0);
+
+        sender.send(b"abc".to_vec()).unwrap();
+        wait_for_len(&mut spool, 3);
+        spool.seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(spool.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"bc");
+        assert!(spool.seek(SeekFrom::Current(-4)).is_err());
+        assert!(!spool.is_finished());
+
+        drop(sender);
+        spool.wait_until_finished();
+        assert!(spool.is_finished());
+    }
+
+    #[test]
+    fn test_document_over_spool() {
+        let (sender, receiver) = mpsc::channel();
+        let mut spool = Spool::new(ChannelReader { receiver });
+        sender.send(b"line 1\nline".to_vec()).unwrap();
+        wait_for_len(&mut spool, 11);
+        let mut document = Document::new(spool).unwrap();
+        assert_eq!(document.query_line_texts(0, 2).unwrap(), ["line 1", "line"]);
+
+        sender.send(b" 2\nline 3\n".to_vec()).unwrap();
+        drop(sender);
+        while document.document_size() < 21 {
+            document.update_docsize_and_lastline().unwrap();
+            thread::yield_now();
+        }
+        assert_eq!(
+            document.query_line_texts(0, 3).unwrap(),
+            ["line 1", "line 2", "line 3"]
+        );
+    }
+}
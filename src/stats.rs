@@ -0,0 +1,77 @@
+use crate::{canvas::Canvas, popup_menu::PopupMenu, render::LineWithRenderScheme};
+
+/// Counters of what the viewer did since it started, kept across opened files unlike the ones
+/// of a document.
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    pub frames: usize,
+    pub searches: usize,
+    // searches which found nothing
+    pub search_misses: usize,
+}
+
+/// Shows the counters of the session and the document, rendered again on every refresh so
+/// that they're current while it's open.
+#[derive(Default)]
+pub struct StatsPopup {
+    active: bool,
+}
+
+impl StatsPopup {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn toggle_active(&mut self) {
+        self.active = !self.active;
+    }
+
+    pub fn render(
+        &self,
+        stats: &[(&str, String)],
+        canvas: &mut Canvas,
+        window_width: usize,
+        window_height: usize,
+    ) {
+        let rows = stats
+            .iter()
+            .map(|(label, value)| LineWithRenderScheme::new(&format!(" {label:<18}{value}")))
+            .collect();
+        PopupMenu {
+            title: "Statistics",
+            height: stats.len(),
+            min_width: 40,
+            rows,
+            ..PopupMenu::default()
+        }
+        .render(canvas, window_width, window_height);
+    }
+}
+
+/// Byte counts in the largest unit keeping them at least 1, e.g. `1.5 MB`.
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+    }
+}
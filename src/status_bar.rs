@@ -1,10 +1,158 @@
-use crate::{canvas::Canvas, render::LineWithRenderScheme};
+use anyhow::{anyhow, Ok, Result};
+use loss_viewer::document::PositionRatio;
+
+use crate::{
+    canvas::Canvas,
+    render::{LineWithRenderScheme, RenderScheme},
+};
+
+/// Which ends of the document are visible.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Boundary {
+    pub at_top: bool,
+    pub at_end: bool,
+    // the end is visible and the document lacks a final line delimiter
+    pub missing_final_newline: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum OneoffKind {
+    #[default]
+    Error,
+    // a notice rather than an error
+    Info,
+    // shown reversed to catch the eye
+    Alert,
+}
+
+/// Content of a status bar segment that adapts to the columns it's given.
+pub trait SegmentSource {
+    /// The widest rendering fitting in `room` columns, `None` if none does.
+    fn render_segment(&self, room: usize) -> Option<LineWithRenderScheme>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Segment {
+    Filename,
+    // of the window top
+    Line,
+    Timestamp,
+    // the format timestamps are detected in, e.g. `timestamps: %Y-%m-%d %H:%M:%S%.f`
+    TimestampFormat,
+    Offset,
+    // the keys of an unfinished sequence
+    PendingInput,
+    // the view filter expression, truncated
+    Filter,
+    // the finder slots, rendered by a `SegmentSource`
+    Slots,
+    // shown dimmed at the end of a document without a final line delimiter
+    FinalNewline,
+    // of the file, when known
+    Modified,
+    Ratio,
+}
+
+/// How much the status bar shows besides its text.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StatusLayout {
+    Minimal,
+    #[default]
+    Standard,
+    Verbose,
+}
+
+impl StatusLayout {
+    // `minimal`, `standard` or `verbose`
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "minimal" => Ok(Self::Minimal),
+            "standard" => Ok(Self::Standard),
+            "verbose" => Ok(Self::Verbose),
+            _ => Err(anyhow!("invalid status layout `{value}`")),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Minimal => "minimal",
+            Self::Standard => "standard",
+            Self::Verbose => "verbose",
+        }
+    }
+
+    pub fn cycle(self) -> Self {
+        match self {
+            Self::Minimal => Self::Standard,
+            Self::Standard => Self::Verbose,
+            Self::Verbose => Self::Minimal,
+        }
+    }
+
+    // whether the line number and timestamp of the window top are shown, which are costly
+    // to look up
+    pub fn shows_top_line_details(self) -> bool {
+        self == Self::Verbose
+    }
+
+    // in display order, with the priority of keeping each on narrow windows
+    fn segments(self) -> &'static [(Segment, u8)] {
+        match self {
+            Self::Minimal => &[
+                (Segment::PendingInput, 2),
+                (Segment::Filter, 0),
+                (Segment::Ratio, 1),
+            ],
+            Self::Standard => &[
+                (Segment::PendingInput, 4),
+                (Segment::Filter, 2),
+                (Segment::Slots, 1),
+                (Segment::FinalNewline, 0),
+                (Segment::Ratio, 3),
+            ],
+            Self::Verbose => &[
+                (Segment::Filename, 4),
+                (Segment::Modified, 0),
+                (Segment::Line, 7),
+                (Segment::Timestamp, 3),
+                (Segment::TimestampFormat, 0),
+                (Segment::Offset, 2),
+                (Segment::PendingInput, 9),
+                (Segment::Filter, 6),
+                (Segment::Slots, 5),
+                (Segment::FinalNewline, 1),
+                (Segment::Ratio, 8),
+            ],
+        }
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct StatusBar {
     text: String,
+    // shown ahead of text while some mode is on
+    indicator: String,
     oneoff_error_text: Option<String>,
-    ratio: usize,
+    oneoff_kind: OneoffKind,
+    // the one-off text has been notified about, or needs no notification
+    oneoff_notified: bool,
+    // the one-off text is on screen, until the next render
+    oneoff_shown: bool,
+    // show the one-off text inverted for one frame
+    flash: bool,
+    ratio: PositionRatio,
+    boundary: Boundary,
+    layout: StatusLayout,
+    filename: String,
+    // of the window top, only looked up for layouts showing them
+    line_number: Option<usize>,
+    timestamp: Option<String>,
+    timestamp_format: Option<String>,
+    offset: usize,
+    // when the file was last modified, formatted
+    modified: Option<String>,
+    pending_input: Option<String>,
+    filter: Option<String>,
 }
 
 impl StatusBar {
@@ -12,39 +160,562 @@ impl StatusBar {
         self.text = text.to_string();
     }
 
+    pub fn set_indicator(&mut self, indicator: &str) {
+        self.indicator = indicator.to_string();
+    }
+
     pub fn clear_text(&mut self) {
         self.text = String::default();
     }
 
     pub fn set_oneoff_error_text(&mut self, text: &str) {
         self.oneoff_error_text = Some(text.to_string());
+        self.oneoff_kind = OneoffKind::Error;
+        self.oneoff_notified = false;
+    }
+
+    pub fn set_oneoff_info_text(&mut self, text: &str) {
+        self.oneoff_error_text = Some(text.to_string());
+        self.oneoff_kind = OneoffKind::Info;
     }
 
-    pub fn set_ratio(&mut self, ratio: usize) {
+    pub fn set_oneoff_alert_text(&mut self, text: &str) {
+        self.oneoff_error_text = Some(text.to_string());
+        self.oneoff_kind = OneoffKind::Alert;
+    }
+
+    pub fn mark_oneoff_notified(&mut self) {
+        self.oneoff_notified = true;
+    }
+
+    /// The one-off error no notification has been given for yet, marked as notified.
+    pub fn take_unnotified_error(&mut self) -> Option<String> {
+        if self.oneoff_notified {
+            return None;
+        }
+        self.oneoff_notified = true;
+        self.oneoff_error().map(str::to_string)
+    }
+
+    pub fn flash(&mut self) {
+        self.flash = true;
+    }
+
+    pub fn clear_oneoff_text(&mut self) {
+        self.oneoff_error_text = None;
+        self.oneoff_shown = false;
+    }
+
+    /// Whether a one-off text is waiting to be rendered or on screen since the last render.
+    pub fn has_oneoff_text(&self) -> bool {
+        self.oneoff_error_text.is_some() || self.oneoff_shown
+    }
+
+    pub fn oneoff_error(&self) -> Option<&str> {
+        self.oneoff_error_text
+            .as_deref()
+            .filter(|_| self.oneoff_kind == OneoffKind::Error)
+    }
+
+    pub fn set_pending_input(&mut self, text: Option<String>) {
+        self.pending_input = text;
+    }
+
+    pub fn set_filter(&mut self, expr: Option<String>) {
+        self.filter = expr;
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    pub fn set_ratio(&mut self, ratio: PositionRatio) {
         self.ratio = ratio;
     }
 
-    pub fn render(&mut self, canvas: &mut Canvas, window_width: usize) -> Option<usize> {
+    pub fn layout(&self) -> StatusLayout {
+        self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: StatusLayout) {
+        self.layout = layout;
+    }
+
+    pub fn set_filename(&mut self, filename: &str) {
+        self.filename = filename.to_string();
+    }
+
+    pub fn set_modified(&mut self, modified: Option<String>) {
+        self.modified = modified;
+    }
+
+    pub fn set_top_line(
+        &mut self,
+        offset: usize,
+        line_number: Option<usize>,
+        timestamp: Option<String>,
+    ) {
+        self.offset = offset;
+        self.line_number = line_number;
+        self.timestamp = timestamp;
+    }
+
+    pub fn set_timestamp_format(&mut self, timestamp_format: Option<String>) {
+        self.timestamp_format = timestamp_format;
+    }
+
+    /// Renders the one-off text if any, otherwise the text followed by the segments of the
+    /// layout, right-aligned.
+    pub fn render(&mut self, canvas: &mut Canvas, window_width: usize, slots: &dyn SegmentSource) {
+        self.oneoff_shown = self.oneoff_error_text.is_some();
         if let Some(text) = self.oneoff_error_text.clone() {
             self.oneoff_error_text = None;
-            canvas.status_bar = LineWithRenderScheme::new(&text).truncate(window_width);
-            canvas.cursor_pos_x = Some(text.len());
-            return None;
+            let text = truncate_middle(&text, window_width);
+            let mut line = LineWithRenderScheme::new(&text);
+            let flash = std::mem::take(&mut self.flash);
+            // an alert stays reversed through a flash
+            if flash || self.oneoff_kind == OneoffKind::Alert {
+                if flash {
+                    line.pad_to(window_width);
+                }
+                let len = line.raw_content().len();
+                line.add_scheme_if_not_overlap(0..len, RenderScheme::Reverse);
+            }
+            canvas.status_bar = line;
+            canvas.cursor_pos_x = Some(cursor_column(&text, window_width));
+            return;
+        }
+        let prefix = format!("{}{}", self.indicator, boundary_text(self.boundary));
+        // the text is cut rather than the modes ahead of it, and a column is left for the
+        // cursor after a prompt typed beyond the width
+        let room = window_width.saturating_sub(1);
+        let text = truncate_middle(
+            &format!(
+                "{prefix}{}",
+                truncate_middle(&self.text, room.saturating_sub(prefix.chars().count()))
+            ),
+            room,
+        );
+        let text_width = text.chars().count();
+        canvas.cursor_pos_x = Some(cursor_column(&text, window_width));
+        let segments = self.layout_segments(window_width.saturating_sub(text_width), slots);
+        let segments_width: usize = segments
+            .iter()
+            .map(|segment| segment.raw_content().chars().count() + 1)
+            .sum();
+        let mut line = LineWithRenderScheme::new(&text);
+        line.pad_to(window_width - segments_width);
+        for segment in segments.iter() {
+            line.push_str(" ");
+            line.push_line(segment);
+        }
+        canvas.status_bar = line;
+    }
+
+    // the segments of the layout kept in `room` columns, each taking a space ahead of it.
+    // segments are given room by priority and dropped if there's not enough left
+    fn layout_segments(
+        &self,
+        mut room: usize,
+        slots: &dyn SegmentSource,
+    ) -> Vec<LineWithRenderScheme> {
+        let layout = self.layout.segments();
+        let mut by_priority: Vec<usize> = (0..layout.len()).collect();
+        by_priority.sort_by_key(|index| std::cmp::Reverse(layout[*index].1));
+        let mut rendered = vec![None; layout.len()];
+        for index in by_priority {
+            if room == 0 {
+                break;
+            }
+            let segment = match layout[index].0 {
+                Segment::Slots => slots.render_segment(room - 1),
+                segment => self.render_segment(segment, room - 1),
+            };
+            if let Some(segment) = segment {
+                room -= segment.raw_content().chars().count() + 1;
+                rendered[index] = Some(segment);
+            }
+        }
+        rendered.into_iter().flatten().collect()
+    }
+
+    fn render_segment(&self, segment: Segment, room: usize) -> Option<LineWithRenderScheme> {
+        let text = match segment {
+            Segment::Filename => {
+                // the base name if the whole path doesn't fit
+                let base_name = self.filename.rsplit('/').next().unwrap_or_default();
+                [self.filename.as_str(), base_name]
+                    .into_iter()
+                    .find(|name| !name.is_empty() && name.chars().count() <= room)?
+                    .to_string()
+            }
+            Segment::Line => format!("L{}", self.line_number?),
+            Segment::Timestamp => self.timestamp.clone()?,
+            Segment::TimestampFormat => format!("timestamps: {}", self.timestamp_format.as_ref()?),
+            Segment::Offset => format!("@{}", self.offset),
+            Segment::Modified => format!("modified {}", self.modified.as_ref()?),
+            Segment::PendingInput => self.pending_input.clone()?,
+            Segment::Filter => {
+                let width = std::cmp::min(room, FILTER_MAX_WIDTH);
+                if width < FILTER_MIN_WIDTH {
+                    return None;
+                }
+                truncate_with_ellipsis(&format!("&{}", self.filter.as_ref()?), width)
+            }
+            Segment::Ratio => format!("{:>RATIO_MAX_WIDTH$}", ratio_text(self.ratio)),
+            Segment::FinalNewline => {
+                if !self.boundary.missing_final_newline || FINAL_NEWLINE_TEXT.chars().count() > room
+                {
+                    return None;
+                }
+                let mut line = LineWithRenderScheme::new(FINAL_NEWLINE_TEXT);
+                line.add_scheme_if_not_overlap(0..FINAL_NEWLINE_TEXT.len(), RenderScheme::Dim);
+                return Some(line);
+            }
+            Segment::Slots => unreachable!("slots are rendered by their source"),
+        };
+        (text.chars().count() <= room).then(|| LineWithRenderScheme::new(&text))
+    }
+}
+
+fn boundary_text(boundary: Boundary) -> &'static str {
+    match (boundary.at_top, boundary.at_end) {
+        (true, true) => "(TOP) (END) ",
+        (true, false) => "(TOP) ",
+        (false, true) => "(END) ",
+        (false, false) => "",
+    }
+}
+
+const FINAL_NEWLINE_TEXT: &str = "⏎ missing";
+
+// columns the filter expression takes at most, and at least to be shown at all
+const FILTER_MAX_WIDTH: usize = 24;
+const FILTER_MIN_WIDTH: usize = 6;
+
+// ends with `…` if cut
+fn truncate_with_ellipsis(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Fits `text` in `width` columns with `…` in place of its middle, so that the end of a long
+/// message or of a prompt typed beyond the width stays visible. A leading label such as
+/// `Search: ` is kept whole when it takes no more than half of them.
+pub fn truncate_middle(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width {
+        return text.to_string();
+    }
+    let Some(room) = width.checked_sub(1) else {
+        return String::new();
+    };
+    let head_len = match text.find(": ") {
+        Some(index) if text[..index].chars().count() + 2 <= room / 2 => {
+            text[..index].chars().count() + 2
+        }
+        _ => room / 2,
+    };
+    let head: String = text.chars().take(head_len).collect();
+    let tail: String = text.chars().skip(len - (room - head_len)).collect();
+    format!("{head}…{tail}")
+}
+
+/// Where the cursor goes after `text`, never beyond the last column.
+pub fn cursor_column(text: &str, window_width: usize) -> usize {
+    std::cmp::min(text.chars().count(), window_width.saturating_sub(1))
+}
+
+// columns the position ratio takes at most, it's right-aligned in them
+const RATIO_MAX_WIDTH: usize = 4;
+
+// at most `RATIO_MAX_WIDTH` columns wide, right after the finder slots section
+fn ratio_text(ratio: PositionRatio) -> String {
+    match ratio {
+        PositionRatio::Empty => "—".to_string(),
+        PositionRatio::Percent(percent) => format!("{percent}%"),
+        PositionRatio::End => "END".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // slots shown as a fixed text if it fits
+    struct Slots(&'static str);
+
+    impl SegmentSource for Slots {
+        fn render_segment(&self, room: usize) -> Option<LineWithRenderScheme> {
+            (!self.0.is_empty() && self.0.len() <= room).then(|| LineWithRenderScheme::new(self.0))
+        }
+    }
+
+    #[test]
+    fn test_render_layouts() {
+        let text = "Search: long pattern";
+        let mut status_bar = StatusBar::default();
+        status_bar.set_text(text);
+        status_bar.set_ratio(PositionRatio::Percent(42));
+        status_bar.set_filename("/var/log/app/server.log");
+        status_bar.set_top_line(56789, Some(1234), Some("2024-05-01 10:00:00".to_string()));
+        status_bar.set_timestamp_format(Some("%Y-%m-%d %H:%M:%S".to_string()));
+        let mut render = |layout, width| {
+            let mut canvas = Canvas::default();
+            status_bar.set_layout(layout);
+            status_bar.render(&mut canvas, width, &Slots("[*1 3]"));
+            let content = canvas.status_bar.raw_content().to_string();
+            assert_eq!(content.len(), width);
+            assert!(content.starts_with(text));
+            content[text.len()..].trim_start().to_string()
+        };
+
+        let verbose = StatusLayout::Verbose;
+        assert_eq!(
+            render(verbose, 120),
+            "/var/log/app/server.log L1234 2024-05-01 10:00:00 timestamps: %Y-%m-%d %H:%M:%S \
+             @56789 [*1 3]  42%"
+        );
+        // the timestamp format goes first
+        assert_eq!(
+            render(verbose, 100),
+            "/var/log/app/server.log L1234 2024-05-01 10:00:00 @56789 [*1 3]  42%"
+        );
+        // the timestamp goes first, while the shorter offset still fits
+        assert_eq!(
+            render(verbose, 80),
+            "/var/log/app/server.log L1234 @56789 [*1 3]  42%"
+        );
+        assert_eq!(render(verbose, 50), "server.log L1234 [*1 3]  42%");
+        assert_eq!(render(verbose, 30), "42%");
+        assert_eq!(render(verbose, 22), "");
+
+        let standard = StatusLayout::Standard;
+        assert_eq!(render(standard, 120), "[*1 3]  42%");
+        assert_eq!(render(standard, 30), "42%");
+        assert_eq!(render(standard, 24), "");
+
+        let minimal = StatusLayout::Minimal;
+        assert_eq!(render(minimal, 120), "42%");
+        assert_eq!(render(minimal, 25), "42%");
+        assert_eq!(render(minimal, 24), "");
+    }
+
+    #[test]
+    fn test_cycle_layout() {
+        let mut layout = StatusLayout::default();
+        let mut names = vec![];
+        for _ in 0..3 {
+            layout = layout.cycle();
+            names.push(layout.name());
+        }
+        assert_eq!(names, ["verbose", "minimal", "standard"]);
+        assert_eq!(
+            StatusLayout::parse("minimal").unwrap(),
+            StatusLayout::Minimal
+        );
+        assert!(StatusLayout::parse("full").is_err());
+    }
+
+    #[test]
+    fn test_ratio_text() {
+        assert_eq!(ratio_text(PositionRatio::Empty), "—");
+        assert_eq!(ratio_text(PositionRatio::Percent(0)), "0%");
+        assert_eq!(ratio_text(PositionRatio::Percent(100)), "100%");
+        assert_eq!(ratio_text(PositionRatio::End), "END");
+    }
+
+    #[test]
+    fn test_oneoff_notification() {
+        let mut canvas = Canvas::default();
+        let mut status_bar = StatusBar::default();
+        status_bar.set_oneoff_error_text("No macro recorded");
+        assert_eq!(
+            status_bar.take_unnotified_error().as_deref(),
+            Some("No macro recorded")
+        );
+        assert_eq!(status_bar.take_unnotified_error(), None);
+        status_bar.flash();
+        status_bar.render(&mut canvas, 20, &Slots(""));
+        assert_eq!(canvas.status_bar.raw_content().len(), 20);
+        assert!(canvas.status_bar.render().starts_with("\x1b[7m"));
+
+        status_bar.set_oneoff_error_text("Not found");
+        status_bar.mark_oneoff_notified();
+        assert_eq!(status_bar.take_unnotified_error(), None);
+        status_bar.render(&mut canvas, 20, &Slots(""));
+        assert_eq!(canvas.status_bar.render(), "Not found");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        let text = "Bookmark name should have no more than 50 chars";
+        assert_eq!(truncate_middle(text, 47), text);
+        assert_eq!(truncate_middle(text, 21), "Bookmark n…n 50 chars");
+        assert_eq!(
+            truncate_middle("Search: a long pattern", 18),
+            "Search: …g pattern"
+        );
+        // a label taking more than half is cut as well
+        assert_eq!(
+            truncate_middle("Search: a long pattern", 12),
+            "Searc…attern"
+        );
+        assert_eq!(truncate_middle("ünïcödé", 5), "ün…dé");
+        assert_eq!(truncate_middle("abc", 1), "…");
+        assert_eq!(truncate_middle("abc", 0), "");
+    }
+
+    #[test]
+    fn test_prompt_viewport() {
+        let mut canvas = Canvas::default();
+        let mut status_bar = StatusBar::default();
+        let mut content = String::new();
+        for c in "abcdefghijklmnopqrstuvwxyz".chars() {
+            content.push(c);
+            status_bar.set_text(&format!("Search: {content}"));
+            status_bar.render(&mut canvas, 20, &Slots(""));
+            let cursor = canvas.cursor_pos_x.unwrap();
+            let shown: String = canvas
+                .status_bar
+                .raw_content()
+                .chars()
+                .take(cursor)
+                .collect();
+            // the end of what's typed is always shown, with the cursor after it on screen
+            assert!(shown.ends_with(c), "{shown}");
+            assert!(shown.starts_with("Search: "), "{shown}");
+            assert!(cursor < 20);
+        }
+        assert_eq!(canvas.status_bar.raw_content(), "Search: …qrstuvwxyz ");
+
+        // a long message is cut in the middle, and the cursor kept on screen
+        status_bar.set_oneoff_error_text(&format!("Not found: {}", "x".repeat(30)));
+        status_bar.render(&mut canvas, 20, &Slots(""));
+        assert_eq!(canvas.status_bar.raw_content(), "Not found…xxxxxxxxxx");
+        assert_eq!(canvas.cursor_pos_x, Some(19));
+    }
+
+    #[test]
+    fn test_render_boundary() {
+        let mut canvas = Canvas::default();
+        let mut status_bar = StatusBar::default();
+        status_bar.set_text("Search: ");
+        status_bar.set_boundary(Boundary {
+            at_top: true,
+            at_end: true,
+            ..Boundary::default()
+        });
+        status_bar.render(&mut canvas, 40, &Slots(""));
+        assert!(canvas
+            .status_bar
+            .raw_content()
+            .starts_with("(TOP) (END) Search: "));
+        assert_eq!(canvas.cursor_pos_x, Some(20));
+        status_bar.set_boundary(Boundary {
+            at_top: false,
+            at_end: true,
+            ..Boundary::default()
+        });
+        status_bar.render(&mut canvas, 40, &Slots(""));
+        assert!(canvas
+            .status_bar
+            .raw_content()
+            .starts_with("(END) Search: "));
+    }
+
+    #[test]
+    fn test_render_final_newline() {
+        let mut canvas = Canvas::default();
+        let mut status_bar = StatusBar::default();
+        status_bar.set_ratio(PositionRatio::End);
+        status_bar.set_boundary(Boundary {
+            at_end: true,
+            missing_final_newline: true,
+            ..Boundary::default()
+        });
+        status_bar.render(&mut canvas, 40, &Slots("[*1]"));
+        assert!(canvas
+            .status_bar
+            .raw_content()
+            .ends_with(" [*1] ⏎ missing  END"));
+        assert!(canvas.status_bar.render().contains("\x1b[2m⏎ missing"));
+        // the first to go on a narrow window
+        status_bar.render(&mut canvas, 24, &Slots("[*1]"));
+        assert!(canvas.status_bar.raw_content().ends_with(" [*1]  END"));
+        // not in the minimal layout
+        status_bar.set_layout(StatusLayout::Minimal);
+        status_bar.render(&mut canvas, 40, &Slots("[*1]"));
+        assert!(!canvas.status_bar.raw_content().contains('⏎'));
+    }
+
+    #[test]
+    fn test_render_pending_input() {
+        let mut canvas = Canvas::default();
+        let mut status_bar = StatusBar::default();
+        status_bar.set_ratio(PositionRatio::Percent(42));
+        status_bar.set_pending_input(Some("#12…".to_string()));
+        status_bar.render(&mut canvas, 40, &Slots("[*1]"));
+        assert!(canvas.status_bar.raw_content().ends_with(" #12… [*1]  42%"));
+        assert_eq!(canvas.status_bar.raw_content().chars().count(), 40);
+        // kept over the slots on a narrow window
+        status_bar.render(&mut canvas, 12, &Slots("[*1]"));
+        assert!(canvas.status_bar.raw_content().ends_with(" #12…  42%"));
+        status_bar.set_layout(StatusLayout::Minimal);
+        status_bar.render(&mut canvas, 40, &Slots("[*1]"));
+        assert!(canvas.status_bar.raw_content().ends_with(" #12…  42%"));
+
+        status_bar.set_pending_input(None);
+        status_bar.render(&mut canvas, 40, &Slots("[*1]"));
+        assert!(!canvas.status_bar.raw_content().contains('…'));
+    }
+
+    #[test]
+    fn test_render_filter() {
+        let mut canvas = Canvas::default();
+        let mut status_bar = StatusBar::default();
+        status_bar.set_ratio(PositionRatio::Percent(42));
+        status_bar.set_filter(Some("svc=auth".to_string()));
+        status_bar.render(&mut canvas, 40, &Slots("[*1]"));
+        assert!(canvas
+            .status_bar
+            .raw_content()
+            .ends_with(" &svc=auth [*1]  42%"));
+
+        status_bar.set_filter(Some(
+            "level=ERROR AND (svc=auth OR svc=billing)".to_string(),
+        ));
+        status_bar.render(&mut canvas, 60, &Slots("[*1]"));
+        assert!(canvas
+            .status_bar
+            .raw_content()
+            .ends_with(" &level=ERROR AND (svc=a… [*1]  42%"));
+        // cut further on a narrow window, where the slots are dropped first
+        status_bar.render(&mut canvas, 16, &Slots("[*1]"));
+        assert!(canvas
+            .status_bar
+            .raw_content()
+            .ends_with(" &level=ER…  42%"));
+    }
+
+    #[test]
+    fn test_render_ratio() {
+        let mut canvas = Canvas::default();
+        let mut status_bar = StatusBar::default();
+        for (ratio, expected) in [
+            (PositionRatio::Empty, "—"),
+            (PositionRatio::Percent(42), "42%"),
+            (PositionRatio::End, "END"),
+        ] {
+            status_bar.set_ratio(ratio);
+            status_bar.render(&mut canvas, 20, &Slots("[*1]"));
+            let content = canvas.status_bar.raw_content();
+            assert!(content.ends_with(&format!(" [*1] {expected:>4}")));
+            assert_eq!(content.chars().count(), 20);
         }
-        let mut text = self.text.clone();
-        canvas.cursor_pos_x = Some(text.len());
-        let space_count;
-        if self.text.len() + 6 < window_width {
-            let ratio_str = format!("{}%", self.ratio);
-            assert!(ratio_str.len() <= 4);
-            space_count = Some(window_width - self.text.len() - ratio_str.len());
-            text.extend(std::iter::repeat_n(' ', space_count.unwrap()));
-            text.push_str(&ratio_str);
-        } else {
-            space_count = None;
-            text.truncate(window_width);
-        }
-        canvas.status_bar = LineWithRenderScheme::new(&text);
-        space_count
     }
 }
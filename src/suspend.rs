@@ -0,0 +1,118 @@
+use std::io::stdout;
+
+use anyhow::{Ok, Result};
+use crossterm::{
+    cursor::Show,
+    terminal::{disable_raw_mode, enable_raw_mode, size},
+    ExecutableCommand,
+};
+
+/// What suspending the pager does to the terminal and the process, faked in tests.
+pub trait TerminalGuard {
+    // hands the terminal back to the shell in the mode it was in before the pager started
+    fn release(&mut self) -> Result<()>;
+
+    // stops the process until it's continued, e.g. by `fg`
+    fn stop(&mut self) -> Result<()>;
+
+    // takes the terminal over again, returning its size as it may have been resized meanwhile
+    fn reacquire(&mut self) -> Result<(usize, usize)>;
+}
+
+/// The terminal the pager runs in.
+#[derive(Debug, Default)]
+pub struct Terminal;
+
+impl TerminalGuard for Terminal {
+    fn release(&mut self) -> Result<()> {
+        stdout().execute(Show)?;
+        disable_raw_mode()?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn stop(&mut self) -> Result<()> {
+        // raw mode turns off the signal keys, so Ctrl-Z arrives as a key and the stop is raised
+        // here. SIGSTOP can't be caught or ignored, the shell sees the job stopped either way
+        // SAFETY: raising a signal on the calling thread touches no memory of ours
+        if unsafe { libc::raise(libc::SIGSTOP) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn stop(&mut self) -> Result<()> {
+        Err(anyhow::anyhow!("Suspending is only supported on unix"))
+    }
+
+    fn reacquire(&mut self) -> Result<(usize, usize)> {
+        enable_raw_mode()?;
+        let (width, height) = size()?;
+        Ok((width as usize, height as usize))
+    }
+}
+
+/// Releases the terminal and stops until continued, then takes the terminal back even if
+/// stopping failed. The size of the terminal once resumed is returned.
+pub fn suspend(guard: &mut dyn TerminalGuard) -> Result<(usize, usize)> {
+    guard.release()?;
+    let stopped = guard.stop();
+    let size = guard.reacquire()?;
+    stopped?;
+    Ok(size)
+}
+
+/// Records the calls made on it and resumes at a fixed size. Clones share the record.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct FakeTerminal {
+    pub calls: std::rc::Rc<std::cell::RefCell<Vec<&'static str>>>,
+    pub fail_to_stop: bool,
+    pub size: (usize, usize),
+}
+
+#[cfg(test)]
+impl TerminalGuard for FakeTerminal {
+    fn release(&mut self) -> Result<()> {
+        self.calls.borrow_mut().push("release");
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.calls.borrow_mut().push("stop");
+        if self.fail_to_stop {
+            return Err(anyhow::anyhow!("Cannot stop"));
+        }
+        Ok(())
+    }
+
+    fn reacquire(&mut self) -> Result<(usize, usize)> {
+        self.calls.borrow_mut().push("reacquire");
+        Ok(self.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suspend() {
+        let mut terminal = FakeTerminal {
+            size: (100, 30),
+            ..FakeTerminal::default()
+        };
+        assert_eq!(suspend(&mut terminal).unwrap(), (100, 30));
+        assert_eq!(*terminal.calls.borrow(), ["release", "stop", "reacquire"]);
+
+        // the terminal is taken back before the failure is told
+        terminal.calls.borrow_mut().clear();
+        terminal.fail_to_stop = true;
+        assert_eq!(
+            suspend(&mut terminal).unwrap_err().to_string(),
+            "Cannot stop"
+        );
+        assert_eq!(*terminal.calls.borrow(), ["release", "stop", "reacquire"]);
+    }
+}
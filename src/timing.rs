@@ -0,0 +1,180 @@
+use std::time::{Duration, Instant};
+
+// a frame taking longer is reported with where its time went
+pub const SLOW_FRAME_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Where the time of a frame goes. Chunk loads happen within the other phases, mostly while
+/// querying lines, so the phases may add up to more than the frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    QueryLines,
+    Search,
+    ChunkLoad,
+    Render,
+}
+
+// in the order of their declaration, which indexes the time spent in them
+const PHASES: [Phase; 4] = [
+    Phase::QueryLines,
+    Phase::Search,
+    Phase::ChunkLoad,
+    Phase::Render,
+];
+
+impl Phase {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::QueryLines => "query lines",
+            Self::Search => "search",
+            Self::ChunkLoad => "chunk load",
+            Self::Render => "render",
+        }
+    }
+
+    // as a key of the log line
+    fn key(&self) -> &'static str {
+        match self {
+            Self::QueryLines => "query_lines_ms",
+            Self::Search => "search_ms",
+            Self::ChunkLoad => "chunk_load_ms",
+            Self::Render => "render_ms",
+        }
+    }
+}
+
+/// A frame over the threshold and the time spent in each phase of it.
+#[derive(Debug, PartialEq)]
+pub struct SlowFrame {
+    pub total: Duration,
+    pub phases: [(Phase, Duration); 4],
+}
+
+impl SlowFrame {
+    /// Keys and values for the log, e.g. `total_ms=1400 query_lines_ms=1300 ...`.
+    pub fn log_line(&self) -> String {
+        let mut line = format!("total_ms={}", self.total.as_millis());
+        for (phase, spent) in self.phases.iter() {
+            line.push_str(&format!(" {}={}", phase.key(), spent.as_millis()));
+        }
+        line
+    }
+
+    /// The phase taking the most of the frame, e.g. `Slow frame: chunk load 1.2s`, the later
+    /// one on a tie.
+    pub fn summary(&self) -> String {
+        let (phase, spent) = self
+            .phases
+            .iter()
+            .max_by_key(|(_, spent)| *spent)
+            .copied()
+            .unwrap();
+        if spent.is_zero() {
+            return format!("Slow frame: {}", format_seconds(self.total));
+        }
+        format!("Slow frame: {} {}", phase.name(), format_seconds(spent))
+    }
+}
+
+fn format_seconds(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+/// Times the phases of the frame being handled, from its event to drawing it. Nothing is
+/// timed while disabled, which only costs a check per phase.
+#[derive(Debug)]
+pub struct FrameTiming {
+    enabled: bool,
+    frame_start: Option<Instant>,
+    spent: [Duration; 4],
+}
+
+impl FrameTiming {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            frame_start: None,
+            spent: [Duration::ZERO; 4],
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.frame_start = None;
+    }
+
+    /// The start of a phase, `None` while disabled.
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    /// Ends the phase begun at `started`.
+    pub fn record(&mut self, phase: Phase, started: Option<Instant>) {
+        if let Some(started) = started {
+            self.add(phase, started.elapsed());
+        }
+    }
+
+    pub fn add(&mut self, phase: Phase, spent: Duration) {
+        self.spent[phase as usize] += spent;
+    }
+
+    pub fn begin_frame(&mut self, now: Instant) {
+        if self.enabled {
+            self.frame_start = Some(now);
+            self.spent = [Duration::ZERO; 4];
+        }
+    }
+
+    /// Ends the frame begun last, returning it if it took longer than the threshold.
+    pub fn end_frame(&mut self, now: Instant) -> Option<SlowFrame> {
+        let total = now.saturating_duration_since(self.frame_start.take()?);
+        (total > SLOW_FRAME_THRESHOLD).then(|| SlowFrame {
+            total,
+            phases: core::array::from_fn(|index| (PHASES[index], self.spent[index])),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_frame_threshold() {
+        let mut timing = FrameTiming::new(true);
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        timing.begin_frame(start);
+        timing.add(Phase::Render, ms(5));
+        assert_eq!(timing.end_frame(start + SLOW_FRAME_THRESHOLD), None);
+
+        timing.begin_frame(start);
+        timing.add(Phase::QueryLines, ms(1300));
+        timing.add(Phase::ChunkLoad, ms(1200));
+        timing.add(Phase::ChunkLoad, ms(100));
+        timing.add(Phase::Render, ms(10));
+        let slow = timing.end_frame(start + ms(1400)).unwrap();
+        assert_eq!(
+            slow.log_line(),
+            "total_ms=1400 query_lines_ms=1300 search_ms=0 chunk_load_ms=1300 render_ms=10"
+        );
+        // chunk loads win a tie with the phase they happened in, as the more specific cause
+        assert_eq!(slow.summary(), "Slow frame: chunk load 1.3s");
+        // phases are counted afresh for every frame, and a frame is ended once
+        timing.begin_frame(start);
+        timing.add(Phase::Search, ms(300));
+        let slow = timing.end_frame(start + ms(300)).unwrap();
+        assert_eq!(slow.summary(), "Slow frame: search 0.3s");
+        assert_eq!(timing.end_frame(start + ms(300)), None);
+        // time spent outside the phases
+        timing.begin_frame(start);
+        let slow = timing.end_frame(start + ms(2000)).unwrap();
+        assert_eq!(slow.summary(), "Slow frame: 2.0s");
+
+        // nothing is timed while disabled
+        timing.set_enabled(false);
+        assert_eq!(timing.start(), None);
+        timing.begin_frame(start);
+        assert_eq!(timing.end_frame(start + ms(2000)), None);
+    }
+}
@@ -24,6 +24,10 @@ impl OffsetHistory {
         self.current_index = self.offsets.len() - 1;
     }
 
+    fn replace_current(&mut self, offset: usize) {
+        self.offsets[self.current_index] = offset;
+    }
+
     fn previous_one(&mut self) -> usize {
         self.current_index = self.current_index.saturating_sub(1);
         self.offsets[self.current_index]
@@ -39,22 +43,37 @@ impl OffsetHistory {
 pub struct Window {
     pub width: usize,
     pub height: usize,
+    terminal_height: usize,
     // offset of first line start instead of top-left corner of window
     offset: usize,
     pub horizontal_shift: usize,
+    // rows of the top line scrolled off when wrapping, only a single-line document has a line
+    // taller than the window worth scrolling through
+    pub row_shift: usize,
+    // the horizontal shift when wrapping was turned on, restored when it's turned off
+    shift_before_wrap: Option<usize>,
     offset_history: OffsetHistory,
 }
 
 impl Window {
     pub fn new() -> Result<Self> {
         let (width, height) = terminal::size()?;
-        Ok(Self {
-            width: width as usize,
-            height: height as usize - 1,
+        Ok(Self::with_terminal_size(width as usize, height as usize))
+    }
+
+    pub fn with_terminal_size(width: usize, height: usize) -> Self {
+        let mut window = Self {
+            width: 0,
+            height: 0,
+            terminal_height: 0,
             offset: 0,
             horizontal_shift: 0,
+            row_shift: 0,
+            shift_before_wrap: None,
             offset_history: OffsetHistory::new(),
-        })
+        };
+        window.resize(width, height);
+        window
     }
 
     pub fn move_offset_by(&mut self, distance: usize, direction: Direction) {
@@ -66,9 +85,48 @@ impl Window {
         }
     }
 
+    // the body keeps at least 1x1 even if the terminal is too small to also fit the status bar,
+    // or reports 0x0 in the middle of resizing
     pub fn resize(&mut self, width: usize, height: usize) {
-        self.width = width;
-        self.height = height - 1;
+        self.width = std::cmp::max(width, 1);
+        self.height = std::cmp::max(height.saturating_sub(1), 1);
+        self.terminal_height = height;
+    }
+
+    /// Whether the terminal has room for a single line only, see [`Canvas::render`].
+    ///
+    /// [`Canvas::render`]: crate::canvas::Canvas::render
+    pub fn is_single_line(&self) -> bool {
+        self.terminal_height <= 1
+    }
+
+    // back to the top with no history, e.g. after switching to another file
+    pub fn reset_position(&mut self) {
+        self.offset = 0;
+        self.horizontal_shift = 0;
+        self.row_shift = 0;
+        self.shift_before_wrap = None;
+        self.offset_history = OffsetHistory::new();
+    }
+
+    /// Turning wrapping on remembers the horizontal shift and scrolls back to the first column,
+    /// turning it off restores the remembered shift. Setting the same state again does nothing.
+    pub fn set_wrapped(&mut self, wrapped: bool) {
+        match (wrapped, self.shift_before_wrap) {
+            (true, None) => {
+                self.shift_before_wrap = Some(std::mem::take(&mut self.horizontal_shift));
+            }
+            (false, Some(shift)) => {
+                self.horizontal_shift = shift;
+                self.shift_before_wrap = None;
+            }
+            _ => {}
+        }
+    }
+
+    // the shift lines are shown with once wrapping is turned off
+    pub fn unwrapped_horizontal_shift(&self) -> usize {
+        self.shift_before_wrap.unwrap_or(self.horizontal_shift)
     }
 
     pub fn offset(&self) -> usize {
@@ -80,6 +138,21 @@ impl Window {
         self.offset_history.push(offset);
     }
 
+    // the same position counted from another document start, offsets in history no longer
+    // point where they did so it's dropped
+    pub fn rebase_offset(&mut self, offset: usize) {
+        self.offset = offset;
+        self.row_shift = 0;
+        self.offset_history = OffsetHistory::new();
+        self.offset_history.replace_current(offset);
+    }
+
+    // fix up current offset in place, without leaving the broken one in history
+    pub fn correct_offset(&mut self, offset: usize) {
+        self.offset = offset;
+        self.offset_history.replace_current(offset);
+    }
+
     pub fn goto_previous_offset(&mut self) {
         self.offset = self.offset_history.previous_one();
     }
@@ -88,3 +161,58 @@ impl Window {
         self.offset = self.offset_history.next_one();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correct_offset() {
+        let mut window = Window::with_terminal_size(80, 25);
+        window.set_offset(10);
+        window.move_offset_by(13, Direction::Down);
+        window.correct_offset(20);
+        assert_eq!(window.offset(), 20);
+        window.goto_previous_offset();
+        assert_eq!(window.offset(), 10);
+        window.goto_next_offset();
+        assert_eq!(window.offset(), 20);
+        window.goto_next_offset();
+        assert_eq!(window.offset(), 20);
+    }
+
+    #[test]
+    fn test_pathological_sizes() {
+        let size = |window: &Window| (window.width, window.height, window.is_single_line());
+        assert_eq!(size(&Window::with_terminal_size(80, 25)), (80, 24, false));
+        assert_eq!(size(&Window::with_terminal_size(80, 2)), (80, 1, false));
+        assert_eq!(size(&Window::with_terminal_size(80, 1)), (80, 1, true));
+        assert_eq!(size(&Window::with_terminal_size(0, 0)), (1, 1, true));
+
+        let mut window = Window::with_terminal_size(80, 25);
+        window.resize(0, 0);
+        assert_eq!(size(&window), (1, 1, true));
+        window.resize(100, 40);
+        assert_eq!(size(&window), (100, 39, false));
+    }
+
+    #[test]
+    fn test_set_wrapped() {
+        let mut window = Window::with_terminal_size(80, 25);
+        window.horizontal_shift = 12;
+        window.set_wrapped(true);
+        assert_eq!(window.horizontal_shift, 0);
+        assert_eq!(window.unwrapped_horizontal_shift(), 12);
+        // turning it on again doesn't lose the remembered shift
+        window.set_wrapped(true);
+        window.set_wrapped(false);
+        assert_eq!(window.horizontal_shift, 12);
+        window.set_wrapped(false);
+        assert_eq!(window.horizontal_shift, 12);
+
+        window.set_wrapped(true);
+        window.reset_position();
+        window.set_wrapped(false);
+        assert_eq!(window.horizontal_shift, 0);
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    io::Cursor,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use loss_viewer::document::Document;
+
+// counts records instead of writing them, like a log file configured at info level
+struct CountingLogger {
+    record_count: AtomicUsize,
+}
+
+impl Log for CountingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.record_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CountingLogger = CountingLogger {
+    record_count: AtomicUsize::new(0),
+};
+
+#[test]
+fn scrolling_logs_little_at_info_level() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    let content = (0..5_000)
+        .map(|index| format!("line {index}\n"))
+        .collect::<String>();
+    let mut document = Document::new(Cursor::new(content)).unwrap();
+    let start = Instant::now();
+    let mut offset = 0;
+    let mut query_count = 0;
+    while offset < document.last_line_start_offset() {
+        document.query_lines(offset, 50).unwrap();
+        offset += document.query_distance_to_below_n_lines(offset, 1).unwrap();
+        query_count += 2;
+    }
+    let elapsed = start.elapsed();
+
+    // only the periodic chunk load summary, which used to be a record per query
+    let record_count = LOGGER.record_count.load(Ordering::Relaxed);
+    assert!(
+        record_count <= elapsed.as_secs() as usize + 1,
+        "{record_count} records logged for {query_count} queries in {elapsed:?}"
+    );
+}